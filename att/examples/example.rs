@@ -37,34 +37,34 @@ impl Handler for H {
     fn handle_find_information_request(
         &mut self,
         item: &pkt::FindInformationRequest,
-    ) -> Result<pkt::FindInformationResponse, ErrorResponse> {
+    ) -> Result<pkt::FindInformationResponse, HandlerError> {
         match (
-            item.starting_handle().clone().into(),
-            item.ending_handle().clone().into(),
+            item.starting_handle().as_u16(),
+            item.ending_handle().as_u16(),
         ) {
             (0x000F, 0x000F) => {
                 Ok(vec![
-                    (0x000F.into(), Uuid::new_uuid16(0x2902).into()), // Client Characteristic Configuration
+                    (0x000F.into(), Uuid::new_uuid16(0x2902)), // Client Characteristic Configuration
                 ]
                 .into_iter()
                 .collect())
             }
             (0x0026, 0x0027) => {
                 Ok(vec![
-                    (0x0026.into(), Uuid::new_uuid16(0x2902).into()), // Client Characteristic Configuration
+                    (0x0026.into(), Uuid::new_uuid16(0x2902)), // Client Characteristic Configuration
                 ]
                 .into_iter()
                 .collect())
             }
             (0x0027, 0x0027) => {
                 Ok(vec![
-                    (0x0027.into(), Uuid::new_uuid16(0x2904).into()), // Characteristic Presentation Format Descriptor
+                    (0x0027.into(), Uuid::new_uuid16(0x2904)), // Characteristic Presentation Format Descriptor
                 ]
                 .into_iter()
                 .collect())
             }
-            (x, _) => Err(ErrorResponse::new(
-                x.clone().into(),
+            (_, _) => Err(HandlerError::new(
+                item.starting_handle().clone(),
                 pkt::ErrorCode::AttributeNotFound,
             )),
         }
@@ -73,10 +73,10 @@ impl Handler for H {
     fn handle_read_by_type_request(
         &mut self,
         item: &pkt::ReadByTypeRequest,
-    ) -> Result<pkt::ReadByTypeResponse, ErrorResponse> {
+    ) -> Result<pkt::ReadByTypeResponse, HandlerError> {
         match (
-            item.starting_handle().clone().into(),
-            item.ending_handle().clone().into(),
+            item.starting_handle().as_u16(),
+            item.ending_handle().as_u16(),
             item.attribute_type(),
         ) {
             (0x0001, 0x000B, Uuid::Uuid16(uuid)) if u16::from(uuid.clone()) == 0x2803 => {
@@ -110,8 +110,8 @@ impl Handler for H {
                 .into_iter()
                 .collect())
             }
-            (x, _, _) => Err(ErrorResponse::new(
-                x.clone().into(),
+            (_, _, _) => Err(HandlerError::new(
+                item.starting_handle().clone(),
                 pkt::ErrorCode::AttributeNotFound,
             )),
         }
@@ -120,21 +120,21 @@ impl Handler for H {
     fn handle_read_request(
         &mut self,
         item: &pkt::ReadRequest,
-    ) -> Result<pkt::ReadResponse, ErrorResponse> {
-        match item.attribute_handle().clone().into() {
+    ) -> Result<pkt::ReadResponse, HandlerError> {
+        match item.attribute_handle().as_u16() {
             0x0005 => Ok(pkt::ReadResponse::new(vec![0x00].into())),
             _ => Ok(pkt::ReadResponse::new(vec![0x00].into())),
-            //x => Err((x.into(), pkt::ErrorCode::AttributeNotFound).into())
+            //_ => Err((item.attribute_handle().clone(), pkt::ErrorCode::AttributeNotFound).into())
         }
     }
 
     fn handle_read_by_group_type_request(
         &mut self,
         item: &pkt::ReadByGroupTypeRequest,
-    ) -> Result<pkt::ReadByGroupTypeResponse, ErrorResponse> {
+    ) -> Result<pkt::ReadByGroupTypeResponse, HandlerError> {
         match (
-            item.starting_handle().clone().into(),
-            item.ending_handle().clone().into(),
+            item.starting_handle().as_u16(),
+            item.ending_handle().as_u16(),
         ) {
             (0x0001, 0xFFFF) => {
                 Ok(vec![
@@ -152,8 +152,8 @@ impl Handler for H {
                 .into_iter()
                 .collect())
             }
-            (x, _) => Err(ErrorResponse::new(
-                x.clone().into(),
+            (_, _) => Err(HandlerError::new(
+                item.starting_handle().clone(),
                 pkt::ErrorCode::AttributeNotFound,
             )),
         }
@@ -162,7 +162,7 @@ impl Handler for H {
     fn handle_write_request(
         &mut self,
         _item: &pkt::WriteRequest,
-    ) -> Result<pkt::WriteResponse, ErrorResponse> {
+    ) -> Result<pkt::WriteResponse, HandlerError> {
         Ok(pkt::WriteResponse::default())
     }
 }
@@ -182,8 +182,8 @@ async fn main() -> anyhow::Result<()> {
 
     let mut server = Server::new()?;
     //server.needs_bond_mitm()?;
-    let (connection, addr) = server.accept().await?.unwrap();
-    println!("accept {:?}", addr);
+    let (connection, peer) = server.accept().await?.unwrap();
+    println!("accept {:?}", peer);
     let mut notification = connection.notification(0x0025.into());
     let mut indication = connection.indication(0x000E.into());
 
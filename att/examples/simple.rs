@@ -9,10 +9,10 @@ impl Handler for MyHandler {
     fn handle_read_by_group_type_request(
         &mut self,
         item: &pkt::ReadByGroupTypeRequest,
-    ) -> Result<pkt::ReadByGroupTypeResponse, ErrorResponse> {
+    ) -> Result<pkt::ReadByGroupTypeResponse, HandlerError> {
         match (
-            item.starting_handle().clone().into(),
-            item.ending_handle().clone().into(),
+            item.starting_handle().as_u16(),
+            item.ending_handle().as_u16(),
         ) {
             (0x0001, 0xFFFF) => {
                 Ok(vec![
@@ -22,8 +22,8 @@ impl Handler for MyHandler {
                 .into_iter()
                 .collect())
             }
-            (x, _) => Err(ErrorResponse::new(
-                x.clone().into(),
+            (_, _) => Err(HandlerError::new(
+                item.starting_handle().clone(),
                 pkt::ErrorCode::AttributeNotFound,
             )),
         }
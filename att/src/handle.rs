@@ -1,18 +1,45 @@
-use std::fmt;
+use core::fmt;
+use core::num::ParseIntError;
+use core::str::FromStr;
 
 packable_newtype! {
     /// Attribute Handle.
-    #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    ///
+    /// Formatted as `0x0040` by both `Debug` and `Display`. Parsed from
+    /// `0x` prefixed hex or decimal. Defaults to the reserved handle `0x0000`.
+    #[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
     pub struct Handle(u16);
 }
 
 impl Handle {
+    /// Reserved handle `0x0000`, never assigned to an attribute.
+    pub const NULL: Handle = Handle::new(0x0000);
+
+    /// Lowest handle of an attribute, `0x0001`.
+    pub const MIN: Handle = Handle::new(0x0001);
+
+    /// Highest handle of an attribute, `0xFFFF`.
+    pub const MAX: Handle = Handle::new(0xFFFF);
+
     /// Construct new Handle instance.
+    ///
+    /// `0x0000` is reserved and never names an attribute; use
+    /// [`try_new`](Self::try_new) for values not known to be valid.
     pub const fn new(v: u16) -> Self {
         Self(v)
     }
 
-    pub fn as_u16(&self) -> u16 {
+    /// Construct new Handle instance, rejecting the reserved `0x0000`.
+    pub const fn try_new(v: u16) -> Result<Self, InvalidHandle> {
+        if v == 0x0000 {
+            Err(InvalidHandle)
+        } else {
+            Ok(Self(v))
+        }
+    }
+
+    pub const fn as_u16(&self) -> u16 {
         self.0
     }
 }
@@ -23,6 +50,61 @@ impl fmt::Debug for Handle {
     }
 }
 
+impl fmt::Display for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Error for [`Handle::try_new`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHandle;
+
+impl fmt::Display for InvalidHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "handle 0x0000 is reserved.")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidHandle {}
+
+/// Error for [`Handle::from_str`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHandleError(ParseIntError);
+
+impl fmt::Display for ParseHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid handle. ({})", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseHandleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl From<ParseIntError> for ParseHandleError {
+    fn from(v: ParseIntError) -> Self {
+        Self(v)
+    }
+}
+
+impl FromStr for Handle {
+    type Err = ParseHandleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16)?,
+            None => s.parse()?,
+        };
+        Ok(Self(v))
+    }
+}
+
+/// Infallible; see [`Handle::new`].
 impl From<u16> for Handle {
     fn from(v: u16) -> Self {
         Self(v)
@@ -34,3 +116,74 @@ impl From<Handle> for u16 {
         v.0
     }
 }
+
+impl From<Handle> for usize {
+    fn from(v: Handle) -> Self {
+        v.0.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consts() {
+        assert_eq!(Handle::NULL, Handle::default());
+        assert_eq!(Handle::MIN.as_u16(), 0x0001);
+        assert_eq!(Handle::MAX.as_u16(), u16::MAX);
+        assert!(Handle::NULL < Handle::MIN && Handle::MIN < Handle::MAX);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(Handle::try_new(0x0000), Err(InvalidHandle));
+        assert_eq!(Handle::try_new(0x0001), Ok(Handle::MIN));
+        assert_eq!(Handle::try_new(0xFFFF), Ok(Handle::MAX));
+        assert_eq!(InvalidHandle.to_string(), "handle 0x0000 is reserved.");
+    }
+
+    #[test]
+    fn test_display() {
+        let handle = Handle::new(0x0040);
+        assert_eq!(format!("{}", handle), "0x0040");
+        assert_eq!(format!("{:?}", handle), "0x0040");
+        assert_eq!(usize::from(handle), 0x40);
+    }
+
+    #[test]
+    fn test_const() {
+        const BATTERY_LEVEL: Handle = Handle::new(0x0025);
+        static HANDLES: [Handle; 2] = [Handle::new(0x0003), BATTERY_LEVEL];
+        const RAW: u16 = BATTERY_LEVEL.as_u16();
+
+        assert_eq!(HANDLES[1].as_u16(), RAW);
+        assert_eq!(RAW, 0x0025);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("0x0025".parse(), Ok(Handle::new(0x0025)));
+        assert_eq!("0XfFfF".parse(), Ok(Handle::new(0xFFFF)));
+        assert_eq!("37".parse(), Ok(Handle::new(37)));
+        assert_eq!("65535".parse(), Ok(Handle::new(0xFFFF)));
+
+        for invalid in [
+            "", "0x", "65536", "0x10000", "-1", "0x-1", "abc", " 1", "0b1",
+        ] {
+            assert!(invalid.parse::<Handle>().is_err(), "{:?}", invalid);
+        }
+        assert_eq!(
+            "0x".parse::<Handle>().unwrap_err().to_string(),
+            "invalid handle. (cannot parse integer from empty string)"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let handle = Handle::new(0x0025);
+        assert_eq!(serde_json::to_string(&handle).unwrap(), "37");
+        assert_eq!(serde_json::from_str::<Handle>("37").unwrap(), handle);
+    }
+}
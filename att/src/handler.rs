@@ -1,34 +1,75 @@
 use crate::packet as pkt;
 use crate::Handle;
 
-/// ATT Protocol Error Response
+/// Error returned by [`Handler`] methods. Sent to the client as an `Error Response` PDU.
 #[derive(Debug, thiserror::Error)]
-#[error("error response {0:?} {1:?}")]
-pub struct ErrorResponse(pub(crate) Handle, pub(crate) pkt::ErrorCode);
+#[error("error response {:?} for handle {}", .1, self.handle())]
+pub struct HandlerError(pub(crate) Option<Handle>, pub(crate) pkt::ErrorCode);
 
-impl ErrorResponse {
+impl HandlerError {
     /// Constract Instance
     pub fn new(handle: Handle, code: pkt::ErrorCode) -> Self {
-        Self(handle, code)
+        Self(Some(handle), code)
+    }
+
+    /// Error of a request no attribute handle applies to. (e.g. an empty
+    /// Read Multiple Request) Sent with handle `0x0000`, as the
+    /// specification requires.
+    pub fn without_handle(code: pkt::ErrorCode) -> Self {
+        Self(None, code)
+    }
+
+    /// Attribute handle in error, `0x0000` if none applies.
+    pub fn handle(&self) -> &Handle {
+        self.0.as_ref().unwrap_or(&Handle::NULL)
+    }
+
+    /// Attribute handle in error, if any applies.
+    pub fn attribute_handle(&self) -> Option<&Handle> {
+        self.0.as_ref()
+    }
+
+    /// Error code.
+    pub fn code(&self) -> &pkt::ErrorCode {
+        &self.1
     }
 }
 
+impl From<(Handle, pkt::ErrorCode)> for HandlerError {
+    fn from((handle, code): (Handle, pkt::ErrorCode)) -> Self {
+        Self::new(handle, code)
+    }
+}
+
+/// Former name of [`HandlerError`].
+#[deprecated(note = "renamed to `HandlerError`")]
+pub type ErrorResponse = HandlerError;
+
 /// ATT Protocol Handler
 pub trait Handler {
     /// handle `exchange mtu request`
+    ///
+    /// Only called by an [`AttServerMachine`](crate::machine::AttServerMachine)
+    /// without a server MTU. A [`Connection`](crate::server::Connection)
+    /// always has one, [`DEFAULT_SERVER_MTU`](crate::machine::DEFAULT_SERVER_MTU)
+    /// unless set with
+    /// [`Connection::set_server_mtu`](crate::server::Connection::set_server_mtu).
+    /// Answers with `DEFAULT_SERVER_MTU`, whatever the client offers.
     fn handle_exchange_mtu_request(
         &mut self,
-        item: &pkt::ExchangeMtuRequest,
-    ) -> Result<pkt::ExchangeMtuResponse, ErrorResponse> {
-        Ok(pkt::ExchangeMtuResponse::new(*item.client_rx_mtu()))
+        _item: &pkt::ExchangeMtuRequest,
+    ) -> Result<pkt::ExchangeMtuResponse, HandlerError> {
+        Ok(pkt::ExchangeMtuResponse::new(
+            crate::machine::DEFAULT_SERVER_MTU,
+        ))
     }
 
     /// handle `find information request`
     fn handle_find_information_request(
         &mut self,
         item: &pkt::FindInformationRequest,
-    ) -> Result<pkt::FindInformationResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::FindInformationResponse, HandlerError> {
+        Err(HandlerError::new(
             item.starting_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -38,8 +79,8 @@ pub trait Handler {
     fn handle_find_by_type_value_request(
         &mut self,
         item: &pkt::FindByTypeValueRequest,
-    ) -> Result<pkt::FindByTypeValueResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::FindByTypeValueResponse, HandlerError> {
+        Err(HandlerError::new(
             item.starting_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -49,8 +90,8 @@ pub trait Handler {
     fn handle_read_by_type_request(
         &mut self,
         item: &pkt::ReadByTypeRequest,
-    ) -> Result<pkt::ReadByTypeResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::ReadByTypeResponse, HandlerError> {
+        Err(HandlerError::new(
             item.starting_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -60,8 +101,8 @@ pub trait Handler {
     fn handle_read_request(
         &mut self,
         item: &pkt::ReadRequest,
-    ) -> Result<pkt::ReadResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::ReadResponse, HandlerError> {
+        Err(HandlerError::new(
             item.attribute_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -71,8 +112,8 @@ pub trait Handler {
     fn handle_read_blob_request(
         &mut self,
         item: &pkt::ReadBlobRequest,
-    ) -> Result<pkt::ReadBlobResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::ReadBlobResponse, HandlerError> {
+        Err(HandlerError::new(
             item.attribute_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -82,19 +123,22 @@ pub trait Handler {
     fn handle_read_multiple_request(
         &mut self,
         item: &pkt::ReadMultipleRequest,
-    ) -> Result<pkt::ReadMultipleResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
-            item.into_iter().next().unwrap().clone(),
-            pkt::ErrorCode::RequestNotSupported,
-        ))
+    ) -> Result<pkt::ReadMultipleResponse, HandlerError> {
+        match item.into_iter().next() {
+            Some(handle) => Err(HandlerError::new(
+                handle.clone(),
+                pkt::ErrorCode::RequestNotSupported,
+            )),
+            None => Err(HandlerError::without_handle(pkt::ErrorCode::InvalidPDU)),
+        }
     }
 
     /// handle `read by group type request`
     fn handle_read_by_group_type_request(
         &mut self,
         item: &pkt::ReadByGroupTypeRequest,
-    ) -> Result<pkt::ReadByGroupTypeResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::ReadByGroupTypeResponse, HandlerError> {
+        Err(HandlerError::new(
             item.starting_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -104,8 +148,8 @@ pub trait Handler {
     fn handle_write_request(
         &mut self,
         item: &pkt::WriteRequest,
-    ) -> Result<pkt::WriteResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::WriteResponse, HandlerError> {
+        Err(HandlerError::new(
             item.attribute_handle().clone(),
             pkt::ErrorCode::RequestNotSupported,
         ))
@@ -118,26 +162,31 @@ pub trait Handler {
     }
 
     /// handle `prepare write request`
+    ///
+    /// Defaults to `Attribute Not Long` for the attribute, as a server
+    /// without long writes has no attribute to write in parts.
     fn handle_prepare_write_request(
         &mut self,
         item: &pkt::PrepareWriteRequest,
-    ) -> Result<pkt::PrepareWriteResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
+    ) -> Result<pkt::PrepareWriteResponse, HandlerError> {
+        Err(HandlerError::new(
             item.attribute_handle().clone(),
-            pkt::ErrorCode::RequestNotSupported,
+            pkt::ErrorCode::AttributeNotLong,
         ))
     }
 
     /// handle `execute write request`
+    ///
+    /// Defaults to `Unlikely Error` without a handle: the default
+    /// [`handle_prepare_write_request`](Self::handle_prepare_write_request)
+    /// queues nothing, so no prepared write is in error. Servers queueing
+    /// prepared writes should name the handle of the offending one.
     #[allow(unused_variables)]
     fn handle_execute_write_request(
         &mut self,
         item: &pkt::ExecuteWriteRequest,
-    ) -> Result<pkt::ExecuteWriteResponse, ErrorResponse> {
-        Err(ErrorResponse::new(
-            0x0000.into(),
-            pkt::ErrorCode::RequestNotSupported,
-        ))
+    ) -> Result<pkt::ExecuteWriteResponse, HandlerError> {
+        Err(HandlerError::without_handle(pkt::ErrorCode::UnlikelyError))
     }
 
     /// handle `signed write command`
@@ -146,3 +195,54 @@ pub trait Handler {
         // nop
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_error() {
+        let err = HandlerError::from((Handle::new(0x0003), pkt::ErrorCode::ReadNotPermitted));
+        assert_eq!(err.handle(), &Handle::new(0x0003));
+        assert_eq!(err.code(), &pkt::ErrorCode::ReadNotPermitted);
+
+        #[allow(deprecated)]
+        let err: ErrorResponse = HandlerError::new(Handle::new(1), pkt::ErrorCode::InvalidHandle);
+        assert_eq!(err.handle(), &Handle::new(1));
+    }
+
+    #[test]
+    fn test_long_write_defaults() {
+        struct H;
+        impl Handler for H {}
+
+        let prepare = pkt::PrepareWriteRequest::new(Handle::new(0x0003), 0, vec![1].into());
+        let err = H.handle_prepare_write_request(&prepare).unwrap_err();
+        assert_eq!(err.handle(), &Handle::new(0x0003));
+        assert_eq!(err.code(), &pkt::ErrorCode::AttributeNotLong);
+
+        let execute = pkt::ExecuteWriteRequest::new(true);
+        let err = H.handle_execute_write_request(&execute).unwrap_err();
+        assert_eq!(err.handle(), &Handle::NULL);
+        assert_eq!(err.attribute_handle(), None);
+        assert_eq!(err.code(), &pkt::ErrorCode::UnlikelyError);
+    }
+
+    #[test]
+    fn test_without_handle() {
+        let err = HandlerError::without_handle(pkt::ErrorCode::InvalidPDU);
+        assert_eq!(err.attribute_handle(), None);
+        assert_eq!(err.code(), &pkt::ErrorCode::InvalidPDU);
+        assert_eq!(
+            err.to_string(),
+            "error response InvalidPDU for handle 0x0000"
+        );
+
+        let err = HandlerError::new(Handle::new(0x0003), pkt::ErrorCode::ReadNotPermitted);
+        assert_eq!(err.attribute_handle(), Some(&Handle::new(0x0003)));
+        assert_eq!(
+            err.to_string(),
+            "error response ReadNotPermitted for handle 0x0003"
+        );
+    }
+}
@@ -1,52 +1,68 @@
 #![doc(html_root_url = "https://docs.rs/att/0.2.1")]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 //! Bluetooth Low Energy Attribute Protocol Library.
 //!
 //! ref BLUETOOTH CORE SPECIFICATION Version 5.1 | Vol 3, Part F
 //!     Attribute Protocol (ATT)
 //!
-//! ## Example
-//!
-//! ```
-//! use att::packet as pkt;
-//! use att::server::*;
-//!
-//! /// GAP / GATT Service only (with no Characteristics)
-//! #[derive(Debug)]
-//! struct MyHandler;
-//!
-//! impl Handler for MyHandler {
-//!     fn handle_read_by_group_type_request(
-//!         &mut self,
-//!         item: &pkt::ReadByGroupTypeRequest,
-//!     ) -> Result<pkt::ReadByGroupTypeResponse, ErrorResponse> {
-//!         match (
-//!             item.starting_handle().clone().into(),
-//!             item.ending_handle().clone().into(),
-//!         ) {
-//!             (0x0001, 0xFFFF) => {
-//!                 Ok(vec![
-//!                     (0x0001.into(), 0x000B.into(), vec![0x00, 0x18].into()), // Generic Access
-//!                     (0x000C.into(), 0x000F.into(), vec![0x01, 0x18].into()), // Generic Attribute
-//!                 ]
-//!                 .into_iter()
-//!                 .collect())
-//!             }
-//!             (x, _) => Err(ErrorResponse::new(
-//!                 x.clone().into(),
-//!                 pkt::ErrorCode::AttributeNotFound,
-//!             )),
-//!         }
-//!     }
-//! }
+#![cfg_attr(
+    feature = "server",
+    doc = r#"
+## Example
+
+```no_run
+use att::packet as pkt;
+use att::server::*;
+
+/// GAP / GATT Service only (with no Characteristics)
+#[derive(Debug)]
+struct MyHandler;
+
+impl Handler for MyHandler {
+    fn handle_read_by_group_type_request(
+        &mut self,
+        item: &pkt::ReadByGroupTypeRequest,
+    ) -> Result<pkt::ReadByGroupTypeResponse, HandlerError> {
+        match (
+            item.starting_handle().as_u16(),
+            item.ending_handle().as_u16(),
+        ) {
+            (0x0001, 0xFFFF) => {
+                Ok(vec![
+                    (0x0001.into(), 0x000B.into(), vec![0x00, 0x18].into()), // Generic Access
+                    (0x000C.into(), 0x000F.into(), vec![0x01, 0x18].into()), // Generic Attribute
+                ]
+                .into_iter()
+                .collect())
+            }
+            (x, _) => Err(HandlerError::new(
+                x.clone().into(),
+                pkt::ErrorCode::AttributeNotFound,
+            )),
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let server = Server::new()?;
+    // let connection = server.accept().await?;
+    // connection.run(MyHandler).await?;
+    Ok(())
+}
+```
+"#
+)]
+//! # Features
 //!
-//! #[tokio::main(flavor = "current_thread")]
-//! async fn main() -> anyhow::Result<()> {
-//!     let server = Server::new()?;
-//!     // let connection = server.accept().await?;
-//!     // connection.run(MyHandler).await?;
-//!     Ok(())
-//! }
-//! ```
+//! - `server` (default): [`server`], its sans-io core [`machine`] and the
+//!   Bluetooth socket transport. Implies `std`.
+//! - `std`: [`packet`], [`uuid`], [`Handle`] and [`Address`], without the
+//!   tokio runtime.
+//! - `tracing`: emit diagnostics with `tracing` instead of `log`. Received and
+//!   sent PDUs are `trace` events with `direction` and `packet` fields, in an
+//!   `att_connection` span carrying the peer address.
+//! - `alloc`: [`packet`], [`uuid`] and [`Handle`] only. Builds with `no_std`.
 //!
 //! # Supported target
 //!
@@ -61,6 +77,7 @@
 //!   ([LICENSE-APACHE](LICENSE-APACHE) or http://www.apache.org/licenses/LICENSE-2.0)
 //! * MIT license
 //!   ([LICENSE-MIT](LICENSE-MIT) or http://opensource.org/licenses/MIT)
+//!
 //! at your option.
 //!
 //! ## Contribution
@@ -69,19 +86,35 @@
 //! for inclusion in the work by you, as defined in the Apache-2.0 license, shall be
 //! dual licensed as above, without any additional terms or conditions.!
 
+#[cfg(not(feature = "alloc"))]
+compile_error!("either `std` or `alloc` feature is required.");
+
+extern crate alloc;
+
 pub use crate::uuid::Uuid;
+#[cfg(feature = "std")]
 pub use bdaddr::Address;
-pub use handle::Handle;
-pub use handler::{ErrorResponse, Handler};
+pub use handle::{Handle, InvalidHandle, ParseHandleError};
+#[allow(deprecated)]
+#[cfg(feature = "server")]
+pub use handler::ErrorResponse;
+#[cfg(feature = "server")]
+pub use handler::{Handler, HandlerError};
+#[cfg(feature = "server")]
 pub use server::Server;
 
 #[macro_use]
 mod macros;
 
 mod handle;
+#[cfg(feature = "server")]
 mod handler;
+#[cfg(feature = "server")]
+pub mod machine;
 pub mod packet;
+#[cfg(feature = "server")]
 pub mod server;
 mod size;
+#[cfg(feature = "server")]
 mod sock;
 pub mod uuid;
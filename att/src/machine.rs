@@ -0,0 +1,848 @@
+//! Sans-io ATT server protocol state.
+//!
+//! [`AttServerMachine`] keeps the transaction rules of the Attribute Protocol
+//! without doing any I/O: feed it received PDUs, hand the requests to a
+//! [`Handler`] and send the bytes it returns. [`server`](crate::server) is a
+//! driver of this machine over a Bluetooth socket.
+//!
+//! ```
+//! use att::machine::{Action, AttServerMachine};
+//! use att::Handler;
+//!
+//! struct H;
+//! impl Handler for H {}
+//!
+//! let mut machine = AttServerMachine::new();
+//! for action in machine.handle_bytes(&[0x02, 0x00, 0x01]) {
+//!     if let Action::DeliverToHandler(request) = action {
+//!         let response = machine.dispatch(&mut H, request).unwrap();
+//!         assert_eq!(response, Some(vec![0x03, 0x05, 0x02]));
+//!         // written to the transport
+//!         machine.response_sent();
+//!     }
+//! }
+//! assert_eq!(machine.mtu(), 256);
+//! ```
+use std::convert::TryFrom;
+
+use crate::packet as pkt;
+use crate::{Handle, Handler, HandlerError};
+use pkt::pack::{self, Pack, Unpack};
+use pkt::{DeviceSend, Packet};
+
+/// Default ATT_MTU of LE. (3.2.8)
+pub(crate) const DEFAULT_MTU: usize = 23;
+
+/// Largest ATT_MTU worth advertising: an attribute value of 512 octets plus
+/// the header of a Read Blob or Prepare Write. (3.2.9)
+pub(crate) const MAX_MTU: u16 = 517;
+
+/// Server Rx MTU a [`Connection`](crate::server::Connection) advertises
+/// unless configured: the largest one worth advertising.
+pub const DEFAULT_SERVER_MTU: u16 = MAX_MTU;
+
+/// What the driver does with a received PDU.
+#[derive(Debug)]
+pub enum Action {
+    /// Send the packet to the client.
+    Respond(Vec<u8>),
+    /// Pass the request or command to the handler, e.g. with
+    /// [`AttServerMachine::dispatch`].
+    DeliverToHandler(pkt::DeviceRecv),
+    /// The client confirmed the outstanding indication.
+    ConfirmationReceived,
+}
+
+/// Error returned when the machine can not produce a notification or indication now.
+#[derive(Debug, thiserror::Error)]
+pub enum NotReady {
+    #[error("previous indication is not confirmed yet.")]
+    AwaitingConfirmation,
+
+    #[error("value of {len} octets does not fit in ATT_MTU {mtu}.")]
+    ExceedsMtu { len: usize, mtu: usize },
+}
+
+/// ATT_MTU of a connection, which changes once with the Exchange MTU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MtuState {
+    /// No Exchange MTU Request was answered yet. ATT_MTU is 23 both ways,
+    /// though the client may exchange at any time.
+    PreExchange,
+    /// Exchanged once and for all. (3.4.2.1)
+    Exchanged {
+        /// Client Rx MTU, the longest PDU the server may send.
+        tx_mtu: usize,
+        /// Server Rx MTU, the longest PDU the client may send.
+        rx_mtu: usize,
+    },
+}
+
+impl MtuState {
+    /// Client Rx MTU.
+    pub fn tx_mtu(self) -> usize {
+        match self {
+            Self::PreExchange => DEFAULT_MTU,
+            Self::Exchanged { tx_mtu, .. } => tx_mtu,
+        }
+    }
+
+    /// Server Rx MTU.
+    pub fn rx_mtu(self) -> usize {
+        match self {
+            Self::PreExchange => DEFAULT_MTU,
+            Self::Exchanged { rx_mtu, .. } => rx_mtu,
+        }
+    }
+
+    /// ATT_MTU, the smaller of the Rx MTUs. 23 before the exchange.
+    pub fn mtu(self) -> usize {
+        self.tx_mtu().min(self.rx_mtu())
+    }
+}
+
+/// ATT server state of one connection.
+///
+/// Tracks the negotiated ATT_MTU, the request waiting for its response and the
+/// indication waiting for its confirmation.
+#[derive(Debug)]
+pub struct AttServerMachine {
+    mtu: MtuState,
+    /// A request was delivered and its response is not sent yet.
+    pending_request: bool,
+    /// Handle an Error Response to the pending request names.
+    pending_handle: Handle,
+    /// Client Rx MTU of the Exchange MTU Request being handled.
+    exchange_mtu: Option<u16>,
+    /// Server Rx MTU advertised in the Exchange MTU Response.
+    server_mtu: Option<u16>,
+    awaiting_confirmation: bool,
+}
+
+impl Default for AttServerMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttServerMachine {
+    pub fn new() -> Self {
+        Self {
+            mtu: MtuState::PreExchange,
+            pending_request: false,
+            pending_handle: Handle::NULL,
+            exchange_mtu: None,
+            server_mtu: None,
+            awaiting_confirmation: false,
+        }
+    }
+
+    /// Answer Exchange MTU Requests with `mtu` instead of asking the handler.
+    ///
+    /// `mtu` is clamped to 23..=517.
+    pub fn set_server_mtu(&mut self, mtu: u16) {
+        self.server_mtu = Some(mtu.clamp(DEFAULT_MTU as u16, MAX_MTU));
+    }
+
+    /// Server Rx MTU set by [`set_server_mtu`](Self::set_server_mtu).
+    pub fn server_mtu(&self) -> Option<u16> {
+        self.server_mtu
+    }
+
+    /// Whether the MTU was exchanged, and to what.
+    pub fn mtu_state(&self) -> MtuState {
+        self.mtu
+    }
+
+    /// Client Rx MTU.
+    pub fn tx_mtu(&self) -> usize {
+        self.mtu.tx_mtu()
+    }
+
+    /// Server Rx MTU. Longer PDUs are rejected; read with a buffer of at
+    /// least one more octet to tell them from PDUs that just fit.
+    pub fn rx_mtu(&self) -> usize {
+        self.mtu.rx_mtu()
+    }
+
+    /// Negotiated ATT_MTU. Every outgoing packet fits in it.
+    pub fn mtu(&self) -> usize {
+        self.mtu.mtu()
+    }
+
+    /// Whether a request was received and its response is not sent yet.
+    pub fn pending_request(&self) -> bool {
+        self.pending_request
+    }
+
+    /// Whether an indication was sent and its confirmation did not arrive yet.
+    pub fn awaiting_confirmation(&self) -> bool {
+        self.awaiting_confirmation
+    }
+
+    /// Process one received PDU.
+    ///
+    /// A request received while a response is outstanding is answered with
+    /// `Insufficient Resources`. A PDU that does not decode, or is longer than
+    /// the Server Rx MTU (23 before the exchange), is answered with
+    /// `Invalid PDU` when it is a known request, `Request Not Supported` when it
+    /// is an unknown request, and ignored otherwise. An Exchange MTU Request
+    /// after the MTU was exchanged is answered with `Request Not Supported`.
+    pub fn handle_bytes(&mut self, input: &[u8]) -> Vec<Action> {
+        let opcode = match input.first() {
+            Some(opcode) => *opcode,
+            None => return vec![],
+        };
+        if input.len() > self.rx_mtu() {
+            debug!(
+                "PDU 0x{:02X} of {} bytes exceeds Server Rx MTU {}.",
+                opcode,
+                input.len(),
+                self.rx_mtu()
+            );
+            return classify_malformed(opcode).into_iter().collect();
+        }
+
+        let recv = match pkt::DeviceRecv::unpack(&mut &input[..]) {
+            Ok(recv) => recv,
+            Err(err) => {
+                let err = err.with_context(&input[..input.len().min(16)]);
+                debug!("malformed packet: {}", err);
+                return classify_malformed(opcode).into_iter().collect();
+            }
+        };
+        trace_pdu!("recv", recv);
+
+        if let pkt::DeviceRecv::HandleValueConfirmation(..) = recv {
+            if !self.awaiting_confirmation {
+                log::warn!("unexpected confirmation.");
+                return vec![];
+            }
+            self.awaiting_confirmation = false;
+            return vec![Action::ConfirmationReceived];
+        }
+
+        if let Some(opcode) = request_opcode(&recv) {
+            if self.pending_request {
+                log::warn!(
+                    "{:?} received before the previous response was sent.",
+                    opcode
+                );
+                return vec![Action::Respond(error_response(
+                    opcode as u8,
+                    request_handle(&recv),
+                    pkt::ErrorCode::InsufficientResources,
+                ))];
+            }
+            if opcode == pkt::OpCode::ExchangeMtuRequest && self.mtu != MtuState::PreExchange {
+                log::warn!("MTU exchanged more than once.");
+                return vec![Action::Respond(error_response(
+                    opcode as u8,
+                    Handle::NULL,
+                    pkt::ErrorCode::RequestNotSupported,
+                ))];
+            }
+            self.pending_request = true;
+            self.pending_handle = request_handle(&recv);
+        }
+        if let pkt::DeviceRecv::ExchangeMtuRequest(item) = &recv {
+            self.exchange_mtu = Some(*item.client_rx_mtu());
+        }
+        vec![Action::DeliverToHandler(recv)]
+    }
+
+    /// Pack the handler's answer to the delivered request `R`.
+    ///
+    /// The response is truncated to the ATT_MTU. A response still exceeding
+    /// it is answered with an Error Response of Unlikely Error instead. A
+    /// successful Exchange MTU Response applies the new ATT_MTU to later
+    /// packets.
+    pub fn respond<R>(
+        &mut self,
+        response: Result<R::Response, HandlerError>,
+    ) -> pack::Result<Vec<u8>>
+    where
+        R: pkt::Request,
+    {
+        let packet = match response {
+            Ok(mut response) => {
+                pkt::Response::truncate(&mut response, self.mtu());
+                self.pack(response)?
+            }
+            Err(HandlerError(handle, code)) => {
+                // 0x0000 for requests no handle applies to. (3.4.1.1)
+                let handle = handle.unwrap_or(Handle::NULL);
+                self.pack(pkt::ErrorResponse::new(R::opcode(), handle, code))?
+            }
+        };
+        // never cut a response short; the client could not tell.
+        let packet = if packet.len() > self.mtu() {
+            debug!(
+                "response to {:?} of {} bytes exceeds ATT_MTU {}.",
+                R::opcode(),
+                packet.len(),
+                self.mtu()
+            );
+            error_response(
+                R::opcode() as u8,
+                self.pending_handle.clone(),
+                pkt::ErrorCode::UnlikelyError,
+            )
+        } else {
+            packet
+        };
+
+        if R::opcode() == pkt::OpCode::ExchangeMtuRequest {
+            if let (Some(client_rx_mtu), [0x03, lo, hi]) = (self.exchange_mtu.take(), &packet[..]) {
+                let server_rx_mtu = u16::from_le_bytes([*lo, *hi]);
+                self.mtu = MtuState::Exchanged {
+                    tx_mtu: (client_rx_mtu as usize).max(DEFAULT_MTU),
+                    rx_mtu: (server_rx_mtu as usize).max(DEFAULT_MTU),
+                };
+            }
+        }
+        Ok(packet)
+    }
+
+    /// Call the `handler` method for `recv` and [`respond`](Self::respond).
+    ///
+    /// Returns the packet to send, or `None` for commands.
+    pub fn dispatch<H>(
+        &mut self,
+        handler: &mut H,
+        recv: pkt::DeviceRecv,
+    ) -> pack::Result<Option<Vec<u8>>>
+    where
+        H: Handler,
+    {
+        let packet = match recv {
+            pkt::DeviceRecv::ExchangeMtuRequest(item) => {
+                let response = match self.server_mtu {
+                    Some(mtu) => Ok(pkt::ExchangeMtuResponse::new(mtu)),
+                    None => handler.handle_exchange_mtu_request(&item),
+                };
+                self.respond::<pkt::ExchangeMtuRequest>(response)?
+            }
+
+            pkt::DeviceRecv::FindInformationRequest(item) => {
+                let response = handler.handle_find_information_request(&item);
+                self.respond::<pkt::FindInformationRequest>(response)?
+            }
+
+            pkt::DeviceRecv::FindByTypeValueRequest(item) => {
+                let response = handler.handle_find_by_type_value_request(&item);
+                self.respond::<pkt::FindByTypeValueRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ReadByTypeRequest(item) => {
+                let response = handler.handle_read_by_type_request(&item);
+                self.respond::<pkt::ReadByTypeRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ReadRequest(item) => {
+                let response = handler.handle_read_request(&item);
+                self.respond::<pkt::ReadRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ReadBlobRequest(item) => {
+                let response = handler.handle_read_blob_request(&item);
+                self.respond::<pkt::ReadBlobRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ReadMultipleRequest(item) => {
+                let response = handler.handle_read_multiple_request(&item);
+                self.respond::<pkt::ReadMultipleRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ReadByGroupTypeRequest(item) => {
+                let response = handler.handle_read_by_group_type_request(&item);
+                self.respond::<pkt::ReadByGroupTypeRequest>(response)?
+            }
+
+            pkt::DeviceRecv::WriteRequest(item) => {
+                let response = handler.handle_write_request(&item);
+                self.respond::<pkt::WriteRequest>(response)?
+            }
+
+            pkt::DeviceRecv::WriteCommand(item) => {
+                handler.handle_write_command(&item);
+                return Ok(None);
+            }
+
+            pkt::DeviceRecv::PrepareWriteRequest(item) => {
+                let response = handler.handle_prepare_write_request(&item);
+                self.respond::<pkt::PrepareWriteRequest>(response)?
+            }
+
+            pkt::DeviceRecv::ExecuteWriteRequest(item) => {
+                let response = handler.handle_execute_write_request(&item);
+                self.respond::<pkt::ExecuteWriteRequest>(response)?
+            }
+
+            pkt::DeviceRecv::SignedWriteCommand(item) => {
+                handler.handle_signed_write_command(&item);
+                return Ok(None);
+            }
+
+            pkt::DeviceRecv::HandleValueConfirmation(..) => return Ok(None),
+        };
+        Ok(Some(packet))
+    }
+
+    /// The response to the pending request was sent. Requests are accepted again.
+    pub fn response_sent(&mut self) {
+        self.pending_request = false;
+    }
+
+    /// Handle Value Notification PDU for `value`.
+    pub fn queue_notification(
+        &mut self,
+        handle: &Handle,
+        value: &[u8],
+    ) -> Result<Vec<u8>, NotReady> {
+        self.handle_value(pkt::HandleValueNotification::OPCODE as u8, handle, value)
+    }
+
+    /// Handle Value Indication PDU for `value`.
+    ///
+    /// Only one indication is outstanding at a time: fails with
+    /// [`NotReady::AwaitingConfirmation`] until the previous one is confirmed.
+    pub fn queue_indication(&mut self, handle: &Handle, value: &[u8]) -> Result<Vec<u8>, NotReady> {
+        if self.awaiting_confirmation {
+            return Err(NotReady::AwaitingConfirmation);
+        }
+        let packet = self.handle_value(pkt::HandleValueIndication::OPCODE as u8, handle, value)?;
+        self.awaiting_confirmation = true;
+        Ok(packet)
+    }
+
+    fn handle_value(&self, opcode: u8, handle: &Handle, value: &[u8]) -> Result<Vec<u8>, NotReady> {
+        let mtu = self.mtu();
+        if 3 + value.len() > mtu {
+            return Err(NotReady::ExceedsMtu {
+                len: value.len(),
+                mtu,
+            });
+        }
+        let mut packet = Vec::with_capacity(3 + value.len());
+        packet.push(opcode);
+        packet.extend_from_slice(&handle.as_u16().to_le_bytes());
+        packet.extend_from_slice(value);
+        trace_pdu!("send", packet);
+        Ok(packet)
+    }
+
+    fn pack<S>(&self, item: S) -> pack::Result<Vec<u8>>
+    where
+        S: DeviceSend,
+    {
+        trace_pdu!("send", item);
+
+        let mut buf = vec![];
+        item.pack_with_code(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Op Code of request PDUs, which the client must not send while a response is outstanding.
+fn request_opcode(request: &pkt::DeviceRecv) -> Option<pkt::OpCode> {
+    match request {
+        pkt::DeviceRecv::WriteCommand(..)
+        | pkt::DeviceRecv::SignedWriteCommand(..)
+        | pkt::DeviceRecv::HandleValueConfirmation(..) => None,
+        request => Some(request.opcode()),
+    }
+}
+
+/// First handle `request` carries, which an Error Response to it names.
+/// 0x0000 for requests without any. (3.4.1.1)
+fn request_handle(request: &pkt::DeviceRecv) -> Handle {
+    match request {
+        pkt::DeviceRecv::FindInformationRequest(item) => item.starting_handle().clone(),
+        pkt::DeviceRecv::FindByTypeValueRequest(item) => item.starting_handle().clone(),
+        pkt::DeviceRecv::ReadByTypeRequest(item) => item.starting_handle().clone(),
+        pkt::DeviceRecv::ReadRequest(item) => item.attribute_handle().clone(),
+        pkt::DeviceRecv::ReadBlobRequest(item) => item.attribute_handle().clone(),
+        pkt::DeviceRecv::ReadMultipleRequest(item) => {
+            item.into_iter().next().cloned().unwrap_or(Handle::NULL)
+        }
+        pkt::DeviceRecv::ReadByGroupTypeRequest(item) => item.starting_handle().clone(),
+        pkt::DeviceRecv::WriteRequest(item) => item.attribute_handle().clone(),
+        pkt::DeviceRecv::PrepareWriteRequest(item) => item.attribute_handle().clone(),
+        _ => Handle::NULL,
+    }
+}
+
+/// Answer to a PDU which did not decode. (3.4.1.1, 3.3.1)
+fn classify_malformed(opcode: u8) -> Option<Action> {
+    /// Command Flag of the Attribute Opcode.
+    const COMMAND: u8 = 0x40;
+
+    let code = match pkt::OpCode::try_from(opcode) {
+        Ok(
+            pkt::OpCode::ExchangeMtuRequest
+            | pkt::OpCode::FindInformationRequest
+            | pkt::OpCode::FindByTypeValueRequest
+            | pkt::OpCode::ReadByTypeRequest
+            | pkt::OpCode::ReadRequest
+            | pkt::OpCode::ReadBlobRequest
+            | pkt::OpCode::ReadMultipleRequest
+            | pkt::OpCode::ReadByGroupTypeRequest
+            | pkt::OpCode::WriteRequest
+            | pkt::OpCode::PrepareWriteRequest
+            | pkt::OpCode::ExecuteWriteRequest,
+        ) => pkt::ErrorCode::InvalidPDU,
+        // responses, notifications, indications and commands the server does not act on
+        Ok(..) => return None,
+        Err(..) if opcode & COMMAND != 0 => return None,
+        Err(..) => pkt::ErrorCode::RequestNotSupported,
+    };
+    // the handle did not decode either.
+    Some(Action::Respond(error_response(opcode, Handle::NULL, code)))
+}
+
+/// Error Response PDU for `opcode`, which may be unknown to [`pkt::OpCode`].
+fn error_response(opcode: u8, handle: Handle, code: pkt::ErrorCode) -> Vec<u8> {
+    let [lo, hi] = handle.as_u16().to_le_bytes();
+    let mut packet = vec![pkt::ErrorResponse::OPCODE as u8, opcode, lo, hi];
+    code.pack(&mut packet)
+        .expect("writing to Vec<u8> never fails");
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct H;
+
+    impl Handler for H {
+        fn handle_exchange_mtu_request(
+            &mut self,
+            _: &pkt::ExchangeMtuRequest,
+        ) -> Result<pkt::ExchangeMtuResponse, HandlerError> {
+            Ok(pkt::ExchangeMtuResponse::new(100))
+        }
+
+        fn handle_read_request(
+            &mut self,
+            _: &pkt::ReadRequest,
+        ) -> Result<pkt::ReadResponse, HandlerError> {
+            Ok(pkt::ReadResponse::new(vec![0xAA; 512].into()))
+        }
+    }
+
+    /// Handle `input` with `H`, and send the response.
+    fn exchange(machine: &mut AttServerMachine, input: &[u8]) -> Vec<Vec<u8>> {
+        let mut sent = vec![];
+        for action in machine.handle_bytes(input) {
+            match action {
+                Action::Respond(packet) => sent.push(packet),
+                Action::DeliverToHandler(recv) => {
+                    if let Some(packet) = machine.dispatch(&mut H, recv).unwrap() {
+                        sent.push(packet);
+                        machine.response_sent();
+                    }
+                }
+                Action::ConfirmationReceived => {}
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn test_exchange_mtu() {
+        let mut machine = AttServerMachine::new();
+        assert_eq!(machine.mtu(), DEFAULT_MTU);
+        assert_eq!(exchange(&mut machine, &[0x0A, 0x01, 0x00])[0].len(), 23);
+
+        let sent = exchange(&mut machine, &[0x02, 0xF7, 0x00]);
+        assert_eq!(sent, vec![vec![0x03, 0x64, 0x00]]);
+        assert_eq!(machine.tx_mtu(), 247);
+        assert_eq!(machine.rx_mtu(), 100);
+        assert_eq!(machine.mtu(), 100);
+        assert_eq!(exchange(&mut machine, &[0x0A, 0x01, 0x00])[0].len(), 100);
+        assert_eq!(
+            machine
+                .queue_notification(&Handle::new(1), &[0; 97])
+                .unwrap()
+                .len(),
+            100
+        );
+        assert!(matches!(
+            machine.queue_notification(&Handle::new(1), &[0; 98]),
+            Err(NotReady::ExceedsMtu { len: 98, mtu: 100 })
+        ));
+    }
+
+    #[test]
+    fn test_pre_exchange() {
+        let mut machine = AttServerMachine::new();
+        assert_eq!(machine.mtu_state(), MtuState::PreExchange);
+
+        // longer than 23 octets
+        let mut request = vec![0x12, 0x01, 0x00];
+        request.extend_from_slice(&[0xAA; 21]);
+        let actions = machine.handle_bytes(&request);
+        assert!(matches!(
+            &actions[..],
+            [Action::Respond(packet)] if packet == &[0x01, 0x12, 0x00, 0x00, 0x04]
+        ));
+        assert!(!machine.pending_request());
+        request[0] = 0x52;
+        assert!(machine.handle_bytes(&request).is_empty());
+
+        // 23 octets fit
+        request.pop();
+        let actions = machine.handle_bytes(&request);
+        assert!(matches!(
+            &actions[..],
+            [Action::DeliverToHandler(pkt::DeviceRecv::WriteCommand(..))]
+        ));
+
+        assert_eq!(
+            machine
+                .queue_notification(&Handle::new(1), &[0; 20])
+                .unwrap()
+                .len(),
+            23
+        );
+        assert!(matches!(
+            machine.queue_notification(&Handle::new(1), &[0; 21]),
+            Err(NotReady::ExceedsMtu { len: 21, mtu: 23 })
+        ));
+    }
+
+    #[test]
+    fn test_exchange_mtu_once() {
+        let mut machine = AttServerMachine::new();
+        exchange(&mut machine, &[0x02, 0xF7, 0x00]);
+        let exchanged = MtuState::Exchanged {
+            tx_mtu: 247,
+            rx_mtu: 100,
+        };
+        assert_eq!(machine.mtu_state(), exchanged);
+
+        let sent = exchange(&mut machine, &[0x02, 0x40, 0x00]);
+        assert_eq!(sent, vec![vec![0x01, 0x02, 0x00, 0x00, 0x06]]);
+        assert_eq!(machine.mtu_state(), exchanged);
+        assert!(!machine.pending_request());
+
+        // now up to 100 octets
+        let mut request = vec![0x12, 0x01, 0x00];
+        request.extend_from_slice(&[0xAA; 97]);
+        assert!(matches!(
+            &machine.handle_bytes(&request)[..],
+            [Action::DeliverToHandler(..)]
+        ));
+    }
+
+    #[test]
+    fn test_server_mtu() {
+        let mut machine = AttServerMachine::new();
+        machine.set_server_mtu(1000);
+        assert_eq!(machine.server_mtu(), Some(517));
+        machine.set_server_mtu(0);
+        assert_eq!(machine.server_mtu(), Some(23));
+
+        machine.set_server_mtu(100);
+        let sent = exchange(&mut machine, &[0x02, 0x05, 0x02]);
+        assert_eq!(sent, vec![vec![0x03, 0x64, 0x00]]);
+        assert_eq!(machine.tx_mtu(), 517);
+        assert_eq!(machine.rx_mtu(), 100);
+        assert_eq!(machine.mtu(), 100);
+    }
+
+    #[test]
+    fn test_exchange_mtu_below_default() {
+        let mut machine = AttServerMachine::new();
+        exchange(&mut machine, &[0x02, 0x05, 0x00]);
+        assert_eq!(machine.mtu(), DEFAULT_MTU);
+
+        let mut machine = AttServerMachine::new();
+        let response = Err(HandlerError::without_handle(
+            pkt::ErrorCode::RequestNotSupported,
+        ));
+        machine.handle_bytes(&[0x02, 0xF7, 0x00]);
+        let sent = machine
+            .respond::<pkt::ExchangeMtuRequest>(response)
+            .unwrap();
+        assert_eq!(sent, [0x01, 0x02, 0x00, 0x00, 0x06]);
+        assert_eq!(machine.mtu(), DEFAULT_MTU);
+        // refused, so the client may try again.
+        assert_eq!(machine.mtu_state(), MtuState::PreExchange);
+    }
+
+    #[test]
+    fn test_one_outstanding_request() {
+        let mut machine = AttServerMachine::new();
+        let actions = machine.handle_bytes(&[0x0A, 0x01, 0x00]);
+        assert!(matches!(
+            &actions[..],
+            [Action::DeliverToHandler(pkt::DeviceRecv::ReadRequest(..))]
+        ));
+        assert!(machine.pending_request());
+
+        let actions = machine.handle_bytes(&[0x0A, 0x02, 0x00]);
+        assert!(matches!(
+            &actions[..],
+            [Action::Respond(packet)] if packet == &[0x01, 0x0A, 0x02, 0x00, 0x11]
+        ));
+        let actions = machine.handle_bytes(&[0x10, 0x03, 0x00, 0xFF, 0xFF, 0x00, 0x28]);
+        assert!(matches!(
+            &actions[..],
+            [Action::Respond(packet)] if packet == &[0x01, 0x10, 0x03, 0x00, 0x11]
+        ));
+        // commands are not transactions
+        let actions = machine.handle_bytes(&[0x52, 0x01, 0x00, 0x01]);
+        assert!(matches!(
+            &actions[..],
+            [Action::DeliverToHandler(pkt::DeviceRecv::WriteCommand(..))]
+        ));
+
+        machine.response_sent();
+        assert!(!machine.pending_request());
+        let actions = machine.handle_bytes(&[0x0A, 0x02, 0x00]);
+        assert!(matches!(&actions[..], [Action::DeliverToHandler(..)]));
+    }
+
+    #[test]
+    fn test_one_outstanding_indication() {
+        let mut machine = AttServerMachine::new();
+        assert!(machine.handle_bytes(&[0x1E]).is_empty());
+
+        let packet = machine.queue_indication(&Handle::new(1), b"ok").unwrap();
+        assert_eq!(packet, [0x1D, 0x01, 0x00, 0x6F, 0x6B]);
+        assert!(machine.awaiting_confirmation());
+        assert!(matches!(
+            machine.queue_indication(&Handle::new(2), b"ok"),
+            Err(NotReady::AwaitingConfirmation)
+        ));
+        // notifications are not confirmed
+        assert_eq!(
+            machine.queue_notification(&Handle::new(2), b"ok").unwrap(),
+            [0x1B, 0x02, 0x00, 0x6F, 0x6B]
+        );
+
+        let actions = machine.handle_bytes(&[0x1E]);
+        assert!(matches!(&actions[..], [Action::ConfirmationReceived]));
+        assert!(!machine.awaiting_confirmation());
+        assert!(machine.handle_bytes(&[0x1E]).is_empty());
+        assert!(machine.queue_indication(&Handle::new(2), b"ok").is_ok());
+    }
+
+    #[test]
+    fn test_indication_exceeds_mtu() {
+        let mut machine = AttServerMachine::new();
+        assert!(matches!(
+            machine.queue_indication(&Handle::new(1), &[0; 21]),
+            Err(NotReady::ExceedsMtu { .. })
+        ));
+        assert!(!machine.awaiting_confirmation());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut machine = AttServerMachine::new();
+        assert!(machine.handle_bytes(&[]).is_empty());
+
+        // known request, too short
+        let actions = machine.handle_bytes(&[0x0A, 0x01]);
+        assert!(matches!(
+            &actions[..],
+            [Action::Respond(packet)] if packet == &[0x01, 0x0A, 0x00, 0x00, 0x04]
+        ));
+        // unknown request
+        let actions = machine.handle_bytes(&[0x3F, 0x01, 0x02]);
+        assert!(matches!(
+            &actions[..],
+            [Action::Respond(packet)] if packet == &[0x01, 0x3F, 0x00, 0x00, 0x06]
+        ));
+        // unknown command
+        assert!(machine.handle_bytes(&[0x7F, 0x01, 0x02]).is_empty());
+        // known command, too short
+        assert!(machine.handle_bytes(&[0x52, 0x01]).is_empty());
+        // PDU sent by servers
+        assert!(machine.handle_bytes(&[0x1B, 0x01, 0x00]).is_empty());
+        assert!(machine.handle_bytes(&[0x03, 0x17, 0x00]).is_empty());
+
+        assert!(!machine.pending_request());
+    }
+
+    #[test]
+    fn test_read_multiple_too_few_handles() {
+        let mut machine = AttServerMachine::new();
+        for bytes in [&[0x0E][..], &[0x0E, 0x01, 0x00]] {
+            let actions = machine.handle_bytes(bytes);
+            assert!(matches!(
+                &actions[..],
+                [Action::Respond(packet)] if packet == &[0x01, 0x0E, 0x00, 0x00, 0x04]
+            ));
+        }
+
+        let mut actions = machine.handle_bytes(&[0x0E, 0x01, 0x00, 0x02, 0x00]);
+        let recv = match actions.pop() {
+            Some(Action::DeliverToHandler(recv)) => recv,
+            action => panic!("{:?}", action),
+        };
+        let packet = machine.dispatch(&mut H, recv).unwrap().unwrap();
+        assert_eq!(packet, [0x01, 0x0E, 0x01, 0x00, 0x06]);
+    }
+
+    /// Read Response ignoring the ATT_MTU, as a faulty `truncate` would.
+    #[derive(Debug)]
+    struct Untruncated(Box<[u8]>);
+
+    impl Packet for Untruncated {
+        const OPCODE: pkt::OpCode = pkt::OpCode::ReadResponse;
+    }
+
+    impl Pack for Untruncated {
+        fn pack<W>(self, write: &mut W) -> pack::Result<()>
+        where
+            W: pack::Write,
+        {
+            self.0.pack(write)
+        }
+    }
+
+    impl DeviceSend for Untruncated {}
+
+    impl pkt::Response for Untruncated {
+        fn truncate(&mut self, _: usize) {}
+    }
+
+    #[derive(Debug)]
+    struct UntruncatedRead;
+
+    impl Packet for UntruncatedRead {
+        const OPCODE: pkt::OpCode = pkt::OpCode::ReadRequest;
+    }
+
+    impl TryFrom<pkt::DeviceRecv> for UntruncatedRead {
+        type Error = pkt::DeviceRecv;
+
+        fn try_from(v: pkt::DeviceRecv) -> Result<Self, Self::Error> {
+            Err(v)
+        }
+    }
+
+    impl pkt::Request for UntruncatedRead {
+        type Response = Untruncated;
+    }
+
+    #[test]
+    fn test_response_too_large() {
+        let mut machine = AttServerMachine::new();
+        machine.handle_bytes(&[0x0A, 0x05, 0x00]);
+        let response = Untruncated(vec![0xAA; DEFAULT_MTU].into());
+        let packet = machine.respond::<UntruncatedRead>(Ok(response)).unwrap();
+        assert_eq!(packet, [0x01, 0x0A, 0x05, 0x00, 0x0E]);
+        machine.response_sent();
+
+        let response = Untruncated(vec![0xAA; DEFAULT_MTU - 1].into());
+        machine.handle_bytes(&[0x0A, 0x01, 0x00]);
+        let packet = machine.respond::<UntruncatedRead>(Ok(response)).unwrap();
+        assert_eq!(packet.len(), DEFAULT_MTU);
+    }
+}
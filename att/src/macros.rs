@@ -1,3 +1,26 @@
+/// `log::debug!`, or `tracing::debug!` with the `tracing` feature.
+#[cfg(feature = "server")]
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        log::debug!($($arg)*);
+    }};
+}
+
+/// Trace a received or sent PDU. With the `tracing` feature, `direction`
+/// and `packet` are recorded as fields.
+#[cfg(feature = "server")]
+macro_rules! trace_pdu {
+    ($direction:literal, $packet:expr) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!(direction = $direction, packet = ?$packet);
+        #[cfg(not(feature = "tracing"))]
+        log::trace!("packet {} {:?}", $direction, $packet);
+    }};
+}
+
 macro_rules! packable_enum {
     (
         $(#[$attrs:meta])*
@@ -17,18 +40,18 @@ macro_rules! packable_enum {
         }
 
         impl $crate::packet::pack::Pack for $name {
-            fn pack<W>(self, write: &mut W) -> $crate::packet::pack::Result<()> where W: std::io::Write {
+            fn pack<W>(self, write: &mut W) -> $crate::packet::pack::Result<()> where W: $crate::packet::pack::Write {
                 <$ty as $crate::packet::pack::Pack>::pack(self as $ty, write)
             }
         }
 
         impl $crate::packet::pack::Unpack for $name {
-            fn unpack<R>(read: &mut R) -> $crate::packet::pack::Result<Self> where R: std::io::Read {
+            fn unpack<R>(read: &mut R) -> $crate::packet::pack::Result<Self> where R: $crate::packet::pack::Read {
                 #![allow(non_upper_case_globals)]
                 $( const $vname: $ty = $name::$vname as $ty; )*
                 Ok(match <$ty as $crate::packet::pack::Unpack>::unpack(read)? {
                     $( $vname => Self::$vname, )*
-                    unknown => return Err($crate::packet::pack::Error::Unexpected(format!("{:X?}", unknown))),
+                    unknown => return Err($crate::packet::pack::Error::unexpected(alloc::format!("{:X?}", unknown))),
                 })
             }
         }
@@ -44,13 +67,13 @@ macro_rules! packable_newtype {
         $vis struct $name ( $ty );
 
         impl $crate::packet::pack::Pack for $name {
-            fn pack<W>(self, write: &mut W) -> $crate::packet::pack::Result<()> where W: std::io::Write {
+            fn pack<W>(self, write: &mut W) -> $crate::packet::pack::Result<()> where W: $crate::packet::pack::Write {
                 self.0.pack(write)
             }
         }
 
         impl $crate::packet::pack::Unpack for $name {
-            fn unpack<R>(read: &mut R) -> $crate::packet::pack::Result<Self> where R: std::io::Read {
+            fn unpack<R>(read: &mut R) -> $crate::packet::pack::Result<Self> where R: $crate::packet::pack::Read {
                 Ok(Self($crate::packet::pack::Unpack::unpack(read)?))
             }
         }
@@ -76,14 +99,14 @@ macro_rules! packable_struct {
         }
 
         impl $crate::packet::pack::Pack for $name {
-            fn pack<W>(self, #[allow(unused_variables)]write: &mut W) -> $crate::packet::pack::Result<()> where W: std::io::Write {
+            fn pack<W>(self, #[allow(unused_variables)]write: &mut W) -> $crate::packet::pack::Result<()> where W: $crate::packet::pack::Write {
                 $( self.$fname.pack(write)?; )*
                 Ok(())
             }
         }
 
         impl $crate::packet::pack::Unpack for $name {
-            fn unpack<R>(#[allow(unused_variables)]read: &mut R) -> $crate::packet::pack::Result<Self> where R: std::io::Read {
+            fn unpack<R>(#[allow(unused_variables)]read: &mut R) -> $crate::packet::pack::Result<Self> where R: $crate::packet::pack::Read {
                 Ok(Self {
                     $( $fname : $crate::packet::pack::Unpack::unpack(read)?, )*
                 })
@@ -1,11 +1,14 @@
 //! ATT Protocol Packet
-use std::convert::TryFrom;
-use std::fmt;
-use std::io;
-use std::num::{NonZeroU8, NonZeroUsize};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::num::{NonZeroU8, NonZeroUsize};
 
 use derive_new::new as New;
-use getset::Getters;
+use getset::{Getters, WithSetters};
 
 //use crate::pack::{Error as UnpackError, Pack, Unpack};
 use crate::size::Size;
@@ -14,14 +17,17 @@ use crate::{Handle, Uuid};
 use pack::{Error as PackError, Pack, Result as PackResult, Unpack};
 
 #[macro_use]
-pub(crate) mod pack;
+pub mod pack;
 mod impls;
 
 /// ATT Error Response - Error Code
 ///
 /// see BLUETOOTH CORE SPECIFICATION Version 5.1 |Vol 3, Part F
 ///     Table 3.3: Error Codes
-#[derive(Debug, PartialEq, Eq)]
+///
+/// Ordered by declaration, which is the order of the codes except for
+/// `ReservedForFutureUse` that sorts last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ErrorCode {
     /// Invalid Handle
     InvalidHandle,
@@ -72,7 +78,7 @@ pub enum ErrorCode {
 impl Pack for ErrorCode {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         let v = match self {
             Self::InvalidHandle => 0x01,
@@ -105,7 +111,7 @@ impl Pack for ErrorCode {
 impl Unpack for ErrorCode {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         Ok(match u8::unpack(read)? {
             0x01 => Self::InvalidHandle,
@@ -140,7 +146,7 @@ struct HandlesInformationList(Vec<(Handle, Handle)>);
 impl Pack for HandlesInformationList {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         pack::RemainingVec(self.0).pack(write)
     }
@@ -149,7 +155,7 @@ impl Pack for HandlesInformationList {
 impl Unpack for HandlesInformationList {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         let v = pack::RemainingVec::<(Handle, Handle)>::unpack(read)?;
         Ok(Self(v.0))
@@ -161,7 +167,7 @@ struct SetOfHandles(Vec<Handle>);
 
 impl<'a> IntoIterator for &'a SetOfHandles {
     type Item = &'a Handle;
-    type IntoIter = std::slice::Iter<'a, Handle>;
+    type IntoIter = core::slice::Iter<'a, Handle>;
     fn into_iter(self) -> Self::IntoIter {
         self.0.iter()
     }
@@ -170,7 +176,7 @@ impl<'a> IntoIterator for &'a SetOfHandles {
 impl Pack for SetOfHandles {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         pack::RemainingVec(self.0).pack(write)
     }
@@ -179,9 +185,13 @@ impl Pack for SetOfHandles {
 impl Unpack for SetOfHandles {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         let v = pack::RemainingVec::<Handle>::unpack(read)?;
+        // 3.4.4.7: two or more handles.
+        if v.0.len() < 2 {
+            return Err(PackError::unexpected("set of handles"));
+        }
         Ok(Self(v.0))
     }
 }
@@ -204,20 +214,21 @@ impl AttributeData for (Handle, Uuid) {
         Ok(match val.get() {
             0x01 => NonZeroUsize::new(2 + 2).unwrap(),
             0x02 => NonZeroUsize::new(2 + 16).unwrap(),
-            unknown => return Err(PackError::Unexpected(format!("format {}", unknown))),
+            unknown => return Err(PackError::unexpected(format!("format {}", unknown))),
         })
     }
 }
 
 impl AttributeData for (Handle, Box<[u8]>) {
     fn format(&self) -> NonZeroU8 {
-        NonZeroU8::new(2 + self.1.len() as u8).unwrap()
+        // longer values do not fit in the Length field and fail to pack.
+        NonZeroU8::new(u8::try_from(2 + self.1.len()).unwrap_or(u8::MAX)).unwrap()
     }
 }
 
 impl AttributeData for (Handle, Handle, Box<[u8]>) {
     fn format(&self) -> NonZeroU8 {
-        NonZeroU8::new(2 + 2 + self.2.len() as u8).unwrap()
+        NonZeroU8::new(u8::try_from(2 + 2 + self.2.len()).unwrap_or(u8::MAX)).unwrap()
     }
 }
 
@@ -230,7 +241,7 @@ where
 {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         if self.0.is_empty() {
             return 0u8.pack(write);
@@ -246,7 +257,7 @@ where
             let mut w = &mut buf[..];
             data.pack(&mut w)?;
             if !w.is_empty() {
-                return Err(PackError::Unexpected("length".into()));
+                return Err(PackError::unexpected("length"));
             }
             write.write_all(&buf)?;
         }
@@ -260,7 +271,7 @@ where
 {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         let format = u8::unpack(read)?;
         let format = if let Some(format) = NonZeroU8::new(format) {
@@ -294,6 +305,19 @@ where
     }
 }
 
+/// Error for `OpCode::try_from`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpCode(pub u8);
+
+impl fmt::Display for UnknownOpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown op code 0x{:02X}.", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownOpCode {}
+
 macro_rules! packet {
     (
         $(
@@ -324,12 +348,29 @@ macro_rules! packet {
 
         packable_enum! {
             /// ATT Op Codes
-            #[derive(Debug, PartialEq, Eq, Hash)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
             pub enum OpCode: u8 {
                 $($name = $op,)*
             }
         }
 
+        impl From<OpCode> for u8 {
+            fn from(v: OpCode) -> Self {
+                v as u8
+            }
+        }
+
+        impl TryFrom<u8> for OpCode {
+            type Error = UnknownOpCode;
+
+            fn try_from(v: u8) -> Result<Self, Self::Error> {
+                match v {
+                    $($op => Ok(Self::$name),)*
+                    v => Err(UnknownOpCode(v)),
+                }
+            }
+        }
+
         /// ATT Packet
         pub trait Packet: fmt::Debug {
             const OPCODE: OpCode;
@@ -344,30 +385,36 @@ macro_rules! packet {
 
 packet! {
     /// Error Response
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, WithSetters)]
+    #[set_with = "pub"]
     pub struct ErrorResponse: 0x01 {
         request_opcode_in_error: OpCode,
         attribute_handle_in_error: Handle,
+        /// Reason the request failed.
+        #[get = "pub"]
         error_code: ErrorCode,
     }
 
     /// Exchange MTU Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ExchangeMtuRequest: 0x02 {
         client_rx_mtu: u16,
     }
 
     /// Exchange MTU Response
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ExchangeMtuResponse: 0x03 {
         server_rx_mtu: u16,
     }
 
     /// Find Information Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct FindInformationRequest: 0x04 {
         starting_handle: Handle,
         ending_handle: Handle,
@@ -380,8 +427,9 @@ packet! {
     }
 
     /// Find By Type Value Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct FindByTypeValueRequest: 0x06 {
         starting_handle: Handle,
         ending_handle: Handle,
@@ -396,8 +444,9 @@ packet! {
     }
 
     /// Read By Type Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadByTypeRequest: 0x08 {
         starting_handle: Handle,
         ending_handle: Handle,
@@ -411,30 +460,34 @@ packet! {
     }
 
     /// Read Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadRequest: 0x0A {
         attribute_handle: Handle,
     }
 
     /// Read Response
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadResponse: 0x0B {
         attribute_value: Box<[u8]>,
     }
 
     /// Read Blob Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadBlobRequest: 0x0C {
         attribute_handle: Handle,
         attribute_offset: u16,
     }
 
     /// Read Blob Response
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadBlobResponse: 0x0D {
         attribute_value: Box<[u8]>,
     }
@@ -446,14 +499,16 @@ packet! {
     }
 
     /// Read Multiple Response
-    #[derive(Debug, New)]
+    #[derive(Debug, New, Default, WithSetters)]
+    #[set_with = "pub"]
     pub struct ReadMultipleResponse: 0x0F {
         set_of_values: Box<[u8]>, // FIXME
     }
 
     /// Read By Group Type Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ReadByGroupTypeRequest: 0x10 {
         starting_handle: Handle,
         ending_handle: Handle,
@@ -467,8 +522,9 @@ packet! {
     }
 
     /// Write Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct WriteRequest: 0x12 {
         attribute_handle: Handle,
         attribute_value: Box<[u8]>,
@@ -480,16 +536,18 @@ packet! {
     }
 
     /// Write Command
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct WriteCommand: 0x52 {
         attribute_handle: Handle,
         attribute_value: Box<[u8]>,
     }
 
     /// Signed Write Command
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct SignedWriteCommand: 0xD2 {
         attribute_handle: Handle,
         attribute_value: Box<[u8]>,
@@ -497,8 +555,9 @@ packet! {
     }
 
     /// Prepare Write Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct PrepareWriteRequest: 0x16 {
         attribute_handle: Handle,
         value_offset: u16,
@@ -506,8 +565,9 @@ packet! {
     }
 
     /// Prepare Write Response
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct PrepareWriteResponse: 0x17 {
         attribute_handle: Handle,
         value_offset: u16,
@@ -515,8 +575,9 @@ packet! {
     }
 
     /// Execute Write Request
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct ExecuteWriteRequest: 0x18 {
         flags: bool,
     }
@@ -527,16 +588,18 @@ packet! {
     }
 
     /// Handle Value Notification
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct HandleValueNotification: 0x1B {
         attribute_handle: Handle,
         attribute_value: Box<[u8]>,
     }
 
     /// Handle Value Indication
-    #[derive(Debug, New, Getters)]
+    #[derive(Debug, New, Getters, Default, WithSetters)]
     #[get = "pub"]
+    #[set_with = "pub"]
     pub struct HandleValueIndication: 0x1D {
         attribute_handle: Handle,
         attribute_value: Box<[u8]>,
@@ -558,6 +621,7 @@ macro_rules! device_recv {
             $( $ident($ident), )*
         }
 
+        #[allow(dead_code)]
         trait AssertUnpack: Packet + Unpack + Sized {}
 
         $(
@@ -571,7 +635,7 @@ macro_rules! device_recv {
 
             impl TryFrom<DeviceRecv> for $ident {
                 type Error = DeviceRecv;
-                fn try_from(v: DeviceRecv) -> std::result::Result<Self, Self::Error> {
+                fn try_from(v: DeviceRecv) -> core::result::Result<Self, Self::Error> {
                     match v {
                         DeviceRecv::$ident(v) => Ok(v),
                         v => Err(v),
@@ -580,11 +644,26 @@ macro_rules! device_recv {
             }
         )*
 
+        impl DeviceRecv {
+            /// Op Code of the contained packet.
+            pub fn opcode(&self) -> OpCode {
+                match self {
+                    $( Self::$ident(..) => $ident::OPCODE, )*
+                }
+            }
+
+            /// Op Code of a packed PDU, without unpacking its parameters.
+            /// `None` if `bytes` is empty or the Op Code is unknown.
+            pub fn peek_opcode(bytes: &[u8]) -> Option<OpCode> {
+                OpCode::try_from(*bytes.first()?).ok()
+            }
+        }
+
         impl Unpack for DeviceRecv {
-            fn unpack<R>(read: &mut R) -> PackResult<Self> where R: io::Read {
+            fn unpack<R>(read: &mut R) -> PackResult<Self> where R: pack::Read {
                 Ok(match OpCode::unpack(read)? {
                     $( OpCode::$ident => $ident::unpack(read)?.into(), )*
-                    unknown => return Err(PackError::Unexpected(format!("{:?}", unknown))),
+                    unknown => return Err(PackError::unexpected(format!("{:?}", unknown))),
                 })
             }
         }
@@ -636,7 +715,7 @@ device_send![
 pub trait DeviceSend: Packet + Pack + Sized {
     fn pack_with_code<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         Self::OPCODE.pack(write)?;
         self.pack(write)?;
@@ -669,6 +748,18 @@ pub trait Indication: Packet + DeviceSend {
 /// ATT Confirmation
 pub trait Confirmation: Packet + TryFrom<DeviceRecv> {}
 
+impl ErrorResponse {
+    /// Opcode of the request that generated this error.
+    pub fn request_opcode(&self) -> &OpCode {
+        &self.request_opcode_in_error
+    }
+
+    /// Handle of the attribute the request was for, or 0x0000.
+    pub fn attribute_handle(&self) -> &Handle {
+        &self.attribute_handle_in_error
+    }
+}
+
 impl Response for ErrorResponse {
     fn truncate(&mut self, _: usize) {}
 }
@@ -685,7 +776,7 @@ impl Request for FindInformationRequest {
 }
 impl Response for FindInformationResponse {
     fn truncate(&mut self, mtu: usize) {
-        let mut remaining = mtu - 2;
+        let mut remaining = mtu.saturating_sub(2);
         let mut len = 0;
         for item in &self.values.0 {
             let item_len = item.size();
@@ -704,7 +795,7 @@ impl Request for FindByTypeValueRequest {
 }
 impl Response for FindByTypeValueResponse {
     fn truncate(&mut self, mtu: usize) {
-        let mut remaining = mtu - 1;
+        let mut remaining = mtu.saturating_sub(1);
         let mut len = 0;
         for item in &self.values.0 {
             let item_len = item.size();
@@ -723,7 +814,14 @@ impl Request for ReadByTypeRequest {
 }
 impl Response for ReadByTypeResponse {
     fn truncate(&mut self, mtu: usize) {
-        let mut remaining = mtu - 2;
+        // 3.4.4.2: at most (ATT_MTU - 4) or 253 octets of each value.
+        let max = mtu.saturating_sub(4).min(253);
+        for (_, value) in &mut self.values.0 {
+            if value.len() > max {
+                *value = (&value[..max]).into();
+            }
+        }
+        let mut remaining = mtu.saturating_sub(2);
         let mut len = 0;
         for item in &self.values.0 {
             let item_len = item.size();
@@ -742,8 +840,8 @@ impl Request for ReadRequest {
 }
 impl Response for ReadResponse {
     fn truncate(&mut self, mtu: usize) {
-        if self.attribute_value.len() > mtu - 1 {
-            self.attribute_value = (&self.attribute_value[..mtu - 1]).into();
+        if self.attribute_value.len() > mtu.saturating_sub(1) {
+            self.attribute_value = (&self.attribute_value[..mtu.saturating_sub(1)]).into();
         }
     }
 }
@@ -753,8 +851,8 @@ impl Request for ReadBlobRequest {
 }
 impl Response for ReadBlobResponse {
     fn truncate(&mut self, mtu: usize) {
-        if self.attribute_value.len() > mtu - 1 {
-            self.attribute_value = (&self.attribute_value[..mtu - 1]).into();
+        if self.attribute_value.len() > mtu.saturating_sub(1) {
+            self.attribute_value = (&self.attribute_value[..mtu.saturating_sub(1)]).into();
         }
     }
 }
@@ -764,8 +862,8 @@ impl Request for ReadMultipleRequest {
 }
 impl Response for ReadMultipleResponse {
     fn truncate(&mut self, mtu: usize) {
-        if self.set_of_values.len() > mtu - 1 {
-            self.set_of_values = (&self.set_of_values[..mtu - 1]).into();
+        if self.set_of_values.len() > mtu.saturating_sub(1) {
+            self.set_of_values = (&self.set_of_values[..mtu.saturating_sub(1)]).into();
         }
     }
 }
@@ -775,7 +873,14 @@ impl Request for ReadByGroupTypeRequest {
 }
 impl Response for ReadByGroupTypeResponse {
     fn truncate(&mut self, mtu: usize) {
-        let mut remaining = mtu - 2;
+        // 3.4.4.10: at most (ATT_MTU - 6) or 251 octets of each value.
+        let max = mtu.saturating_sub(6).min(251);
+        for (_, _, value) in &mut self.values.0 {
+            if value.len() > max {
+                *value = (&value[..max]).into();
+            }
+        }
+        let mut remaining = mtu.saturating_sub(2);
         let mut len = 0;
         for item in &self.values.0 {
             let item_len = item.size();
@@ -803,8 +908,10 @@ impl Request for PrepareWriteRequest {
 }
 impl Response for PrepareWriteResponse {
     fn truncate(&mut self, mtu: usize) {
-        if self.part_attribute_value.len() > mtu - 1 {
-            self.part_attribute_value = (&self.part_attribute_value[..mtu - 1]).into();
+        // Op Code, Attribute Handle and Value Offset
+        if self.part_attribute_value.len() > mtu.saturating_sub(5) {
+            self.part_attribute_value =
+                (&self.part_attribute_value[..mtu.saturating_sub(5)]).into();
         }
     }
 }
@@ -836,7 +943,7 @@ impl<'a> Packet for HandleValueNotificationBorrow<'a> {
 impl<'a> Pack for HandleValueNotificationBorrow<'a> {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         self.0.pack(write)?;
         write.write_all(self.1)?;
@@ -859,7 +966,7 @@ impl<'a> Packet for HandleValueIndicationBorrow<'a> {
 impl<'a> Pack for HandleValueIndicationBorrow<'a> {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         self.0.pack(write)?;
         write.write_all(self.1)?;
@@ -872,3 +979,296 @@ impl<'a> DeviceSend for HandleValueIndicationBorrow<'a> {}
 impl<'a> Indication for HandleValueNotificationBorrow<'a> {
     type Confirmation = HandleValueConfirmation;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn to_bytes<P>(packet: P) -> Vec<u8>
+    where
+        P: Packet + Pack,
+    {
+        let mut buf = vec![];
+        P::OPCODE.pack(&mut buf).unwrap();
+        packet.pack(&mut buf).unwrap();
+        buf
+    }
+
+    fn recv<P>(bytes: &[u8]) -> P
+    where
+        P: TryFrom<DeviceRecv>,
+        P::Error: fmt::Debug,
+    {
+        let packet = DeviceRecv::unpack(&mut &bytes[..]).unwrap();
+        P::try_from(packet).unwrap()
+    }
+
+    fn uuid() -> impl Strategy<Value = Uuid> {
+        prop_oneof![
+            any::<u16>().prop_map(Uuid::new_uuid16),
+            any::<u128>().prop_map(Uuid::new_uuid128),
+        ]
+    }
+
+    fn value() -> impl Strategy<Value = Box<[u8]>> {
+        proptest::collection::vec(any::<u8>(), 0..=512).prop_map(Into::into)
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip_exchange_mtu_request(mtu in any::<u16>()) {
+            let bytes = to_bytes(ExchangeMtuRequest::new(mtu));
+            let packet = recv::<ExchangeMtuRequest>(&bytes);
+            prop_assert_eq!(*packet.client_rx_mtu(), mtu);
+        }
+
+        #[test]
+        fn roundtrip_read_request(handle in any::<u16>()) {
+            let bytes = to_bytes(ReadRequest::new(handle.into()));
+            let packet = recv::<ReadRequest>(&bytes);
+            prop_assert_eq!(packet.attribute_handle(), &Handle::from(handle));
+        }
+
+        #[test]
+        fn roundtrip_read_by_type_request(
+            start in any::<u16>(),
+            end in any::<u16>(),
+            uuid in uuid(),
+        ) {
+            let bytes = to_bytes(ReadByTypeRequest::new(start.into(), end.into(), uuid.clone()));
+            let packet = recv::<ReadByTypeRequest>(&bytes);
+            prop_assert_eq!(packet.starting_handle(), &Handle::from(start));
+            prop_assert_eq!(packet.ending_handle(), &Handle::from(end));
+            prop_assert_eq!(packet.attribute_type(), &uuid);
+        }
+
+        #[test]
+        fn roundtrip_write_request(handle in any::<u16>(), value in value()) {
+            let bytes = to_bytes(WriteRequest::new(handle.into(), value.clone()));
+            let packet = recv::<WriteRequest>(&bytes);
+            prop_assert_eq!(packet.attribute_handle(), &Handle::from(handle));
+            prop_assert_eq!(packet.attribute_value(), &value);
+        }
+
+        #[test]
+        fn roundtrip_handle_value_notification(handle in any::<u16>(), value in value()) {
+            let bytes = to_bytes(HandleValueNotification::new(handle.into(), value.clone()));
+
+            let mut borrow = vec![];
+            HandleValueNotificationBorrow::new(handle.into(), &value)
+                .pack_with_code(&mut borrow)
+                .unwrap();
+            prop_assert_eq!(&borrow, &bytes);
+
+            prop_assert_eq!(OpCode::unpack(&mut &bytes[..1]).unwrap(), OpCode::HandleValueNotification);
+            let packet = HandleValueNotification::unpack(&mut &bytes[1..]).unwrap();
+            prop_assert_eq!(packet.attribute_handle(), &Handle::from(handle));
+            prop_assert_eq!(packet.attribute_value(), &value);
+        }
+
+        #[test]
+        fn roundtrip_handle_value_indication(handle in any::<u16>(), value in value()) {
+            let bytes = to_bytes(HandleValueIndication::new(handle.into(), value.clone()));
+
+            let mut borrow = vec![];
+            HandleValueIndicationBorrow::new(handle.into(), &value)
+                .pack_with_code(&mut borrow)
+                .unwrap();
+            prop_assert_eq!(&borrow, &bytes);
+
+            prop_assert_eq!(OpCode::unpack(&mut &bytes[..1]).unwrap(), OpCode::HandleValueIndication);
+            let packet = HandleValueIndication::unpack(&mut &bytes[1..]).unwrap();
+            prop_assert_eq!(packet.attribute_handle(), &Handle::from(handle));
+            prop_assert_eq!(packet.attribute_value(), &value);
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut response = ReadResponse::new(vec![0; 10].into());
+        response.truncate(0);
+        assert!(response.attribute_value().is_empty());
+
+        let mut response = ReadByTypeResponse {
+            values: AttributeDataList(vec![(Handle::new(1), vec![0; 300].into())]),
+        };
+        response.truncate(512);
+        assert_eq!(response.values.0[0].1.len(), 253);
+        let mut buf = vec![];
+        response.pack(&mut buf).unwrap();
+        assert_eq!(buf[0], 255);
+        assert_eq!(buf.len(), 1 + 255);
+
+        let mut response = ReadByGroupTypeResponse {
+            values: AttributeDataList(vec![(Handle::new(1), Handle::new(2), vec![0; 30].into())]),
+        };
+        response.truncate(23);
+        assert_eq!(response.values.0[0].2.len(), 17);
+
+        let mut response = PrepareWriteResponse::new(Handle::new(1), 0, vec![0; 30].into());
+        response.truncate(23);
+        assert_eq!(to_bytes(response).len(), 23);
+    }
+
+    #[test]
+    fn test_opcode_try_from() {
+        for v in 0..=u8::MAX {
+            match OpCode::try_from(v) {
+                Ok(opcode) => {
+                    assert_eq!(u8::from(opcode), v);
+                    assert_eq!(
+                        OpCode::unpack(&mut &[v][..]).unwrap(),
+                        OpCode::try_from(v).unwrap()
+                    );
+                }
+                Err(err) => {
+                    assert_eq!(err, UnknownOpCode(v));
+                    assert!(OpCode::unpack(&mut &[v][..]).is_err());
+                }
+            }
+        }
+        assert_eq!(OpCode::try_from(0x0A), Ok(OpCode::ReadRequest));
+        assert_eq!(u8::from(OpCode::SignedWriteCommand), 0xD2);
+        assert_eq!(
+            OpCode::try_from(0x3F).unwrap_err().to_string(),
+            "unknown op code 0x3F."
+        );
+    }
+
+    #[test]
+    fn test_device_recv_opcode() {
+        let recv = DeviceRecv::from(ReadRequest::new(Handle::new(3)));
+        assert_eq!(recv.opcode(), OpCode::ReadRequest);
+        let recv = DeviceRecv::from(HandleValueConfirmation::new());
+        assert_eq!(recv.opcode(), OpCode::HandleValueConfirmation);
+
+        let bytes = [0x12, 0x03];
+        assert_eq!(DeviceRecv::peek_opcode(&bytes), Some(OpCode::WriteRequest));
+        assert!(DeviceRecv::unpack(&mut &bytes[..]).is_err());
+        assert_eq!(DeviceRecv::peek_opcode(&[0x3F]), None);
+        assert_eq!(DeviceRecv::peek_opcode(&[]), None);
+    }
+
+    #[test]
+    fn test_error_code_collections() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let codes = [0xE0, 0x0A, 0x80, 0x01, 0x13, 0x0A, 0x81]
+            .iter()
+            .map(|v| ErrorCode::unpack(&mut &[*v][..]).unwrap())
+            .collect::<BTreeSet<_>>();
+        let codes = codes
+            .into_iter()
+            .map(|code| {
+                let mut buf = vec![];
+                code.pack(&mut buf).unwrap();
+                buf[0]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(codes, [0x01, 0x0A, 0x13, 0x80, 0x81, 0xE0]);
+
+        let codes = vec![ErrorCode::InvalidHandle, ErrorCode::InvalidHandle]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(codes.len(), 1);
+    }
+
+    #[test]
+    fn test_error_response_null_handle() {
+        // requests no handle applies to are answered with 0x0000. (3.4.1.1)
+        let response = ErrorResponse::new(
+            OpCode::ExecuteWriteRequest,
+            Handle::NULL,
+            ErrorCode::UnlikelyError,
+        );
+        let bytes = to_bytes(response);
+        assert_eq!(bytes, [0x01, 0x18, 0x00, 0x00, 0x0E]);
+        let response = ErrorResponse::unpack(&mut &bytes[1..]).unwrap();
+        assert_eq!(response.request_opcode(), &OpCode::ExecuteWriteRequest);
+        assert_eq!(response.attribute_handle(), &Handle::NULL);
+        assert_eq!(response.error_code(), &ErrorCode::UnlikelyError);
+    }
+
+    #[test]
+    fn test_hash_keys() {
+        use std::collections::{HashMap, HashSet};
+
+        let mut handles = HashMap::<Handle, ()>::new();
+        handles.insert(Handle::new(1), ());
+        assert!(handles.insert(Handle::new(1), ()).is_some());
+
+        let uuids = vec![Uuid16::new(0x1800), Uuid16::new(0x1800)]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(uuids.len(), 1);
+
+        let filter = vec![OpCode::ReadRequest, OpCode::WriteRequest]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert!(filter.contains(&OpCode::ReadRequest));
+        assert!(!filter.contains(&OpCode::WriteCommand));
+    }
+
+    #[test]
+    fn test_response_constructors() {
+        let response = FindByTypeValueResponse::with_handles(vec![
+            (Handle::new(1), Handle::new(5)),
+            (Handle::new(6), Handle::new(9)),
+        ]);
+        assert_eq!(response.len(), 2);
+        assert_eq!(
+            response.handles().collect::<Vec<_>>(),
+            [
+                (&Handle::new(1), &Handle::new(5)),
+                (&Handle::new(6), &Handle::new(9))
+            ]
+        );
+        assert_eq!(to_bytes(response), [0x07, 1, 0, 5, 0, 6, 0, 9, 0]);
+
+        let response = FindInformationResponse::with_values(vec![]);
+        assert!(response.is_empty());
+        assert_eq!(response.values().count(), 0);
+
+        let response = ReadByTypeResponse::with_values(vec![(Handle::new(3), vec![1, 2].into())]);
+        assert_eq!(
+            response.values().collect::<Vec<_>>(),
+            [(&Handle::new(3), &[1, 2][..])]
+        );
+
+        let response = ReadByGroupTypeResponse::with_values(vec![(
+            Handle::new(1),
+            Handle::new(4),
+            vec![0x0F, 0x18].into(),
+        )]);
+        assert_eq!(response.len(), 1);
+        assert_eq!(
+            response.values().next(),
+            Some((&Handle::new(1), &Handle::new(4), &[0x0F, 0x18][..]))
+        );
+    }
+
+    #[test]
+    fn test_with_setters() {
+        let response = PrepareWriteResponse::default()
+            .with_attribute_handle(Handle::new(3))
+            .with_value_offset(1)
+            .with_part_attribute_value(vec![0xAA].into());
+        assert_eq!(
+            to_bytes(response),
+            to_bytes(PrepareWriteResponse::new(
+                Handle::new(3),
+                1,
+                vec![0xAA].into()
+            ))
+        );
+
+        let response = ErrorResponse::new(
+            OpCode::ReadRequest,
+            Handle::new(1),
+            ErrorCode::ReadNotPermitted,
+        )
+        .with_error_code(ErrorCode::InsufficientAuthentication);
+        assert_eq!(to_bytes(response), [0x01, 0x0A, 0x01, 0x00, 0x05]);
+    }
+}
@@ -1,4 +1,5 @@
-use std::iter::{Extend, FromIterator};
+use alloc::boxed::Box;
+use core::iter::{Extend, FromIterator};
 
 use super::*;
 
@@ -58,9 +59,85 @@ impl Extend<(Handle, Handle, Box<[u8]>)> for ReadByGroupTypeResponse {
     }
 }
 
+impl FindInformationResponse {
+    pub fn with_values(values: impl IntoIterator<Item = (Handle, Uuid)>) -> Self {
+        values.into_iter().collect()
+    }
+
+    /// Handles and types of the attributes found.
+    pub fn values(&self) -> impl Iterator<Item = (&Handle, &Uuid)> {
+        self.values.0.iter().map(|(h, u)| (h, u))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+}
+
+impl FindByTypeValueResponse {
+    pub fn with_handles(pairs: impl IntoIterator<Item = (Handle, Handle)>) -> Self {
+        pairs.into_iter().collect()
+    }
+
+    /// Found Attribute Handles and Group End Handles.
+    pub fn handles(&self) -> impl Iterator<Item = (&Handle, &Handle)> {
+        self.values.0.iter().map(|(h, e)| (h, e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+}
+
+impl ReadByTypeResponse {
+    pub fn with_values(values: impl IntoIterator<Item = (Handle, Box<[u8]>)>) -> Self {
+        values.into_iter().collect()
+    }
+
+    /// Handles and values of the attributes read.
+    pub fn values(&self) -> impl Iterator<Item = (&Handle, &[u8])> {
+        self.values.0.iter().map(|(h, v)| (h, &v[..]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+}
+
+impl ReadByGroupTypeResponse {
+    pub fn with_values(values: impl IntoIterator<Item = (Handle, Handle, Box<[u8]>)>) -> Self {
+        values.into_iter().collect()
+    }
+
+    /// Attribute Handles, End Group Handles and values of the groups read.
+    pub fn values(&self) -> impl Iterator<Item = (&Handle, &Handle, &[u8])> {
+        self.values.0.iter().map(|(h, e, v)| (h, e, &v[..]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+}
+
 impl IntoIterator for ReadMultipleRequest {
     type Item = Handle;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
     fn into_iter(self) -> Self::IntoIter {
         self.set_of_handles.0.into_iter()
     }
@@ -68,7 +145,7 @@ impl IntoIterator for ReadMultipleRequest {
 
 impl<'a> IntoIterator for &'a ReadMultipleRequest {
     type Item = &'a Handle;
-    type IntoIter = std::slice::Iter<'a, Handle>;
+    type IntoIter = core::slice::Iter<'a, Handle>;
     fn into_iter(self) -> Self::IntoIter {
         self.set_of_handles.into_iter()
     }
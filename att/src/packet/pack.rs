@@ -1,53 +1,239 @@
-use std::io;
+//! Encode / Decode PDU fields.
+//!
+//! Without the `std` feature, [`Read`] and [`Write`] are small stand-ins
+//! for the `std::io` traits implemented for byte slices and `Vec<u8>`.
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "std")]
+pub use std::io::{Read, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::io::{Read, Write};
 
-#[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("no data available.")]
     NoDataAvailable,
 
-    #[error(transparent)]
-    Io(#[from] io::Error),
+    UnexpectedEof,
 
-    #[error("unexpected {0}")]
-    Unexpected(String),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+
+    /// Malformed input, with the leading bytes of the PDU it was found in if known.
+    Unexpected {
+        message: String,
+        context: Option<Vec<u8>>,
+    },
+}
+
+impl Error {
+    pub fn unexpected<S>(message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::Unexpected {
+            message: message.into(),
+            context: None,
+        }
+    }
+
+    pub fn unexpected_with_context(message: String, ctx: &[u8]) -> Self {
+        Self::Unexpected {
+            message,
+            context: Some(ctx.to_vec()),
+        }
+    }
+
+    /// Attach `ctx` to an [`Unexpected`](Self::Unexpected) error. Other errors
+    /// are returned as is.
+    pub fn with_context(self, ctx: &[u8]) -> Self {
+        match self {
+            Self::Unexpected { message, .. } => Self::unexpected_with_context(message, ctx),
+            err => err,
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDataAvailable => f.write_str("NoDataAvailable"),
+            Self::UnexpectedEof => f.write_str("UnexpectedEof"),
+            #[cfg(feature = "std")]
+            Self::Io(err) => f.debug_tuple("Io").field(err).finish(),
+            Self::Unexpected { message, context } => f
+                .debug_struct("Unexpected")
+                .field("message", message)
+                .field("context", &format_args!("{:02X?}", context))
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDataAvailable => write!(f, "no data available."),
+            Self::UnexpectedEof => write!(f, "failed to fill whole buffer."),
+            #[cfg(feature = "std")]
+            Self::Io(err) => fmt::Display::fmt(err, f),
+            Self::Unexpected {
+                message,
+                context: None,
+            } => write!(f, "unexpected {}", message),
+            Self::Unexpected {
+                message,
+                context: Some(context),
+            } => write!(f, "unexpected {} in {:02X?}", message, context),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(v: std::io::Error) -> Self {
+        Self::Io(v)
+    }
 }
 
-pub type Result<R> = std::result::Result<R, Error>;
+pub type Result<R> = core::result::Result<R, Error>;
+
+#[cfg(not(feature = "std"))]
+mod io {
+    use super::{Error, Result};
+    use alloc::vec::Vec;
+
+    /// Byte source. (`std::Read` subset)
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize>;
+    }
+
+    /// Byte sink. (`std::Write` subset)
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            (**self).read_to_end(buf)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let n = self.len();
+            buf.extend_from_slice(self);
+            *self = &[];
+            Ok(n)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl Write for &mut [u8] {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            if buf.len() > self.len() {
+                return Err(Error::UnexpectedEof);
+            }
+            let (head, tail) = core::mem::take(self).split_at_mut(buf.len());
+            head.copy_from_slice(buf);
+            *self = tail;
+            Ok(())
+        }
+    }
+}
 
 pub trait Pack {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write;
+        W: Write;
 }
 
 pub trait Unpack: Sized {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read;
+        R: Read;
+}
+
+#[cfg(feature = "std")]
+fn read_some<R>(this: &mut R, buf: &mut [u8]) -> Result<Option<usize>>
+where
+    R: Read,
+{
+    match this.read(buf) {
+        Ok(n) => Ok(Some(n)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn read_some<R>(this: &mut R, buf: &mut [u8]) -> Result<Option<usize>>
+where
+    R: Read,
+{
+    this.read(buf).map(Some)
 }
 
 fn fill<R>(mut this: R, mut buf: &mut [u8]) -> Result<()>
 where
-    R: io::Read,
+    R: Read,
 {
     let mut total_read = 0;
     while !buf.is_empty() {
-        match this.read(buf) {
-            Ok(0) => break,
-            Ok(n) => {
+        match read_some(&mut this, buf)? {
+            Some(0) => break,
+            Some(n) => {
                 total_read += n;
                 let tmp = buf;
                 buf = &mut tmp[n..];
             }
-            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
-            Err(e) => return Err(e.into()),
+            None => {}
         }
     }
 
     if total_read == 0 {
         Err(Error::NoDataAvailable)
     } else if !buf.is_empty() {
-        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer").into())
+        Err(Error::UnexpectedEof)
     } else {
         Ok(())
     }
@@ -55,7 +241,7 @@ where
 impl Pack for () {
     fn pack<W>(self, _: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         Ok(())
     }
@@ -64,7 +250,7 @@ impl Pack for () {
 impl Unpack for () {
     fn unpack<R>(_: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(())
     }
@@ -73,7 +259,7 @@ impl Unpack for () {
 impl<const N: usize> Pack for [u8; N] {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         write.write_all(&self)?;
         Ok(())
@@ -83,7 +269,7 @@ impl<const N: usize> Pack for [u8; N] {
 impl<const N: usize> Unpack for [u8; N] {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         let mut this = [0; N];
         fill(read, &mut this)?;
@@ -94,7 +280,7 @@ impl<const N: usize> Unpack for [u8; N] {
 impl Pack for bool {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         (if self { 1u8 } else { 0 }).pack(write)
     }
@@ -103,7 +289,7 @@ impl Pack for bool {
 impl Unpack for bool {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(u8::unpack(read)? != 0)
     }
@@ -112,7 +298,7 @@ impl Unpack for bool {
 impl Pack for u8 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         self.to_le_bytes().pack(write)
     }
@@ -121,7 +307,7 @@ impl Pack for u8 {
 impl Unpack for u8 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(Self::from_le_bytes(Unpack::unpack(read)?))
     }
@@ -130,7 +316,7 @@ impl Unpack for u8 {
 impl Pack for u16 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         self.to_le_bytes().pack(write)
     }
@@ -139,7 +325,7 @@ impl Pack for u16 {
 impl Unpack for u16 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(Self::from_le_bytes(Unpack::unpack(read)?))
     }
@@ -148,7 +334,7 @@ impl Unpack for u16 {
 impl Pack for u32 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         self.to_le_bytes().pack(write)
     }
@@ -157,7 +343,7 @@ impl Pack for u32 {
 impl Unpack for u32 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(Self::from_le_bytes(Unpack::unpack(read)?))
     }
@@ -166,7 +352,7 @@ impl Unpack for u32 {
 impl Pack for u128 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         self.to_le_bytes().pack(write)
     }
@@ -175,7 +361,7 @@ impl Pack for u128 {
 impl Unpack for u128 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         Ok(Self::from_le_bytes(Unpack::unpack(read)?))
     }
@@ -187,7 +373,7 @@ where
 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         if let Some(v) = self {
             v.pack(write)?;
@@ -202,7 +388,7 @@ where
 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         match P::unpack(read) {
             Ok(v) => Ok(Some(v)),
@@ -218,7 +404,7 @@ where
 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         (self.len() as u16).pack(write)?;
         for item in self {
@@ -234,7 +420,7 @@ where
 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         let len = u16::unpack(read)?;
         (0..len).map(|_| P::unpack(read)).collect()
@@ -244,7 +430,7 @@ where
 impl Pack for Box<[u8]> {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         write.write_all(&self)?;
         Ok(())
@@ -254,7 +440,7 @@ impl Pack for Box<[u8]> {
 impl Unpack for Box<[u8]> {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         let mut buf = vec![];
         read.read_to_end(&mut buf)?;
@@ -265,7 +451,7 @@ impl Unpack for Box<[u8]> {
 macro_rules! impl_tuple {
     ($($n:ident : $p:ident),+) => {
         impl<$($p),+> Pack for ($($p),+) where $($p: Pack),+ {
-            fn pack<W>(self, write: &mut W) -> Result<()> where W: io::Write {
+            fn pack<W>(self, write: &mut W) -> Result<()> where W: Write {
                 let ($($n),+) = self;
                 $( $n.pack(write)?; )+
                 Ok(())
@@ -273,7 +459,7 @@ macro_rules! impl_tuple {
         }
 
         impl<$($p),+> Unpack for ($($p),+) where $($p: Unpack),+ {
-            fn unpack<R>(read: &mut R) -> Result<Self> where R: io::Read {
+            fn unpack<R>(read: &mut R) -> Result<Self> where R: Read {
                 Ok((
                     $( <$p as Unpack>::unpack(read)?, )+
                 ))
@@ -293,7 +479,7 @@ where
 {
     fn pack<W>(self, write: &mut W) -> Result<()>
     where
-        W: io::Write,
+        W: Write,
     {
         for item in self.0 {
             item.pack(write)?;
@@ -308,7 +494,7 @@ where
 {
     fn unpack<R>(read: &mut R) -> Result<Self>
     where
-        R: io::Read,
+        R: Read,
     {
         let mut v = vec![];
         loop {
@@ -321,3 +507,48 @@ where
         Ok(Self(v))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_eof() {
+        assert!(matches!(
+            u16::unpack(&mut &[][..]),
+            Err(Error::NoDataAvailable)
+        ));
+        assert!(matches!(
+            u16::unpack(&mut &[0x01][..]),
+            Err(Error::UnexpectedEof)
+        ));
+        assert_eq!(u16::unpack(&mut &[0x01, 0x02][..]).unwrap(), 0x0201);
+    }
+
+    #[test]
+    fn test_pack_slice() {
+        let mut buf = [0; 3];
+        let mut w = &mut buf[..];
+        (0x0201u16, 0x03u8).pack(&mut w).unwrap();
+        assert!(w.is_empty());
+        assert_eq!(buf, [0x01, 0x02, 0x03]);
+        assert!(0x0201u16.pack(&mut &mut buf[..1]).is_err());
+    }
+
+    #[test]
+    fn test_unexpected_context() {
+        let err = Error::unexpected("format 3");
+        assert_eq!(err.to_string(), "unexpected format 3");
+
+        let err = err.with_context(&[0x04, 0x01, 0x00, 0x0A]);
+        assert_eq!(err.to_string(), "unexpected format 3 in [04, 01, 00, 0A]");
+        assert_eq!(
+            format!("{:?}", err),
+            "Unexpected { message: \"format 3\", context: Some([04, 01, 00, 0A]) }"
+        );
+        assert!(matches!(
+            Error::UnexpectedEof.with_context(&[0x01]),
+            Error::UnexpectedEof
+        ));
+    }
+}
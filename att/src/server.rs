@@ -1,26 +1,47 @@
+//! ATT server over a Bluetooth socket or any other transport.
+//!
+//! A [`Connection`] drives the sans-io [`machine`](crate::machine) with a
+//! [`Handler`]. [`Notification`], [`Indication`] and
+//! [`Outgoing`] share its state and only queue packets; the run loop alone
+//! writes to the socket.
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
 use std::future::Future;
 use std::io;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 
 use futures_channel::oneshot;
 use futures_core::ready;
 use futures_core::stream::Stream;
 use futures_sink::Sink;
-use futures_util::future::FutureExt;
-use futures_util::lock::{Mutex, MutexGuard};
-use futures_util::sink::SinkExt;
-use futures_util::stream::{StreamExt, TryStreamExt};
+use futures_util::future::{self, poll_fn, FutureExt};
+use futures_util::stream::StreamExt;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::watch;
 
+pub use crate::machine::DEFAULT_SERVER_MTU;
+use crate::machine::{Action, AttServerMachine, MtuState, NotReady, DEFAULT_MTU};
 use crate::packet as pkt;
-use crate::sock::{AttListener, AttStream};
+pub use crate::sock::{AddressType, AttListener, AttStream, Channel, L2capMode, PeerInfo};
+#[allow(deprecated)]
+pub use crate::ErrorResponse;
 use crate::Handle;
-pub use crate::{ErrorResponse, Handler};
+pub use crate::{Handler, HandlerError};
 use pkt::pack::{self, Unpack};
 
-const DEFAULT_MTU: usize = 23;
+/// Default number of packets the outgoing queue holds before notifications are refused.
+pub const DEFAULT_OUTGOING_QUEUE_CAPACITY: usize = 16;
+
+/// ATT transaction timeout, applied to indications waiting for their
+/// confirmation unless configured. (3.3.3)
+pub const DEFAULT_INDICATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait before accepting again when out of file descriptors or memory.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -29,46 +50,240 @@ pub enum Error {
 
     #[error(transparent)]
     Pack(#[from] pack::Error),
+
+    #[error("stale handle {0}. the attribute changed after the writer was created.")]
+    StaleHandle(Handle),
+
+    #[error(transparent)]
+    NotReady(#[from] NotReady),
+
+    #[error("{opcode:?} of {size} bytes exceeds ATT_MTU {mtu}.")]
+    ResponseTooLarge {
+        opcode: pkt::OpCode,
+        size: usize,
+        mtu: usize,
+    },
+
+    /// The indication was not confirmed in time. The connection is faulted.
+    #[error("indication not confirmed within {0:?}.")]
+    IndicationTimeout(Duration),
+
+    /// An indication was not confirmed in time, so the ATT bearer is
+    /// considered timed out and the connection stopped serving.
+    #[error("ATT transaction timed out.")]
+    TransactionTimeout,
 }
 
 type Result<R> = std::result::Result<R, Error>;
 
+/// Error returned by [`Notification::try_notify`].
+#[derive(Debug, thiserror::Error)]
+pub enum TryNotifyError {
+    #[error("outgoing queue is full.")]
+    Full,
+
+    #[error(transparent)]
+    Error(#[from] Error),
+}
+
+/// Counters of a connection at one point in time.
+///
+/// Obtained by [`Connection::stats`] or, while the connection is running,
+/// [`Outgoing::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// PDUs received, by Op Code. PDUs of unknown Op Codes are not counted.
+    pub received: HashMap<pkt::OpCode, u64>,
+    /// Error Responses sent, by Error Code.
+    pub error_responses: HashMap<pkt::ErrorCode, u64>,
+    pub notifications_sent: u64,
+    pub indications_sent: u64,
+    pub confirmations_received: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    /// Negotiated ATT_MTU.
+    pub mtu: usize,
+    /// Packets waiting in the outgoing queue.
+    pub outgoing_queue_depth: usize,
+    /// Time since the connection was created.
+    pub uptime: Duration,
+}
+
+#[derive(Debug)]
+struct ConnStats {
+    created: Instant,
+    received: HashMap<pkt::OpCode, u64>,
+    error_responses: HashMap<pkt::ErrorCode, u64>,
+    notifications_sent: u64,
+    indications_sent: u64,
+    confirmations_received: u64,
+    bytes_received: u64,
+    bytes_sent: u64,
+}
+
+impl ConnStats {
+    fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            received: HashMap::new(),
+            error_responses: HashMap::new(),
+            notifications_sent: 0,
+            indications_sent: 0,
+            confirmations_received: 0,
+            bytes_received: 0,
+            bytes_sent: 0,
+        }
+    }
+
+    fn record_received(&mut self, packet: &[u8]) {
+        self.bytes_received += packet.len() as u64;
+        if let Some(opcode) = packet.first().and_then(|v| pkt::OpCode::try_from(*v).ok()) {
+            *self.received.entry(opcode).or_default() += 1;
+        }
+    }
+
+    fn record_sent(&mut self, packet: &[u8]) {
+        self.bytes_sent += packet.len() as u64;
+        match packet {
+            [0x01, _, _, _, code] => {
+                if let Ok(code) = pkt::ErrorCode::unpack(&mut &[*code][..]) {
+                    *self.error_responses.entry(code).or_default() += 1;
+                }
+            }
+            [0x1B, ..] => self.notifications_sent += 1,
+            [0x1D, ..] => self.indications_sent += 1,
+            _ => {}
+        }
+    }
+}
+
 struct PacketStream<R> {
     inner: R,
     rxbuf: Box<[u8]>,
-    txbuf: Box<[u8]>,
-    txlen: usize,
-    txwaker: Vec<Waker>,
+    txmtu: usize,
+    txqueue: VecDeque<Box<[u8]>>,
+    txpos: usize,
+    /// Number of queued packets up to and including the last response.
+    unsent_response: Option<usize>,
+    /// When the last indication was written, until the connection takes it.
+    indication_sent: Option<tokio::time::Instant>,
+    stats: ConnStats,
 }
 
 impl<R> PacketStream<R> {
     fn new(inner: R) -> Self {
         Self {
             inner,
-            rxbuf: [0; DEFAULT_MTU].into(),
-            txbuf: [0; DEFAULT_MTU].into(),
-            txlen: 0,
-            txwaker: vec![],
+            rxbuf: [0; DEFAULT_MTU + 1].into(),
+            txmtu: DEFAULT_MTU,
+            txqueue: VecDeque::new(),
+            txpos: 0,
+            unsent_response: None,
+            indication_sent: None,
+            stats: ConnStats::new(),
         }
     }
 
-    fn txmtu(&self) -> usize {
-        self.txbuf.len()
-    }
-
     fn set_txmtu(&mut self, mtu: usize) {
-        let mut buf = vec![0; mtu];
-        let len = mtu.min(self.txbuf.len());
-        (&mut buf[..len]).copy_from_slice(&self.txbuf[..len]);
-        self.txbuf = buf.into();
+        self.txmtu = mtu;
     }
 
+    /// Size `rxbuf` one octet over the Server Rx MTU, so that a longer PDU is
+    /// seen as such rather than cut to a PDU which fits.
     fn set_rxmtu(&mut self, mtu: usize) {
+        let mtu = mtu + 1;
         let mut buf = vec![0; mtu];
         let len = mtu.min(self.rxbuf.len());
-        (&mut buf[..len]).copy_from_slice(&self.rxbuf[..len]);
+        buf[..len].copy_from_slice(&self.rxbuf[..len]);
         self.rxbuf = buf.into();
     }
+
+    /// Pack item and append it to the outgoing queue. Fails with
+    /// [`Error::ResponseTooLarge`] rather than queue a packet longer than the MTU.
+    fn enqueue<S>(&mut self, item: S) -> Result<()>
+    where
+        S: pkt::DeviceSend,
+    {
+        trace_pdu!("send", item);
+
+        let mut buf = vec![];
+        item.pack_with_code(&mut buf)?;
+        if buf.len() > self.txmtu {
+            return Err(Error::ResponseTooLarge {
+                opcode: S::OPCODE,
+                size: buf.len(),
+                mtu: self.txmtu,
+            });
+        }
+        self.txqueue.push_back(buf.into());
+        Ok(())
+    }
+
+    /// Append a packed PDU to the outgoing queue.
+    fn push(&mut self, packet: Vec<u8>) {
+        self.txqueue.push_back(packet.into());
+    }
+
+    /// Mark the last queued packet as the response to the current request.
+    fn mark_response(&mut self) {
+        self.unsent_response = Some(self.txqueue.len());
+    }
+
+    /// Whether a request was received and its response is not sent yet.
+    fn pending_request(&self) -> bool {
+        self.unsent_response.is_some()
+    }
+}
+
+impl<W> PacketStream<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Write queued packets in order until the queue is empty.
+    fn poll_write_queue(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Self {
+            inner,
+            txqueue,
+            txpos,
+            unsent_response,
+            indication_sent,
+            stats,
+            ..
+        } = self;
+
+        while let Some(packet) = txqueue.front() {
+            let n = ready!(Pin::new(&mut *inner).poll_write(cx, &packet[*txpos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+            *txpos += n;
+            if *txpos >= packet.len() {
+                if let [0x1D, ..] = packet[..] {
+                    *indication_sent = Some(tokio::time::Instant::now());
+                }
+                stats.record_sent(packet);
+                txqueue.pop_front();
+                *txpos = 0;
+                *unsent_response =
+                    unsent_response.and_then(|n| n.checked_sub(1).filter(|n| *n > 0));
+            }
+        }
+        ready!(Pin::new(&mut *inner).poll_flush(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R> PacketStream<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Read a packet into `rxbuf` and return its length. `0` on EOF.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut buf = ReadBuf::new(&mut self.rxbuf);
+        ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf))?;
+        self.stats.record_received(buf.filled());
+        Poll::Ready(Ok(buf.filled().len()))
+    }
 }
 
 impl<R> Stream for PacketStream<R>
@@ -78,17 +293,15 @@ where
     type Item = Result<pkt::DeviceRecv>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let Self { inner, rxbuf, .. } = self.get_mut();
-
-        let mut buf = ReadBuf::new(rxbuf);
-        ready!(Pin::new(inner).poll_read(cx, &mut buf))?;
-        let mut filled = buf.filled();
-        if filled.is_empty() {
-            Poll::Ready(None)
-        } else {
-            let item = Unpack::unpack(&mut filled)?;
-            log::trace!("packet recv {:?}", item);
-            Poll::Ready(Some(Ok(item)))
+        let this = self.get_mut();
+        match ready!(this.poll_recv(cx))? {
+            0 => Poll::Ready(None),
+            len => {
+                let item = Unpack::unpack(&mut &this.rxbuf[..len])
+                    .map_err(|err: pack::Error| err.with_context(&this.rxbuf[..len.min(16)]))?;
+                trace_pdu!("recv", item);
+                Poll::Ready(Some(Ok(item)))
+            }
         }
     }
 }
@@ -100,53 +313,60 @@ where
 {
     type Error = Error;
 
-    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        let Self { txlen, txwaker, .. } = self.get_mut();
-        if *txlen != 0 {
-            txwaker.push(cx.waker().clone());
-            Poll::Pending
-        } else {
-            Poll::Ready(Ok(()))
-        }
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
     }
 
     fn start_send(self: Pin<&mut Self>, item: S) -> Result<()> {
-        let Self { txlen, txbuf, .. } = self.get_mut();
-        log::trace!("packet send {:?}", item);
-
-        let mut write = txbuf.as_mut();
-        let len = write.len();
-        item.pack_with_code(&mut write)?;
-        *txlen = len - write.len();
-        Ok(())
+        self.get_mut().enqueue(item)
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        let Self {
-            inner,
-            txlen,
-            txbuf,
-            ..
-        } = self.get_mut();
-
-        while *txlen != 0 {
-            *txlen -= ready!(Pin::new(&mut *inner).poll_write(cx, &txbuf[..*txlen]))?;
-        }
-        ready!(Pin::new(&mut *inner).poll_flush(cx))?;
-        Poll::Ready(Ok(()))
+        self.get_mut().poll_write_queue(cx)
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
         let this = self.get_mut();
-        ready!(Sink::<S>::poll_flush(Pin::new(this), cx))?;
+        ready!(this.poll_write_queue(cx))?;
         ready!(Pin::new(&mut this.inner).poll_shutdown(cx))?;
         Poll::Ready(Ok(()))
     }
 }
 
+/// State of a connection shared by the run loop and the senders.
+///
+/// Senders hold the lock only to queue packets; the socket is written by the
+/// run loop alone, so a slow write never keeps the read path waiting on it.
 struct Inner<IO> {
     stream: PacketStream<IO>,
+    machine: AttServerMachine,
     await_confirmation: Option<oneshot::Sender<()>>,
+    /// Negotiated ATT_MTU, shared with notification / indication senders.
+    mtu: Arc<AtomicUsize>,
+    outgoing_capacity: usize,
+    /// Bumped on every structural change of the attribute table.
+    generation: u64,
+    /// Generation in which each handle was last removed or reassigned.
+    changed: HashMap<Handle, u64>,
+    /// Waker of the task running the connection. Woken when packets are queued.
+    driver: Option<Waker>,
+    /// Wakers of notifications / indications waiting for queue space.
+    writers: Vec<Waker>,
+    /// Keep serving the outgoing half after the client stopped sending.
+    half_close: bool,
+    /// The client stopped sending.
+    read_closed: bool,
+    /// A sender shut the connection down.
+    write_closed: bool,
+    /// The connection is dropped, so nothing queued is sent anymore.
+    stopped: bool,
+    indication_timeout: Duration,
+    /// When the outstanding indication times out, counted from when it was
+    /// written rather than queued.
+    confirmation_due: Option<tokio::time::Instant>,
+    confirmation_timer: Option<Pin<Box<tokio::time::Sleep>>>,
+    /// The confirmation timed out; nothing is served anymore.
+    timed_out: bool,
     // TODO used notification / indication handles
 }
 
@@ -154,20 +374,205 @@ impl<IO> Inner<IO> {
     fn new(io: IO) -> Self {
         Self {
             stream: PacketStream::new(io),
+            machine: {
+                let mut machine = AttServerMachine::new();
+                machine.set_server_mtu(DEFAULT_SERVER_MTU);
+                machine
+            },
             await_confirmation: Default::default(),
+            mtu: Arc::new(AtomicUsize::new(DEFAULT_MTU)),
+            outgoing_capacity: DEFAULT_OUTGOING_QUEUE_CAPACITY,
+            generation: 0,
+            changed: HashMap::new(),
+            driver: None,
+            writers: vec![],
+            half_close: false,
+            read_closed: false,
+            write_closed: false,
+            stopped: false,
+            indication_timeout: DEFAULT_INDICATION_TIMEOUT,
+            confirmation_due: None,
+            confirmation_timer: None,
+            timed_out: false,
         }
     }
-}
 
-enum NotificationState {
-    Write,
-    NeedFlush(usize),
+    fn has_capacity(&self) -> bool {
+        self.stream.txqueue.len() < self.outgoing_capacity
+    }
+
+    fn poll_capacity(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.has_capacity() {
+            Poll::Ready(())
+        } else {
+            self.writers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Fail once the connection is dropped, rather than wait forever.
+    fn check_running(&self) -> Result<()> {
+        match self.stopped {
+            true => Err(io::Error::from(io::ErrorKind::NotConnected).into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Fail if `handle` changed after `generation`.
+    fn check_generation(&self, handle: &Handle, generation: u64) -> Result<()> {
+        match self.changed.get(handle) {
+            Some(changed) if *changed > generation => Err(Error::StaleHandle(handle.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    fn push(&mut self, packet: Vec<u8>) {
+        self.stream.push(packet);
+        self.wake_driver();
+    }
+
+    fn wake_driver(&mut self) {
+        if let Some(waker) = self.driver.take() {
+            waker.wake();
+        }
+    }
+
+    /// The socket was shut down, so that a half closed connection is done.
+    fn close_write(&mut self) {
+        self.write_closed = true;
+        self.wake_driver();
+    }
+
+    /// Stop reading, and finish once the queued packets are written.
+    fn close(&mut self) {
+        self.read_closed = true;
+        self.write_closed = true;
+    }
+
+    /// Wake every parked writer to fail, as no one drives the connection.
+    fn stop(&mut self) {
+        self.stopped = true;
+        self.driver = None;
+        // a dropped sender cancels the indication awaiting confirmation.
+        self.await_confirmation = None;
+        for waker in self.writers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Fail once the outstanding indication is not confirmed in time.
+    fn poll_confirmation_timeout(&mut self, cx: &mut Context<'_>) -> Result<()> {
+        let due = match self.confirmation_due {
+            Some(due) => due,
+            None => return Ok(()),
+        };
+        let timer = self
+            .confirmation_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(due)));
+        if timer.deadline() != due {
+            timer.as_mut().reset(due);
+        }
+        if timer.as_mut().poll(cx).is_pending() {
+            return Ok(());
+        }
+        log::warn!(
+            "indication not confirmed within {:?}.",
+            self.indication_timeout
+        );
+        self.confirmation_due = None;
+        self.timed_out = true;
+        // the waiting sender sees `timed_out`.
+        self.await_confirmation = None;
+        Err(Error::TransactionTimeout)
+    }
+
+    fn handles_changed<I>(&mut self, handles: I)
+    where
+        I: IntoIterator<Item = Handle>,
+    {
+        self.generation += 1;
+        let generation = self.generation;
+        self.changed
+            .extend(handles.into_iter().map(|h| (h, generation)));
+    }
+
+    /// Apply the MTU negotiated by the machine to the stream and the senders.
+    fn sync_mtu(&mut self) {
+        self.stream.set_txmtu(self.machine.tx_mtu());
+        if self.stream.rxbuf.len() != self.machine.rx_mtu() + 1 {
+            self.stream.set_rxmtu(self.machine.rx_mtu());
+        }
+        self.mtu.store(self.machine.mtu(), Ordering::SeqCst);
+    }
+
+    fn stats(&self) -> StatsSnapshot {
+        let stats = &self.stream.stats;
+        StatsSnapshot {
+            received: stats.received.clone(),
+            error_responses: stats.error_responses.clone(),
+            notifications_sent: stats.notifications_sent,
+            indications_sent: stats.indications_sent,
+            confirmations_received: stats.confirmations_received,
+            bytes_received: stats.bytes_received,
+            bytes_sent: stats.bytes_sent,
+            mtu: self.machine.mtu(),
+            outgoing_queue_depth: self.stream.txqueue.len(),
+            uptime: stats.created.elapsed(),
+        }
+    }
+
+    fn wake_writers(&mut self) {
+        if self.has_capacity() {
+            for waker in self.writers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
 }
 
 struct NotificationInner<IO> {
     handle: Handle,
+    generation: u64,
     inner: Arc<Mutex<Inner<IO>>>,
-    state: NotificationState,
+    mtu: Arc<AtomicUsize>,
+}
+
+impl<IO> Clone for NotificationInner<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+            generation: self.generation,
+            inner: self.inner.clone(),
+            mtu: self.mtu.clone(),
+        }
+    }
+}
+
+impl<IO> NotificationInner<IO> {
+    fn poll_notify(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<()>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_running()?;
+        inner.check_generation(&self.handle, self.generation)?;
+        ready!(inner.poll_capacity(cx));
+        let packet = inner.machine.queue_notification(&self.handle, buf)?;
+        inner.push(packet);
+        Poll::Ready(Ok(()))
+    }
+
+    fn try_notify(&self, buf: &[u8]) -> std::result::Result<(), TryNotifyError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_running()?;
+        inner.check_generation(&self.handle, self.generation)?;
+        if !inner.has_capacity() {
+            return Err(TryNotifyError::Full);
+        }
+        let packet = inner
+            .machine
+            .queue_notification(&self.handle, buf)
+            .map_err(Error::from)?;
+        inner.push(packet);
+        Ok(())
+    }
 }
 
 impl<IO> AsyncWrite for NotificationInner<IO>
@@ -179,47 +584,10 @@ where
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        let Self {
-            handle,
-            inner,
-            state,
-            ..
-        } = self.get_mut();
-        let mut guard = ready!(inner.lock().poll_unpin(cx));
-
-        loop {
-            match &state {
-                NotificationState::Write => {
-                    if let Err(err) =
-                        ready!(Sink::<pkt::HandleValueNotificationBorrow>::poll_ready(
-                            Pin::new(&mut guard.stream),
-                            cx
-                        ))
-                    {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
-                    let item = pkt::HandleValueNotificationBorrow::new(handle.clone(), buf);
-                    if let Err(err) = guard.stream.start_send_unpin(item) {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
-                    *state = NotificationState::NeedFlush(buf.len());
-                }
-
-                NotificationState::NeedFlush(len) => {
-                    if let Err(err) =
-                        ready!(Sink::<pkt::HandleValueNotificationBorrow>::poll_flush(
-                            Pin::new(&mut guard.stream),
-                            cx
-                        ))
-                    {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
-                    let len = *len;
-                    *state = NotificationState::Write;
-                    return Poll::Ready(Ok(len));
-                }
-            }
+        if let Err(err) = ready!(self.get_mut().poll_notify(cx, buf)) {
+            return Poll::Ready(Err(io::Error::other(err)));
         }
+        Poll::Ready(Ok(buf.len()))
     }
 
     fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
@@ -227,230 +595,133 @@ where
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        let Self { inner, .. } = self.get_mut();
-        let mut guard = ready!(inner.lock().poll_unpin(cx));
+        let mut inner = self.inner.lock().unwrap();
         if let Err(err) = ready!(Sink::<pkt::HandleValueNotificationBorrow>::poll_close(
-            Pin::new(&mut guard.stream),
+            Pin::new(&mut inner.stream),
             cx,
         )) {
-            return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
+            return Poll::Ready(Err(io::Error::other(err)));
         }
+        inner.close_write();
         Poll::Ready(Ok(()))
     }
 }
 
 enum IndicationState {
     Write,
-    NeedFlush(usize),
     AwaitConfirmation(usize, oneshot::Receiver<()>),
 }
 
 struct IndicationInner<IO> {
     handle: Handle,
+    generation: u64,
     inner: Arc<Mutex<Inner<IO>>>,
     state: IndicationState,
 }
 
-impl<IO> AsyncWrite for IndicationInner<IO>
-where
-    IO: AsyncWrite + Unpin,
-{
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        let Self {
-            state,
-            handle,
-            inner,
-            ..
-        } = self.get_mut();
-        let mut guard = ready!(inner.lock().poll_unpin(cx));
-
+impl<IO> IndicationInner<IO> {
+    fn poll_indicate(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
         loop {
-            match state {
+            match &mut self.state {
                 IndicationState::Write => {
-                    if let Err(err) = ready!(Sink::<pkt::HandleValueIndicationBorrow>::poll_ready(
-                        Pin::new(&mut guard.stream),
-                        cx
-                    )) {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
-                    let item = pkt::HandleValueIndicationBorrow::new(handle.clone(), buf);
-                    if let Err(err) = guard.stream.start_send_unpin(item) {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
-                    *state = IndicationState::NeedFlush(buf.len());
-                }
-
-                IndicationState::NeedFlush(len) => {
-                    if let Err(err) = ready!(Sink::<pkt::HandleValueIndicationBorrow>::poll_flush(
-                        Pin::new(&mut guard.stream),
-                        cx
-                    )) {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
-                    }
+                    let mut inner = self.inner.lock().unwrap();
+                    inner.check_running()?;
+                    inner.check_generation(&self.handle, self.generation)?;
+                    ready!(inner.poll_capacity(cx));
+                    let packet = match inner.machine.queue_indication(&self.handle, buf) {
+                        Ok(packet) => packet,
+                        Err(NotReady::AwaitingConfirmation) => {
+                            inner.writers.push(cx.waker().clone());
+                            return Poll::Pending;
+                        }
+                        Err(err) => return Poll::Ready(Err(err.into())),
+                    };
+                    inner.push(packet);
                     let (tx, rx) = oneshot::channel();
-                    guard.await_confirmation = Some(tx); // TODO check existence
-                    *state = IndicationState::AwaitConfirmation(*len, rx);
+                    inner.await_confirmation = Some(tx);
+                    self.state = IndicationState::AwaitConfirmation(buf.len(), rx);
                 }
 
                 IndicationState::AwaitConfirmation(len, rx) => {
                     if let Err(err) = ready!(rx.poll_unpin(cx)) {
-                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
+                        self.state = IndicationState::Write;
+                        let inner = self.inner.lock().unwrap();
+                        if inner.timed_out {
+                            return Poll::Ready(Err(Error::IndicationTimeout(
+                                inner.indication_timeout,
+                            )));
+                        }
+                        return Poll::Ready(Err(io::Error::other(err).into()));
                     }
                     let len = *len;
-                    *state = IndicationState::Write;
+                    self.state = IndicationState::Write;
                     return Poll::Ready(Ok(len));
                 }
             }
         }
     }
+}
+
+impl<IO> AsyncWrite for IndicationInner<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match ready!(self.get_mut().poll_indicate(cx, buf)) {
+            Ok(len) => Poll::Ready(Ok(len)),
+            Err(Error::Io(err)) => Poll::Ready(Err(err)),
+            Err(err) => Poll::Ready(Err(io::Error::other(err))),
+        }
+    }
 
     fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
         Poll::Ready(Ok(()))
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        let Self { inner, .. } = self.get_mut();
-        let mut guard = ready!(inner.lock().poll_unpin(cx));
+        let mut inner = self.inner.lock().unwrap();
         if let Err(err) = ready!(Sink::<pkt::HandleValueIndicationBorrow>::poll_close(
-            Pin::new(&mut guard.stream),
+            Pin::new(&mut inner.stream),
             cx
         )) {
-            return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, err)));
+            return Poll::Ready(Err(io::Error::other(err)));
         }
+        inner.close_write();
         Poll::Ready(Ok(()))
     }
 }
 
-struct TryLockNext<'a, IO> {
-    inner: &'a Mutex<Inner<IO>>,
-}
-
-impl<'a, IO> Future for TryLockNext<'a, IO>
-where
-    IO: AsyncRead + Unpin,
-{
-    type Output = (MutexGuard<'a, Inner<IO>>, Option<Result<pkt::DeviceRecv>>);
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let Self { inner } = self.get_mut();
-
-        let mut guard = ready!(inner.lock().poll_unpin(cx));
-        let item = ready!(guard.stream.poll_next_unpin(cx));
-        Poll::Ready((guard, item))
-    }
-}
-
-async fn respond<IO, R>(
-    stream: &mut PacketStream<IO>,
-    r: std::result::Result<R::Response, crate::handler::ErrorResponse>,
-) -> Result<()>
-where
-    IO: AsyncWrite + Unpin,
-    R: pkt::Request,
-{
-    let mtu = stream.txmtu();
-    match r {
-        Ok(mut r) => {
-            pkt::Response::truncate(&mut r, mtu);
-            stream.send(r).await?;
-        }
-        Err(crate::ErrorResponse(handle, code)) => {
-            let err = pkt::ErrorResponse::new(R::opcode(), handle, code);
-            stream.send(err).await?;
-        }
-    }
-    Ok(())
-}
-
-async fn handle<IO, H>(
-    inner: &mut Inner<IO>,
-    handler: &mut H,
-    request: pkt::DeviceRecv,
-) -> Result<()>
+fn handle<IO, H>(inner: &mut Inner<IO>, handler: &mut H, len: usize) -> Result<()>
 where
-    IO: AsyncWrite + Unpin,
     H: crate::Handler,
 {
-    match request {
-        pkt::DeviceRecv::ExchangeMtuRequest(item) => {
-            let response = handler.handle_exchange_mtu_request(&item);
-            if let Ok(response) = &response {
-                let client_rx_mtu = *item.client_rx_mtu() as usize;
-                let server_rx_mtu = *response.server_rx_mtu() as usize;
-                inner.stream.set_txmtu(client_rx_mtu);
-                inner.stream.set_rxmtu(server_rx_mtu);
+    let actions = inner.machine.handle_bytes(&inner.stream.rxbuf[..len]);
+    for action in actions {
+        match action {
+            Action::Respond(packet) => inner.stream.push(packet),
+
+            Action::DeliverToHandler(recv) => {
+                if let Some(packet) = inner.machine.dispatch(handler, recv)? {
+                    inner.stream.push(packet);
+                    inner.stream.mark_response();
+                }
             }
-            respond::<_, pkt::ExchangeMtuRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::FindInformationRequest(item) => {
-            let response = handler.handle_find_information_request(&item);
-            respond::<_, pkt::FindInformationRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::FindByTypeValueRequest(item) => {
-            let response = handler.handle_find_by_type_value_request(&item);
-            respond::<_, pkt::FindByTypeValueRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ReadByTypeRequest(item) => {
-            let response = handler.handle_read_by_type_request(&item);
-            respond::<_, pkt::ReadByTypeRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ReadRequest(item) => {
-            let response = handler.handle_read_request(&item);
-            respond::<_, pkt::ReadRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ReadBlobRequest(item) => {
-            let response = handler.handle_read_blob_request(&item);
-            respond::<_, pkt::ReadBlobRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ReadMultipleRequest(item) => {
-            let response = handler.handle_read_multiple_request(&item);
-            respond::<_, pkt::ReadMultipleRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ReadByGroupTypeRequest(item) => {
-            let response = handler.handle_read_by_group_type_request(&item);
-            respond::<_, pkt::ReadByGroupTypeRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::WriteRequest(item) => {
-            let response = handler.handle_write_request(&item);
-            respond::<_, pkt::WriteRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::WriteCommand(item) => {
-            handler.handle_write_command(&item);
-        }
-
-        pkt::DeviceRecv::PrepareWriteRequest(item) => {
-            let response = handler.handle_prepare_write_request(&item);
-            respond::<_, pkt::PrepareWriteRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::ExecuteWriteRequest(item) => {
-            let response = handler.handle_execute_write_request(&item);
-            respond::<_, pkt::ExecuteWriteRequest>(&mut inner.stream, response).await?;
-        }
-
-        pkt::DeviceRecv::SignedWriteCommand(item) => {
-            handler.handle_signed_write_command(&item);
-        }
 
-        pkt::DeviceRecv::HandleValueConfirmation(..) => {
-            if let Some(channel) = inner.await_confirmation.take() {
-                channel.send(()).ok();
+            Action::ConfirmationReceived => {
+                inner.stream.stats.confirmations_received += 1;
+                inner.confirmation_due = None;
+                if let Some(channel) = inner.await_confirmation.take() {
+                    channel.send(()).ok();
+                }
+                inner.wake_writers();
             }
         }
     }
+    inner.sync_mtu();
     Ok(())
 }
 
@@ -463,65 +734,342 @@ where
     IO: AsyncRead + AsyncWrite + Unpin,
 {
     fn notification(&self, handle: Handle) -> NotificationInner<IO> {
+        let inner = self.inner.lock().unwrap();
         NotificationInner {
             handle,
+            generation: inner.generation,
             inner: self.inner.clone(),
-            state: NotificationState::Write,
+            mtu: inner.mtu.clone(),
         }
     }
 
     fn indication(&self, handle: Handle) -> IndicationInner<IO> {
         IndicationInner {
             handle,
+            generation: self.inner.lock().unwrap().generation,
             inner: self.inner.clone(),
             state: IndicationState::Write,
         }
     }
 
-    async fn run<H>(self, mut handler: H) -> Result<()>
+    fn handles_changed<I>(&self, handles: I)
     where
-        H: crate::Handler,
+        I: IntoIterator<Item = Handle>,
     {
-        loop {
-            let (mut guard, request) = TryLockNext { inner: &self.inner }.await;
-            let request = if let Some(request) = request {
-                request?
-            } else {
-                return Ok(());
-            };
+        self.inner.lock().unwrap().handles_changed(handles)
+    }
 
-            handle(&mut *guard, &mut handler, request).await?;
+    fn outgoing(&self) -> Outgoing<IO> {
+        Outgoing {
+            inner: self.inner.clone(),
         }
     }
-}
 
-pub struct Notification {
-    inner: NotificationInner<AttStream>,
-}
+    fn outgoing_queue_depth(&self) -> usize {
+        self.inner.lock().unwrap().stream.txqueue.len()
+    }
 
-impl AsyncWrite for Notification {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    fn stats(&self) -> StatsSnapshot {
+        self.inner.lock().unwrap().stats()
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    fn current_mtu(&self) -> usize {
+        self.inner.lock().unwrap().mtu.load(Ordering::SeqCst)
     }
 
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    fn shared_mtu(&self) -> SharedMtu {
+        SharedMtu(self.inner.lock().unwrap().mtu.clone())
     }
-}
 
-pub struct Indication {
-    inner: IndicationInner<AttStream>,
-}
+    fn mtu_state(&self) -> MtuState {
+        self.inner.lock().unwrap().machine.mtu_state()
+    }
 
-impl AsyncWrite for Indication {
+    fn set_outgoing_queue_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.outgoing_capacity = capacity.max(1);
+        inner.wake_writers();
+    }
+
+    fn set_server_mtu(&self, mtu: u16) {
+        self.inner.lock().unwrap().machine.set_server_mtu(mtu);
+    }
+
+    fn allow_half_close(&self, allow: bool) {
+        self.inner.lock().unwrap().half_close = allow;
+    }
+
+    fn set_indication_timeout(&self, timeout: Duration) {
+        self.inner.lock().unwrap().indication_timeout = timeout;
+    }
+
+    fn poll_run<H>(&self, cx: &mut Context<'_>, handler: &mut H) -> Poll<Result<()>>
+    where
+        H: crate::Handler,
+    {
+        // held while the handler runs, so that responses are queued before
+        // any packet queued meanwhile.
+        let mut inner = self.inner.lock().unwrap();
+        inner.driver = Some(cx.waker().clone());
+        inner.poll_confirmation_timeout(cx)?;
+
+        loop {
+            let written = match inner.stream.poll_write_queue(cx) {
+                Poll::Ready(result) => {
+                    result?;
+                    true
+                }
+                Poll::Pending => false,
+            };
+            if !inner.stream.pending_request() {
+                inner.machine.response_sent();
+            }
+            if let Some(sent) = inner.stream.indication_sent.take() {
+                inner.confirmation_due = Some(sent + inner.indication_timeout);
+                inner.poll_confirmation_timeout(cx)?;
+            }
+            inner.wake_writers();
+
+            if inner.read_closed {
+                if written && inner.write_closed {
+                    return Poll::Ready(Ok(()));
+                }
+                // keep reading for the hangup. A transport half closed by the
+                // client keeps reporting EOF, while one it disconnected from
+                // fails.
+                match ready!(inner.stream.poll_recv(cx))? {
+                    0 => return Poll::Pending,
+                    len => {
+                        handle(&mut inner, handler, len)?;
+                        continue;
+                    }
+                }
+            }
+
+            match ready!(inner.stream.poll_recv(cx))? {
+                // an ATT PDU has at least an Op Code, so no read is empty but EOF.
+                0 if inner.half_close => {
+                    debug!("Peer stopped sending.");
+                    inner.read_closed = true;
+                }
+                0 if written => return Poll::Ready(Ok(())),
+                0 => return Poll::Pending,
+                len => handle(&mut inner, handler, len)?,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    async fn run<H>(self, handler: H) -> Result<()>
+    where
+        H: crate::Handler,
+    {
+        self.run_with_shutdown(handler, future::pending()).await
+    }
+
+    async fn run_with_shutdown<H, S>(self, mut handler: H, shutdown: S) -> Result<()>
+    where
+        H: crate::Handler,
+        S: Future<Output = ()>,
+    {
+        let mut shutdown = Box::pin(shutdown.fuse());
+        poll_fn(|cx| {
+            if shutdown.poll_unpin(cx).is_ready() {
+                self.inner.lock().unwrap().close();
+            }
+            self.poll_run(cx, &mut handler)
+        })
+        .await
+    }
+}
+
+impl<IO> Drop for ConnectionInner<IO> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.stop();
+        }
+    }
+}
+
+/// Notification sender for a characteristic value.
+///
+/// Notifications are not acknowledged by the client. Completing a write (or
+/// [`notify`](Self::notify)) only means the packet was accepted into the
+/// connection's outgoing queue; it does not mean the client received it.
+/// The queue is bounded: [`notify`](Self::notify) and `AsyncWrite` wait for
+/// space, while [`try_notify`](Self::try_notify) fails fast with
+/// [`TryNotifyError::Full`].
+///
+/// Clones send to the same characteristic value through the same queue.
+pub struct Notification<IO = AttStream> {
+    inner: NotificationInner<IO>,
+}
+
+impl<IO> Clone for Notification<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<IO> Notification<IO> {
+    /// Queue a notification, waiting while the outgoing queue is full.
+    pub async fn notify(&mut self, value: &[u8]) -> Result<()> {
+        poll_fn(|cx| self.inner.poll_notify(cx, value)).await
+    }
+
+    /// Queue a notification if the outgoing queue has space.
+    pub fn try_notify(&self, value: &[u8]) -> std::result::Result<(), TryNotifyError> {
+        self.inner.try_notify(value)
+    }
+
+    /// Negotiated ATT_MTU of the connection. A notification carries at most
+    /// `current_mtu() - 3` bytes of value.
+    pub fn current_mtu(&self) -> usize {
+        self.inner.mtu.load(Ordering::SeqCst)
+    }
+}
+
+impl<IO> AsyncWrite for Notification<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Sender of packets to any handle of a connection.
+///
+/// Clones share the connection's outgoing queue.
+pub struct Outgoing<IO = AttStream> {
+    inner: Arc<Mutex<Inner<IO>>>,
+}
+
+impl<IO> Clone for Outgoing<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<IO> Outgoing<IO> {
+    /// Counters of the connection, also while it is running.
+    pub fn stats(&self) -> StatsSnapshot {
+        self.inner.lock().unwrap().stats()
+    }
+
+    /// [`Connection::handles_changed`], also while the connection is running.
+    pub fn handles_changed<I>(&self, handles: I)
+    where
+        I: IntoIterator<Item = Handle>,
+    {
+        self.inner.lock().unwrap().handles_changed(handles)
+    }
+
+    /// Queue notifications back to back, with no other packet in between.
+    ///
+    /// Either every notification is queued or, with [`TryNotifyError::Full`]
+    /// when the outgoing queue has no room for all of them, none is.
+    pub fn send_batch(
+        &self,
+        notifications: Vec<(Handle, Box<[u8]>)>,
+    ) -> std::result::Result<(), TryNotifyError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.check_running()?;
+        if inner.stream.txqueue.len() + notifications.len() > inner.outgoing_capacity {
+            return Err(TryNotifyError::Full);
+        }
+        let packets = notifications
+            .iter()
+            .map(|(handle, value)| inner.machine.queue_notification(handle, value))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)?;
+        for packet in packets {
+            inner.push(packet);
+        }
+        Ok(())
+    }
+
+    /// [`WeakOutgoing`] to this connection's outgoing queue.
+    pub fn downgrade(&self) -> WeakOutgoing<IO> {
+        WeakOutgoing {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+}
+
+/// [`Outgoing`] that does not keep the connection alive.
+///
+/// An [`Outgoing`] holds the connection, socket included, until it is
+/// dropped, even after [`Connection::run`] returned. Hold this one in
+/// background tasks that may outlive the connection.
+pub struct WeakOutgoing<IO = AttStream> {
+    inner: Weak<Mutex<Inner<IO>>>,
+}
+
+impl<IO> Clone for WeakOutgoing<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<IO> WeakOutgoing<IO> {
+    /// [`Outgoing`] of the connection, unless it was dropped.
+    pub fn upgrade(&self) -> Option<Outgoing<IO>> {
+        self.inner.upgrade().map(|inner| Outgoing { inner })
+    }
+
+    /// Same as [`Outgoing::send_batch`]. Fails with
+    /// [`io::ErrorKind::NotConnected`] once the connection was dropped.
+    pub fn send_batch(
+        &self,
+        notifications: Vec<(Handle, Box<[u8]>)>,
+    ) -> std::result::Result<(), TryNotifyError> {
+        match self.upgrade() {
+            Some(outgoing) => outgoing.send_batch(notifications),
+            None => Err(Error::from(io::Error::from(io::ErrorKind::NotConnected)).into()),
+        }
+    }
+}
+
+/// Indication sender for a characteristic value.
+///
+/// Unlike [`Notification`], an indication completes only after the client
+/// sent a Handle Value Confirmation, so it naturally applies back-pressure
+/// end to end.
+pub struct Indication<IO = AttStream> {
+    inner: IndicationInner<IO>,
+}
+
+impl<IO> Indication<IO> {
+    /// Send an indication and wait for the client's confirmation.
+    pub async fn indicate(&mut self, value: &[u8]) -> Result<()> {
+        poll_fn(|cx| self.inner.poll_indicate(cx, value)).await?;
+        Ok(())
+    }
+}
+
+impl<IO> AsyncWrite for Indication<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
     fn poll_write(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -539,72 +1087,416 @@ impl AsyncWrite for Indication {
     }
 }
 
+/// How [`Server::accept`] treats an error of the listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AcceptErrorClass {
+    /// Accept again after yielding. (e.g. the connection was aborted before it was accepted)
+    Retry,
+    /// Accept again after [`ACCEPT_BACKOFF`], waiting for resources to be released.
+    Backoff,
+    /// The listener is unusable.
+    Fatal,
+}
+
+impl AcceptErrorClass {
+    fn of(err: &io::Error) -> Self {
+        match err.raw_os_error() {
+            Some(libc::EMFILE) | Some(libc::ENFILE) | Some(libc::ENOBUFS) | Some(libc::ENOMEM) => {
+                Self::Backoff
+            }
+            Some(libc::EBADF)
+            | Some(libc::EINVAL)
+            | Some(libc::ENOTSOCK)
+            | Some(libc::EOPNOTSUPP)
+            | Some(libc::EFAULT) => Self::Fatal,
+            Some(libc::ECONNABORTED)
+            | Some(libc::EINTR)
+            | Some(libc::EAGAIN)
+            | Some(libc::EPROTO)
+            | Some(libc::EPERM)
+            | Some(libc::ETIMEDOUT)
+            | Some(libc::EHOSTUNREACH)
+            | Some(libc::ENETDOWN)
+            | Some(libc::ENETUNREACH) => Self::Retry,
+            // Raised by the listener for a single connection, e.g. an unexpected peer address.
+            None => Self::Retry,
+            // Unknown errors may persist, so do not spin on them.
+            Some(_) => Self::Backoff,
+        }
+    }
+}
+
+type AcceptErrorHook = Box<dyn FnMut(&io::Error) + Send>;
+
 struct ServerInner<L> {
     inner: L,
+    terminated: bool,
+    on_accept_error: Option<AcceptErrorHook>,
+}
+
+impl<L> ServerInner<L> {
+    fn new(inner: L) -> Self {
+        Self {
+            inner,
+            terminated: false,
+            on_accept_error: None,
+        }
+    }
 }
 
 impl<L, IO> ServerInner<L>
 where
-    L: Stream<Item = io::Result<(IO, socket2::SockAddr)>> + Unpin,
+    L: Stream<Item = io::Result<(IO, PeerInfo)>> + Unpin,
     IO: AsyncRead + AsyncWrite + Unpin,
 {
-    async fn accept(&mut self) -> io::Result<Option<(ConnectionInner<IO>, socket2::SockAddr)>> {
-        if let Some((sock, addr)) = self.inner.try_next().await? {
-            return Ok(Some((
-                ConnectionInner {
-                    inner: Arc::new(Mutex::new(Inner::new(sock))),
-                },
-                addr,
-            )));
+    async fn accept(&mut self) -> io::Result<Option<(ConnectionInner<IO>, PeerInfo)>> {
+        while !self.terminated {
+            let err = match self.inner.next().await {
+                Some(Ok((sock, peer))) => {
+                    return Ok(Some((
+                        ConnectionInner {
+                            inner: Arc::new(Mutex::new(Inner::new(sock))),
+                        },
+                        peer,
+                    )));
+                }
+                Some(Err(err)) => err,
+                None => break,
+            };
+
+            let class = AcceptErrorClass::of(&err);
+            debug!("accept failed. {} ({:?})", err, class);
+            if class == AcceptErrorClass::Fatal {
+                self.terminated = true;
+                return Err(err);
+            }
+            if let Some(hook) = &mut self.on_accept_error {
+                hook(&err);
+            }
+            match class {
+                AcceptErrorClass::Backoff => tokio::time::sleep(ACCEPT_BACKOFF).await,
+                _ => tokio::task::yield_now().await,
+            }
         }
         Ok(None)
     }
 }
 
-pub struct Connection {
-    inner: ConnectionInner<AttStream>,
+/// Whether the link of a [`Connection`] is authenticated.
+///
+/// Shared between clones. Nothing in this crate sets it; the layer doing
+/// pairing calls [`mark_authenticated`](Self::mark_authenticated).
+#[derive(Debug, Clone, Default)]
+pub struct AuthenticationState(Arc<AtomicBool>);
+
+impl AuthenticationState {
+    pub fn is_authenticated(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn mark_authenticated(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Negotiated ATT_MTU of a [`Connection`], following it while the connection
+/// runs. A detached one created by [`Default`] stays 23.
+#[derive(Debug, Clone)]
+pub struct SharedMtu(Arc<AtomicUsize>);
+
+impl SharedMtu {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for SharedMtu {
+    fn default() -> Self {
+        Self(Arc::new(AtomicUsize::new(DEFAULT_MTU)))
+    }
+}
+
+pub struct Connection<IO = AttStream> {
+    inner: ConnectionInner<IO>,
     addr: crate::Address,
+    authentication: AuthenticationState,
+    /// State of the [`Server`] that accepted this connection.
+    server_state: Option<Arc<watch::Sender<ServerState>>>,
 }
 
-impl Connection {
+impl<IO> Connection<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Serve ATT over an already connected transport.
+    ///
+    /// [`Server::accept`] does this for L2CAP sockets. `io` must preserve
+    /// packet boundaries: each read yields one ATT PDU.
+    pub fn new(io: IO, addr: crate::Address) -> Self {
+        Self {
+            inner: ConnectionInner {
+                inner: Arc::new(Mutex::new(Inner::new(io))),
+            },
+            addr,
+            authentication: Default::default(),
+            server_state: None,
+        }
+    }
+
     pub fn address(&self) -> &crate::Address {
         &self.addr
     }
 
-    pub fn notification(&self, handle: Handle) -> Notification {
+    /// `false` until [`AuthenticationState::mark_authenticated`] is called.
+    pub fn is_authenticated(&self) -> bool {
+        self.authentication.is_authenticated()
+    }
+
+    /// Shared authentication state, to be updated once the link is authenticated.
+    pub fn authentication_state(&self) -> AuthenticationState {
+        self.authentication.clone()
+    }
+
+    pub fn notification(&self, handle: Handle) -> Notification<IO> {
         Notification {
             inner: self.inner.notification(handle),
         }
     }
 
-    pub fn indication(&self, handle: Handle) -> Indication {
+    pub fn indication(&self, handle: Handle) -> Indication<IO> {
         Indication {
             inner: self.inner.indication(handle),
         }
     }
 
+    /// Sender not bound to a handle, e.g. for [`Outgoing::send_batch`].
+    pub fn outgoing(&self) -> Outgoing<IO> {
+        self.inner.outgoing()
+    }
+
+    /// Record a structural change of the attribute table.
+    ///
+    /// `handles` are the handles removed or reassigned to another attribute.
+    /// Notifications and indications created before this call for one of them
+    /// fail with [`Error::StaleHandle`]. Create a new writer afterwards. Use
+    /// [`Outgoing::handles_changed`] once the connection is moved into
+    /// [`run`](Self::run).
+    pub fn handles_changed<I>(&self, handles: I)
+    where
+        I: IntoIterator<Item = Handle>,
+    {
+        self.inner.handles_changed(handles)
+    }
+
+    /// Number of packets waiting in the outgoing queue.
+    pub fn outgoing_queue_depth(&self) -> usize {
+        self.inner.outgoing_queue_depth()
+    }
+
+    /// Negotiated ATT_MTU. 23 until the client exchanges MTU.
+    pub fn current_mtu(&self) -> usize {
+        self.inner.current_mtu()
+    }
+
+    /// [`current_mtu`](Self::current_mtu) that keeps following the
+    /// connection once it is moved into [`run`](Self::run), e.g. for the
+    /// handler.
+    pub fn shared_mtu(&self) -> SharedMtu {
+        self.inner.shared_mtu()
+    }
+
+    /// Whether the client exchanged MTU. Before that, requests longer than
+    /// 23 octets are answered with Invalid PDU and notifications carry at
+    /// most 20 octets of value.
+    pub fn mtu_state(&self) -> MtuState {
+        self.inner.mtu_state()
+    }
+
+    /// Counters of this connection. Use [`Outgoing::stats`] once the
+    /// connection is moved into [`run`](Self::run).
+    pub fn stats(&self) -> StatsSnapshot {
+        self.inner.stats()
+    }
+
+    /// Set how many packets the outgoing queue holds before notifications
+    /// are refused. Defaults to [`DEFAULT_OUTGOING_QUEUE_CAPACITY`].
+    pub fn set_outgoing_queue_capacity(&self, capacity: usize) {
+        self.inner.set_outgoing_queue_capacity(capacity)
+    }
+
+    /// Keep the connection open for notifications after the client shut
+    /// down its sending half. Disabled by default.
+    ///
+    /// When allowed, [`run`](Self::run) returns only after a [`Notification`]
+    /// or [`Indication`] was shut down with
+    /// [`AsyncWriteExt::shutdown`](tokio::io::AsyncWriteExt::shutdown), or on
+    /// error. A client disconnecting in both directions is such an error:
+    /// reads past EOF of an [`AttStream`] fail with
+    /// [`NotConnected`](io::ErrorKind::NotConnected) once the channel is
+    /// gone. Indications wait for confirmations the client cannot send
+    /// anymore, so use notifications on a half closed connection.
+    pub fn allow_half_close(&self, allow: bool) {
+        self.inner.allow_half_close(allow)
+    }
+
+    /// Server Rx MTU to advertise when the client exchanges MTU, clamped to
+    /// 23..=517. Defaults to [`DEFAULT_SERVER_MTU`].
+    ///
+    /// Exchange MTU Requests are answered by the connection, never by
+    /// [`Handler::handle_exchange_mtu_request`]. The receive buffer grows to
+    /// the advertised size before the next PDU is read.
+    pub fn set_server_mtu(&self, mtu: u16) {
+        self.inner.set_server_mtu(mtu)
+    }
+
+    /// How long an indication waits for its confirmation, counted from when
+    /// it is written to the socket. Defaults to
+    /// [`DEFAULT_INDICATION_TIMEOUT`], the ATT transaction timeout.
+    ///
+    /// On expiry the waiting [`Indication`] fails with
+    /// [`Error::IndicationTimeout`] and [`run`](Self::run) returns
+    /// [`Error::TransactionTimeout`] without answering anything more, as the
+    /// client has to reconnect to use ATT again.
+    pub fn set_indication_timeout(&self, timeout: Duration) {
+        self.inner.set_indication_timeout(timeout)
+    }
+
+    /// Serve requests with `handler` until the client disconnects.
+    ///
+    /// Responses, notifications and indications go out in the order they
+    /// were queued. `handler` runs with the outgoing queue locked, so a
+    /// response follows the packets queued before its request arrived and
+    /// precedes those queued while it is handled. Queueing from within
+    /// `handler` deadlocks; do it from another task.
+    ///
+    /// Once this returns or is dropped, senders waiting for queue space or
+    /// a confirmation fail rather than wait forever.
     pub async fn run<H>(self, handler: H) -> Result<()>
     where
         H: crate::Handler,
     {
-        log::debug!("Start serving.");
-        self.inner.run(handler).await?;
-        log::debug!("Done serving.");
-        Ok(())
+        self.run_with_shutdown(handler, future::pending()).await
+    }
+
+    /// Same as [`run`](Self::run), and stop reading requests once `shutdown`
+    /// completes. Returns `Ok` after the packets queued by then are written.
+    pub async fn run_with_shutdown<H, S>(self, handler: H, shutdown: S) -> Result<()>
+    where
+        H: crate::Handler,
+        S: Future<Output = ()>,
+    {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("att_connection", peer = ?self.addr);
+        let Self {
+            inner,
+            server_state,
+            ..
+        } = self;
+        let run = async {
+            debug!("Start serving.");
+            let result = inner.run_with_shutdown(handler, shutdown).await;
+            debug!("Done serving.");
+            if let Some(state) = server_state {
+                state.send(ServerState::Disconnected).ok();
+            }
+            result
+        };
+        #[cfg(feature = "tracing")]
+        let run = tracing::Instrument::instrument(run, span);
+        run.await
+    }
+}
+
+impl Connection<AttStream> {
+    /// Address of the local adapter this connection was accepted on, e.g.
+    /// when the server listens on any adapter.
+    pub fn local_address(&self) -> io::Result<crate::Address> {
+        self.inner
+            .inner
+            .lock()
+            .unwrap()
+            .stream
+            .inner
+            .local_address()
     }
 }
 
-pub struct Server {
-    inner: ServerInner<AttListener>,
+/// State of a [`Server`], e.g. to enable advertising while it is listening.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerState {
+    /// Not accepting connections.
+    Idle,
+    /// [`Server::accept`] is waiting for a connection.
+    Listening,
+    /// A connection from this address was accepted.
+    Connected(crate::Address),
+    /// [`Connection::run`] of an accepted connection returned.
+    Disconnected,
+}
+
+/// ATT Server.
+///
+/// Accepts L2CAP connections by default. Other transports are served by
+/// [`Server::from_listener`] over a stream of accepted connections.
+pub struct Server<L = AttListener> {
+    inner: ServerInner<L>,
+    state: Arc<watch::Sender<ServerState>>,
+    /// Keeps the channel open, so that states are recorded without subscribers.
+    state_rx: watch::Receiver<ServerState>,
+}
+
+impl<L> Server<L> {
+    fn with_listener(listener: L) -> Self {
+        let (state, state_rx) = watch::channel(ServerState::Idle);
+        Self {
+            inner: ServerInner::new(listener),
+            state: Arc::new(state),
+            state_rx,
+        }
+    }
+
+    /// Follow the state of this server and of the connections it accepts.
+    ///
+    /// The state is [`Listening`](ServerState::Listening) while
+    /// [`accept`](Self::accept) waits, [`Connected`](ServerState::Connected)
+    /// once it returns a connection, and
+    /// [`Disconnected`](ServerState::Disconnected) when
+    /// [`Connection::run`] of that connection returns. Only the latest state
+    /// is kept, so with several connections at once it reflects the last
+    /// change.
+    pub fn state_events(&self) -> watch::Receiver<ServerState> {
+        self.state_rx.clone()
+    }
 }
 
 impl Server {
     /// Constract Instance.
     pub fn new() -> io::Result<Self> {
         let sock = AttListener::new()?;
-        Ok(Self {
-            inner: ServerInner { inner: sock },
-        })
+        Ok(Self::with_listener(sock))
+    }
+
+    /// Same as [`new`](Self::new), for code constructing values by a
+    /// parameterless fallible constructor. [`TryFrom<()>`] does the same.
+    pub fn try_default() -> io::Result<Self> {
+        Self::new()
+    }
+
+    /// Builder to listen on other than the fixed ATT channel.
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Address of the local adapter the listener is bound to.
+    ///
+    /// A listener bound to any adapter (BDADDR_ANY), as [`Server::new`]
+    /// does, has no single address: this is then the public address of the
+    /// only adapter up, looked up with `HCIGETDEVINFO`, and fails with
+    /// [`io::ErrorKind::AddrNotAvailable`] with none or several up. Use
+    /// [`Connection::local_address`] for the adapter a connection was
+    /// accepted on.
+    pub fn local_address(&self) -> io::Result<crate::Address> {
+        self.inner.inner.local_address()
     }
 
     pub fn needs_bond(&self) -> io::Result<()> {
@@ -619,54 +1511,532 @@ impl Server {
             .set_sockopt_bt_security(crate::sock::BT_SECURITY_HIGH, 0)
     }
 
-    pub async fn accept(&mut self) -> io::Result<Option<(Connection, crate::Address)>> {
-        if let Some((connection, addr)) = self.inner.accept().await? {
-            log::debug!("Connection accepted.");
-            let addr = crate::sock::try_from(addr)?;
-            Ok(Some((
-                Connection {
-                    inner: connection,
-                    addr: addr.clone(),
-                },
-                addr,
-            )))
-        } else {
-            Ok(None)
-        }
+    /// Set the L2CAP mode of accepted channels. See [`L2capMode`].
+    pub fn set_l2cap_mode(&self, mode: L2capMode) -> io::Result<()> {
+        self.inner.inner.set_l2cap_mode(mode)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::convert::TryFrom;
-    use tokio::io::AsyncWriteExt;
-    use tokio_test::io::Builder;
+impl TryFrom<()> for Server {
+    type Error = io::Error;
 
-    #[tokio::test]
-    async fn test_stream() {
-        let stream = Builder::new()
-            .read(&[0x02, 0x17, 0x00])
-            .write(&[0x03, 0x18, 0x00])
-            .build();
-        let mut stream = PacketStream::new(stream);
-        let packet = stream.try_next().await.unwrap().unwrap();
-        let packet = pkt::ExchangeMtuRequest::try_from(packet).unwrap();
-        assert_eq!(*packet.client_rx_mtu(), 23);
+    fn try_from(_: ()) -> io::Result<Self> {
+        Self::new()
+    }
+}
 
-        let packet = pkt::ExchangeMtuResponse::new(0x0018);
-        stream.send(packet).await.unwrap();
+impl<L, IO> Server<L>
+where
+    L: Stream<Item = io::Result<(IO, PeerInfo)>> + Unpin,
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Serve connections yielded by `listener`.
+    ///
+    /// Each `IO` must preserve packet boundaries, as for [`Connection::new`].
+    pub fn from_listener(listener: L) -> Self {
+        Self::with_listener(listener)
     }
 
-    #[tokio::test]
-    async fn test_connection() {
+    /// Report errors [`accept`](Self::accept) retries internally to `hook`.
+    /// (e.g. to count them)
+    pub fn on_accept_error<F>(&mut self, hook: F)
+    where
+        F: FnMut(&io::Error) + Send + 'static,
+    {
+        self.inner.on_accept_error = Some(Box::new(hook));
+    }
+
+    /// Accept a connection, or `None` once the listener ended.
+    ///
+    /// Transient errors (e.g. `ECONNABORTED`) are retried, and running out of
+    /// file descriptors (`EMFILE` / `ENFILE`) or any unknown error retried
+    /// after a short backoff.
+    /// Only an unusable listener (e.g. `EBADF` / `EINVAL`) fails, after which
+    /// this returns `None`.
+    pub async fn accept(&mut self) -> io::Result<Option<(Connection<IO>, PeerInfo)>> {
+        self.state.send(ServerState::Listening).ok();
+        match self.inner.accept().await {
+            Ok(Some((connection, peer))) => {
+                debug!("Connection accepted. {:?}", peer);
+                self.state
+                    .send(ServerState::Connected(peer.address.clone()))
+                    .ok();
+                Ok(Some((
+                    Connection {
+                        inner: connection,
+                        addr: peer.address.clone(),
+                        authentication: Default::default(),
+                        server_state: Some(self.state.clone()),
+                    },
+                    peer,
+                )))
+            }
+            result => {
+                self.state.send(ServerState::Idle).ok();
+                result.map(|_| None)
+            }
+        }
+    }
+}
+
+/// [`Server`] Builder.
+///
+/// By default the server listens on the fixed ATT channel (CID 0x0004) of the
+/// LE public address, which is the only channel standard GATT clients use.
+/// Binding it requires bluetoothd to be stopped. Other CIDs or a PSM are meant
+/// for tests against a second adapter or an emulator while bluetoothd keeps
+/// CID 0x0004.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerBuilder {
+    channel: Channel,
+    address_type: AddressType,
+    backlog: u32,
+    /// BT_SECURITY level of accepted channels.
+    security: Option<u8>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self {
+            channel: Channel::Cid(crate::sock::ATT_CID),
+            address_type: AddressType::LePublic,
+            backlog: crate::sock::DEFAULT_BACKLOG,
+            security: None,
+        }
+    }
+}
+
+impl ServerBuilder {
+    /// Listen on fixed channel `cid`.
+    pub fn cid(mut self, cid: u16) -> Self {
+        self.channel = Channel::Cid(cid);
+        self
+    }
+
+    /// Listen on a connection oriented channel on `psm`.
+    pub fn psm(mut self, psm: u16) -> Self {
+        self.channel = Channel::Psm(psm);
+        self
+    }
+
+    /// Local address type to listen on. (BR/EDR or LE)
+    pub fn address_type(mut self, address_type: AddressType) -> Self {
+        self.address_type = address_type;
+        self
+    }
+
+    /// Number of connections the kernel queues until they are accepted.
+    /// Defaults to 1.
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// Accept only bonded links. Same as [`Server::needs_bond`], but set
+    /// before the socket listens.
+    pub fn needs_bond(mut self) -> Self {
+        self.security = Some(crate::sock::BT_SECURITY_MEDIUM);
+        self
+    }
+
+    /// Accept only bonded links with MITM protection. Same as
+    /// [`Server::needs_bond_mitm`], but set before the socket listens.
+    pub fn needs_bond_mitm(mut self) -> Self {
+        self.security = Some(crate::sock::BT_SECURITY_HIGH);
+        self
+    }
+
+    /// Bind and listen.
+    ///
+    /// Fails with `AddrInUse` if the channel is taken (e.g. CID 0x0004 by
+    /// bluetoothd) and `PermissionDenied` without the needed capabilities.
+    pub fn build(self) -> io::Result<Server> {
+        let sock = AttListener::bind(self.channel, self.address_type, self.backlog, self.security)?;
+        Ok(Server::with_listener(sock))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::sink::SinkExt;
+    use futures_util::stream::TryStreamExt;
+    use std::convert::TryFrom;
+    use tokio::io::AsyncWriteExt;
+    use tokio_test::io::Builder;
+
+    #[test]
+    fn test_server_builder() {
+        let builder = ServerBuilder::default();
+        assert_eq!(
+            builder,
+            ServerBuilder {
+                channel: Channel::Cid(0x0004),
+                address_type: AddressType::LePublic,
+                backlog: 1,
+                security: None,
+            }
+        );
+
+        let builder = builder
+            .psm(0x0080)
+            .address_type(AddressType::LeRandom)
+            .backlog(4)
+            .needs_bond();
+        assert_eq!(
+            builder,
+            ServerBuilder {
+                channel: Channel::Psm(0x0080),
+                address_type: AddressType::LeRandom,
+                backlog: 4,
+                security: Some(crate::sock::BT_SECURITY_MEDIUM),
+            }
+        );
+        let builder = builder.cid(0x0040).needs_bond_mitm();
+        assert_eq!(builder.channel, Channel::Cid(0x0040));
+        assert_eq!(builder.security, Some(crate::sock::BT_SECURITY_HIGH));
+    }
+
+    #[test]
+    fn test_enqueue_too_large() {
+        let mut stream = PacketStream::new(Builder::new().build());
+        let response = pkt::ReadResponse::new(vec![0xAA; DEFAULT_MTU].into());
+        let err = stream.enqueue(response).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ResponseTooLarge {
+                opcode: pkt::OpCode::ReadResponse,
+                size: 24,
+                mtu: DEFAULT_MTU,
+            }
+        ));
+        assert!(stream.txqueue.is_empty());
+
+        let response = pkt::ReadResponse::new(vec![0xAA; DEFAULT_MTU - 1].into());
+        stream.enqueue(response).unwrap();
+        assert_eq!(stream.txqueue[0].len(), DEFAULT_MTU);
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let stream = Builder::new()
+            .read(&[0x02, 0x17, 0x00])
+            .write(&[0x03, 0x18, 0x00])
+            .build();
+        let mut stream = PacketStream::new(stream);
+        let packet = stream.try_next().await.unwrap().unwrap();
+        let packet = pkt::ExchangeMtuRequest::try_from(packet).unwrap();
+        assert_eq!(*packet.client_rx_mtu(), 23);
+
+        let packet = pkt::ExchangeMtuResponse::new(0x0018);
+        stream.send(packet).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_from_listener() {
+        let peer = PeerInfo {
+            address: crate::Address::le_public_from([1; 6]),
+            address_type: AddressType::LePublic,
+        };
+        let stream = Builder::new()
+            .read(&[0x02, 0x17, 0x00])
+            .write(&[0x03, 0x05, 0x02])
+            .build();
+        let listener = futures_util::stream::iter(vec![Ok((stream, peer.clone()))]);
+        let mut server = Server::from_listener(listener);
+
+        let (connection, accepted) = server.accept().await.unwrap().unwrap();
+        assert_eq!(accepted, peer);
+        assert_eq!(connection.address(), &peer.address);
+        struct H;
+        impl Handler for H {}
+        connection.run(H).await.unwrap();
+
+        assert!(server.accept().await.unwrap().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_server_state_events() {
+        use futures_util::StreamExt;
+
+        let peer = PeerInfo {
+            address: crate::Address::le_public_from([1; 6]),
+            address_type: AddressType::LePublic,
+        };
+        let stream = Builder::new().read(&[0x52, 0x03, 0x00, 0x01]).build();
+        let listener = futures_util::stream::iter(vec![Ok((stream, peer.clone()))])
+            .chain(futures_util::stream::pending());
+        let mut server = Server::from_listener(listener);
+        let mut states = server.state_events();
+        assert_eq!(*states.borrow_and_update(), ServerState::Idle);
+
+        let (connection, _) = server.accept().await.unwrap().unwrap();
+        assert_eq!(
+            *states.borrow_and_update(),
+            ServerState::Connected(peer.address.clone())
+        );
+
+        struct H;
+        impl Handler for H {}
+        connection.run(H).await.unwrap();
+        assert!(states.has_changed().unwrap());
+        assert_eq!(*states.borrow_and_update(), ServerState::Disconnected);
+
+        let accept = tokio::time::timeout(Duration::from_secs(1), server.accept());
+        assert!(accept.await.is_err());
+        assert_eq!(*states.borrow_and_update(), ServerState::Listening);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_accept_errors() {
+        let peer = PeerInfo {
+            address: crate::Address::le_public_from([1; 6]),
+            address_type: AddressType::LePublic,
+        };
+        let errno = |errno| Err(io::Error::from_raw_os_error(errno));
+        let listener = futures_util::stream::iter(vec![
+            errno(libc::ECONNABORTED),
+            errno(libc::EINTR),
+            errno(libc::EAGAIN),
+            errno(libc::EMFILE),
+            errno(libc::ENFILE),
+            errno(libc::EIO),
+            Ok((Builder::new().build(), peer.clone())),
+            errno(libc::EBADF),
+            Ok((Builder::new().build(), peer.clone())),
+        ]);
+        let mut server = Server::from_listener(listener);
+        let retried = Arc::new(Mutex::new(vec![]));
+        server.on_accept_error({
+            let retried = retried.clone();
+            move |err| retried.lock().unwrap().push(err.raw_os_error().unwrap())
+        });
+
+        let started = tokio::time::Instant::now();
+        let (_, accepted) = server.accept().await.unwrap().unwrap();
+        assert_eq!(accepted, peer);
+        assert_eq!(
+            *retried.lock().unwrap(),
+            [
+                libc::ECONNABORTED,
+                libc::EINTR,
+                libc::EAGAIN,
+                libc::EMFILE,
+                libc::ENFILE,
+                libc::EIO
+            ]
+        );
+        assert_eq!(started.elapsed(), ACCEPT_BACKOFF * 3);
+
+        let err = server.accept().await.err().unwrap();
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        assert!(server.accept().await.unwrap().is_none());
+        assert_eq!(retried.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_accept_error_class() {
+        let class = |errno| AcceptErrorClass::of(&io::Error::from_raw_os_error(errno));
+        assert_eq!(class(libc::ECONNABORTED), AcceptErrorClass::Retry);
+        assert_eq!(class(libc::EPROTO), AcceptErrorClass::Retry);
+        assert_eq!(class(libc::EMFILE), AcceptErrorClass::Backoff);
+        assert_eq!(class(libc::ENOBUFS), AcceptErrorClass::Backoff);
+        assert_eq!(class(libc::EINVAL), AcceptErrorClass::Fatal);
+        assert_eq!(class(libc::EBADF), AcceptErrorClass::Fatal);
+        assert_eq!(class(libc::EIO), AcceptErrorClass::Backoff);
+        assert_eq!(
+            AcceptErrorClass::of(&io::Error::other("unexpected l2 address type.")),
+            AcceptErrorClass::Retry
+        );
+    }
+
+    #[tokio::test]
+    async fn test_response_order() {
+        struct H {
+            notification: Notification<tokio_test::io::Mock>,
+            inner: Arc<Mutex<Inner<tokio_test::io::Mock>>>,
+            sent: Option<std::thread::JoinHandle<()>>,
+        }
+        impl Handler for H {
+            fn handle_read_request(
+                &mut self,
+                _: &pkt::ReadRequest,
+            ) -> std::result::Result<pkt::ReadResponse, HandlerError> {
+                // the connection is locked until the response is queued, so
+                // the notification below is queued after it whenever the
+                // thread gets to run.
+                assert!(matches!(
+                    self.inner.try_lock(),
+                    Err(std::sync::TryLockError::WouldBlock)
+                ));
+                let notification = self.notification.clone();
+                self.sent = Some(std::thread::spawn(move || {
+                    notification.try_notify(&[0x02]).unwrap();
+                }));
+                Ok(pkt::ReadResponse::new(vec![0xAA].into()))
+            }
+        }
+
+        let stream = Builder::new()
+            .write(&[0x1B, 0x01, 0x00, 0x01])
+            .read(&[0x0A, 0x03, 0x00])
+            .write(&[0x0B, 0xAA])
+            .write(&[0x1B, 0x01, 0x00, 0x02])
+            .build();
+        let connection = Connection::new(stream, crate::Address::le_public_from([0; 6]));
+        let notification = connection.notification(Handle::new(1));
+        notification.try_notify(&[0x01]).unwrap();
+
+        let mut handler = H {
+            notification,
+            inner: connection.inner.inner.clone(),
+            sent: None,
+        };
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        // writes the first notification and the response.
+        assert!(connection
+            .inner
+            .poll_run(&mut cx, &mut handler)
+            .is_pending());
+        handler.sent.take().unwrap().join().unwrap();
+        {
+            let inner = handler.inner.lock().unwrap();
+            let queued = inner.stream.txqueue.iter().collect::<Vec<_>>();
+            assert_eq!(queued.len(), 1);
+            assert_eq!(&queued[0][..], &[0x1B, 0x01, 0x00, 0x02]);
+        }
+
+        connection.run(handler).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_batch() {
+        struct H;
+        impl Handler for H {}
+
+        let stream = Builder::new()
+            .write(&[0x1B, 0x01, 0x00, 0x01])
+            .write(&[0x1B, 0x02, 0x00, 0x02])
+            .write(&[0x1B, 0x03, 0x00, 0x03])
+            .build();
+        let connection = Connection::new(stream, crate::Address::le_public_from([0; 6]));
+        connection.set_outgoing_queue_capacity(3);
+        let outgoing = connection.outgoing();
+
+        let batch = |n: u16| (Handle::new(n), vec![n as u8].into_boxed_slice());
+        outgoing
+            .clone()
+            .send_batch(vec![batch(1), batch(2), batch(3)])
+            .unwrap();
+        assert!(matches!(
+            outgoing.send_batch(vec![batch(4)]),
+            Err(TryNotifyError::Full)
+        ));
+        assert_eq!(connection.outgoing_queue_depth(), 3);
+        connection.run(H).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_weak_outgoing() {
+        struct H;
+        impl Handler for H {}
+
+        let stream = Builder::new().write(&[0x1B, 0x01, 0x00, 0x01]).build();
+        let connection = Connection::new(stream, crate::Address::le_public_from([0; 6]));
+        let weak = connection.outgoing().downgrade();
+        weak.send_batch(vec![(Handle::new(1), vec![1].into())])
+            .unwrap();
+        assert!(weak.upgrade().is_some());
+        connection.run(H).await.unwrap();
+
+        // run() dropped the connection; nothing else held it.
+        assert!(weak.upgrade().is_none());
+        assert!(matches!(
+            weak.send_batch(vec![(Handle::new(1), vec![1].into())]),
+            Err(TryNotifyError::Error(Error::Io(err))) if err.kind() == io::ErrorKind::NotConnected
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        struct H;
+        impl Handler for H {
+            fn handle_read_request(
+                &mut self,
+                _: &pkt::ReadRequest,
+            ) -> std::result::Result<pkt::ReadResponse, HandlerError> {
+                Ok(pkt::ReadResponse::new(vec![0x01, 0x02].into()))
+            }
+
+            fn handle_write_request(
+                &mut self,
+                item: &pkt::WriteRequest,
+            ) -> std::result::Result<pkt::WriteResponse, HandlerError> {
+                Err(HandlerError::new(
+                    item.attribute_handle().clone(),
+                    pkt::ErrorCode::WriteNotPermitted,
+                ))
+            }
+        }
+
+        let stream = Builder::new()
+            .write(&[0x1B, 0x01, 0x00, 0xAA])
+            .read(&[0x02, 0x40, 0x00])
+            .write(&[0x03, 0x05, 0x02])
+            .read(&[0x0A, 0x01, 0x00])
+            .write(&[0x0B, 0x01, 0x02])
+            .read(&[0x0A, 0x02, 0x00])
+            .write(&[0x0B, 0x01, 0x02])
+            .read(&[0x12, 0x01, 0x00, 0xFF])
+            .write(&[0x01, 0x12, 0x01, 0x00, 0x03])
+            .build();
+        let connection = Connection::new(stream, crate::Address::le_public_from([0; 6]));
+        connection
+            .notification(Handle::new(1))
+            .try_notify(&[0xAA])
+            .unwrap();
+        let stats = connection.stats();
+        assert_eq!(stats.outgoing_queue_depth, 1);
+        assert_eq!(stats.bytes_sent, 0);
+
+        let outgoing = connection.outgoing();
+        connection.run(H).await.unwrap();
+
+        let stats = outgoing.stats();
+        assert_eq!(
+            stats.received,
+            vec![
+                (pkt::OpCode::ExchangeMtuRequest, 1),
+                (pkt::OpCode::ReadRequest, 2),
+                (pkt::OpCode::WriteRequest, 1),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            stats.error_responses,
+            vec![(pkt::ErrorCode::WriteNotPermitted, 1)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(stats.notifications_sent, 1);
+        assert_eq!(stats.indications_sent, 0);
+        assert_eq!(stats.confirmations_received, 0);
+        assert_eq!(stats.bytes_received, 13);
+        assert_eq!(stats.bytes_sent, 18);
+        assert_eq!(stats.mtu, 0x40);
+        assert_eq!(stats.outgoing_queue_depth, 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection() {
         struct H;
         impl Handler for H {}
 
         let stream = Builder::new()
             .write(&[0x1B, 0x01, 0x00, 0x6F, 0x6B])
             .read(&[0x02, 0x17, 0x00])
-            .write(&[0x03, 0x17, 0x00])
+            .write(&[0x03, 0x05, 0x02])
             .build();
         let connection = ConnectionInner {
             inner: Arc::new(Mutex::new(Inner::new(stream))),
@@ -677,6 +2047,149 @@ mod tests {
         connection.run(H).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_stale_handle() {
+        struct H;
+        impl Handler for H {}
+
+        let stream = Builder::new()
+            .write(&[0x1B, 0x01, 0x00, 0x6F, 0x6B])
+            .write(&[0x1B, 0x02, 0x00, 0x6F, 0x6B])
+            .write(&[0x1B, 0x01, 0x00, 0x6F, 0x6B])
+            .build();
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(stream))),
+        };
+
+        let mut stale = connection.notification(Handle::new(1));
+        let mut unchanged = connection.notification(Handle::new(2));
+        poll_fn(|cx| stale.poll_notify(cx, b"ok")).await.unwrap();
+
+        connection.handles_changed(vec![Handle::new(1)]);
+        let err = poll_fn(|cx| stale.poll_notify(cx, b"ok")).await;
+        assert!(matches!(err, Err(Error::StaleHandle(h)) if h == Handle::new(1)));
+        assert!(matches!(
+            stale.try_notify(b"ok"),
+            Err(TryNotifyError::Error(Error::StaleHandle(..)))
+        ));
+        poll_fn(|cx| unchanged.poll_notify(cx, b"ok"))
+            .await
+            .unwrap();
+
+        let mut refreshed = connection.notification(Handle::new(1));
+        poll_fn(|cx| refreshed.poll_notify(cx, b"ok"))
+            .await
+            .unwrap();
+
+        let mut indication = connection.indication(Handle::new(1));
+        connection.outgoing().handles_changed(vec![Handle::new(1)]);
+        let err = poll_fn(|cx| indication.poll_indicate(cx, b"ok")).await;
+        assert!(matches!(err, Err(Error::StaleHandle(..))));
+
+        connection.run(H).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_current_mtu() {
+        struct H;
+        impl Handler for H {}
+
+        let stream = Builder::new()
+            .read(&[0x02, 0xF7, 0x00])
+            .write(&[0x03, 0x64, 0x00])
+            .build();
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(stream))),
+        };
+        connection.set_server_mtu(100);
+        assert_eq!(connection.current_mtu(), 23);
+
+        let shared = connection.shared_mtu();
+        let notification = connection.notification(Handle::new(1));
+        let inner = connection.inner.clone();
+        connection.run(H).await.unwrap();
+        assert_eq!(inner.lock().unwrap().mtu.load(Ordering::SeqCst), 100);
+        assert_eq!(notification.mtu.load(Ordering::SeqCst), 100);
+        assert_eq!(shared.get(), 100);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_server_mtu() {
+        struct H(Arc<AtomicUsize>);
+        impl Handler for H {
+            fn handle_write_command(&mut self, item: &pkt::WriteCommand) {
+                self.0.store(item.attribute_value().len(), Ordering::SeqCst);
+            }
+        }
+
+        let mut command = vec![0x52, 0x01, 0x00];
+        command.extend_from_slice(&[0xAA; 97]);
+        let stream = Builder::new()
+            .read(&[0x02, 0x05, 0x02])
+            .write(&[0x03, 0x64, 0x00])
+            .wait(Duration::from_millis(10))
+            .read(&command)
+            .build();
+        let connection = connection(stream);
+        connection.set_server_mtu(100);
+
+        let written = Arc::new(AtomicUsize::new(0));
+        let mut handler = H(written.clone());
+        let inner = connection.inner.clone();
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        assert!(connection.poll_run(&mut cx, &mut handler).is_pending());
+        assert_eq!(inner.lock().unwrap().stream.rxbuf.len(), 101);
+
+        connection.run(handler).await.unwrap();
+        assert_eq!(written.load(Ordering::SeqCst), 97);
+        assert_eq!(inner.lock().unwrap().mtu.load(Ordering::SeqCst), 100);
+    }
+
+    #[tokio::test]
+    async fn test_default_server_mtu() {
+        struct H;
+        impl Handler for H {
+            fn handle_exchange_mtu_request(
+                &mut self,
+                _: &pkt::ExchangeMtuRequest,
+            ) -> std::result::Result<pkt::ExchangeMtuResponse, HandlerError> {
+                unreachable!("answered by the connection")
+            }
+        }
+
+        // the client's 65535 is not echoed back.
+        let stream = Builder::new()
+            .read(&[0x02, 0xFF, 0xFF])
+            .write(&[0x03, 0x05, 0x02])
+            .build();
+        let connection = connection(stream);
+        let inner = connection.inner.clone();
+        connection.run(H).await.unwrap();
+
+        let inner = inner.lock().unwrap();
+        assert_eq!(
+            inner.machine.mtu_state(),
+            MtuState::Exchanged {
+                tx_mtu: 0xFFFF,
+                rx_mtu: DEFAULT_SERVER_MTU as usize,
+            }
+        );
+        assert_eq!(inner.mtu.load(Ordering::SeqCst), 517);
+        assert_eq!(inner.stream.rxbuf.len(), 518);
+    }
+
+    #[test]
+    fn test_is_authenticated() {
+        let connection = Connection::new(
+            Builder::new().build(),
+            crate::Address::le_public_from([0; 6]),
+        );
+        assert!(!connection.is_authenticated());
+
+        connection.authentication_state().mark_authenticated();
+        assert!(connection.is_authenticated());
+    }
+
     #[tokio::test]
     async fn test_indication() {
         struct H;
@@ -697,4 +2210,738 @@ mod tests {
 
         task.await.unwrap().unwrap();
     }
+
+    /// Socket that yields `reads` then never becomes readable again, and
+    /// only accepts writes while `writable` is set.
+    #[derive(Default)]
+    struct StalledIo {
+        reads: std::collections::VecDeque<Vec<u8>>,
+        /// Read EOF instead of stalling once `reads` are consumed.
+        eof: bool,
+        /// Fail reads past EOF and writes, as a transport the client
+        /// disconnected from in both directions.
+        hung_up: bool,
+        eof_read: bool,
+        writable: bool,
+        written: Vec<Vec<u8>>,
+        shutdown: bool,
+    }
+
+    impl AsyncRead for StalledIo {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            match this.reads.pop_front() {
+                Some(packet) => {
+                    buf.put_slice(&packet);
+                    Poll::Ready(Ok(()))
+                }
+                None if this.eof && this.hung_up && this.eof_read => {
+                    Poll::Ready(Err(io::ErrorKind::NotConnected.into()))
+                }
+                None if this.eof => {
+                    this.eof_read = true;
+                    Poll::Ready(Ok(()))
+                }
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    impl AsyncWrite for StalledIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            if this.hung_up {
+                Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+            } else if this.writable {
+                this.written.push(buf.to_vec());
+                Poll::Ready(Ok(buf.len()))
+            } else {
+                Poll::Pending
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().shutdown = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_half_close() {
+        struct H;
+        impl Handler for H {}
+
+        let io = StalledIo {
+            eof: true,
+            writable: true,
+            ..Default::default()
+        };
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        };
+        connection.run(H).await.unwrap();
+
+        let io = StalledIo {
+            reads: vec![vec![0x02, 0x17, 0x00]].into(),
+            eof: true,
+            writable: true,
+            ..Default::default()
+        };
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        };
+        connection.allow_half_close(true);
+        let inner = connection.inner.clone();
+        let mut notification = connection.notification(Handle::new(1));
+        let mut task = tokio::spawn(connection.run(H));
+
+        while inner.lock().unwrap().stream.inner.written.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        for value in [b"1", b"2"] {
+            notification.write_all(value).await.unwrap();
+            while !inner.lock().unwrap().stream.txqueue.is_empty() {
+                tokio::task::yield_now().await;
+            }
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut task)
+            .await
+            .is_err());
+        assert_eq!(
+            inner.lock().unwrap().stream.inner.written,
+            [
+                vec![0x03, 0x05, 0x02],
+                vec![0x1B, 0x01, 0x00, b'1'],
+                vec![0x1B, 0x01, 0x00, b'2'],
+            ]
+        );
+
+        notification.shutdown().await.unwrap();
+        task.await.unwrap().unwrap();
+        assert!(inner.lock().unwrap().stream.inner.shutdown);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_half_close_hangup() {
+        struct H;
+        impl Handler for H {}
+
+        // nothing to send once the client is gone.
+        let io = StalledIo {
+            reads: vec![vec![0x02, 0x17, 0x00]].into(),
+            eof: true,
+            writable: true,
+            ..Default::default()
+        };
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        };
+        connection.allow_half_close(true);
+        let inner = connection.inner.clone();
+        let mut task = tokio::spawn(connection.run(H));
+        while inner.lock().unwrap().stream.inner.written.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert!(tokio::time::timeout(Duration::from_secs(1), &mut task)
+            .await
+            .is_err());
+
+        inner.lock().unwrap().stream.inner.hung_up = true;
+        inner.lock().unwrap().wake_driver();
+        let err = tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(err) if err.kind() == io::ErrorKind::NotConnected));
+
+        // a notification queued after the hangup fails to be written.
+        let io = StalledIo {
+            eof: true,
+            hung_up: true,
+            ..Default::default()
+        };
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        };
+        connection.allow_half_close(true);
+        connection
+            .notification(Handle::new(1))
+            .try_notify(b"1")
+            .unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(1), connection.run(H))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(err) if err.kind() == io::ErrorKind::BrokenPipe));
+    }
+
+    #[tokio::test]
+    async fn test_try_notify_full() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo::default()))),
+        };
+        connection.set_outgoing_queue_capacity(2);
+        let notification = connection.notification(Handle::new(1));
+        let task = tokio::spawn(connection.run(H));
+
+        notification.try_notify(b"1").unwrap();
+        notification.try_notify(b"2").unwrap();
+        assert!(matches!(
+            notification.try_notify(b"3"),
+            Err(TryNotifyError::Full)
+        ));
+        assert_eq!(notification.inner.lock().unwrap().stream.txqueue.len(), 2);
+
+        task.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_notify_back_pressure() {
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo::default()))),
+        };
+        connection.set_outgoing_queue_capacity(1);
+        let mut notification = connection.notification(Handle::new(1));
+
+        poll_fn(|cx| notification.poll_notify(cx, b"1"))
+            .await
+            .unwrap();
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(50),
+            poll_fn(|cx| notification.poll_notify(cx, b"2")),
+        )
+        .await;
+        assert!(blocked.is_err());
+        assert_eq!(connection.outgoing_queue_depth(), 1);
+
+        connection.inner.lock().unwrap().stream.txqueue.clear();
+        connection.inner.lock().unwrap().wake_writers();
+        poll_fn(|cx| notification.poll_notify(cx, b"2"))
+            .await
+            .unwrap();
+        assert_eq!(connection.outgoing_queue_depth(), 1);
+    }
+
+    #[test]
+    fn test_wake_parked_writer() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo::default()))),
+        };
+        connection.set_outgoing_queue_capacity(1);
+        let inner = connection.inner.clone();
+        let mut notification = connection.notification(Handle::new(1));
+        notification.try_notify(b"1").unwrap();
+
+        let mut run = tokio_test::task::spawn(connection.run(H));
+        let mut writer = tokio_test::task::spawn(poll_fn(|cx| notification.poll_notify(cx, b"2")));
+        assert!(run.poll().is_pending());
+        assert!(writer.poll().is_pending());
+
+        inner.lock().unwrap().stream.inner.writable = true;
+        assert!(run.poll().is_pending());
+        assert!(writer.is_woken());
+        assert!(matches!(writer.poll(), Poll::Ready(Ok(()))));
+        drop(writer);
+        assert!(run.is_woken());
+        assert!(run.poll().is_pending());
+        assert_eq!(
+            inner.lock().unwrap().stream.inner.written,
+            [vec![0x1B, 0x01, 0x00, b'1'], vec![0x1B, 0x01, 0x00, b'2']]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_wakes_writers() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo::default()))),
+        };
+        connection.set_outgoing_queue_capacity(1);
+        let mut notification = connection.notification(Handle::new(1));
+        let mut indication = connection.indication(Handle::new(2));
+        notification.try_notify(b"1").unwrap();
+
+        let mut run = tokio_test::task::spawn(connection.run(H));
+        let mut notify = tokio_test::task::spawn(poll_fn(|cx| notification.poll_notify(cx, b"2")));
+        let mut indicate =
+            tokio_test::task::spawn(poll_fn(|cx| indication.poll_indicate(cx, b"3")));
+        assert!(run.poll().is_pending());
+        assert!(notify.poll().is_pending());
+        assert!(indicate.poll().is_pending());
+
+        drop(run);
+        assert!(notify.is_woken());
+        assert!(indicate.is_woken());
+        assert!(matches!(notify.poll(), Poll::Ready(Err(Error::Io(..)))));
+        assert!(matches!(indicate.poll(), Poll::Ready(Err(Error::Io(..)))));
+        drop(notify);
+        assert!(matches!(
+            notification.try_notify(b"4"),
+            Err(TryNotifyError::Error(..))
+        ));
+
+        // dropped while awaiting a confirmation
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo {
+                writable: true,
+                ..Default::default()
+            }))),
+        };
+        let mut indication = connection.indication(Handle::new(2));
+        let mut run = tokio_test::task::spawn(connection.run(H));
+        let mut indicate =
+            tokio_test::task::spawn(poll_fn(|cx| indication.poll_indicate(cx, b"3")));
+        assert!(indicate.poll().is_pending());
+        assert!(run.poll().is_pending());
+        assert!(indicate.poll().is_pending());
+
+        drop(run);
+        assert!(indicate.is_woken());
+        assert!(matches!(indicate.poll(), Poll::Ready(Err(Error::Io(..)))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_with_shutdown() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo {
+                writable: true,
+                ..Default::default()
+            }))),
+        };
+        let inner = connection.inner.clone();
+        let notification = connection.notification(Handle::new(1));
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut task = tokio::spawn(connection.run_with_shutdown(H, rx.map(drop)));
+
+        assert!(tokio::time::timeout(Duration::from_millis(10), &mut task)
+            .await
+            .is_err());
+        notification.try_notify(b"1").unwrap();
+        tx.send(()).unwrap();
+        task.await.unwrap().unwrap();
+        assert_eq!(
+            inner.lock().unwrap().stream.inner.written,
+            [vec![0x1B, 0x01, 0x00, b'1']]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_indication_awaits_confirmation() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(StalledIo {
+                writable: true,
+                ..Default::default()
+            }))),
+        };
+        let inner = connection.inner.clone();
+        let mut indication = connection.indication(Handle::new(1));
+        let task = tokio::spawn(connection.run(H));
+
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(50),
+            poll_fn(|cx| indication.poll_indicate(cx, b"ok")),
+        )
+        .await;
+        assert!(waiting.is_err());
+        assert_eq!(
+            inner.lock().unwrap().stream.inner.written,
+            vec![vec![0x1D, 0x01, 0x00, 0x6F, 0x6B]]
+        );
+
+        task.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_indication_confirmed_before_timeout() {
+        struct H;
+        impl Handler for H {}
+
+        let stream = Builder::new()
+            .write(&[0x1D, 0x01, 0x00, 0x6F, 0x6B])
+            .wait(Duration::from_secs(29))
+            .read(&[0x1E])
+            .build();
+        let connection = connection(stream);
+        let mut indication = connection.indication(Handle::new(1));
+        let task = tokio::spawn(connection.run(H));
+
+        indication.write_all(b"ok").await.unwrap();
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_indication_timeout() {
+        struct H;
+        impl Handler for H {}
+
+        let connection = connection(StalledIo {
+            writable: true,
+            ..Default::default()
+        });
+        let mut indication = connection.indication(Handle::new(1));
+        let started = tokio::time::Instant::now();
+        let task = tokio::spawn(connection.run(H));
+
+        let err = poll_fn(|cx| indication.poll_indicate(cx, b"ok"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::IndicationTimeout(timeout) if timeout == DEFAULT_INDICATION_TIMEOUT)
+        );
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(Error::TransactionTimeout)
+        ));
+        assert_eq!(started.elapsed(), DEFAULT_INDICATION_TIMEOUT);
+
+        // nothing is served anymore.
+        let err = poll_fn(|cx| indication.poll_indicate(cx, b"ok"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(err) if err.kind() == io::ErrorKind::NotConnected));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_indication_timeout_from_send() {
+        struct H;
+        impl Handler for H {}
+
+        // the socket takes 10s to accept the indication.
+        let io = Builder::new()
+            .wait(Duration::from_secs(10))
+            .write(&[0x1D, 0x01, 0x00, b'o', b'k'])
+            .wait(Duration::from_secs(60))
+            .build();
+        let connection = connection(io);
+        let mut indication = connection.indication(Handle::new(1));
+        let started = tokio::time::Instant::now();
+        let task = tokio::spawn(connection.run(H));
+
+        let err = poll_fn(|cx| indication.poll_indicate(cx, b"ok"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::IndicationTimeout(..)));
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(Error::TransactionTimeout)
+        ));
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_secs(10) + DEFAULT_INDICATION_TIMEOUT
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late_confirmation() {
+        use tokio::io::AsyncReadExt;
+
+        struct H;
+        impl Handler for H {}
+
+        let (io, mut client) = tokio::io::duplex(64);
+        let connection = connection(io);
+        let mut indication = connection.indication(Handle::new(1));
+        let started = tokio::time::Instant::now();
+        let task = tokio::spawn(connection.run(H));
+        let client = tokio::spawn(async move {
+            let mut buf = [0; 5];
+            client.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, [0x1D, 0x01, 0x00, b'o', b'k']);
+            tokio::time::sleep(Duration::from_secs(31)).await;
+            client.write_all(&[0x1E]).await
+        });
+
+        let err = poll_fn(|cx| indication.poll_indicate(cx, b"ok"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::IndicationTimeout(..)));
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(Error::TransactionTimeout)
+        ));
+        assert_eq!(started.elapsed(), DEFAULT_INDICATION_TIMEOUT);
+        drop(indication);
+
+        // the confirmation at 31s finds the connection gone.
+        let err = client.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(started.elapsed(), Duration::from_secs(31));
+    }
+
+    #[test]
+    fn test_reject_overlapping_request() {
+        struct H;
+        impl Handler for H {}
+
+        let io = StalledIo {
+            reads: vec![vec![0x02, 0x17, 0x00], vec![0x0A, 0x01, 0x00]].into(),
+            ..Default::default()
+        };
+        let connection = ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        };
+        let inner = connection.inner.clone();
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+        assert!(connection.poll_run(&mut cx, &mut H).is_pending());
+
+        {
+            let inner = inner.lock().unwrap();
+            let queued = inner.stream.txqueue.iter().collect::<Vec<_>>();
+            assert_eq!(queued.len(), 2);
+            assert_eq!(&queued[0][..], &[0x03, 0x05, 0x02]);
+            assert_eq!(&queued[1][..], &[0x01, 0x0A, 0x01, 0x00, 0x11]);
+            assert!(inner.stream.pending_request());
+        }
+
+        {
+            let mut inner = inner.lock().unwrap();
+            inner.stream.inner.writable = true;
+            inner.stream.inner.reads.push_back(vec![0x0A, 0x01, 0x00]);
+        }
+        assert!(connection.poll_run(&mut cx, &mut H).is_pending());
+
+        {
+            let inner = inner.lock().unwrap();
+            assert_eq!(
+                inner.stream.inner.written,
+                vec![
+                    vec![0x03, 0x05, 0x02],
+                    vec![0x01, 0x0A, 0x01, 0x00, 0x11],
+                    vec![0x01, 0x0A, 0x01, 0x00, 0x06],
+                ]
+            );
+            assert!(!inner.stream.pending_request());
+        }
+    }
+
+    type Writes = futures_channel::mpsc::UnboundedReceiver<(Handle, Box<[u8]>)>;
+
+    /// Battery service with a notifiable level characteristic, its CCCD and
+    /// presentation format. Writes are reported to `writes`.
+    struct TableHandler {
+        writes: futures_channel::mpsc::UnboundedSender<(Handle, Box<[u8]>)>,
+    }
+
+    impl TableHandler {
+        const TABLE: &'static [(u16, u16)] = &[
+            (0x0001, 0x2800), // Primary Service (Battery)
+            (0x0002, 0x2803), // Characteristic
+            (0x0003, 0x2A19), // Battery Level
+            (0x0004, 0x2902), // Client Characteristic Configuration
+            (0x0005, 0x2904), // Characteristic Presentation Format
+        ];
+    }
+
+    impl Handler for TableHandler {
+        fn handle_find_information_request(
+            &mut self,
+            item: &pkt::FindInformationRequest,
+        ) -> std::result::Result<pkt::FindInformationResponse, HandlerError> {
+            let range = item.starting_handle().as_u16()..=item.ending_handle().as_u16();
+            let r = Self::TABLE
+                .iter()
+                .filter(|(h, _)| range.contains(h))
+                .map(|(h, t)| (Handle::new(*h), crate::Uuid::new_uuid16(*t)))
+                .collect::<Vec<_>>();
+            if r.is_empty() {
+                return Err(HandlerError::new(
+                    item.starting_handle().clone(),
+                    pkt::ErrorCode::AttributeNotFound,
+                ));
+            }
+            Ok(r.into_iter().collect())
+        }
+
+        fn handle_read_by_group_type_request(
+            &mut self,
+            item: &pkt::ReadByGroupTypeRequest,
+        ) -> std::result::Result<pkt::ReadByGroupTypeResponse, HandlerError> {
+            if item.starting_handle() <= &Handle::new(0x0001)
+                && item.attribute_group_type() == &crate::Uuid::new_uuid16(0x2800)
+            {
+                Ok(vec![(
+                    Handle::new(0x0001),
+                    Handle::new(0x0005),
+                    vec![0x0F, 0x18].into(),
+                )]
+                .into_iter()
+                .collect())
+            } else {
+                Err(HandlerError::new(
+                    item.starting_handle().clone(),
+                    pkt::ErrorCode::AttributeNotFound,
+                ))
+            }
+        }
+
+        fn handle_write_request(
+            &mut self,
+            item: &pkt::WriteRequest,
+        ) -> std::result::Result<pkt::WriteResponse, HandlerError> {
+            self.writes
+                .unbounded_send((
+                    item.attribute_handle().clone(),
+                    item.attribute_value().clone(),
+                ))
+                .unwrap();
+            Ok(pkt::WriteResponse::new())
+        }
+    }
+
+    fn table_handler() -> (TableHandler, Writes) {
+        let (writes, rx) = futures_channel::mpsc::unbounded();
+        (TableHandler { writes }, rx)
+    }
+
+    fn connection<IO>(io: IO) -> ConnectionInner<IO> {
+        ConnectionInner {
+            inner: Arc::new(Mutex::new(Inner::new(io))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cycle_exchange_mtu() {
+        let stream = Builder::new()
+            .read(&[0x02, 0x50, 0x00])
+            .write(&[0x03, 0x05, 0x02])
+            .build();
+        let connection = connection(stream);
+        let inner = connection.inner.clone();
+        connection.run(table_handler().0).await.unwrap();
+        assert_eq!(inner.lock().unwrap().mtu.load(Ordering::SeqCst), 0x50);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_without_exchange_mtu() {
+        // one octet over; the mock stream does not keep packet boundaries.
+        let mut oversized = vec![0x12, 0x03, 0x00];
+        oversized.extend_from_slice(&[0xAA; 21]);
+        let mut request = vec![0x12, 0x03, 0x00];
+        request.extend_from_slice(&[0xBB; 20]);
+        let mut notification = vec![0x1B, 0x03, 0x00];
+        notification.extend_from_slice(&[0xCC; 20]);
+
+        let stream = Builder::new()
+            .write(&notification)
+            .read(&oversized)
+            .write(&[0x01, 0x12, 0x00, 0x00, 0x04])
+            .read(&request)
+            .write(&[0x13])
+            .build();
+        let connection = connection(stream);
+        assert_eq!(connection.mtu_state(), MtuState::PreExchange);
+        let notify = connection.notification(Handle::new(0x0003));
+        assert!(matches!(
+            notify.try_notify(&[0xCC; 21]),
+            Err(TryNotifyError::Error(Error::NotReady(
+                NotReady::ExceedsMtu { len: 21, mtu: 23 }
+            )))
+        ));
+        notify.try_notify(&[0xCC; 20]).unwrap();
+
+        let (handler, mut writes) = table_handler();
+        let inner = connection.inner.clone();
+        connection.run(handler).await.unwrap();
+        let (_, value) = writes.try_recv().unwrap();
+        assert_eq!(&*value, &[0xBB; 20]);
+        assert!(writes.try_recv().is_err());
+        assert_eq!(inner.lock().unwrap().mtu.load(Ordering::SeqCst), 23);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_find_information() {
+        let stream = Builder::new()
+            .read(&[0x04, 0x01, 0x00, 0xFF, 0xFF])
+            .write(&[
+                0x05, 0x01, // format: 16bit
+                0x01, 0x00, 0x00, 0x28, //
+                0x02, 0x00, 0x03, 0x28, //
+                0x03, 0x00, 0x19, 0x2A, //
+                0x04, 0x00, 0x02, 0x29, // CCCD
+                0x05, 0x00, 0x04, 0x29, // Presentation Format
+            ])
+            .read(&[0x04, 0x06, 0x00, 0xFF, 0xFF])
+            .write(&[0x01, 0x04, 0x06, 0x00, 0x0A])
+            .build();
+        connection(stream).run(table_handler().0).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cycle_read_by_group_type() {
+        let stream = Builder::new()
+            .read(&[0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+            .write(&[0x11, 0x06, 0x01, 0x00, 0x05, 0x00, 0x0F, 0x18])
+            .read(&[0x10, 0x06, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+            .write(&[0x01, 0x10, 0x06, 0x00, 0x0A])
+            .build();
+        connection(stream).run(table_handler().0).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cycle_write_request() {
+        let stream = Builder::new()
+            .read(&[0x12, 0x04, 0x00, 0x01, 0x00])
+            .write(&[0x13])
+            .build();
+        let (handler, mut writes) = table_handler();
+        connection(stream).run(handler).await.unwrap();
+
+        let (handle, value) = writes.try_recv().unwrap();
+        assert_eq!(handle, Handle::new(0x0004));
+        assert_eq!(&*value, &[0x01, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_indication() {
+        // 30 bytes value only fits after the MTU exchange.
+        let value = [0x64; 30];
+        let mut packet = vec![0x1D, 0x03, 0x00];
+        packet.extend_from_slice(&value);
+
+        let stream = Builder::new()
+            .read(&[0x02, 0x50, 0x00])
+            .write(&[0x03, 0x05, 0x02])
+            .write(&packet)
+            .read(&[0x1E])
+            .build();
+        let connection = connection(stream);
+        let mut indication = connection.indication(Handle::new(0x0003));
+        let inner = connection.inner.clone();
+        let task = tokio::spawn(connection.run(table_handler().0));
+
+        while inner.lock().unwrap().mtu.load(Ordering::SeqCst) != 0x50 {
+            tokio::task::yield_now().await;
+        }
+        poll_fn(|cx| indication.poll_indicate(cx, &value))
+            .await
+            .unwrap();
+        task.await.unwrap().unwrap();
+    }
 }
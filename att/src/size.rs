@@ -1,3 +1,5 @@
+use alloc::boxed::Box;
+
 use crate::{Handle, Uuid};
 
 pub(crate) trait Size {
@@ -14,6 +14,7 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 // <bluetooth/bluetooth.h>
 const BTPROTO_L2CAP: libc::c_int = 0;
+const BTPROTO_HCI: libc::c_int = 1;
 const BDADDR_BREDR: u8 = 0x00;
 const BDADDR_LE_PUBLIC: u8 = 0x01;
 const BDADDR_LE_RANDOM: u8 = 0x02;
@@ -24,6 +25,12 @@ const BT_SECURITY: libc::c_int = 4;
 pub(crate) const BT_SECURITY_MEDIUM: u8 = 2;
 pub(crate) const BT_SECURITY_HIGH: u8 = 3;
 //pub(crate) const BT_SECURITY_FIPS: u8 = 4;
+const BT_MODE: libc::c_int = 15;
+const BT_MODE_BASIC: u8 = 0x00;
+const BT_MODE_ERTM: u8 = 0x01;
+const BT_MODE_STREAMING: u8 = 0x02;
+//const BT_MODE_LE_FLOWCTL: u8 = 0x03;
+const BT_MODE_EXT_FLOWCTL: u8 = 0x04;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -52,6 +59,85 @@ struct sockaddr_l2 {
     l2_bdaddr_type: u8,
 }
 
+// <bluetooth/hci.h>
+const HCI_MAX_DEV: usize = 16;
+const HCI_UP: u32 = 1 << 0;
+const HCIGETDEVLIST: libc::c_ulong = 0x800448d2; // _IOR('H', 210, int)
+const HCIGETDEVINFO: libc::c_ulong = 0x800448d3; // _IOR('H', 211, int)
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(non_camel_case_types)]
+struct hci_dev_req {
+    dev_id: u16,
+    dev_opt: u32,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+#[allow(non_camel_case_types)]
+struct hci_dev_list_req {
+    dev_num: u16,
+    dev_req: [hci_dev_req; HCI_MAX_DEV],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+#[allow(non_camel_case_types)]
+struct hci_dev_info {
+    dev_id: u16,
+    name: [u8; 8],
+    bdaddr: bdaddr_t,
+    flags: u32,
+    r#type: u8,
+    features: [u8; 8],
+    pkt_type: u32,
+    link_policy: u32,
+    link_mode: u32,
+    acl_mtu: u16,
+    acl_pkts: u16,
+    sco_mtu: u16,
+    sco_pkts: u16,
+    stat: [u32; 10],
+}
+
+fn hci_ioctl<T>(sock: &Socket, request: libc::c_ulong, arg: &mut T) -> io::Result<()> {
+    let r = unsafe { libc::ioctl(sock.as_raw_fd(), request as _, arg as *mut T) };
+    if r < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Addresses of the adapters that are up. (`HCIGETDEVLIST`, `HCIGETDEVINFO`)
+fn adapter_addresses() -> io::Result<Vec<[u8; 6]>> {
+    let domain = Domain::from(libc::AF_BLUETOOTH);
+    let sock = Socket::new(
+        domain,
+        Type::RAW.cloexec(),
+        Some(Protocol::from(BTPROTO_HCI)),
+    )?;
+
+    let mut list = hci_dev_list_req {
+        dev_num: HCI_MAX_DEV as u16,
+        dev_req: [hci_dev_req::default(); HCI_MAX_DEV],
+    };
+    hci_ioctl(&sock, HCIGETDEVLIST, &mut list)?;
+
+    let mut addresses = vec![];
+    for req in list.dev_req.iter().take(list.dev_num.into()) {
+        if req.dev_opt & HCI_UP == 0 {
+            continue;
+        }
+        let mut info: hci_dev_info = unsafe { mem::zeroed() };
+        info.dev_id = req.dev_id;
+        hci_ioctl(&sock, HCIGETDEVINFO, &mut info)?;
+        addresses.push(info.bdaddr.b);
+    }
+    Ok(addresses)
+}
+
 fn sock_open() -> io::Result<Socket> {
     let domain = Domain::from(libc::AF_BLUETOOTH);
     let r#type = Type::SEQPACKET.nonblocking().cloexec();
@@ -59,22 +145,146 @@ fn sock_open() -> io::Result<Socket> {
     Socket::new(domain, r#type, Some(proto))
 }
 
-fn sock_bind(sock: &Socket, cid: libc::c_ushort) -> io::Result<()> {
+/// Fixed L2CAP channel of the Attribute Protocol.
+pub(crate) const ATT_CID: u16 = 0x0004;
+
+/// Listen backlog unless configured.
+pub(crate) const DEFAULT_BACKLOG: u32 = 1;
+
+/// Address type of the local adapter to listen on, or of a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    /// BR/EDR
+    BrEdr,
+    /// LE Public
+    LePublic,
+    /// LE Random
+    LeRandom,
+}
+
+impl AddressType {
+    fn to_raw(self) -> u8 {
+        match self {
+            Self::BrEdr => BDADDR_BREDR,
+            Self::LePublic => BDADDR_LE_PUBLIC,
+            Self::LeRandom => BDADDR_LE_RANDOM,
+        }
+    }
+
+    fn from_raw(raw: u8) -> io::Result<Self> {
+        match raw {
+            BDADDR_BREDR => Ok(Self::BrEdr),
+            BDADDR_LE_PUBLIC => Ok(Self::LePublic),
+            BDADDR_LE_RANDOM => Ok(Self::LeRandom),
+            _ => Err(io::Error::other("unexpected l2 address type.")),
+        }
+    }
+}
+
+/// L2CAP mode of the channels a listener accepts.
+///
+/// `Basic` is the default, and the only mode of the fixed ATT channel (CID
+/// 0x0004). `CreditBased` is required for the connection oriented channels
+/// of EATT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L2capMode {
+    /// Basic L2CAP mode.
+    Basic,
+    /// Enhanced Retransmission Mode. (BR/EDR)
+    Ertm,
+    /// Streaming Mode. (BR/EDR)
+    Streaming,
+    /// Enhanced Credit Based Flow Control Mode.
+    CreditBased,
+}
+
+impl L2capMode {
+    fn to_raw(self) -> u8 {
+        match self {
+            Self::Basic => BT_MODE_BASIC,
+            Self::Ertm => BT_MODE_ERTM,
+            Self::Streaming => BT_MODE_STREAMING,
+            Self::CreditBased => BT_MODE_EXT_FLOWCTL,
+        }
+    }
+}
+
+/// Remote device of an accepted connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub address: crate::Address,
+    pub address_type: AddressType,
+}
+
+/// L2CAP channel to listen on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Fixed channel. Standard GATT clients only use CID 0x0004.
+    Cid(u16),
+    /// Connection oriented channel on a PSM (SPSM for LE).
+    Psm(u16),
+}
+
+impl Channel {
+    fn validate(&self, address_type: AddressType) -> io::Result<()> {
+        let valid = match (self, address_type) {
+            (Self::Cid(cid), _) => *cid != 0,
+            (Self::Psm(psm), AddressType::BrEdr) => *psm & 0x0101 == 0x0001,
+            (Self::Psm(psm), _) => (0x0001..=0x00FF).contains(psm),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid channel {:?} for {:?}.", self, address_type),
+            ))
+        }
+    }
+}
+
+fn sockaddr(channel: Channel, address_type: AddressType) -> io::Result<SockAddr> {
+    let (psm, cid) = match channel {
+        Channel::Cid(cid) => (0, cid),
+        Channel::Psm(psm) => (psm, 0),
+    };
     let (_, addr) = unsafe {
-        SockAddr::init(|addr, _| {
+        SockAddr::init(|addr, len| {
             let addr = &mut *(addr as *mut sockaddr_l2);
             *addr = sockaddr_l2 {
                 l2_family: (libc::AF_BLUETOOTH as libc::sa_family_t),
-                l2_psm: Default::default(),
+                l2_psm: psm.to_le(),
                 l2_cid: cid.to_le(),
                 l2_bdaddr: bdaddr_t { b: [0; 6] },
-                l2_bdaddr_type: BDADDR_LE_PUBLIC,
+                l2_bdaddr_type: address_type.to_raw(),
             };
+            *len = mem::size_of::<sockaddr_l2>() as libc::socklen_t;
             Ok(())
         })?
     };
-    sock.bind(&addr)?;
-    Ok(())
+    Ok(addr)
+}
+
+fn sock_bind(sock: &Socket, channel: Channel, address_type: AddressType) -> io::Result<()> {
+    channel.validate(address_type)?;
+    let addr = sockaddr(channel, address_type)?;
+    sock.bind(&addr).map_err(|err| match err.raw_os_error() {
+        Some(libc::EADDRINUSE) => io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!(
+                "{:?} already in use. (CID 0x0004 is owned by bluetoothd while it is running)",
+                channel
+            ),
+        ),
+        Some(libc::EPERM) | Some(libc::EACCES) => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "binding {:?} not permitted. (needs CAP_NET_BIND_SERVICE / CAP_NET_ADMIN)",
+                channel
+            ),
+        ),
+        _ => err,
+    })
 }
 
 fn set_sockopt_bt_security(fd: RawFd, level: u8, key_size: u8) -> io::Result<()> {
@@ -98,30 +308,109 @@ fn set_sockopt_bt_security(fd: RawFd, level: u8, key_size: u8) -> io::Result<()>
     }
 }
 
-pub(crate) fn try_from(addr: socket2::SockAddr) -> io::Result<crate::Address> {
+/// Set `BT_MODE`. The kernel only accepts it with the `enable_ecred`
+/// parameter of the bluetooth module set, and fails with `ENOPROTOOPT`
+/// otherwise.
+pub(crate) fn set_l2cap_mode(fd: RawFd, mode: L2capMode) -> io::Result<()> {
+    let opt = mode.to_raw();
+    let len = mem::size_of::<u8>() as libc::socklen_t;
+
+    let r = unsafe {
+        libc::setsockopt(
+            fd,
+            SOL_BLUETOOTH,
+            BT_MODE,
+            &opt as *const _ as *const libc::c_void,
+            len,
+        )
+    };
+
+    if r < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn peer_info(addr: &SockAddr) -> io::Result<PeerInfo> {
     if addr.family() == libc::AF_BLUETOOTH as libc::sa_family_t {
         let addr = unsafe { &*(addr.as_ptr() as *const sockaddr_l2) };
         let bdaddr = BdAddr::from(addr.l2_bdaddr.b);
-        match addr.l2_bdaddr_type {
-            BDADDR_BREDR => Ok(bdaddr.to_br_edr_addr()),
-            BDADDR_LE_PUBLIC => Ok(bdaddr.to_le_public_addr()),
-            BDADDR_LE_RANDOM => Ok(bdaddr.to_le_random_addr()),
-            _ => Err(io::Error::new(
-                io::ErrorKind::Other,
-                "unexpected l2 address type.",
-            )),
-        }
+        let address_type = AddressType::from_raw(addr.l2_bdaddr_type)?;
+        let address = match address_type {
+            AddressType::BrEdr => bdaddr.to_br_edr_addr(),
+            AddressType::LePublic => bdaddr.to_le_public_addr(),
+            AddressType::LeRandom => bdaddr.to_le_random_addr(),
+        };
+        Ok(PeerInfo {
+            address,
+            address_type,
+        })
+    } else {
+        Err(io::Error::other("unexpected address family."))
+    }
+}
+
+/// Local address from `getsockname`.
+///
+/// A socket bound to any adapter (BDADDR_ANY) has no single address until it
+/// is connected. Then the public address of the only adapter up, from
+/// `adapters`, is the one it is reachable at.
+fn local_address<F>(addr: &SockAddr, adapters: F) -> io::Result<crate::Address>
+where
+    F: FnOnce() -> io::Result<Vec<[u8; 6]>>,
+{
+    let info = peer_info(addr)?;
+    if <[u8; 6]>::from(info.address.clone().into_bd_addr()) != [0; 6] {
+        return Ok(info.address);
+    }
+    let not_available = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("bound to any adapter; {}", reason),
+        )
+    };
+    let address = match adapters()?.as_slice() {
+        [] => return Err(not_available("no adapter is up.")),
+        [address] => BdAddr::from(*address),
+        _ => return Err(not_available("more than one adapter is up.")),
+    };
+    match info.address_type {
+        AddressType::BrEdr => Ok(address.to_br_edr_addr()),
+        AddressType::LePublic => Ok(address.to_le_public_addr()),
+        AddressType::LeRandom => Err(not_available("no public address for LE random.")),
+    }
+}
+
+/// Whether the peer is gone in both directions. (`POLLHUP`)
+fn hung_up(sock: &Socket) -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: sock.as_raw_fd(),
+        events: 0,
+        revents: 0,
+    };
+    let r = unsafe { libc::poll(&mut fd, 1, 0) };
+    if r < 0 {
+        Err(io::Error::last_os_error())
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "unexpected address family.",
-        ))
+        Ok(fd.revents & libc::POLLHUP != 0)
     }
 }
 
+/// L2CAP socket of an accepted ATT connection.
 #[derive(Debug)]
-pub(crate) struct AttStream {
+pub struct AttStream {
     inner: AsyncFd<Socket>,
+    /// A read returned EOF.
+    eof: bool,
+}
+
+impl AttStream {
+    /// Address of the local adapter the connection was accepted on.
+    /// (`getsockname`)
+    pub fn local_address(&self) -> io::Result<crate::Address> {
+        local_address(&self.inner.get_ref().local_addr()?, || Ok(vec![]))
+    }
 }
 
 impl AsyncRead for AttStream {
@@ -130,11 +419,18 @@ impl AsyncRead for AttStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
         loop {
-            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let mut guard = ready!(this.inner.poll_read_ready(cx))?;
             let result = guard.try_io(|fd| fd.get_ref().recv(unsafe { buf.unfilled_mut() }));
             if let Ok(n) = result {
                 let n = n?;
+                // reads past EOF fail once the channel is disconnected, so that
+                // a half closed connection notices the hangup.
+                if n == 0 && this.eof && hung_up(this.inner.get_ref())? {
+                    return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+                }
+                this.eof |= n == 0;
                 unsafe { buf.assume_init(n) };
                 buf.advance(n);
                 return Poll::Ready(Ok(()));
@@ -174,28 +470,63 @@ impl AsyncWrite for AttStream {
     }
 }
 
+/// Listening L2CAP socket, yielding accepted [`AttStream`]s.
 #[derive(Debug)]
-pub(crate) struct AttListener {
+pub struct AttListener {
     inner: AsyncFd<Socket>,
 }
 
 impl AttListener {
     pub(crate) fn new() -> io::Result<Self> {
+        Self::with_cid(ATT_CID, AddressType::LePublic, DEFAULT_BACKLOG)
+    }
+
+    pub(crate) fn with_cid(cid: u16, address_type: AddressType, backlog: u32) -> io::Result<Self> {
+        Self::bind(Channel::Cid(cid), address_type, backlog, None)
+    }
+
+    /// Listen on `channel`, requiring BT_SECURITY `security` of accepted
+    /// channels if any.
+    pub(crate) fn bind(
+        channel: Channel,
+        address_type: AddressType,
+        backlog: u32,
+        security: Option<u8>,
+    ) -> io::Result<Self> {
         let sock = sock_open()?;
-        sock_bind(&sock, 0x0004)?;
-        sock.listen(1)?; // TODO backlog
+        // before listening, so that no channel is accepted without it.
+        if let Some(level) = security {
+            set_sockopt_bt_security(sock.as_raw_fd(), level, 0)?;
+        }
+        sock_bind(&sock, channel, address_type)?;
+        sock.listen(backlog.min(libc::c_int::MAX as u32) as libc::c_int)?;
         Ok(Self {
             inner: AsyncFd::new(sock)?,
         })
     }
 
+    /// Address of the local adapter the socket is bound to. (`getsockname`)
+    ///
+    /// When bound to any adapter, this is the address of the only adapter
+    /// up. Fails with [`io::ErrorKind::AddrNotAvailable`] if there is no
+    /// single one.
+    pub(crate) fn local_address(&self) -> io::Result<crate::Address> {
+        local_address(&self.inner.get_ref().local_addr()?, adapter_addresses)
+    }
+
     pub(crate) fn set_sockopt_bt_security(&self, level: u8, key_size: u8) -> io::Result<()> {
         set_sockopt_bt_security(self.inner.as_raw_fd(), level, key_size)
     }
+
+    /// Set the L2CAP mode of the channels accepted from now on.
+    /// See [`L2capMode`].
+    pub fn set_l2cap_mode(&self, mode: L2capMode) -> io::Result<()> {
+        set_l2cap_mode(self.inner.as_raw_fd(), mode)
+    }
 }
 
 impl Stream for AttListener {
-    type Item = io::Result<(AttStream, socket2::SockAddr)>;
+    type Item = io::Result<(AttStream, PeerInfo)>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
@@ -203,12 +534,171 @@ impl Stream for AttListener {
             let result = guard.try_io(|fd| fd.get_ref().accept());
             if let Ok(result) = result {
                 let (sock, addr) = result?;
+                let peer = peer_info(&addr)?;
                 sock.set_nonblocking(true)?;
                 let sock = AttStream {
                     inner: AsyncFd::new(sock)?,
+                    eof: false,
                 };
-                return Poll::Ready(Some(Ok((sock, addr))));
+                return Poll::Ready(Some(Ok((sock, peer))));
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(addr: &SockAddr) -> &sockaddr_l2 {
+        assert_eq!(addr.family(), libc::AF_BLUETOOTH as libc::sa_family_t);
+        assert_eq!(addr.len() as usize, mem::size_of::<sockaddr_l2>());
+        unsafe { &*(addr.as_ptr() as *const sockaddr_l2) }
+    }
+
+    #[test]
+    fn test_sockaddr_cid() {
+        let addr = sockaddr(Channel::Cid(ATT_CID), AddressType::LePublic).unwrap();
+        let addr = decode(&addr);
+        assert_eq!(u16::from_le(addr.l2_cid), 0x0004);
+        assert_eq!(u16::from_le(addr.l2_psm), 0);
+        assert_eq!(addr.l2_bdaddr.b, [0; 6]);
+        assert_eq!(addr.l2_bdaddr_type, BDADDR_LE_PUBLIC);
+    }
+
+    #[test]
+    fn test_sockaddr_psm() {
+        let addr = sockaddr(Channel::Psm(0x0080), AddressType::LeRandom).unwrap();
+        let addr = decode(&addr);
+        assert_eq!(u16::from_le(addr.l2_cid), 0);
+        assert_eq!(u16::from_le(addr.l2_psm), 0x0080);
+        assert_eq!(addr.l2_bdaddr_type, BDADDR_LE_RANDOM);
+
+        let addr = sockaddr(Channel::Psm(0x1001), AddressType::BrEdr).unwrap();
+        let addr = decode(&addr);
+        assert_eq!(u16::from_le(addr.l2_psm), 0x1001);
+        assert_eq!(addr.l2_bdaddr_type, BDADDR_BREDR);
+    }
+
+    #[test]
+    fn test_l2cap_mode_raw() {
+        assert_eq!(L2capMode::Basic.to_raw(), 0x00);
+        assert_eq!(L2capMode::Ertm.to_raw(), 0x01);
+        assert_eq!(L2capMode::Streaming.to_raw(), 0x02);
+        assert_eq!(L2capMode::CreditBased.to_raw(), 0x04);
+    }
+
+    #[test]
+    fn test_channel_validate() {
+        assert!(Channel::Cid(0x0004).validate(AddressType::LePublic).is_ok());
+        assert!(Channel::Cid(0).validate(AddressType::LePublic).is_err());
+        assert!(Channel::Psm(0x0080).validate(AddressType::LePublic).is_ok());
+        assert!(Channel::Psm(0x0100)
+            .validate(AddressType::LePublic)
+            .is_err());
+        assert!(Channel::Psm(0x1001).validate(AddressType::BrEdr).is_ok());
+        assert!(Channel::Psm(0x1002).validate(AddressType::BrEdr).is_err());
+        assert!(Channel::Psm(0x0101).validate(AddressType::BrEdr).is_err());
+    }
+
+    #[test]
+    fn test_peer_info() {
+        for address_type in [
+            AddressType::BrEdr,
+            AddressType::LePublic,
+            AddressType::LeRandom,
+        ] {
+            let addr = sockaddr(Channel::Cid(ATT_CID), address_type).unwrap();
+            let peer = peer_info(&addr).unwrap();
+            assert_eq!(peer.address_type, address_type);
+            let expected = match address_type {
+                AddressType::BrEdr => crate::Address::bredr_from([0; 6]),
+                AddressType::LePublic => crate::Address::le_public_from([0; 6]),
+                AddressType::LeRandom => crate::Address::le_random_from([0; 6]),
+            };
+            assert_eq!(peer.address, expected);
+        }
+    }
+
+    #[test]
+    fn test_peer_info_address_bytes() {
+        // bdaddr_t holds the address least significant octet first.
+        let b = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        for address_type in [AddressType::LePublic, AddressType::LeRandom] {
+            let (_, addr) = unsafe {
+                SockAddr::init(|addr, len| {
+                    *(addr as *mut sockaddr_l2) = sockaddr_l2 {
+                        l2_family: (libc::AF_BLUETOOTH as libc::sa_family_t),
+                        l2_psm: 0,
+                        l2_cid: ATT_CID.to_le(),
+                        l2_bdaddr: bdaddr_t { b },
+                        l2_bdaddr_type: address_type.to_raw(),
+                    };
+                    *len = mem::size_of::<sockaddr_l2>() as libc::socklen_t;
+                    Ok(())
+                })
+                .unwrap()
+            };
+            assert_eq!(decode(&addr).l2_bdaddr.b, b);
+
+            let peer = peer_info(&addr).unwrap();
+            assert_eq!(peer.address_type, address_type);
+            let expected = match address_type {
+                AddressType::LePublic => crate::Address::le_public_from(b),
+                _ => crate::Address::le_random_from(b),
+            };
+            assert_eq!(peer.address, expected);
+            assert!(format!("{:?}", peer.address).contains("06:05:04:03:02:01"));
+            assert_eq!(<[u8; 6]>::from(peer.address.into_bd_addr()), b);
+        }
+    }
+
+    #[test]
+    fn test_local_address() {
+        let b = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let (_, addr) = unsafe {
+            SockAddr::init(|addr, len| {
+                *(addr as *mut sockaddr_l2) = sockaddr_l2 {
+                    l2_family: (libc::AF_BLUETOOTH as libc::sa_family_t),
+                    l2_psm: 0,
+                    l2_cid: ATT_CID.to_le(),
+                    l2_bdaddr: bdaddr_t { b },
+                    l2_bdaddr_type: BDADDR_LE_PUBLIC,
+                };
+                *len = mem::size_of::<sockaddr_l2>() as libc::socklen_t;
+                Ok(())
+            })
+            .unwrap()
+        };
+        let adapters = || -> io::Result<Vec<[u8; 6]>> { unreachable!() };
+        assert_eq!(
+            local_address(&addr, adapters).unwrap(),
+            crate::Address::le_public_from(b)
+        );
+
+        // what a listener bound to BDADDR_ANY reports
+        let addr = sockaddr(Channel::Cid(ATT_CID), AddressType::LePublic).unwrap();
+        assert_eq!(
+            local_address(&addr, || Ok(vec![b])).unwrap(),
+            crate::Address::le_public_from(b)
+        );
+        for adapters in [vec![], vec![b, [0x10; 6]]] {
+            let err = local_address(&addr, || Ok(adapters)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AddrNotAvailable);
+        }
+        let addr = sockaddr(Channel::Cid(ATT_CID), AddressType::LeRandom).unwrap();
+        let err = local_address(&addr, || Ok(vec![b])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrNotAvailable);
+    }
+
+    #[test]
+    fn test_hci_dev_info_layout() {
+        // offsets of struct hci_dev_info in <bluetooth/hci.h>
+        assert_eq!(mem::size_of::<hci_dev_info>(), 92);
+        let info: hci_dev_info = unsafe { mem::zeroed() };
+        let base = &info as *const _ as usize;
+        assert_eq!(&info.bdaddr as *const _ as usize - base, 10);
+        assert_eq!(&info.flags as *const _ as usize - base, 16);
+        assert_eq!(mem::size_of::<hci_dev_req>(), 8);
+    }
+}
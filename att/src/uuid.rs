@@ -1,14 +1,15 @@
 //! ATT Protocol UUIDs.
-use std::fmt;
-use std::io;
+use alloc::boxed::Box;
+use alloc::format;
+use core::fmt;
 
 pub use uuid::Uuid as Uuid128;
 
-use crate::packet::pack::{Error as PackError, Pack, Result as PackResult, Unpack};
+use crate::packet::pack::{self, Error as PackError, Pack, Result as PackResult, Unpack};
 
 packable_newtype! {
     /// 16bit UUID
-    #[derive(Clone, PartialEq, Eq)]
+    #[derive(Clone, PartialEq, Eq, Hash)]
     pub struct Uuid16(u16);
 }
 
@@ -17,9 +18,14 @@ impl Uuid16 {
         Self(v)
     }
 
-    pub fn as_u16(&self) -> u16 {
+    pub const fn as_u16(&self) -> u16 {
         self.0
     }
+
+    /// Encode as little-endian bytes, as in PDUs.
+    pub const fn to_le_bytes(&self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
 }
 
 impl From<u16> for Uuid16 {
@@ -34,6 +40,20 @@ impl From<Uuid16> for u16 {
     }
 }
 
+/// Decode little-endian bytes.
+impl From<[u8; 2]> for Uuid16 {
+    fn from(v: [u8; 2]) -> Self {
+        Self(u16::from_le_bytes(v))
+    }
+}
+
+/// Encode as little-endian bytes.
+impl From<Uuid16> for [u8; 2] {
+    fn from(v: Uuid16) -> Self {
+        v.0.to_le_bytes()
+    }
+}
+
 impl fmt::Debug for Uuid16 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "0x{:04X}", self.0)
@@ -49,7 +69,7 @@ impl From<Uuid16> for Uuid {
 impl Pack for Uuid128 {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         self.to_u128_le().pack(write)
     }
@@ -58,7 +78,7 @@ impl Pack for Uuid128 {
 impl Unpack for Uuid128 {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         Ok(Self::from_u128_le(Unpack::unpack(read)?))
     }
@@ -70,8 +90,11 @@ impl From<Uuid128> for Uuid {
     }
 }
 
+/// Bluetooth Base UUID, 00000000-0000-1000-8000-00805F9B34FB.
+const BASE_UUID: u128 = 0x0000_0000_0000_1000_8000_0080_5F9B_34FB;
+
 /// 16bit or 128bit UUID
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Uuid {
     /// 16bit UUID
     Uuid16(Uuid16),
@@ -87,12 +110,53 @@ impl Uuid {
     pub const fn new_uuid128(v: u128) -> Self {
         Self::Uuid128(Uuid128::from_u128(v))
     }
+
+    /// Encode the 128bit form as little-endian bytes, as in PDUs. 16bit UUIDs
+    /// are expanded on the Bluetooth Base UUID.
+    ///
+    /// ([`Uuid128`] is a foreign type, so the conversion is provided for [`Uuid`].)
+    pub fn to_le_bytes128(&self) -> [u8; 16] {
+        match self {
+            Self::Uuid16(uuid) => (BASE_UUID | (uuid.as_u16() as u128) << 96).to_le_bytes(),
+            Self::Uuid128(uuid) => uuid.as_u128().to_le_bytes(),
+        }
+    }
+
+    /// 16bit form of a 128bit UUID on the Bluetooth Base UUID, or `self`.
+    ///
+    /// Two UUIDs naming the same attribute type have the same canonical form,
+    /// e.g. 0x2A00 and 00002A00-0000-1000-8000-00805F9B34FB.
+    /// (BLUETOOTH CORE SPECIFICATION Version 5.1 | Vol 3, Part B, 2.5.1)
+    pub fn canonical(&self) -> Self {
+        const ALIAS_MASK: u128 = 0xFFFF << 96;
+
+        match self {
+            Self::Uuid128(uuid) => {
+                let v = uuid.as_u128();
+                if v & !ALIAS_MASK == BASE_UUID {
+                    Self::new_uuid16((v >> 96) as u16)
+                } else {
+                    self.clone()
+                }
+            }
+            Self::Uuid16(..) => self.clone(),
+        }
+    }
+}
+
+/// Decode little-endian bytes as 128bit UUID.
+///
+/// ([`Uuid128`] is a foreign type, so the conversion is provided for [`Uuid`].)
+impl From<[u8; 16]> for Uuid {
+    fn from(v: [u8; 16]) -> Self {
+        Self::Uuid128(Uuid128::from_u128(u128::from_le_bytes(v)))
+    }
 }
 
 impl Pack for Uuid {
     fn pack<W>(self, write: &mut W) -> PackResult<()>
     where
-        W: io::Write,
+        W: pack::Write,
     {
         match self {
             Self::Uuid16(uuid) => uuid.pack(write),
@@ -104,13 +168,56 @@ impl Pack for Uuid {
 impl Unpack for Uuid {
     fn unpack<R>(read: &mut R) -> PackResult<Self>
     where
-        R: io::Read,
+        R: pack::Read,
     {
         let buf = Box::<[u8]>::unpack(read)?;
         Ok(match buf.len() {
             2 => Self::Uuid16(Unpack::unpack(&mut buf.as_ref())?),
             16 => Self::Uuid128(Unpack::unpack(&mut buf.as_ref())?),
-            unknown => return Err(PackError::Unexpected(format!("uuid length {}", unknown))),
+            unknown => return Err(PackError::unexpected(format!("uuid length {}", unknown))),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_conversion() {
+        assert_eq!(Uuid16::from([0x00, 0x18]), Uuid16::new(0x1800));
+        assert_eq!(<[u8; 2]>::from(Uuid16::new(0x1800)), [0x00, 0x18]);
+
+        let mut bytes = [0; 16];
+        bytes[0] = 0x34;
+        bytes[1] = 0x12;
+        assert_eq!(Uuid::from(bytes), Uuid::new_uuid128(0x1234));
+
+        assert_eq!(Uuid16::new(0x1800).to_le_bytes(), [0x00, 0x18]);
+        assert_eq!(Uuid::new_uuid128(0x1234).to_le_bytes128(), bytes);
+        assert_eq!(
+            Uuid::from(Uuid::new_uuid16(0x2A00).to_le_bytes128()),
+            Uuid::new_uuid128(0x00002A00_0000_1000_8000_00805F9B34FB)
+        );
+    }
+
+    #[test]
+    fn test_canonical() {
+        assert_eq!(
+            Uuid::new_uuid128(0x00002A00_0000_1000_8000_00805F9B34FB).canonical(),
+            Uuid::new_uuid16(0x2A00)
+        );
+        assert_eq!(
+            Uuid::new_uuid16(0x2A00).canonical(),
+            Uuid::new_uuid16(0x2A00)
+        );
+        // 32bit alias and custom UUIDs stay as they are.
+        for v in [
+            0x00012A00_0000_1000_8000_00805F9B34FB,
+            0x00002A00_0000_1000_8000_00805F9B34FC,
+            0x6E400001_B5A3_F393_E0A9_E50E24DCCA9E,
+        ] {
+            assert_eq!(Uuid::new_uuid128(v).canonical(), Uuid::new_uuid128(v));
+        }
+    }
+}
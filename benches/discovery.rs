@@ -0,0 +1,37 @@
+//! Discovery over a 300 attributes table.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use gatt::{bench, CharacteristicProperties, Registration, Uuid};
+
+/// 30 services with a 16bit and a 128bit notifiable characteristic each.
+fn large_db() -> gatt::Database {
+    let mut registration = Registration::<()>::new();
+    for i in 0..30u16 {
+        if i % 3 == 2 {
+            registration.add_primary_service(Uuid::new_uuid128(0xFFFF_0000 + i as u128));
+        } else {
+            registration.add_primary_service(Uuid::new_uuid16(0x1800 + i));
+        }
+        for uuid in [Uuid::new_uuid16(0x2A19), Uuid::new_uuid128(0xFFFF_0001)] {
+            registration.add_characteristic(
+                uuid,
+                vec![0; 4],
+                CharacteristicProperties::NOTIFY | CharacteristicProperties::READ,
+            );
+            registration.add_presentation_format(0, 0, 0, 0, 0);
+        }
+    }
+    bench::database(registration)
+}
+
+/// Same requests a client issues while discovering the whole table.
+fn discovery(c: &mut Criterion) {
+    let db = large_db();
+    let types = [Uuid::new_uuid16(0x2803), Uuid::new_uuid128(0xFFFF_0001)];
+
+    c.bench_function("discovery", |b| {
+        b.iter(|| black_box(bench::discover(&db, &types)))
+    });
+}
+
+criterion_group!(benches, discovery);
+criterion_main!(benches);
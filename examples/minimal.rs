@@ -0,0 +1,18 @@
+use gatt::{characteristics as ch, services as srv};
+use gatt::{CharacteristicProperties, Server, SimpleRegistration};
+
+pub fn registration() -> SimpleRegistration {
+    let mut registration = SimpleRegistration::new_simple();
+    registration.add_primary_service(srv::BATTERY);
+    registration.add_characteristic(ch::BATTERY_LEVEL, [100], CharacteristicProperties::READ);
+    registration
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let mut server = Server::bind()?;
+    while let Some(connection) = server.accept(registration()).await? {
+        connection.run().await?;
+    }
+    Ok(())
+}
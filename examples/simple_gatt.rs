@@ -20,27 +20,31 @@ fn new_registration() -> Registration<Token> {
         "abc",
         CharacteristicProperties::WRITE,
     );
-    registration.add_characteristic(
+    registration.add_characteristic_with_initial_value(
         ch::APPEARANCE,
-        0x03c0u16.to_le_bytes().to_vec(),
+        0x03c0u16.to_le_bytes(),
         CharacteristicProperties::READ,
     );
 
     registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
-    registration.add_characteristic(ch::SERVICE_CHANGED, "", CharacteristicProperties::INDICATE);
+    registration.add_characteristic_with_initial_value(
+        ch::SERVICE_CHANGED,
+        [0x01, 0x00, 0xFF, 0xFF],
+        CharacteristicProperties::INDICATE,
+    );
 
     registration.add_primary_service(srv::DEVICE_INFORMATION);
-    registration.add_characteristic(
+    registration.add_characteristic_with_initial_value(
         ch::MANUFACTURER_NAME_STRING,
         "機械",
         CharacteristicProperties::READ,
     );
-    registration.add_characteristic(
+    registration.add_characteristic_with_initial_value(
         ch::MODEL_NUMBER_STRING,
         "A123",
         CharacteristicProperties::READ,
     );
-    registration.add_characteristic(
+    registration.add_characteristic_with_initial_value(
         ch::SERIAL_NUMBER_STRING,
         "333-444",
         CharacteristicProperties::READ,
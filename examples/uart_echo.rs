@@ -0,0 +1,29 @@
+use gatt::profiles::nus::add_nus;
+use gatt::services as srv;
+use gatt::{Registration, Server};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Token {
+    Rx,
+    Tx,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init();
+
+    let mut registration = Registration::new();
+    registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+    let tokens = add_nus(&mut registration, Token::Rx, Token::Tx);
+
+    let mut server = Server::bind()?;
+    let mut connection = server.accept(registration).await?.unwrap();
+
+    // echo back everything the client writes.
+    let pipe = connection.nus_pipe(&tokens)?;
+    let (mut rx, mut tx) = tokio::io::split(pipe);
+    tokio::spawn(async move { tokio::io::copy(&mut rx, &mut tx).await });
+
+    connection.run().await?;
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! Fuzz targets of `att` and `gatt`.
+//!
+//! ```text
+//! cargo fuzz run fuzz_unpack_devicerecv
+//! cargo fuzz run fuzz_database_queries
+//! ```
+//!
+//! `corpus/` holds seeds derived from the PDU formats of the specification.
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+
+use att::machine::AttServerMachine;
+use att::packet as pkt;
+use att::server::AuthenticationState;
+use att::{Handle, Uuid};
+use gatt::{CharacteristicProperties, Registration};
+use pkt::pack::{Pack, Unpack};
+
+/// Pack `recv` with its Op Code.
+pub fn repack(recv: pkt::DeviceRecv) -> Vec<u8> {
+    macro_rules! repack {
+        ($($ident:ident,)*) => {
+            match recv {
+                $(
+                    pkt::DeviceRecv::$ident(item) => {
+                        let mut buf = vec![];
+                        <pkt::$ident as pkt::Packet>::OPCODE.pack(&mut buf).unwrap();
+                        item.pack(&mut buf).unwrap();
+                        buf
+                    }
+                )*
+            }
+        };
+    }
+
+    repack![
+        ExchangeMtuRequest,
+        FindInformationRequest,
+        FindByTypeValueRequest,
+        ReadByTypeRequest,
+        ReadRequest,
+        ReadBlobRequest,
+        ReadMultipleRequest,
+        ReadByGroupTypeRequest,
+        WriteRequest,
+        PrepareWriteRequest,
+        ExecuteWriteRequest,
+        WriteCommand,
+        SignedWriteCommand,
+        HandleValueConfirmation,
+    ]
+}
+
+/// `data` never panics to unpack, and a parsed packet packs to bytes which
+/// parse and pack to the same bytes again.
+pub fn unpack_devicerecv(data: &[u8]) {
+    let recv = match pkt::DeviceRecv::unpack(&mut &data[..]) {
+        Ok(recv) => recv,
+        Err(..) => return,
+    };
+    let bytes = repack(recv);
+    let reparsed = pkt::DeviceRecv::unpack(&mut &bytes[..]).expect("repacked packet must parse");
+    assert_eq!(repack(reparsed), bytes);
+}
+
+/// Reader of fuzz input. Yields zeros once exhausted.
+struct Input<'a>(&'a [u8]);
+
+impl Input<'_> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn u8(&mut self) -> u8 {
+        match self.0.split_first() {
+            Some((b, rest)) => {
+                self.0 = rest;
+                *b
+            }
+            None => 0,
+        }
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+
+    /// Half of them near the start of the table.
+    fn handle(&mut self) -> Handle {
+        match self.u16() {
+            h if h & 0x8000 != 0 => Handle::new(h),
+            h => Handle::new(h % 0x40),
+        }
+    }
+
+    fn bytes(&mut self, max: usize) -> Vec<u8> {
+        let len = (self.u8() as usize % (max + 1)).min(self.0.len());
+        let (b, rest) = self.0.split_at(len);
+        self.0 = rest;
+        b.to_vec()
+    }
+
+    /// Mostly well known types, so that queries find something.
+    fn uuid(&mut self) -> Uuid {
+        const TYPES: &[u16] = &[0x2800, 0x2801, 0x2803, 0x2902, 0x180F, 0x2A19, 0x2A00];
+        match self.u8() as usize {
+            n if n < TYPES.len() * 32 => Uuid::new_uuid16(TYPES[n % TYPES.len()]),
+            _ => {
+                let mut b = [0; 16];
+                b.iter_mut().for_each(|b| *b = self.u8());
+                Uuid::new_uuid128(u128::from_le_bytes(b))
+            }
+        }
+    }
+}
+
+/// Attribute table drawn from `input`.
+///
+/// Services, characteristics and descriptors with handle gaps, in any order
+/// [`Database::validate`](gatt::Database::validate) accepts.
+fn table(input: &mut Input<'_>) -> Registration<u16> {
+    let mut registration = Registration::new();
+    registration.add_primary_service(input.uuid());
+    let mut characteristic = false;
+    for token in 0..input.u8() % 32 {
+        // a characteristic takes up to three handles.
+        match registration.next_free_handle() {
+            Some(next) if next.as_u16() <= 0xFFFC => {}
+            _ => break,
+        }
+        match input.u8() % 8 {
+            0 | 1 => {
+                registration.add_primary_service(input.uuid());
+                characteristic = false;
+            }
+            2..=4 => {
+                let uuid = input.uuid();
+                let value = input.bytes(300);
+                let properties =
+                    CharacteristicProperties::from_bits_truncate(u32::from_le_bytes([
+                        input.u8(),
+                        input.u8(),
+                        input.u8(),
+                        0,
+                    ]));
+                registration.add_characteristic_with_token(token as u16, uuid, value, properties);
+                characteristic = true;
+            }
+            5 | 6 if characteristic => {
+                let uuid = input.uuid();
+                let value = input.bytes(32);
+                registration.add_descriptor(uuid, value, input.u8() & 1 == 0)
+            }
+            5 | 6 => {}
+            _ => {
+                registration.at_handle(input.u16()).ok();
+            }
+        }
+    }
+    registration
+}
+
+/// Queries on a table drawn from `data` never panic, always pack, and only
+/// report handles in the table and in the requested range.
+pub fn database_queries(data: &[u8]) {
+    let mut input = Input(data);
+    let registration = table(&mut input);
+    let handles = registration
+        .iter()
+        .map(|attr| attr.handle().as_u16())
+        .collect::<BTreeSet<_>>();
+    let (mut handler, _events) =
+        gatt::compat::database_handler(registration, AuthenticationState::default());
+
+    let mut machine = AttServerMachine::new();
+    let mtu = pkt::ExchangeMtuRequest::new(input.u16());
+    machine.dispatch(&mut handler, mtu.into()).unwrap();
+
+    while !input.is_empty() {
+        let op = input.u8();
+        let (start, end) = (input.handle(), input.handle());
+        let range = start.as_u16()..=end.as_u16();
+        let request: pkt::DeviceRecv = match op % 6 {
+            0 => pkt::FindInformationRequest::new(start, end).into(),
+            1 => {
+                let value = input.bytes(16);
+                pkt::FindByTypeValueRequest::new(start, end, 0x2800.into(), value.into()).into()
+            }
+            2 => pkt::ReadByTypeRequest::new(start, end, input.uuid()).into(),
+            3 => pkt::ReadByGroupTypeRequest::new(start, end, input.uuid()).into(),
+            4 => pkt::ReadRequest::new(start).into(),
+            _ => pkt::ReadBlobRequest::new(start, end.as_u16() % 0x200).into(),
+        };
+        let response = machine
+            .dispatch(&mut handler, request)
+            .expect("response must pack")
+            .expect("requests are answered");
+        assert!(response.len() <= machine.mtu());
+        check_response(&response, &range, &handles);
+    }
+}
+
+fn check_response(response: &[u8], range: &RangeInclusive<u16>, handles: &BTreeSet<u16>) {
+    let handle = |b: &[u8]| u16::from_le_bytes([b[0], b[1]]);
+    let check = |h: u16| {
+        assert!(range.contains(&h), "{:04X} out of {:04X?}", h, range);
+        assert!(handles.contains(&h), "{:04X} not in table", h);
+    };
+
+    match response[0] {
+        // Find Information Response
+        0x05 => {
+            let len = match response[1] {
+                0x01 => 4,
+                0x02 => 18,
+                format => panic!("format {}", format),
+            };
+            assert_eq!((response.len() - 2) % len, 0);
+            response[2..].chunks(len).for_each(|b| check(handle(b)));
+        }
+        // Find By Type Value Response
+        0x07 => {
+            assert_eq!((response.len() - 1) % 4, 0);
+            for b in response[1..].chunks(4) {
+                check(handle(b));
+                assert!(handle(&b[2..]) >= handle(b));
+                assert!(handles.contains(&handle(&b[2..])));
+            }
+        }
+        // Read By Type Response
+        0x09 => {
+            let len = response[1] as usize;
+            assert_eq!((response.len() - 2) % len, 0);
+            response[2..].chunks(len).for_each(|b| check(handle(b)));
+        }
+        // Read By Group Type Response
+        0x11 => {
+            let len = response[1] as usize;
+            assert_eq!((response.len() - 2) % len, 0);
+            for b in response[2..].chunks(len) {
+                check(handle(b));
+                assert!(handle(&b[2..]) >= handle(b));
+                assert!(handles.contains(&handle(&b[2..])));
+            }
+        }
+        // Error Response, Read Response, Read Blob Response
+        0x01 | 0x0B | 0x0D => {}
+        op => panic!("unexpected response {:02X}", op),
+    }
+}
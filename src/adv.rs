@@ -0,0 +1,173 @@
+//! Service UUID AD structures for advertising data.
+//!
+//! Advertising itself is left to the application (e.g. BlueZ). This only
+//! formats the services of a [`Registration`] as advertising data.
+//! (Core Specification Supplement, Part A, 1.1)
+//!
+//! ```
+//! use gatt::adv::ServiceUuids;
+//! use gatt::Registration;
+//!
+//! let mut registration = Registration::<()>::new();
+//! registration.add_primary_service(gatt::services::BATTERY);
+//!
+//! let (data, truncated) = ServiceUuids::from_registration(&registration).fit_into(31);
+//! assert_eq!(data, [0x03, 0x03, 0x0F, 0x18]);
+//! assert!(!truncated);
+//! ```
+use std::iter::FromIterator;
+
+use crate::{Registration, Uuid};
+
+/// Incomplete List of 16-bit Service UUIDs
+pub const INCOMPLETE_LIST_16: u8 = 0x02;
+/// Complete List of 16-bit Service UUIDs
+pub const COMPLETE_LIST_16: u8 = 0x03;
+/// Incomplete List of 128-bit Service UUIDs
+pub const INCOMPLETE_LIST_128: u8 = 0x06;
+/// Complete List of 128-bit Service UUIDs
+pub const COMPLETE_LIST_128: u8 = 0x07;
+
+/// Service UUIDs to advertise, 16-bit and 128-bit apart.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceUuids {
+    uuid16: Vec<[u8; 2]>,
+    uuid128: Vec<[u8; 16]>,
+}
+
+impl ServiceUuids {
+    /// Primary services of `registration`. See
+    /// [`Registration::advertised_services`].
+    pub fn from_registration<T>(registration: &Registration<T>) -> Self {
+        registration.advertised_services().into_iter().collect()
+    }
+
+    /// Complete lists, without a length limit.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.fit_into(usize::MAX).0
+    }
+
+    /// AD structures of at most `max_len` octets in total, e.g. 31 for a
+    /// legacy advertisement. The 16-bit list comes first.
+    ///
+    /// UUIDs not fitting are left out and their list is marked incomplete.
+    /// Returns whether any was left out.
+    pub fn fit_into(&self, max_len: usize) -> (Vec<u8>, bool) {
+        let mut buf = vec![];
+        let mut remaining = max_len;
+        let mut truncated = false;
+        for (uuids, len, complete, incomplete) in [
+            (
+                self.uuid16.iter().map(|u| &u[..]).collect::<Vec<_>>(),
+                2,
+                COMPLETE_LIST_16,
+                INCOMPLETE_LIST_16,
+            ),
+            (
+                self.uuid128.iter().map(|u| &u[..]).collect::<Vec<_>>(),
+                16,
+                COMPLETE_LIST_128,
+                INCOMPLETE_LIST_128,
+            ),
+        ] {
+            if uuids.is_empty() {
+                continue;
+            }
+            // Length and AD Type, then as many UUIDs as the Length octet allows.
+            let fits = (remaining.saturating_sub(2) / len)
+                .min(uuids.len())
+                .min((u8::MAX as usize - 1) / len);
+            truncated |= fits < uuids.len();
+            if fits == 0 {
+                continue;
+            }
+            buf.push((1 + fits * len) as u8);
+            buf.push(if fits == uuids.len() {
+                complete
+            } else {
+                incomplete
+            });
+            for uuid in &uuids[..fits] {
+                buf.extend_from_slice(uuid);
+            }
+            remaining -= 2 + fits * len;
+        }
+        (buf, truncated)
+    }
+}
+
+impl FromIterator<Uuid> for ServiceUuids {
+    fn from_iter<I: IntoIterator<Item = Uuid>>(iter: I) -> Self {
+        let mut uuids = Self::default();
+        for uuid in iter {
+            match uuid.canonical() {
+                Uuid::Uuid16(uuid) => uuids.uuid16.push(uuid.into()),
+                uuid => uuids.uuid128.push(uuid.to_le_bytes128()),
+            }
+        }
+        uuids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM: u128 = 0x6E40_0001_B5A3_F393_E0A9_E50E_24DC_CA9E;
+
+    fn registration(services: &[Uuid]) -> Registration<()> {
+        let mut registration = Registration::new();
+        for uuid in services {
+            registration.add_primary_service(uuid.clone());
+        }
+        registration
+    }
+
+    #[test]
+    fn test_service_uuids() {
+        let registration = registration(&[
+            crate::services::BATTERY,
+            Uuid::new_uuid128(CUSTOM),
+            crate::services::DEVICE_INFORMATION,
+            crate::services::BATTERY,
+        ]);
+        assert_eq!(
+            registration.advertised_services(),
+            [
+                crate::services::BATTERY,
+                Uuid::new_uuid128(CUSTOM),
+                crate::services::DEVICE_INFORMATION,
+            ]
+        );
+
+        let mut expected = vec![0x05, 0x03, 0x0F, 0x18, 0x0A, 0x18, 0x11, 0x07];
+        expected.extend_from_slice(&CUSTOM.to_le_bytes());
+        let uuids = ServiceUuids::from_registration(&registration);
+        assert_eq!(uuids.to_bytes(), expected);
+        assert_eq!(uuids.fit_into(31), (expected, false));
+
+        // no room for the 128-bit list.
+        assert_eq!(
+            uuids.fit_into(23),
+            (vec![0x05, 0x03, 0x0F, 0x18, 0x0A, 0x18], true)
+        );
+        // one of the 16-bit UUIDs only.
+        assert_eq!(uuids.fit_into(5), (vec![0x03, 0x02, 0x0F, 0x18], true));
+    }
+
+    #[test]
+    fn test_fit_into_legacy_advertisement() {
+        let registration = registration(&[
+            crate::services::BATTERY,
+            Uuid::new_uuid128(CUSTOM),
+            Uuid::new_uuid128(CUSTOM + 1),
+        ]);
+        let (data, truncated) = ServiceUuids::from_registration(&registration).fit_into(31);
+
+        let mut expected = vec![0x03, 0x03, 0x0F, 0x18, 0x11, 0x06];
+        expected.extend_from_slice(&CUSTOM.to_le_bytes());
+        assert_eq!(data, expected);
+        assert!(data.len() <= 31);
+        assert!(truncated);
+    }
+}
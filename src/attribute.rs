@@ -3,20 +3,17 @@
 use att::uuid::Uuid16;
 use att::{Handle, Uuid};
 
+use crate::types::{
+    CharacteristicExtendedProperties, CharacteristicProperties, ClientCharacteristicConfiguration,
+    Permission, ServerCharacteristicConfiguration,
+};
+
 trait BufRead {
     fn read<const N: usize>(&mut self) -> [u8; N];
 
-    fn get_u8(&mut self) -> u8 {
-        u8::from_le_bytes(self.read())
-    }
-
     fn get_u16_le(&mut self) -> u16 {
         u16::from_le_bytes(self.read())
     }
-
-    fn get_u128_le(&mut self) -> u128 {
-        u128::from_le_bytes(self.read())
-    }
 }
 
 impl BufRead for &[u8] {
@@ -30,6 +27,9 @@ impl BufRead for &[u8] {
     }
 }
 
+/// Maximum length of an attribute value. (3.2.9)
+const MAX_VALUE_LEN: usize = 512;
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum Error {
     #[error("permission denied")]
@@ -45,48 +45,6 @@ pub(crate) enum Error {
     InvalidDataLength,
 }
 
-bitflags::bitflags! {
-    pub(crate) struct Permission: u8 {
-        const READABLE = 0b0000_0001;
-        const WRITEABLE = 0b0000_0010;
-        const AUTHORIZATION_REQUIRED = 0b0000_0100;
-        const AUTHENTICATION_REQUIRED = 0b0000_1000;
-    }
-}
-
-bitflags::bitflags! {
-    pub(crate) struct CharacteristicProperties: u8 {
-        const BROADCAST = 0x01;
-        const READ = 0x02;
-        const WRITE_WITHOUT_RESPONSE = 0x04;
-        const WRITE = 0x08;
-        const NOTIFY = 0x10;
-        const INDICATE = 0x20;
-        const AUTHENTICATED_SIGNED_WRITES = 0x40;
-        const EXTENDED_PROPERTIES = 0x80;
-    }
-}
-
-bitflags::bitflags! {
-    pub(crate) struct CharacteristicExtendedProperties: u8 {
-        const RELIABLE_WRITE = 0b0001;
-        const WRITABLE_AUXILIARIES = 0b0010;
-    }
-}
-
-bitflags::bitflags! {
-    pub(crate) struct ClientCharacteristicConfiguration: u16 {
-        const NOTIFICATION = 0b0001;
-        const INDICATION = 0b0010;
-    }
-}
-
-bitflags::bitflags! {
-    pub(crate) struct ServerCharacteristicConfiguration: u16 {
-        const BROADCAST = 0b0001;
-    }
-}
-
 const PRIMARY_SERVICE: Uuid = Uuid::Uuid16(Uuid16::new(0x2800));
 
 const SECONDARY_SERVICE: Uuid = Uuid::Uuid16(Uuid16::new(0x2801));
@@ -107,8 +65,13 @@ const CHARACTERISTIC_PRESENTATION_FORMAT: Uuid = Uuid::Uuid16(Uuid16::new(0x2904
 
 const CHARACTERISTIC_AGGREGATE_FORMAT: Uuid = Uuid::Uuid16(Uuid16::new(0x2905));
 
-#[derive(Debug)]
-pub(crate) enum Attribute {
+/// Attribute of the attribute table, as planned by [`Registration`](crate::Registration).
+///
+/// Inspect with [`handle`](Self::handle), [`attr_type`](Self::attr_type) and
+/// [`permission`](Self::permission); the variants may change between releases.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Attribute {
     Service {
         handle: Handle,
         primary: bool,
@@ -161,17 +124,15 @@ pub(crate) enum Attribute {
         permission: Permission,
     },
 
-    #[allow(dead_code)]
     CharacteristicPresentationFormat {
         handle: Handle,
         format: u8,
         exponent: u8,
         unit: u16,
-        name_space: u16,
+        name_space: u8,
         description: u16,
     },
 
-    #[allow(dead_code)]
     CharacteristicAggregateFormat {
         handle: Handle,
         attribute_handles: Vec<Handle>,
@@ -280,13 +241,12 @@ impl Attribute {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn new_characteristic_presentation_format(
         handle: Handle,
         format: u8,
         exponent: u8,
         unit: u16,
-        name_space: u16,
+        name_space: u8,
         description: u16,
     ) -> Self {
         Self::CharacteristicPresentationFormat {
@@ -299,7 +259,6 @@ impl Attribute {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn new_characteristic_aggregate_format(
         handle: Handle,
         attribute_handles: Vec<Handle>,
@@ -326,7 +285,7 @@ impl Attribute {
 }
 
 impl Attribute {
-    pub(crate) fn handle(&self) -> &Handle {
+    pub fn handle(&self) -> &Handle {
         match self {
             Self::Service { handle, .. } => handle,
             Self::Include { handle, .. } => handle,
@@ -342,7 +301,8 @@ impl Attribute {
         }
     }
 
-    pub(crate) fn attr_type(&self) -> &Uuid {
+    /// Attribute Type, e.g. `0x2800` for a primary service declaration.
+    pub fn attr_type(&self) -> &Uuid {
         match self {
             Self::Service { primary, .. } if *primary => &PRIMARY_SERVICE,
             Self::Service { .. } => &SECONDARY_SERVICE,
@@ -359,7 +319,7 @@ impl Attribute {
         }
     }
 
-    pub(crate) fn permission(&self) -> Permission {
+    pub fn permission(&self) -> Permission {
         match self {
             Self::Service { .. } => Permission::READABLE,
             Self::Include { .. } => Permission::READABLE,
@@ -398,8 +358,8 @@ impl Attribute {
 
         Ok(match self {
             Self::Service { uuid, .. } => match uuid {
-                Uuid::Uuid16(uuid) => uuid.as_u16().to_le_bytes().to_vec().into(),
-                Uuid::Uuid128(uuid) => uuid.as_u128().to_le_bytes().to_vec().into(),
+                Uuid::Uuid16(uuid) => uuid.to_le_bytes().to_vec().into(),
+                Uuid::Uuid128(..) => uuid.to_le_bytes128().to_vec().into(),
             },
 
             Self::Include {
@@ -411,9 +371,8 @@ impl Attribute {
                 let mut result = vec![];
                 result.extend_from_slice(&included_service_handle.as_u16().to_le_bytes());
                 result.extend_from_slice(&end_group_handle.as_u16().to_le_bytes());
-                match uuid {
-                    Uuid::Uuid16(uuid) => result.extend_from_slice(&uuid.as_u16().to_le_bytes()),
-                    Uuid::Uuid128(uuid) => result.extend_from_slice(&uuid.as_u128().to_le_bytes()),
+                if let Uuid::Uuid16(uuid) = uuid {
+                    result.extend_from_slice(&uuid.to_le_bytes());
                 }
                 result.into()
             }
@@ -428,8 +387,8 @@ impl Attribute {
                 result.extend_from_slice(&properties.bits().to_le_bytes());
                 result.extend_from_slice(&value_handle.as_u16().to_le_bytes());
                 match uuid {
-                    Uuid::Uuid16(uuid) => result.extend_from_slice(&uuid.as_u16().to_le_bytes()),
-                    Uuid::Uuid128(uuid) => result.extend_from_slice(&uuid.as_u128().to_le_bytes()),
+                    Uuid::Uuid16(uuid) => result.extend_from_slice(&uuid.to_le_bytes()),
+                    Uuid::Uuid128(..) => result.extend_from_slice(&uuid.to_le_bytes128()),
                 }
                 result.into()
             }
@@ -441,7 +400,7 @@ impl Attribute {
                 ..
             } => {
                 let mut result = vec![];
-                result.extend_from_slice(&extended_properties.bits().to_le_bytes());
+                result.extend_from_slice(&u16::from(extended_properties.bits()).to_le_bytes());
                 result.into()
             }
 
@@ -492,12 +451,35 @@ impl Attribute {
         })
     }
 
+    /// Declarations and the descriptors the server defines are read only
+    /// whatever their permission says.
+    fn is_declaration(&self) -> bool {
+        matches!(
+            self,
+            Self::Service { .. }
+                | Self::Include { .. }
+                | Self::Characteristic { .. }
+                | Self::CharacteristicExtendedProperties { .. }
+                | Self::CharacteristicPresentationFormat { .. }
+                | Self::CharacteristicAggregateFormat { .. }
+        )
+    }
+
     pub(crate) fn set(
         &mut self,
-        mut val: &[u8],
+        val: &[u8],
         authorized: bool,
         authenticated: bool,
     ) -> Result<(), Error> {
+        if self.is_declaration() {
+            debug_assert!(
+                !self.permission().contains(Permission::WRITEABLE),
+                "declaration must not be writable. {:?}",
+                self
+            );
+            return Err(Error::PermissionDenied);
+        }
+
         if !self.permission().contains(Permission::WRITEABLE) {
             return Err(Error::PermissionDenied);
         }
@@ -518,67 +500,32 @@ impl Attribute {
             return Err(Error::AuthenticationRequired);
         }
 
-        match self {
-            Self::Service { uuid, .. } => match val.len() {
-                2 => *uuid = Uuid::new_uuid16(val.get_u16_le()),
-                16 => *uuid = Uuid::new_uuid128(val.get_u128_le()),
-                _ => return Err(Error::InvalidDataLength),
-            },
-
-            Self::Include {
-                included_service_handle,
-                end_group_handle,
-                uuid,
-                ..
-            } => {
-                match val.len() {
-                    6 | 20 => {}
-                    _ => return Err(Error::InvalidDataLength),
-                }
-                *included_service_handle = val.get_u16_le().into();
-                *end_group_handle = val.get_u16_le().into();
-                match val.len() {
-                    2 => *uuid = Uuid::new_uuid16(val.get_u16_le()),
-                    16 => *uuid = Uuid::new_uuid128(val.get_u128_le()),
-                    _ => unreachable!(),
-                }
-            }
-
-            Self::Characteristic {
-                properties,
-                value_handle,
-                uuid,
-                ..
-            } => {
-                match val.len() {
-                    5 | 19 => {}
-                    _ => return Err(Error::InvalidDataLength),
-                }
-                *properties = CharacteristicProperties::from_bits_truncate(val.get_u8());
-                *value_handle = val.get_u16_le().into();
-                match val.len() {
-                    2 => *uuid = Uuid::new_uuid16(val.get_u16_le()),
-                    16 => *uuid = Uuid::new_uuid128(val.get_u128_le()),
-                    _ => return Err(Error::InvalidDataLength),
-                }
-            }
+        self.set_value(val)
+    }
 
+    /// Decode `val` into this attribute. Every value must have the length the
+    /// specification defines for its type, so short and trailing octets are
+    /// rejected alike. Declarations are never decoded.
+    pub(crate) fn set_value(&mut self, mut val: &[u8]) -> Result<(), Error> {
+        match self {
             Self::CharacteristicValue { value, .. } => {
+                if val.len() > MAX_VALUE_LEN {
+                    return Err(Error::InvalidDataLength);
+                }
                 *value = val.into();
             }
 
-            Self::CharacteristicExtendedProperties {
-                extended_properties,
-                ..
-            } => {
-                if val.is_empty() {
+            Self::Descriptor { value, .. } => {
+                if val.len() > MAX_VALUE_LEN {
                     return Err(Error::InvalidDataLength);
                 }
-                *extended_properties =
-                    CharacteristicExtendedProperties::from_bits_truncate(val.get_u8());
+                *value = val.into();
             }
 
             Self::CharacteristicUserDescription { description, .. } => {
+                if val.len() > MAX_VALUE_LEN {
+                    return Err(Error::InvalidDataLength);
+                }
                 *description = match String::from_utf8(val.to_vec()) {
                     Ok(v) => v,
                     Err(_) => return Err(Error::InvalidDataLength),
@@ -586,7 +533,7 @@ impl Attribute {
             }
 
             Self::ClientCharacteristicConfiguration { configuration, .. } => {
-                if val.len() < 2 {
+                if val.len() != 2 {
                     return Err(Error::InvalidDataLength);
                 }
                 *configuration =
@@ -594,46 +541,171 @@ impl Attribute {
             }
 
             Self::ServerCharacteristicConfiguration { configuration, .. } => {
-                if val.len() < 2 {
+                if val.len() != 2 {
                     return Err(Error::InvalidDataLength);
                 }
                 *configuration =
                     ServerCharacteristicConfiguration::from_bits_truncate(val.get_u16_le());
             }
 
-            Self::CharacteristicPresentationFormat {
-                format,
-                exponent,
-                unit,
-                name_space,
-                description,
-                ..
-            } => {
-                if val.len() != 8 {
-                    return Err(Error::InvalidDataLength);
-                }
-                *format = val.get_u8();
-                *exponent = val.get_u8();
-                *unit = val.get_u16_le();
-                *name_space = val.get_u16_le();
-                *description = val.get_u16_le();
-            }
+            Self::Service { .. }
+            | Self::Include { .. }
+            | Self::Characteristic { .. }
+            | Self::CharacteristicExtendedProperties { .. }
+            | Self::CharacteristicPresentationFormat { .. }
+            | Self::CharacteristicAggregateFormat { .. } => return Err(Error::PermissionDenied),
+        };
+        Ok(())
+    }
+}
 
-            Self::CharacteristicAggregateFormat {
-                attribute_handles, ..
-            } => {
-                let mut v = vec![];
-                while val.len() > 2 {
-                    v.push(val.get_u16_le().into());
-                }
-                if !val.is_empty() {
-                    return Err(Error::InvalidDataLength);
-                }
-                *attribute_handles = v;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_value_lengths() {
+        type New = fn() -> Attribute;
+        fn h() -> Handle {
+            Handle::new(0x0001)
+        }
+        fn rw() -> Permission {
+            Permission::READABLE | Permission::WRITEABLE
+        }
+        let cases: &[(New, &[usize], &[usize])] = &[
+            (
+                || {
+                    Attribute::new_characteristic_value(
+                        h(),
+                        Uuid::new_uuid16(0x2A00),
+                        [].into(),
+                        rw(),
+                    )
+                },
+                &[0, 1, 512],
+                &[513],
+            ),
+            (
+                || Attribute::CharacteristicUserDescription {
+                    handle: h(),
+                    description: String::new(),
+                    permission: rw(),
+                },
+                &[0, 1, 512],
+                &[513],
+            ),
+            (
+                || {
+                    Attribute::new_client_characteristic_configuration(
+                        h(),
+                        ClientCharacteristicConfiguration::empty(),
+                        rw(),
+                    )
+                },
+                &[2],
+                &[0, 1, 3],
+            ),
+            (
+                || {
+                    Attribute::new_server_characteristic_configuration(
+                        h(),
+                        ServerCharacteristicConfiguration::empty(),
+                        rw(),
+                    )
+                },
+                &[2],
+                &[0, 1, 3],
+            ),
+            (
+                || Attribute::new_descriptor(h(), Uuid::new_uuid16(0x2901), [].into(), rw()),
+                &[0, 1, 512],
+                &[513],
+            ),
+        ];
+
+        for (new, ok, err) in cases {
+            for len in *ok {
+                let mut attr = new();
+                let val = vec![b'a'; *len];
+                assert!(attr.set_value(&val).is_ok(), "{:?} {}", attr, len);
+            }
+            for len in *err {
+                let mut attr = new();
+                let val = vec![b'a'; *len];
+                assert!(
+                    matches!(attr.set_value(&val), Err(Error::InvalidDataLength)),
+                    "{:?} {}",
+                    attr,
+                    len
+                );
             }
+        }
+    }
 
-            Self::Descriptor { value, .. } => *value = val.into(),
+    #[test]
+    fn test_set_value_roundtrip() {
+        let handle = || Handle::new(0x0001);
+        let attr = Attribute::new_characteristic_aggregate_format(handle(), vec![Handle::new(4)]);
+        assert_eq!(&attr.get(false, false).unwrap()[..], [0x04, 0x00]);
+
+        let attr = Attribute::new_characteristic_extended_properties(
+            handle(),
+            CharacteristicExtendedProperties::RELIABLE_WRITE,
+        );
+        assert_eq!(&attr.get(false, false).unwrap()[..], [0x01, 0x00]);
+
+        let attr = Attribute::new_include(handle(), handle(), handle(), Uuid::new_uuid128(1));
+        assert_eq!(attr.get(false, false).unwrap().len(), 4);
+
+        let mut attr = Attribute::CharacteristicUserDescription {
+            handle: handle(),
+            description: String::new(),
+            permission: Permission::READABLE | Permission::WRITEABLE,
         };
-        Ok(())
+        assert!(matches!(
+            attr.set(&[0xFF], false, false),
+            Err(Error::InvalidDataLength)
+        ));
+        let mut attr = Attribute::new_characteristic_presentation_format(handle(), 0, 0, 0, 0, 0);
+        assert!(matches!(
+            attr.set(&[0; 7], false, false),
+            Err(Error::PermissionDenied)
+        ));
+    }
+
+    #[test]
+    fn test_declarations_not_writable() {
+        let h = Handle::new(0x0001);
+        let uuid = Uuid::new_uuid16(0x180F);
+        let mut declarations = vec![
+            Attribute::new_primary_service(h.clone(), uuid.clone()),
+            Attribute::new_secondary_service(h.clone(), uuid.clone()),
+            Attribute::new_include(h.clone(), h.clone(), h.clone(), uuid.clone()),
+            Attribute::new_characteristic(
+                h.clone(),
+                CharacteristicProperties::READ,
+                h.clone(),
+                uuid,
+            ),
+            Attribute::new_characteristic_extended_properties(
+                h.clone(),
+                CharacteristicExtendedProperties::empty(),
+            ),
+            Attribute::new_characteristic_presentation_format(h.clone(), 0, 0, 0, 0, 0),
+            Attribute::new_characteristic_aggregate_format(h.clone(), vec![h]),
+        ];
+        for attr in &mut declarations {
+            let before = attr.get(true, true).unwrap();
+            assert!(!attr.permission().contains(Permission::WRITEABLE));
+            assert!(matches!(
+                attr.set(&before, true, true),
+                Err(Error::PermissionDenied)
+            ));
+            assert!(matches!(
+                attr.set_value(&before),
+                Err(Error::PermissionDenied)
+            ));
+            assert_eq!(attr.get(true, true).unwrap(), before);
+        }
     }
 }
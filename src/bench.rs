@@ -0,0 +1,46 @@
+//! Entry points for `benches/`. Not part of the public API.
+use att::{Handle, Uuid};
+
+use crate::database::Database;
+use crate::registration::Registration;
+
+/// Attribute table of `registration`, as served to clients.
+pub fn database<T>(registration: Registration<T>) -> Database
+where
+    T: std::hash::Hash + Eq + Clone,
+{
+    registration.build().0
+}
+
+/// Requests a client issues while discovering the whole table: every
+/// primary service, then the attributes of `types` in each of them.
+///
+/// Returns the number of attributes found.
+pub fn discover(db: &Database, types: &[Uuid]) -> usize {
+    let primary = Uuid::new_uuid16(0x2800);
+    let mut found = 0;
+    let mut start = 0x0001;
+    let mut services = vec![];
+    while let Ok(r) = db.read_by_group_type(
+        Handle::new(start)..=Handle::new(0xFFFF),
+        &primary,
+        false,
+        false,
+    ) {
+        let last = r.last().unwrap().1.as_u16();
+        services.extend(r.into_iter().map(|(first, last, _)| first..=last));
+        if last == 0xFFFF {
+            break;
+        }
+        start = last + 1;
+    }
+    found += services.len();
+    for range in services {
+        for uuid in types {
+            if let Ok(r) = db.read_by_type(range.clone(), uuid, false, false) {
+                found += r.len();
+            }
+        }
+    }
+    found
+}
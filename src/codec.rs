@@ -0,0 +1,352 @@
+//! Schema for struct-like characteristic values.
+//!
+//! A [`StructCodec`] describes a value as a sequence of fixed width fields.
+//!
+//! ```
+//! use gatt::codec::{FieldValue, StructCodec, Value};
+//!
+//! let codec = StructCodec::builder()
+//!     .u16le("seq")
+//!     .i16le("temp")
+//!     .u8("flags")
+//!     .build();
+//!
+//! let value = Value::new()
+//!     .with("seq", 1u16)
+//!     .with("temp", -5i16)
+//!     .with("flags", 0x80u8);
+//! let bytes = codec.encode(&value).unwrap();
+//! assert_eq!(bytes, [0x01, 0x00, 0xFB, 0xFF, 0x80]);
+//! assert_eq!(codec.decode(&bytes).unwrap().get("temp"), Some(&FieldValue::I16(-5)));
+//! ```
+use std::collections::BTreeMap;
+
+/// Field type of [`StructCodec`]. Multi byte integers are little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    U16Le,
+    I16Le,
+    U32Le,
+    /// Fixed length octets.
+    Bytes(usize),
+    /// Fixed length UTF-8 string, padded with NUL.
+    Utf8(usize),
+}
+
+impl FieldType {
+    /// Encoded length in octets.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16Le | Self::I16Le => 2,
+            Self::U32Le => 4,
+            Self::Bytes(n) | Self::Utf8(n) => *n,
+        }
+    }
+
+    fn decode(&self, b: &[u8]) -> Option<FieldValue> {
+        Some(match self {
+            Self::U8 => FieldValue::U8(b[0]),
+            Self::U16Le => FieldValue::U16(u16::from_le_bytes([b[0], b[1]])),
+            Self::I16Le => FieldValue::I16(i16::from_le_bytes([b[0], b[1]])),
+            Self::U32Le => FieldValue::U32(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            Self::Bytes(..) => FieldValue::Bytes(b.to_vec()),
+            Self::Utf8(..) => {
+                let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+                FieldValue::Utf8(String::from_utf8(b[..end].to_vec()).ok()?)
+            }
+        })
+    }
+
+    fn encode(&self, value: &FieldValue, buf: &mut Vec<u8>) -> bool {
+        match (self, value) {
+            (Self::U8, FieldValue::U8(v)) => buf.push(*v),
+            (Self::U16Le, FieldValue::U16(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+            (Self::I16Le, FieldValue::I16(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+            (Self::U32Le, FieldValue::U32(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+            (Self::Bytes(n), FieldValue::Bytes(v)) if v.len() == *n => buf.extend_from_slice(v),
+            (Self::Utf8(n), FieldValue::Utf8(v)) if v.len() <= *n => {
+                buf.extend_from_slice(v.as_bytes());
+                buf.resize(buf.len() + n - v.len(), 0);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Decoded field of [`Value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    U8(u8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    Bytes(Vec<u8>),
+    Utf8(String),
+}
+
+impl From<u8> for FieldValue {
+    fn from(v: u8) -> Self {
+        Self::U8(v)
+    }
+}
+
+impl From<u16> for FieldValue {
+    fn from(v: u16) -> Self {
+        Self::U16(v)
+    }
+}
+
+impl From<i16> for FieldValue {
+    fn from(v: i16) -> Self {
+        Self::I16(v)
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(v: u32) -> Self {
+        Self::U32(v)
+    }
+}
+
+impl From<Vec<u8>> for FieldValue {
+    fn from(v: Vec<u8>) -> Self {
+        Self::Bytes(v)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(v: &str) -> Self {
+        Self::Utf8(v.into())
+    }
+}
+
+/// Field values by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Value(BTreeMap<String, FieldValue>);
+
+impl Value {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<FieldValue>,
+    {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert<N, V>(&mut self, name: N, value: V) -> Option<FieldValue>
+    where
+        N: Into<String>,
+        V: Into<FieldValue>,
+    {
+        self.0.insert(name.into(), value.into())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.0.get(name)
+    }
+}
+
+/// Error for [`StructCodec`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum CodecError {
+    #[error("invalid length. (expected {expected}, actual {actual})")]
+    InvalidLength { expected: usize, actual: usize },
+
+    #[error("missing field {0}.")]
+    MissingField(String),
+
+    #[error("field {0} does not match its type.")]
+    InvalidField(String),
+}
+
+/// Layout of a struct-like characteristic value.
+///
+/// Use with [`Registration::add_characteristic_typed`](crate::Registration::add_characteristic_typed)
+/// to reject writes of a different length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructCodec {
+    fields: Vec<(String, FieldType)>,
+}
+
+impl StructCodec {
+    pub fn builder() -> StructCodecBuilder {
+        StructCodecBuilder { fields: vec![] }
+    }
+
+    /// Fields in wire order.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, FieldType)> {
+        self.fields.iter().map(|(name, ty)| (name.as_str(), *ty))
+    }
+
+    /// Encoded length in octets.
+    pub fn len(&self) -> usize {
+        self.fields.iter().map(|(_, ty)| ty.size()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn decode(&self, mut bytes: &[u8]) -> Result<Value, CodecError> {
+        if bytes.len() != self.len() {
+            return Err(CodecError::InvalidLength {
+                expected: self.len(),
+                actual: bytes.len(),
+            });
+        }
+
+        let mut value = Value::new();
+        for (name, ty) in &self.fields {
+            let (field, rest) = bytes.split_at(ty.size());
+            let field = ty
+                .decode(field)
+                .ok_or_else(|| CodecError::InvalidField(name.clone()))?;
+            value.insert(name.clone(), field);
+            bytes = rest;
+        }
+        Ok(value)
+    }
+
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, CodecError> {
+        let mut buf = Vec::with_capacity(self.len());
+        for (name, ty) in &self.fields {
+            let field = value
+                .get(name)
+                .ok_or_else(|| CodecError::MissingField(name.clone()))?;
+            if !ty.encode(field, &mut buf) {
+                return Err(CodecError::InvalidField(name.clone()));
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// [`StructCodec`] Builder.
+#[derive(Debug)]
+pub struct StructCodecBuilder {
+    fields: Vec<(String, FieldType)>,
+}
+
+impl StructCodecBuilder {
+    pub fn field<N>(mut self, name: N, ty: FieldType) -> Self
+    where
+        N: Into<String>,
+    {
+        self.fields.push((name.into(), ty));
+        self
+    }
+
+    pub fn u8<N: Into<String>>(self, name: N) -> Self {
+        self.field(name, FieldType::U8)
+    }
+
+    pub fn u16le<N: Into<String>>(self, name: N) -> Self {
+        self.field(name, FieldType::U16Le)
+    }
+
+    pub fn i16le<N: Into<String>>(self, name: N) -> Self {
+        self.field(name, FieldType::I16Le)
+    }
+
+    pub fn u32le<N: Into<String>>(self, name: N) -> Self {
+        self.field(name, FieldType::U32Le)
+    }
+
+    pub fn bytes<N: Into<String>>(self, name: N, len: usize) -> Self {
+        self.field(name, FieldType::Bytes(len))
+    }
+
+    pub fn utf8<N: Into<String>>(self, name: N, len: usize) -> Self {
+        self.field(name, FieldType::Utf8(len))
+    }
+
+    pub fn build(self) -> StructCodec {
+        StructCodec {
+            fields: self.fields,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telemetry() -> StructCodec {
+        StructCodec::builder()
+            .u16le("seq")
+            .i16le("temp")
+            .u8("flags")
+            .u32le("uptime")
+            .bytes("id", 2)
+            .utf8("name", 4)
+            .build()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let codec = telemetry();
+        let value = Value::new()
+            .with("seq", 0x0102u16)
+            .with("temp", -2i16)
+            .with("flags", 0x03u8)
+            .with("uptime", 0x0405_0607u32)
+            .with("id", vec![0xAA, 0xBB])
+            .with("name", "ab");
+
+        let bytes = codec.encode(&value).unwrap();
+        assert_eq!(codec.len(), 15);
+        assert_eq!(
+            bytes,
+            [
+                0x02, 0x01, 0xFE, 0xFF, 0x03, 0x07, 0x06, 0x05, 0x04, 0xAA, 0xBB, b'a', b'b', 0x00,
+                0x00
+            ]
+        );
+        assert_eq!(codec.decode(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let codec = telemetry();
+        assert_eq!(
+            codec.decode(&[0; 14]),
+            Err(CodecError::InvalidLength {
+                expected: 15,
+                actual: 14
+            })
+        );
+        assert!(codec.decode(&[0; 16]).is_err());
+
+        let mut bytes = [0; 15];
+        bytes[11] = 0xFF;
+        assert_eq!(
+            codec.decode(&bytes),
+            Err(CodecError::InvalidField("name".into()))
+        );
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        let codec = StructCodec::builder().u8("a").utf8("b", 2).build();
+        assert_eq!(
+            codec.encode(&Value::new().with("a", 1u8)),
+            Err(CodecError::MissingField("b".into()))
+        );
+        assert_eq!(
+            codec.encode(&Value::new().with("a", 1u16).with("b", "")),
+            Err(CodecError::InvalidField("a".into()))
+        );
+        assert_eq!(
+            codec.encode(&Value::new().with("a", 1u8).with("b", "abc")),
+            Err(CodecError::InvalidField("b".into()))
+        );
+    }
+}
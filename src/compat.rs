@@ -0,0 +1,109 @@
+//! Serve a [`Registration`] from applications driving
+//! [`att::server::Connection`] themselves.
+//!
+//! ```no_run
+//! use gatt::{CharacteristicProperties, Registration};
+//!
+//! # async fn run(connection: att::server::Connection) -> anyhow::Result<()> {
+//! let mut registration = Registration::new();
+//! registration.add_primary_service(gatt::services::BATTERY);
+//! registration.add_characteristic_with_token(
+//!     "level",
+//!     gatt::characteristics::BATTERY_LEVEL,
+//!     [100],
+//!     CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+//! );
+//!
+//! let (handler, _events) =
+//!     gatt::compat::database_handler(registration, connection.authentication_state());
+//! connection.run(handler).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::hash::Hash;
+
+use att::server::{AuthenticationState, Handler, SharedMtu};
+use tokio::sync::broadcast;
+
+use crate::server::{Events, GattHandler, DEFAULT_EVENT_CAPACITY};
+use crate::Registration;
+
+/// [`Handler`] answering from the attribute table of `registration`.
+///
+/// Writes to tokened characteristics are reported on the returned [`Events`],
+/// as with [`Connection::events`](crate::server::Connection::events).
+/// `authentication` should be the state of the connection the handler runs on.
+///
+/// Read Blob Requests are checked for Attribute Not Long at ATT_MTU 23. Use
+/// [`database_handler_with_mtu`] to follow the MTU the client exchanges.
+pub fn database_handler<T>(
+    registration: Registration<T>,
+    authentication: AuthenticationState,
+) -> (impl Handler, Events<T>)
+where
+    T: Hash + Eq + Clone,
+{
+    database_handler_with_mtu(registration, authentication, SharedMtu::default())
+}
+
+/// [`database_handler`] checking Read Blob Requests against `mtu`, which
+/// should be [`Connection::shared_mtu`](att::server::Connection::shared_mtu)
+/// of the connection the handler runs on.
+pub fn database_handler_with_mtu<T>(
+    registration: Registration<T>,
+    authentication: AuthenticationState,
+    mtu: SharedMtu,
+) -> (impl Handler, Events<T>)
+where
+    T: Hash + Eq + Clone,
+{
+    let (db, write_tokens, _) = registration.build();
+    let (tx, rx) = broadcast::channel(DEFAULT_EVENT_CAPACITY);
+    let mut handler = GattHandler::new(db, write_tokens, tx, authentication);
+    handler.mtu = mtu;
+    (handler, Events(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Event;
+    use crate::CharacteristicProperties;
+    use att::server::Connection;
+
+    #[tokio::test]
+    async fn test_database_handler() {
+        let mut registration = Registration::new();
+        registration.add_primary_service(crate::services::BATTERY);
+        registration.add_characteristic_with_token(
+            "level",
+            crate::characteristics::BATTERY_LEVEL,
+            [100],
+            CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+        );
+
+        let io = tokio_test::io::Builder::new()
+            // Read Request (Battery Level)
+            .read(&[0x0A, 0x03, 0x00])
+            .write(&[0x0B, 0x64])
+            // Write Request (Battery Level)
+            .read(&[0x12, 0x03, 0x00, 0x32])
+            .write(&[0x13])
+            .read(&[0x0A, 0x03, 0x00])
+            .write(&[0x0B, 0x32])
+            // Read Request (unknown handle)
+            .read(&[0x0A, 0x10, 0x00])
+            .write(&[0x01, 0x0A, 0x10, 0x00, 0x0A])
+            .build();
+        let connection = Connection::new(io, att::Address::le_public_from([0; 6]));
+        let (handler, mut events) =
+            database_handler(registration, connection.authentication_state());
+        connection.run(handler).await.unwrap();
+
+        assert!(matches!(
+            events.next().await,
+            Some(Event::Write("level", value)) if *value == [0x32]
+        ));
+        assert!(events.next().await.is_none());
+    }
+}
@@ -1,21 +1,566 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
 use std::iter::FromIterator;
 use std::ops::RangeInclusive;
+use std::sync::Arc;
 
 use att::packet::ErrorCode;
 use att::uuid::Uuid16;
 use att::{Handle, Uuid};
 
 use crate::attribute::{Attribute, Error as AttrError};
+use crate::types::{
+    CharacteristicExtendedProperties, CharacteristicProperties, ClientCharacteristicConfiguration,
+    Permission,
+};
 
 type Result<T> = std::result::Result<T, (Handle, ErrorCode)>;
 
+/// Client Characteristic Configuration Descriptor Improperly Configured.
+/// (Core Specification Supplement, Part B, 1.2)
+pub(crate) const CCCD_IMPROPERLY_CONFIGURED: ErrorCode =
+    ErrorCode::CommonProfileAndServiceErrorCodes(0xFD);
+
+/// Link state and request kind a read is checked against. See
+/// [`Database::read_at`].
+#[derive(Debug, Clone)]
+pub(crate) struct ReadContext {
+    pub(crate) authorized: bool,
+    pub(crate) authenticated: bool,
+    /// ATT_MTU of the connection for a Read Blob Request, `None` for a Read
+    /// Request.
+    pub(crate) blob_mtu: Option<usize>,
+}
+
+/// Produces the value of a characteristic on every read.
+#[derive(Clone)]
+pub(crate) struct Generator(Arc<dyn Fn() -> Box<[u8]> + Send + Sync>);
+
+impl Generator {
+    pub(crate) fn new<F, B>(f: F) -> Self
+    where
+        F: Fn() -> B + Send + Sync + 'static,
+        B: AsRef<[u8]>,
+    {
+        Self(Arc::new(move || f().as_ref().into()))
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Generator(..)")
+    }
+}
+
+/// Rejects values a characteristic does not allow, e.g. before notifying them.
+#[derive(Clone)]
+pub(crate) struct ValueCheck(Arc<CheckFn>);
+
+type CheckFn = dyn Fn(&[u8]) -> io::Result<()> + Send + Sync;
+
+impl ValueCheck {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(&[u8]) -> io::Result<()> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn check(&self, value: &[u8]) -> io::Result<()> {
+        (self.0)(value)
+    }
+}
+
+impl fmt::Debug for ValueCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ValueCheck(..)")
+    }
+}
+
+/// Length a written value must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueLength {
+    Exactly(usize),
+    AtMost(usize),
+}
+
+impl ValueLength {
+    pub(crate) fn accepts(self, len: usize) -> bool {
+        match self {
+            Self::Exactly(expected) => len == expected,
+            Self::AtMost(max) => len <= max,
+        }
+    }
+}
+
+/// Broken rule of an attribute table, found by [`Database::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Inconsistency {
+    /// Attribute before the first service declaration.
+    OutsideService(Handle),
+    /// Characteristic declaration not followed by the value of its value
+    /// handle and type.
+    ValueMismatch {
+        declaration: Handle,
+        value_handle: Handle,
+    },
+    /// Characteristic value no declaration points at.
+    OrphanValue(Handle),
+    /// Descriptor not after the value of a characteristic.
+    OrphanDescriptor(Handle),
+    /// Second Client / Server Characteristic Configuration or Extended
+    /// Properties descriptor of a characteristic.
+    DuplicateDescriptor(Handle),
+    /// Include not referring to a service declaration and the end of its group.
+    IncludeMismatch {
+        handle: Handle,
+        included_service_handle: Handle,
+    },
+    /// `properties` of the declaration are set and the descriptor they
+    /// require is missing, or the other way around. (`NOTIFY` / `INDICATE`
+    /// and the Client Characteristic Configuration, `BROADCAST` and the
+    /// Server Characteristic Configuration, `EXTENDED_PROPERTIES` and the
+    /// Extended Properties)
+    PropertiesMismatch {
+        declaration: Handle,
+        properties: CharacteristicProperties,
+    },
+}
+
+/// Every [`Inconsistency`] of an attribute table, in handle order.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("inconsistent attribute table: {0:?}")]
+pub struct DatabaseInconsistency(pub Vec<Inconsistency>);
+
+/// Characteristic being checked by [`Database::validate`].
+struct Extent<'a> {
+    declaration: &'a Handle,
+    properties: CharacteristicProperties,
+    value_handle: &'a Handle,
+    value: bool,
+    client_configuration: bool,
+    server_configuration: bool,
+    extended_properties: bool,
+}
+
+impl Extent<'_> {
+    fn end(self, problems: &mut Vec<Inconsistency>) {
+        let rules = [
+            (
+                CharacteristicProperties::NOTIFY | CharacteristicProperties::INDICATE,
+                self.client_configuration,
+            ),
+            (
+                CharacteristicProperties::BROADCAST,
+                self.server_configuration,
+            ),
+            (
+                CharacteristicProperties::EXTENDED_PROPERTIES,
+                self.extended_properties,
+            ),
+        ];
+        for (properties, present) in rules {
+            if self.properties.intersects(properties) != present {
+                problems.push(Inconsistency::PropertiesMismatch {
+                    declaration: self.declaration.clone(),
+                    properties,
+                });
+            }
+        }
+    }
+}
+
+/// Attribute table of a GATT server.
+///
+/// Exposed read only through [`Connection::database`](crate::server::Connection::database)
+/// for diagnostics.
 #[derive(Debug)]
-pub(crate) struct Database {
+pub struct Database {
     attrs: BTreeMap<Handle, Attribute>,
+    /// canonical attribute type -> sorted handles of that type.
+    types: HashMap<Uuid, Vec<Handle>>,
+    /// Values only accepted with this length.
+    value_lengths: HashMap<Handle, ValueLength>,
+    /// Values produced on every read instead of the stored value.
+    generators: HashMap<Handle, Generator>,
+    /// Checks of the values notified.
+    value_checks: HashMap<Handle, ValueCheck>,
 }
 
 impl Database {
+    fn new() -> Self {
+        Self {
+            attrs: BTreeMap::new(),
+            types: HashMap::new(),
+            value_lengths: HashMap::new(),
+            generators: HashMap::new(),
+            value_checks: HashMap::new(),
+        }
+    }
+
+    /// Number of attributes.
+    pub fn len(&self) -> usize {
+        self.attrs.len()
+    }
+
+    /// Returns `true` if no attribute registered.
+    pub fn is_empty(&self) -> bool {
+        self.attrs.is_empty()
+    }
+
+    /// Iterate attribute handles and types in handle order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Handle, &Uuid)> {
+        self.attrs.iter().map(|(h, a)| (h, a.attr_type()))
+    }
+
+    /// Attributes in handle order.
+    pub(crate) fn attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.attrs.values()
+    }
+
+    /// Attribute type of `handle`.
+    pub fn attribute_type(&self, handle: &Handle) -> Option<&Uuid> {
+        self.attrs.get(handle).map(Attribute::attr_type)
+    }
+
+    /// Check the structure of the table.
+    ///
+    /// Every characteristic declaration is followed by its value, its
+    /// descriptors come after the value and before the next declaration,
+    /// the properties agree with the descriptors present, and includes
+    /// refer to a whole service. Service groups cannot overlap, as a group
+    /// ends at the next service declaration.
+    pub fn validate(&self) -> std::result::Result<(), DatabaseInconsistency> {
+        use std::ops::Bound;
+
+        let mut problems = vec![];
+        let mut in_service = false;
+        let mut current = None::<Extent<'_>>;
+        for (handle, attr) in &self.attrs {
+            if let Attribute::Service { .. } = attr {
+                in_service = true;
+            } else if !in_service {
+                problems.push(Inconsistency::OutsideService(handle.clone()));
+                continue;
+            }
+
+            match attr {
+                Attribute::Service { .. } => {
+                    if let Some(extent) = current.take() {
+                        extent.end(&mut problems);
+                    }
+                }
+
+                Attribute::Include {
+                    included_service_handle,
+                    end_group_handle,
+                    ..
+                } => {
+                    if let Some(extent) = current.take() {
+                        extent.end(&mut problems);
+                    }
+                    if self.service_end(included_service_handle) != Some(end_group_handle) {
+                        problems.push(Inconsistency::IncludeMismatch {
+                            handle: handle.clone(),
+                            included_service_handle: included_service_handle.clone(),
+                        });
+                    }
+                }
+
+                Attribute::Characteristic {
+                    properties,
+                    value_handle,
+                    uuid,
+                    ..
+                } => {
+                    if let Some(extent) = current.take() {
+                        extent.end(&mut problems);
+                    }
+                    let next = self
+                        .attrs
+                        .range((Bound::Excluded(handle.clone()), Bound::Unbounded))
+                        .next();
+                    match next {
+                        Some((next, Attribute::CharacteristicValue { attr_type, .. }))
+                            if next == value_handle
+                                && attr_type.canonical() == uuid.canonical() => {}
+                        _ => problems.push(Inconsistency::ValueMismatch {
+                            declaration: handle.clone(),
+                            value_handle: value_handle.clone(),
+                        }),
+                    }
+                    current = Some(Extent {
+                        declaration: handle,
+                        properties: *properties,
+                        value_handle,
+                        value: false,
+                        client_configuration: false,
+                        server_configuration: false,
+                        extended_properties: false,
+                    });
+                }
+
+                Attribute::CharacteristicValue { .. } => match &mut current {
+                    Some(extent) if extent.value_handle == handle && !extent.value => {
+                        extent.value = true
+                    }
+                    _ => problems.push(Inconsistency::OrphanValue(handle.clone())),
+                },
+
+                descriptor => {
+                    let extent = match &mut current {
+                        Some(extent) if extent.value => extent,
+                        _ => {
+                            problems.push(Inconsistency::OrphanDescriptor(handle.clone()));
+                            continue;
+                        }
+                    };
+                    let seen = match descriptor {
+                        Attribute::ClientCharacteristicConfiguration { .. } => {
+                            &mut extent.client_configuration
+                        }
+                        Attribute::ServerCharacteristicConfiguration { .. } => {
+                            &mut extent.server_configuration
+                        }
+                        Attribute::CharacteristicExtendedProperties { .. } => {
+                            &mut extent.extended_properties
+                        }
+                        _ => continue,
+                    };
+                    if *seen {
+                        problems.push(Inconsistency::DuplicateDescriptor(handle.clone()));
+                    }
+                    *seen = true;
+                }
+            }
+        }
+        if let Some(extent) = current.take() {
+            extent.end(&mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(DatabaseInconsistency(problems))
+        }
+    }
+
+    /// Last handle of the group of the service declared at `handle`.
+    fn service_end(&self, handle: &Handle) -> Option<&Handle> {
+        match self.attrs.get(handle)? {
+            Attribute::Service { .. } => {}
+            _ => return None,
+        }
+        self.attrs
+            .range(handle.clone()..)
+            .take_while(|(h, attr)| *h == handle || !matches!(attr, Attribute::Service { .. }))
+            .last()
+            .map(|(h, _)| h)
+    }
+
+    /// Handles of Client / Server Characteristic Configuration descriptors.
+    pub(crate) fn configuration_handles(&self) -> impl Iterator<Item = &Handle> {
+        self.attrs.iter().filter_map(|(handle, attr)| match attr {
+            Attribute::ClientCharacteristicConfiguration { .. }
+            | Attribute::ServerCharacteristicConfiguration { .. } => Some(handle),
+            _ => None,
+        })
+    }
+
+    /// Declaration handle, properties, extended properties and type of the
+    /// characteristic whose value is `value_handle`.
+    pub(crate) fn characteristic_of(
+        &self,
+        value_handle: &Handle,
+    ) -> Option<(
+        &Handle,
+        CharacteristicProperties,
+        CharacteristicExtendedProperties,
+        &Uuid,
+    )> {
+        let (handle, properties, uuid) = match self.attrs.range(..value_handle.clone()).next_back()
+        {
+            Some((
+                handle,
+                Attribute::Characteristic {
+                    properties,
+                    value_handle: v,
+                    uuid,
+                    ..
+                },
+            )) if v == value_handle => (handle, *properties, uuid),
+            _ => return None,
+        };
+        let extended_properties = self
+            .descriptors_of(value_handle)
+            .find_map(|(_, attr)| match attr {
+                Attribute::CharacteristicExtendedProperties {
+                    extended_properties,
+                    ..
+                } => Some(*extended_properties),
+                _ => None,
+            })
+            .unwrap_or_else(CharacteristicExtendedProperties::empty);
+        Some((handle, properties, extended_properties, uuid))
+    }
+
+    /// Descriptors of the characteristic whose value is `value_handle`.
+    fn descriptors_of(&self, value_handle: &Handle) -> impl Iterator<Item = (&Handle, &Attribute)> {
+        use std::ops::Bound;
+
+        self.attrs
+            .range((Bound::Excluded(value_handle.clone()), Bound::Unbounded))
+            .take_while(|(_, attr)| {
+                !matches!(
+                    attr,
+                    Attribute::Service { .. } | Attribute::Characteristic { .. }
+                )
+            })
+    }
+
+    /// Client Characteristic Configuration descriptor of the characteristic
+    /// whose value is `value_handle`.
+    pub(crate) fn client_configuration_of(&self, value_handle: &Handle) -> Option<&Handle> {
+        self.descriptors_of(value_handle)
+            .find_map(|(handle, attr)| match attr {
+                Attribute::ClientCharacteristicConfiguration { .. } => Some(handle),
+                _ => None,
+            })
+    }
+
+    /// Type of the characteristic `handle` is the Client Characteristic
+    /// Configuration descriptor of.
+    pub(crate) fn client_configuration_owner(&self, handle: &Handle) -> Option<&Uuid> {
+        match self.attrs.get(handle)? {
+            Attribute::ClientCharacteristicConfiguration { .. } => {}
+            _ => return None,
+        }
+        self.attrs
+            .range(..handle.clone())
+            .rev()
+            .find_map(|(_, attr)| match attr {
+                Attribute::Characteristic { uuid, .. } => Some(Some(uuid)),
+                Attribute::Service { .. } => Some(None),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Client Characteristic Configuration bits the characteristic owning the
+    /// descriptor `handle` supports, i.e. its NOTIFY and INDICATE properties.
+    pub(crate) fn client_configuration_supported(
+        &self,
+        handle: &Handle,
+    ) -> Option<ClientCharacteristicConfiguration> {
+        match self.attrs.get(handle)? {
+            Attribute::ClientCharacteristicConfiguration { .. } => {}
+            _ => return None,
+        }
+        let properties = self
+            .attrs
+            .range(..handle.clone())
+            .rev()
+            .find_map(|(_, attr)| match attr {
+                Attribute::Characteristic { properties, .. } => Some(*properties),
+                Attribute::Service { .. } => Some(CharacteristicProperties::empty()),
+                _ => None,
+            })
+            .unwrap_or_else(CharacteristicProperties::empty);
+        let mut supported = ClientCharacteristicConfiguration::empty();
+        if properties.contains(CharacteristicProperties::NOTIFY) {
+            supported |= ClientCharacteristicConfiguration::NOTIFICATION;
+        }
+        if properties.contains(CharacteristicProperties::INDICATE) {
+            supported |= ClientCharacteristicConfiguration::INDICATION;
+        }
+        Some(supported)
+    }
+
+    /// Insert attribute, keeping the per type index up to date.
+    pub(crate) fn insert(&mut self, attr: Attribute) {
+        let handle = attr.handle().clone();
+        if let Some(old) = self.attrs.get(&handle) {
+            Self::unindex(&mut self.types, old.attr_type(), &handle);
+        }
+
+        let handles = self.types.entry(attr.attr_type().canonical()).or_default();
+        if let Err(pos) = handles.binary_search(&handle) {
+            handles.insert(pos, handle.clone());
+        }
+        self.attrs.insert(handle, attr);
+    }
+
+    /// Reject writes to `handle` unless the value has length `len`.
+    pub(crate) fn set_value_length(&mut self, handle: Handle, len: ValueLength) {
+        self.value_lengths.insert(handle, len);
+    }
+
+    pub(crate) fn set_generator(&mut self, handle: Handle, generator: Generator) {
+        self.generators.insert(handle, generator);
+    }
+
+    pub(crate) fn set_value_check(&mut self, handle: Handle, check: ValueCheck) {
+        self.value_checks.insert(handle, check);
+    }
+
+    /// Check of the values notified for `handle`, if any.
+    pub(crate) fn value_check(&self, handle: &Handle) -> Option<&ValueCheck> {
+        self.value_checks.get(handle)
+    }
+
+    /// Whether reads of `handle` produce a new value every time.
+    pub(crate) fn is_generated(&self, handle: &Handle) -> bool {
+        self.generators.contains_key(handle)
+    }
+
+    /// Value of `attr`, produced by its generator if any.
+    fn get(
+        &self,
+        attr: &Attribute,
+        authorized: bool,
+        authenticated: bool,
+    ) -> std::result::Result<Box<[u8]>, AttrError> {
+        let value = attr.get(authorized, authenticated)?;
+        Ok(match self.generators.get(attr.handle()) {
+            Some(Generator(generate)) => generate(),
+            None => value,
+        })
+    }
+
+    /// Remove attribute, keeping the per type index up to date.
+    #[cfg(test)]
+    pub(crate) fn remove(&mut self, handle: &Handle) -> Option<Attribute> {
+        let attr = self.attrs.remove(handle)?;
+        self.value_lengths.remove(handle);
+        Self::unindex(&mut self.types, attr.attr_type(), handle);
+        Some(attr)
+    }
+
+    fn unindex(types: &mut HashMap<Uuid, Vec<Handle>>, uuid: &Uuid, handle: &Handle) {
+        let uuid = uuid.canonical();
+        if let Some(handles) = types.get_mut(&uuid) {
+            if let Ok(pos) = handles.binary_search(handle) {
+                handles.remove(pos);
+            }
+            if handles.is_empty() {
+                types.remove(&uuid);
+            }
+        }
+    }
+
+    /// Handles of `uuid` typed attributes within `range`.
+    fn handles_of_type(&self, range: &RangeInclusive<Handle>, uuid: &Uuid) -> &[Handle] {
+        if let Some(handles) = self.types.get(&uuid.canonical()) {
+            let lo = handles.partition_point(|h| h < range.start());
+            let hi = handles.partition_point(|h| h <= range.end());
+            &handles[lo..hi]
+        } else {
+            &[]
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn read_by_group_type(
         &self,
@@ -26,51 +571,106 @@ impl Database {
     ) -> Result<Vec<(Handle, Handle, Box<[u8]>)>> {
         let start = range.start().clone();
 
-        if range.start() == &Handle::from(0x0000) || range.start() > range.end() {
+        if range.start() == &Handle::NULL || range.start() > range.end() {
             return Err((start, ErrorCode::InvalidHandle));
         }
 
+        // every entry in a response must have the same value length as the first one.
         let mut result = vec![];
-        let mut current = None as Option<(&Handle, Box<[u8]>)>;
-        let mut last = &Handle::from(0x0000);
-        let mut val_len = None;
+        let mut first_len = None;
+        let mut handles = self.handles_of_type(&range, uuid).iter().peekable();
 
-        for (key, val) in self.attrs.range(range) {
-            if val.attr_type() == uuid {
-                if let Some((start, val)) = current {
-                    val_len = Some(val.len());
-                    result.push((start.clone(), last.clone(), val))
+        while let Some(key) = handles.next() {
+            let b = match self.attrs[key].get(authorized, authenticated) {
+                Ok(b) => b,
+                Err(AttrError::PermissionDenied) => {
+                    return Err((key.clone(), ErrorCode::ReadNotPermitted))
                 }
-
-                let b = match val.get(authorized, authenticated) {
-                    Ok(b) => b,
-                    Err(AttrError::PermissionDenied) => {
-                        return Err((key.clone(), ErrorCode::ReadNotPermitted))
-                    }
-                    Err(AttrError::AuthorizationRequired) => {
-                        return Err((key.clone(), ErrorCode::InsufficientAuthorization))
-                    }
-                    Err(AttrError::AuthenticationRequired) => {
-                        return Err((key.clone(), ErrorCode::InsufficientAuthentication))
-                    }
-                    _ => unreachable!(),
-                };
-                if let Some(len) = val_len {
-                    if len != b.len() {
-                        return Ok(result);
-                    }
-                };
-                current = Some((key, b));
+                Err(AttrError::AuthorizationRequired) => {
+                    return Err((key.clone(), ErrorCode::InsufficientAuthorization))
+                }
+                Err(AttrError::AuthenticationRequired) => {
+                    return Err((key.clone(), ErrorCode::InsufficientAuthentication))
+                }
+                _ => unreachable!(),
+            };
+            match first_len {
+                None => first_len = Some(b.len()),
+                Some(len) if len != b.len() => break,
+                Some(_) => {}
             }
 
-            last = key;
+            // the group ends just before the next group starts.
+            let (last, _) = match handles.peek() {
+                Some(next) => self.attrs.range(..*next).next_back().unwrap(),
+                None => self.attrs.range(range.clone()).next_back().unwrap(),
+            };
+            result.push((key.clone(), last.clone(), b));
         }
 
-        if let Some((start, val)) = current {
-            result.push((start.clone(), last.clone(), val));
-            Ok(result)
+        if result.is_empty() {
+            Err((start, ErrorCode::AttributeNotFound))
         } else {
+            Ok(result)
+        }
+    }
+
+    /// Reference implementation of [`Database::read_by_group_type`] scanning every attribute.
+    ///
+    /// Kept for equivalence tests of the indexed path.
+    #[cfg(test)]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn read_by_group_type_scan(
+        &self,
+        range: RangeInclusive<Handle>,
+        uuid: &Uuid,
+        authorized: bool,
+        authenticated: bool,
+    ) -> Result<Vec<(Handle, Handle, Box<[u8]>)>> {
+        let start = range.start().clone();
+
+        if range.start() == &Handle::NULL || range.start() > range.end() {
+            return Err((start, ErrorCode::InvalidHandle));
+        }
+
+        let uuid = uuid.canonical();
+        let mut result = vec![] as Vec<(Handle, Handle, Box<[u8]>)>;
+        let mut first_len = None;
+
+        for (key, val) in self.attrs.range(range) {
+            if val.attr_type().canonical() != uuid {
+                // extend the current group.
+                if let Some((_, last, _)) = result.last_mut() {
+                    *last = key.clone();
+                }
+                continue;
+            }
+
+            let b = match val.get(authorized, authenticated) {
+                Ok(b) => b,
+                Err(AttrError::PermissionDenied) => {
+                    return Err((key.clone(), ErrorCode::ReadNotPermitted))
+                }
+                Err(AttrError::AuthorizationRequired) => {
+                    return Err((key.clone(), ErrorCode::InsufficientAuthorization))
+                }
+                Err(AttrError::AuthenticationRequired) => {
+                    return Err((key.clone(), ErrorCode::InsufficientAuthentication))
+                }
+                _ => unreachable!(),
+            };
+            match first_len {
+                None => first_len = Some(b.len()),
+                Some(len) if len != b.len() => break,
+                Some(_) => {}
+            }
+            result.push((key.clone(), key.clone(), b));
+        }
+
+        if result.is_empty() {
             Err((start, ErrorCode::AttributeNotFound))
+        } else {
+            Ok(result)
         }
     }
 
@@ -110,16 +710,62 @@ impl Database {
     ) -> Result<Vec<(Handle, Box<[u8]>)>> {
         let start = range.start().clone();
 
-        if range.start() == &Handle::from(0x0000) || range.start() > range.end() {
+        if range.start() == &Handle::NULL || range.start() > range.end() {
             return Err((start, ErrorCode::InvalidHandle));
         }
 
-        let result = self
+        let mut result = self
+            .handles_of_type(&range, uuid)
+            .iter()
+            .map(
+                |k| match self.get(&self.attrs[k], authorized, authenticated) {
+                    Ok(b) => Ok((k.clone(), b)),
+                    Err(AttrError::PermissionDenied) => {
+                        Err((k.clone(), ErrorCode::ReadNotPermitted))
+                    }
+                    Err(AttrError::AuthorizationRequired) => {
+                        Err((k.clone(), ErrorCode::InsufficientAuthorization))
+                    }
+                    Err(AttrError::AuthenticationRequired) => {
+                        Err((k.clone(), ErrorCode::InsufficientAuthentication))
+                    }
+                    _ => unreachable!(),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        same_length_prefix(&mut result);
+
+        if result.is_empty() {
+            Err((start, ErrorCode::AttributeNotFound))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Reference implementation of [`Database::read_by_type`] scanning every attribute.
+    ///
+    /// Kept for equivalence tests of the indexed path.
+    #[cfg(test)]
+    pub(crate) fn read_by_type_scan(
+        &self,
+        range: RangeInclusive<Handle>,
+        uuid: &Uuid,
+        authorized: bool,
+        authenticated: bool,
+    ) -> Result<Vec<(Handle, Box<[u8]>)>> {
+        let start = range.start().clone();
+
+        if range.start() == &Handle::NULL || range.start() > range.end() {
+            return Err((start, ErrorCode::InvalidHandle));
+        }
+
+        let uuid = uuid.canonical();
+        let mut result = self
             .attrs
             .range(range)
             .filter_map(|(k, v)| {
-                if v.attr_type() == uuid {
-                    match v.get(authorized, authenticated) {
+                if v.attr_type().canonical() == uuid {
+                    match self.get(v, authorized, authenticated) {
                         Ok(b) => Some(Ok((k.clone(), b))),
                         Err(AttrError::PermissionDenied) => {
                             Some(Err((k.clone(), ErrorCode::ReadNotPermitted)))
@@ -137,6 +783,7 @@ impl Database {
                 }
             })
             .collect::<Result<Vec<_>>>()?;
+        same_length_prefix(&mut result);
 
         if result.is_empty() {
             Err((start, ErrorCode::AttributeNotFound))
@@ -151,7 +798,7 @@ impl Database {
     ) -> Result<Vec<(Handle, Uuid)>> {
         let start = range.start().clone();
 
-        if range.start() == &Handle::from(0x0000) || range.start() > range.end() {
+        if range.start() == &Handle::NULL || range.start() > range.end() {
             return Err((start, ErrorCode::InvalidHandle));
         }
 
@@ -184,29 +831,73 @@ impl Database {
         authorized: bool,
         authenticated: bool,
     ) -> Result<Box<[u8]>> {
-        if handle == &0x0000.into() {
+        if handle == &Handle::NULL {
             return Err((handle.clone(), ErrorCode::InvalidHandle));
         }
 
         if let Some(v) = self.attrs.get(handle) {
-            match v.get(authorized, authenticated) {
-                Ok(v) => Ok(v),
-                Err(AttrError::PermissionDenied) => {
-                    Err((handle.clone(), ErrorCode::ReadNotPermitted))
-                }
-                Err(AttrError::AuthorizationRequired) => {
-                    Err((handle.clone(), ErrorCode::InsufficientAuthorization))
-                }
-                Err(AttrError::AuthenticationRequired) => {
-                    Err((handle.clone(), ErrorCode::InsufficientAuthentication))
-                }
-                _ => unreachable!(),
-            }
+            self.get(v, authorized, authenticated)
+                .map_err(|err| Self::read_error(handle, err))
         } else {
             Err((handle.clone(), ErrorCode::AttributeNotFound))
         }
     }
 
+    /// Read `handle` for a Read Request (`offset` 0, `ctx.blob_mtu` `None`)
+    /// or a Read Blob Request. The response carries `value[offset..]`.
+    ///
+    /// Errors take precedence in this order: the permission checks of
+    /// [`read`](Self::read), Invalid Offset for an `offset` past the end of
+    /// the value, then Attribute Not Long for a Read Blob Request at offset 0
+    /// of a value a Read Response carries whole. (3.4.4.5)
+    ///
+    /// `value` is served instead of the stored one once the permission checks
+    /// passed, without running the generator. (e.g. a per connection value)
+    pub(crate) fn read_at(
+        &self,
+        handle: &Handle,
+        offset: usize,
+        ctx: &ReadContext,
+        value: Option<Box<[u8]>>,
+    ) -> Result<Box<[u8]>> {
+        let value = match value {
+            Some(value) => {
+                if handle == &Handle::NULL {
+                    return Err((handle.clone(), ErrorCode::InvalidHandle));
+                }
+                let attr = self
+                    .attrs
+                    .get(handle)
+                    .ok_or((handle.clone(), ErrorCode::AttributeNotFound))?;
+                attr.get(ctx.authorized, ctx.authenticated)
+                    .map_err(|err| Self::read_error(handle, err))?;
+                value
+            }
+            None => self.read(handle, ctx.authorized, ctx.authenticated)?,
+        };
+
+        if offset > value.len() {
+            return Err((handle.clone(), ErrorCode::InvalidOffset));
+        }
+        if let Some(mtu) = ctx.blob_mtu {
+            // Read Response: opcode, then up to ATT_MTU - 1 octets of value.
+            if offset == 0 && value.len() < mtu {
+                return Err((handle.clone(), ErrorCode::AttributeNotLong));
+            }
+        }
+        Ok(value)
+    }
+
+    fn read_error(handle: &Handle, err: AttrError) -> (Handle, ErrorCode) {
+        let code = match err {
+            AttrError::PermissionDenied => ErrorCode::ReadNotPermitted,
+            AttrError::AuthorizationRequired => ErrorCode::InsufficientAuthorization,
+            AttrError::AuthenticationRequired => ErrorCode::InsufficientAuthentication,
+            _ => unreachable!(),
+        };
+        (handle.clone(), code)
+    }
+
     pub(crate) fn write(
         &mut self,
         handle: &Handle,
@@ -214,11 +905,24 @@ impl Database {
         authorized: bool,
         authenticated: bool,
     ) -> Result<()> {
-        if handle == &0x0000.into() {
+        if handle == &Handle::NULL {
             return Err((handle.clone(), ErrorCode::InvalidHandle));
         }
 
+        if let (Some(supported), [lo, hi]) = (self.client_configuration_supported(handle), val) {
+            let bits = u16::from_le_bytes([*lo, *hi]);
+            if bits & !supported.bits() != 0 {
+                return Err((handle.clone(), CCCD_IMPROPERLY_CONFIGURED));
+            }
+        }
+
         if let Some(v) = self.attrs.get_mut(handle) {
+            let length = self.value_lengths.get(handle);
+            if v.permission().contains(Permission::WRITEABLE)
+                && matches!(length, Some(len) if !len.accepts(val.len()))
+            {
+                return Err((handle.clone(), ErrorCode::InvalidAttributeValueLength));
+            }
             match v.set(val, authorized, authenticated) {
                 Ok(_) => Ok(()),
                 Err(AttrError::PermissionDenied) => {
@@ -242,18 +946,28 @@ impl Database {
 
 impl FromIterator<Attribute> for Database {
     fn from_iter<T: IntoIterator<Item = Attribute>>(iter: T) -> Self {
-        Self {
-            attrs: iter.into_iter().map(|a| (a.handle().clone(), a)).collect(),
+        let mut db = Self::new();
+        for attr in iter {
+            db.insert(attr);
         }
+        db
+    }
+}
+
+/// Keep entries up to the first one with a value length different from the
+/// first entry. Entries of a response must have the same length.
+fn same_length_prefix(result: &mut Vec<(Handle, Box<[u8]>)>) {
+    if let Some((_, first)) = result.first() {
+        let len = first.len();
+        let same = result.iter().take_while(|(_, v)| v.len() == len).count();
+        result.truncate(same);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::attribute::{
-        CharacteristicProperties, ClientCharacteristicConfiguration, Permission,
-    };
+    use crate::types::{CharacteristicProperties, ClientCharacteristicConfiguration};
 
     #[test]
     fn test_read_by_group_type() {
@@ -261,7 +975,7 @@ mod tests {
 
         let result = db
             .read_by_group_type(
-                0x0001.into()..=0xFFFF.into(),
+                Handle::MIN..=Handle::MAX,
                 &Uuid::new_uuid16(0x2800),
                 false,
                 false,
@@ -278,7 +992,7 @@ mod tests {
 
         let result = db
             .read_by_group_type(
-                0x0017.into()..=0xFFFF.into(),
+                0x0017.into()..=Handle::MAX,
                 &Uuid::new_uuid16(0x2800),
                 false,
                 false,
@@ -299,7 +1013,7 @@ mod tests {
 
         let result = db
             .read_by_group_type(
-                0x0021.into()..=0xFFFF.into(),
+                0x0021.into()..=Handle::MAX,
                 &Uuid::new_uuid16(0x2800),
                 false,
                 false,
@@ -312,7 +1026,7 @@ mod tests {
 
         let result = db
             .read_by_group_type(
-                0x0028.into()..=0xFFFF.into(),
+                0x0028.into()..=Handle::MAX,
                 &Uuid::new_uuid16(0x2800),
                 false,
                 false,
@@ -347,9 +1061,9 @@ mod tests {
 
         let result = db
             .find_by_type_value(
-                0x0001.into()..=0xFFFF.into(),
+                Handle::MIN..=Handle::MAX,
                 &Uuid16::new(0x2800),
-                &vec![0x01, 0x18],
+                &[0x01, 0x18],
                 false,
                 false,
             )
@@ -357,9 +1071,9 @@ mod tests {
         assert_eq!(&result, &[(0x000C.into(), 0x000F.into())]);
         let result = db
             .find_by_type_value(
-                0x0010.into()..=0xFFFF.into(),
+                0x0010.into()..=Handle::MAX,
                 &Uuid16::new(0x2800),
-                &vec![0x01, 0x18],
+                &[0x01, 0x18],
                 false,
                 false,
             )
@@ -373,7 +1087,7 @@ mod tests {
 
         let result = db
             .read_by_type(
-                0x0001.into()..=0x000b.into(),
+                Handle::MIN..=0x000b.into(),
                 &Uuid::new_uuid16(0x2802),
                 false,
                 false,
@@ -383,7 +1097,7 @@ mod tests {
 
         let result = db
             .read_by_type(
-                0x0001.into()..=0x000b.into(),
+                Handle::MIN..=0x000b.into(),
                 &Uuid::new_uuid16(0x2803),
                 false,
                 false,
@@ -428,6 +1142,29 @@ mod tests {
         assert_eq!(result, (0x0000.into(), ErrorCode::InvalidHandle));
     }
 
+    #[test]
+    fn test_read_by_type_mixed_length() {
+        let db: Database = vec![
+            Attribute::new_primary_service(0x0001.into(), Uuid::new_uuid16(0x1800)),
+            Attribute::new_primary_service(0x0002.into(), Uuid::new_uuid128(0x1234)),
+            Attribute::new_primary_service(0x0003.into(), Uuid::new_uuid16(0x1801)),
+        ]
+        .into_iter()
+        .collect();
+
+        let range = Handle::MIN..=Handle::MAX;
+        let uuid = Uuid::new_uuid16(0x2800);
+        let expected = [(0x0001.into(), vec![0x00, 0x18].into())];
+        assert_eq!(
+            db.read_by_type(range.clone(), &uuid, false, false).unwrap(),
+            expected
+        );
+        assert_eq!(
+            db.read_by_type_scan(range, &uuid, false, false).unwrap(),
+            expected
+        );
+    }
+
     #[test]
     fn test_find_information() {
         let db = example_db();
@@ -471,18 +1208,443 @@ mod tests {
         assert_eq!(result, (0x0000.into(), ErrorCode::InvalidHandle));
     }
 
+    #[test]
+    fn test_read_at_precedence() {
+        let db: Database = vec![
+            Attribute::new_primary_service(0x0001.into(), Uuid::new_uuid16(0x1800)),
+            Attribute::new_characteristic(
+                0x0002.into(),
+                CharacteristicProperties::READ,
+                0x0003.into(),
+                Uuid::new_uuid16(0x2A00),
+            ),
+            Attribute::new_characteristic_value(
+                0x0003.into(),
+                Uuid::new_uuid16(0x2A00),
+                b"abc".as_ref().into(),
+                Permission::READABLE | Permission::AUTHENTICATION_REQUIRED,
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let ctx = |authenticated, blob_mtu| ReadContext {
+            authorized: false,
+            authenticated,
+            blob_mtu,
+        };
+        let handle = Handle::new(0x0003);
+
+        // unauthenticated + bad offset
+        let result = db.read_at(&handle, 10, &ctx(false, Some(23)), None);
+        assert_eq!(
+            result.unwrap_err(),
+            (handle.clone(), ErrorCode::InsufficientAuthentication)
+        );
+        // a per connection value is checked the same way
+        let result = db.read_at(&handle, 0, &ctx(false, None), Some([0; 2].into()));
+        assert_eq!(
+            result.unwrap_err(),
+            (handle.clone(), ErrorCode::InsufficientAuthentication)
+        );
+
+        // authenticated + offset beyond len
+        let result = db.read_at(&handle, 4, &ctx(true, Some(23)), None);
+        assert_eq!(
+            result.unwrap_err(),
+            (handle.clone(), ErrorCode::InvalidOffset)
+        );
+
+        // authenticated + value fits in ATT_MTU - 1 + offset 0 blob
+        let result = db.read_at(&handle, 0, &ctx(true, Some(23)), None);
+        assert_eq!(
+            result.unwrap_err(),
+            (handle.clone(), ErrorCode::AttributeNotLong)
+        );
+
+        // a Read Request and blob reads past offset 0 are served
+        let result = db.read_at(&handle, 0, &ctx(true, None), None);
+        assert_eq!(&*result.unwrap(), b"abc");
+        let result = db.read_at(&handle, 3, &ctx(true, Some(23)), None);
+        assert_eq!(&*result.unwrap(), b"abc");
+    }
+
     #[test]
     fn test_write() {
         let mut db = example_db();
 
-        let _result = db
-            .write(&0x000F.into(), &vec![0x00, 0x00], false, false)
+        db.write(&0x000F.into(), &[0x00, 0x00], false, false)
             .unwrap();
 
-        let result = db.write(&0x0000.into(), &vec![], false, false).unwrap_err();
+        let result = db.write(&0x0000.into(), &[], false, false).unwrap_err();
         assert_eq!(result, (0x0000.into(), ErrorCode::InvalidHandle));
     }
 
+    #[test]
+    fn test_index_equivalence() {
+        let uuids = [
+            Uuid::new_uuid16(0x2800),
+            Uuid::new_uuid16(0x2803),
+            Uuid::new_uuid16(0x2902),
+            Uuid::new_uuid16(0x2A19),
+            Uuid::new_uuid128(0x1234),
+            Uuid::new_uuid128(0xFFFF_0001),
+            Uuid::new_uuid16(0xFFFF),
+        ];
+
+        for db in &[example_db(), large_db(30)] {
+            let last = db.attrs.keys().next_back().unwrap().as_u16();
+            for start in (0x0000..=last + 1).step_by(3) {
+                for end in [start, start + 1, start + 7, last, 0xFFFF] {
+                    for uuid in &uuids {
+                        let range = Handle::from(start)..=Handle::from(end);
+                        assert_eq!(
+                            db.read_by_type(range.clone(), uuid, false, false),
+                            db.read_by_type_scan(range.clone(), uuid, false, false),
+                        );
+                        assert_eq!(
+                            db.read_by_group_type(range.clone(), uuid, false, false),
+                            db.read_by_group_type_scan(range, uuid, false, false),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_update() {
+        let mut db = example_db();
+
+        let removed = db.remove(&0x0020.into()).unwrap();
+        assert_eq!(removed.handle(), &Handle::from(0x0020));
+        assert!(!db.types.contains_key(&Uuid::new_uuid128(0x1234)));
+        let result = db
+            .read_by_group_type(
+                0x0017.into()..=Handle::MAX,
+                &Uuid::new_uuid16(0x2800),
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            &result,
+            &[(0x0023.into(), 0x0027.into(), vec![0x0F, 0x18].into())]
+        );
+
+        db.insert(Attribute::new_primary_service(
+            0x0018.into(),
+            Uuid::new_uuid16(0x1802),
+        ));
+        let result = db
+            .read_by_group_type(
+                0x0017.into()..=Handle::MAX,
+                &Uuid::new_uuid16(0x2800),
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            &result,
+            &[
+                (0x0018.into(), 0x0018.into(), vec![0x02, 0x18].into()),
+                (0x0023.into(), 0x0027.into(), vec![0x0F, 0x18].into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_by_group_type_mixed_uuid_length() {
+        // [16bit, 128bit, 16bit] services.
+        let db = vec![
+            Attribute::new_primary_service(0x0001.into(), Uuid::new_uuid16(0x1800)),
+            Attribute::new_characteristic(
+                0x0002.into(),
+                CharacteristicProperties::READ,
+                0x0003.into(),
+                Uuid::new_uuid16(0x2A00),
+            ),
+            Attribute::new_characteristic_value(
+                0x0003.into(),
+                Uuid::new_uuid16(0x2A00),
+                [].into(),
+                Permission::READABLE,
+            ),
+            Attribute::new_primary_service(0x0004.into(), Uuid::new_uuid128(0x1234)),
+            Attribute::new_primary_service(0x0005.into(), Uuid::new_uuid16(0x180F)),
+            Attribute::new_characteristic(
+                0x0006.into(),
+                CharacteristicProperties::READ,
+                0x0007.into(),
+                Uuid::new_uuid16(0x2A19),
+            ),
+            Attribute::new_characteristic_value(
+                0x0007.into(),
+                Uuid::new_uuid16(0x2A19),
+                [].into(),
+                Permission::READABLE,
+            ),
+        ]
+        .into_iter()
+        .collect::<Database>();
+        let uuid128 = db.attrs[&0x0004.into()].get(false, false).unwrap();
+        assert_eq!(uuid128.len(), 16);
+
+        for read in &[
+            Database::read_by_group_type,
+            Database::read_by_group_type_scan,
+        ] {
+            let primary = Uuid::new_uuid16(0x2800);
+            let result = read(&db, Handle::MIN..=Handle::MAX, &primary, false, false);
+            assert_eq!(
+                result.unwrap(),
+                vec![(0x0001.into(), 0x0003.into(), [0x00, 0x18].into())]
+            );
+
+            let result = read(&db, 0x0004.into()..=Handle::MAX, &primary, false, false);
+            assert_eq!(
+                result.unwrap(),
+                vec![(0x0004.into(), 0x0004.into(), uuid128.clone())]
+            );
+
+            let result = read(&db, 0x0005.into()..=Handle::MAX, &primary, false, false);
+            assert_eq!(
+                result.unwrap(),
+                vec![(0x0005.into(), 0x0007.into(), [0x0F, 0x18].into())]
+            );
+
+            let result = read(&db, 0x0008.into()..=Handle::MAX, &primary, false, false);
+            assert_eq!(result, Err((0x0008.into(), ErrorCode::AttributeNotFound)));
+        }
+    }
+
+    #[test]
+    fn test_inspect() {
+        let db = example_db();
+        assert!(!db.is_empty());
+        assert_eq!(db.len(), db.iter().count());
+
+        let handles = db.iter().map(|(h, _)| h.clone()).collect::<Vec<_>>();
+        let mut sorted = handles.clone();
+        sorted.sort();
+        assert_eq!(handles, sorted);
+
+        assert_eq!(
+            db.attribute_type(&0x0001.into()),
+            Some(&Uuid::new_uuid16(0x2800))
+        );
+        assert_eq!(db.attribute_type(&Handle::MAX), None);
+    }
+
+    /// `n` services with a 16bit and a 128bit notifiable characteristic each. (10 attributes per service)
+    fn large_db(n: u16) -> Database {
+        let mut attrs = vec![];
+        for i in 0..n {
+            let base = 1 + i * 10;
+            let service = if i % 3 == 2 {
+                Uuid::new_uuid128(0xFFFF_0000 + i as u128)
+            } else {
+                Uuid::new_uuid16(0x1800 + i)
+            };
+            attrs.push(Attribute::new_primary_service(base.into(), service));
+            for (n, uuid) in [
+                (1, Uuid::new_uuid16(0x2A19)),
+                (5, Uuid::new_uuid128(0xFFFF_0001)),
+            ] {
+                attrs.push(Attribute::new_characteristic(
+                    (base + n).into(),
+                    CharacteristicProperties::NOTIFY | CharacteristicProperties::READ,
+                    (base + n + 1).into(),
+                    uuid.clone(),
+                ));
+                attrs.push(Attribute::new_characteristic_value(
+                    (base + n + 1).into(),
+                    uuid,
+                    vec![i as u8; (i % 4) as usize].into(),
+                    Permission::READABLE,
+                ));
+                attrs.push(Attribute::new_client_characteristic_configuration(
+                    (base + n + 2).into(),
+                    ClientCharacteristicConfiguration::empty(),
+                    Permission::READABLE | Permission::WRITEABLE,
+                ));
+                attrs.push(Attribute::new_characteristic_presentation_format(
+                    (base + n + 3).into(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ));
+            }
+        }
+        attrs.into_iter().collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        use crate::types::{CharacteristicExtendedProperties, ServerCharacteristicConfiguration};
+
+        example_db().validate().unwrap();
+
+        let service = |h: u16| Attribute::new_primary_service(h.into(), Uuid::new_uuid16(0x1800));
+        let decl = |h: u16, properties, value: u16| {
+            Attribute::new_characteristic(
+                h.into(),
+                properties,
+                value.into(),
+                Uuid::new_uuid16(0x2A00),
+            )
+        };
+        let value = |h: u16, uuid: u16| {
+            Attribute::new_characteristic_value(
+                h.into(),
+                Uuid::new_uuid16(uuid),
+                [].into(),
+                Permission::READABLE,
+            )
+        };
+        let cccd = |h: u16| {
+            Attribute::new_client_characteristic_configuration(
+                h.into(),
+                ClientCharacteristicConfiguration::empty(),
+                Permission::READABLE | Permission::WRITEABLE,
+            )
+        };
+        let scc = |h: u16| {
+            Attribute::new_server_characteristic_configuration(
+                h.into(),
+                ServerCharacteristicConfiguration::empty(),
+                Permission::READABLE | Permission::WRITEABLE,
+            )
+        };
+        let cep = |h: u16| {
+            Attribute::new_characteristic_extended_properties(
+                h.into(),
+                CharacteristicExtendedProperties::RELIABLE_WRITE,
+            )
+        };
+        let problems = |attrs: Vec<Attribute>| {
+            attrs
+                .into_iter()
+                .collect::<Database>()
+                .validate()
+                .unwrap_err()
+                .0
+        };
+        let none = CharacteristicProperties::empty();
+
+        assert_eq!(
+            problems(vec![decl(1, none, 2), value(2, 0x2A00), service(3)]),
+            [
+                Inconsistency::OutsideService(1.into()),
+                Inconsistency::OutsideService(2.into()),
+            ]
+        );
+        assert_eq!(
+            problems(vec![service(1), decl(2, none, 4), value(3, 0x2A00)]),
+            [
+                Inconsistency::ValueMismatch {
+                    declaration: 2.into(),
+                    value_handle: 4.into(),
+                },
+                Inconsistency::OrphanValue(3.into()),
+            ]
+        );
+        assert_eq!(
+            problems(vec![service(1), decl(2, none, 3), value(3, 0x2A01)]),
+            [Inconsistency::ValueMismatch {
+                declaration: 2.into(),
+                value_handle: 3.into(),
+            }]
+        );
+        assert_eq!(
+            problems(vec![service(1), value(2, 0x2A00)]),
+            [Inconsistency::OrphanValue(2.into())]
+        );
+        assert_eq!(
+            problems(vec![service(1), cccd(2)]),
+            [Inconsistency::OrphanDescriptor(2.into())]
+        );
+        assert_eq!(
+            problems(vec![
+                service(1),
+                decl(2, CharacteristicProperties::NOTIFY, 3),
+                value(3, 0x2A00),
+                cccd(4),
+                cccd(5),
+            ]),
+            [Inconsistency::DuplicateDescriptor(5.into())]
+        );
+        assert_eq!(
+            problems(vec![
+                service(1),
+                Attribute::new_include(2.into(), 5.into(), 5.into(), Uuid::new_uuid16(0x1800)),
+                Attribute::new_include(3.into(), 6.into(), 6.into(), Uuid::new_uuid16(0x1800)),
+                Attribute::new_include(4.into(), 7.into(), 7.into(), Uuid::new_uuid16(0x1800)),
+                service(5),
+                service(7),
+                decl(8, none, 9),
+                value(9, 0x2A00),
+            ]),
+            [
+                Inconsistency::IncludeMismatch {
+                    handle: 3.into(),
+                    included_service_handle: 6.into(),
+                },
+                Inconsistency::IncludeMismatch {
+                    handle: 4.into(),
+                    included_service_handle: 7.into(),
+                },
+            ]
+        );
+
+        let all = CharacteristicProperties::NOTIFY
+            | CharacteristicProperties::BROADCAST
+            | CharacteristicProperties::EXTENDED_PROPERTIES;
+        assert_eq!(
+            problems(vec![service(1), decl(2, all, 3), value(3, 0x2A00)]),
+            [
+                Inconsistency::PropertiesMismatch {
+                    declaration: 2.into(),
+                    properties: CharacteristicProperties::NOTIFY
+                        | CharacteristicProperties::INDICATE,
+                },
+                Inconsistency::PropertiesMismatch {
+                    declaration: 2.into(),
+                    properties: CharacteristicProperties::BROADCAST,
+                },
+                Inconsistency::PropertiesMismatch {
+                    declaration: 2.into(),
+                    properties: CharacteristicProperties::EXTENDED_PROPERTIES,
+                },
+            ]
+        );
+        assert_eq!(
+            problems(vec![
+                service(1),
+                decl(2, none, 3),
+                value(3, 0x2A00),
+                cep(4),
+                cccd(5),
+                scc(6),
+            ])
+            .len(),
+            3
+        );
+        vec![
+            service(1),
+            decl(2, all, 3),
+            value(3, 0x2A00),
+            cep(4),
+            cccd(5),
+            scc(6),
+        ]
+        .into_iter()
+        .collect::<Database>()
+        .validate()
+        .unwrap();
+    }
+
     fn example_db() -> Database {
         vec![
             Attribute::new_primary_service(0x0001.into(), Uuid::new_uuid16(0x1800)),
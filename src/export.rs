@@ -0,0 +1,414 @@
+//! Handle annotated JSON description of an attribute table.
+//!
+//! See [`Database::export_json`] for the schema.
+use std::fmt::Write;
+
+use att::{Handle, Uuid};
+
+use crate::attribute::Attribute;
+use crate::database::Database;
+use crate::registration::Registration;
+use crate::types::{CharacteristicExtendedProperties, CharacteristicProperties, Permission};
+
+/// Version of the schema. Bumped only if a field changes or goes away.
+const SCHEMA_VERSION: u32 = 1;
+
+/// Flags of `org.bluez.GattCharacteristic1`.
+const PROPERTY_NAMES: [(CharacteristicProperties, &str); 8] = [
+    (CharacteristicProperties::BROADCAST, "broadcast"),
+    (CharacteristicProperties::READ, "read"),
+    (
+        CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+        "write-without-response",
+    ),
+    (CharacteristicProperties::WRITE, "write"),
+    (CharacteristicProperties::NOTIFY, "notify"),
+    (CharacteristicProperties::INDICATE, "indicate"),
+    (
+        CharacteristicProperties::AUTHENTICATED_SIGNED_WRITES,
+        "authenticated-signed-writes",
+    ),
+    (
+        CharacteristicProperties::EXTENDED_PROPERTIES,
+        "extended-properties",
+    ),
+];
+
+const EXTENDED_PROPERTY_NAMES: [(CharacteristicExtendedProperties, &str); 2] = [
+    (
+        CharacteristicExtendedProperties::RELIABLE_WRITE,
+        "reliable-write",
+    ),
+    (
+        CharacteristicExtendedProperties::WRITABLE_AUXILIARIES,
+        "writable-auxiliaries",
+    ),
+];
+
+const PERMISSION_NAMES: [(Permission, &str); 4] = [
+    (Permission::READABLE, "read"),
+    (Permission::WRITEABLE, "write"),
+    (Permission::AUTHORIZATION_REQUIRED, "authorization-required"),
+    (
+        Permission::AUTHENTICATION_REQUIRED,
+        "authentication-required",
+    ),
+];
+
+struct Service<'a> {
+    handle: &'a Handle,
+    end_handle: &'a Handle,
+    uuid: &'a Uuid,
+    primary: bool,
+    includes: Vec<Include<'a>>,
+    characteristics: Vec<Characteristic<'a>>,
+}
+
+struct Include<'a> {
+    handle: &'a Handle,
+    service_handle: &'a Handle,
+    end_handle: &'a Handle,
+    uuid: &'a Uuid,
+}
+
+struct Characteristic<'a> {
+    handle: &'a Handle,
+    value_handle: &'a Handle,
+    uuid: &'a Uuid,
+    properties: Vec<&'static str>,
+    permissions: Vec<&'static str>,
+    descriptors: Vec<Descriptor<'a>>,
+}
+
+struct Descriptor<'a> {
+    handle: &'a Handle,
+    uuid: &'a Uuid,
+    permissions: Vec<&'static str>,
+}
+
+fn names<F>(table: &[(F, &'static str)], contains: impl Fn(&F) -> bool) -> Vec<&'static str> {
+    table
+        .iter()
+        .filter(|(flag, _)| contains(flag))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn permissions(permission: Permission) -> Vec<&'static str> {
+    names(&PERMISSION_NAMES, |flag| permission.contains(*flag))
+}
+
+/// Group attributes in handle order into services. Attributes outside of a
+/// service or characteristic they belong to are left out.
+fn group<'a, I>(attrs: I) -> Vec<Service<'a>>
+where
+    I: IntoIterator<Item = &'a Attribute>,
+{
+    let mut services = Vec::<Service>::new();
+    for attr in attrs {
+        if let Attribute::Service {
+            handle,
+            primary,
+            uuid,
+        } = attr
+        {
+            services.push(Service {
+                handle,
+                end_handle: handle,
+                uuid,
+                primary: *primary,
+                includes: vec![],
+                characteristics: vec![],
+            });
+            continue;
+        }
+        let service = match services.last_mut() {
+            Some(service) => service,
+            None => continue,
+        };
+        service.end_handle = attr.handle();
+
+        match attr {
+            Attribute::Include {
+                handle,
+                included_service_handle,
+                end_group_handle,
+                uuid,
+            } => service.includes.push(Include {
+                handle,
+                service_handle: included_service_handle,
+                end_handle: end_group_handle,
+                uuid,
+            }),
+            Attribute::Characteristic {
+                handle,
+                properties,
+                value_handle,
+                uuid,
+            } => service.characteristics.push(Characteristic {
+                handle,
+                value_handle,
+                uuid,
+                properties: names(&PROPERTY_NAMES, |flag| properties.contains(*flag)),
+                permissions: vec![],
+                descriptors: vec![],
+            }),
+            attr => {
+                let characteristic = match service.characteristics.last_mut() {
+                    Some(characteristic) => characteristic,
+                    None => continue,
+                };
+                match attr {
+                    Attribute::CharacteristicValue { handle, .. }
+                        if handle == characteristic.value_handle =>
+                    {
+                        characteristic.permissions = permissions(attr.permission());
+                        continue;
+                    }
+                    Attribute::CharacteristicValue { .. } => continue,
+                    Attribute::CharacteristicExtendedProperties {
+                        extended_properties,
+                        ..
+                    } => characteristic
+                        .properties
+                        .extend(names(&EXTENDED_PROPERTY_NAMES, |flag| {
+                            extended_properties.contains(*flag)
+                        })),
+                    _ => {}
+                }
+                characteristic.descriptors.push(Descriptor {
+                    handle: attr.handle(),
+                    uuid: attr.attr_type(),
+                    permissions: permissions(attr.permission()),
+                });
+            }
+        }
+    }
+    services
+}
+
+/// 128bit form, e.g. `00002a00-0000-1000-8000-00805f9b34fb`, as BlueZ shows UUIDs.
+fn uuid_string(uuid: &Uuid) -> String {
+    match uuid {
+        Uuid::Uuid16(uuid) => format!("{:08x}-0000-1000-8000-00805f9b34fb", uuid.as_u16()),
+        Uuid::Uuid128(uuid) => uuid.to_string(),
+    }
+}
+
+fn string_array(names: &[&str]) -> String {
+    let names = names
+        .iter()
+        .map(|name| format!("\"{}\"", name))
+        .collect::<Vec<_>>();
+    format!("[{}]", names.join(", "))
+}
+
+/// Objects of `items`, each written by `write_item` at `indent` + 1.
+fn object_array<T, F>(out: &mut String, indent: &str, items: &[T], mut write_item: F)
+where
+    F: FnMut(&mut String, &str, &T),
+{
+    if items.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+    let inner = format!("{}  ", indent);
+    out.push_str("[\n");
+    for (n, item) in items.iter().enumerate() {
+        if n > 0 {
+            out.push_str(",\n");
+        }
+        out.push_str(&inner);
+        out.push_str("{\n");
+        write_item(out, &format!("{}  ", inner), item);
+        out.push('\n');
+        out.push_str(&inner);
+        out.push('}');
+    }
+    out.push('\n');
+    out.push_str(indent);
+    out.push(']');
+}
+
+fn to_json(services: &[Service<'_>]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{{\n  \"version\": {},", SCHEMA_VERSION).unwrap();
+    out.push_str("  \"services\": ");
+    object_array(&mut out, "  ", services, |out, i, service| {
+        writeln!(out, "{}\"handle\": {},", i, service.handle.as_u16()).unwrap();
+        writeln!(out, "{}\"end_handle\": {},", i, service.end_handle.as_u16()).unwrap();
+        writeln!(out, "{}\"uuid\": \"{}\",", i, uuid_string(service.uuid)).unwrap();
+        writeln!(out, "{}\"primary\": {},", i, service.primary).unwrap();
+        write!(out, "{}\"includes\": ", i).unwrap();
+        object_array(out, i, &service.includes, |out, i, include| {
+            writeln!(out, "{}\"handle\": {},", i, include.handle.as_u16()).unwrap();
+            writeln!(
+                out,
+                "{}\"service_handle\": {},",
+                i,
+                include.service_handle.as_u16()
+            )
+            .unwrap();
+            writeln!(out, "{}\"end_handle\": {},", i, include.end_handle.as_u16()).unwrap();
+            write!(out, "{}\"uuid\": \"{}\"", i, uuid_string(include.uuid)).unwrap();
+        });
+        write!(out, ",\n{}\"characteristics\": ", i).unwrap();
+        object_array(out, i, &service.characteristics, |out, i, ch| {
+            writeln!(out, "{}\"handle\": {},", i, ch.handle.as_u16()).unwrap();
+            writeln!(out, "{}\"value_handle\": {},", i, ch.value_handle.as_u16()).unwrap();
+            writeln!(out, "{}\"uuid\": \"{}\",", i, uuid_string(ch.uuid)).unwrap();
+            writeln!(
+                out,
+                "{}\"properties\": {},",
+                i,
+                string_array(&ch.properties)
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{}\"permissions\": {},",
+                i,
+                string_array(&ch.permissions)
+            )
+            .unwrap();
+            write!(out, "{}\"descriptors\": ", i).unwrap();
+            object_array(out, i, &ch.descriptors, |out, i, descriptor| {
+                writeln!(out, "{}\"handle\": {},", i, descriptor.handle.as_u16()).unwrap();
+                writeln!(out, "{}\"uuid\": \"{}\",", i, uuid_string(descriptor.uuid)).unwrap();
+                write!(
+                    out,
+                    "{}\"permissions\": {}",
+                    i,
+                    string_array(&descriptor.permissions)
+                )
+                .unwrap();
+            });
+        });
+    });
+    out.push_str("\n}\n");
+    out
+}
+
+impl Database {
+    /// Describe the table as JSON, with the handles assigned to every
+    /// attribute. (e.g. to generate tests run from a BlueZ central)
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "services": [
+    ///     {
+    ///       "handle": 1,
+    ///       "end_handle": 4,
+    ///       "uuid": "0000180f-0000-1000-8000-00805f9b34fb",
+    ///       "primary": true,
+    ///       "includes": [],
+    ///       "characteristics": [
+    ///         {
+    ///           "handle": 2,
+    ///           "value_handle": 3,
+    ///           "uuid": "00002a19-0000-1000-8000-00805f9b34fb",
+    ///           "properties": ["read", "notify"],
+    ///           "permissions": ["read"],
+    ///           "descriptors": [
+    ///             {
+    ///               "handle": 4,
+    ///               "uuid": "00002902-0000-1000-8000-00805f9b34fb",
+    ///               "permissions": ["read", "write"]
+    ///             }
+    ///           ]
+    ///         }
+    ///       ]
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// - Handles are numbers. UUIDs are lower case 128bit strings, as BlueZ
+    ///   shows them.
+    /// - `end_handle` of a service is its last attribute.
+    /// - `includes` have `handle`, `service_handle`, `end_handle` and `uuid`
+    ///   of the included service.
+    /// - `handle` of a characteristic is its declaration. `properties` are
+    ///   the `Flags` names of `org.bluez.GattCharacteristic1`, those of the
+    ///   Extended Properties descriptor included. `permissions` of the value
+    ///   and of descriptors are some of `read`, `write`,
+    ///   `authorization-required` and `authentication-required`.
+    /// - `descriptors` list every attribute after the value, the Extended
+    ///   Properties and Client Characteristic Configuration included.
+    ///
+    /// Within a `version`, fields are only added, never changed or removed.
+    /// Objects and arrays are in handle order and the output is stable for
+    /// a given table.
+    pub fn export_json(&self) -> String {
+        to_json(&group(self.attributes()))
+    }
+}
+
+impl<T> Registration<T> {
+    /// [`Database::export_json`] of the attributes added so far.
+    ///
+    /// The Aggregate Format of the last characteristic is added on build, so
+    /// it is not in here yet.
+    pub fn export_json(&self) -> String {
+        to_json(&group(self.iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CharacteristicProperties;
+
+    #[test]
+    fn test_export_json() {
+        let mut registration = Registration::<()>::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x180F));
+        registration.add_characteristic(
+            Uuid::new_uuid16(0x2A19),
+            [100],
+            CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+        );
+        registration
+            .add_primary_service(Uuid::new_uuid128(0x6E40_0001_B5A3_F393_E0A9_E50E_24DC_CA9E));
+        registration.add_characteristic(
+            Uuid::new_uuid16(0x2A00),
+            "",
+            CharacteristicProperties::WRITE | CharacteristicProperties::RELIABLE_WRITE,
+        );
+        registration.add_presentation_format(0x04, 0, 0x27AD, 0x01, 0x0001);
+        registration.add_presentation_format(0x04, 0, 0x27AD, 0x01, 0x0002);
+
+        let exported = registration.export_json();
+        let (db, _, _) = registration.build();
+        let json = db.export_json();
+        assert_ne!(exported, json);
+
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(json["version"], 1);
+        let services = json["services"].as_array().unwrap();
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0]["end_handle"], 4);
+        assert_eq!(services[1]["uuid"], "6e400001-b5a3-f393-e0a9-e50e24dcca9e");
+        let characteristic = &services[1]["characteristics"][0];
+        assert_eq!(
+            characteristic["properties"],
+            serde_json::json!(["write", "extended-properties", "reliable-write"])
+        );
+        let descriptors = characteristic["descriptors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| (d["handle"].as_u64().unwrap(), d["uuid"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptors,
+            [
+                (8, "00002900-0000-1000-8000-00805f9b34fb"),
+                (9, "00002904-0000-1000-8000-00805f9b34fb"),
+                (10, "00002904-0000-1000-8000-00805f9b34fb"),
+                (11, "00002905-0000-1000-8000-00805f9b34fb"),
+            ]
+        );
+    }
+}
@@ -27,31 +27,31 @@
 //!         "abc",
 //!         CharacteristicProperties::WRITE,
 //!     );
-//!     registration.add_characteristic(
+//!     registration.add_characteristic_with_initial_value(
 //!         ch::APPEARANCE,
 //!         0x03c0u16.to_le_bytes().to_vec(),
 //!         CharacteristicProperties::READ,
 //!     );
 //!
 //!     registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
-//!     registration.add_characteristic(
+//!     registration.add_characteristic_with_initial_value(
 //!         ch::SERVICE_CHANGED,
-//!         "",
+//!         [0x01, 0x00, 0xFF, 0xFF],
 //!         CharacteristicProperties::INDICATE,
 //!     );
 //!
 //!     registration.add_primary_service(srv::DEVICE_INFORMATION);
-//!     registration.add_characteristic(
+//!     registration.add_characteristic_with_initial_value(
 //!         ch::MANUFACTURER_NAME_STRING,
 //!         "機械",
 //!         CharacteristicProperties::READ,
 //!     );
-//!     registration.add_characteristic(
+//!     registration.add_characteristic_with_initial_value(
 //!         ch::MODEL_NUMBER_STRING,
 //!         "A123",
 //!         CharacteristicProperties::READ,
 //!     );
-//!     registration.add_characteristic(
+//!     registration.add_characteristic_with_initial_value(
 //!         ch::SERIAL_NUMBER_STRING,
 //!         "333-444",
 //!         CharacteristicProperties::READ,
@@ -120,6 +120,7 @@
 //!   ([LICENSE-APACHE](LICENSE-APACHE) or http://www.apache.org/licenses/LICENSE-2.0)
 //! * MIT license
 //!   ([LICENSE-MIT](LICENSE-MIT) or http://opensource.org/licenses/MIT)
+//!
 //! at your option.
 //!
 //! ## Contribution
@@ -127,13 +128,27 @@
 //! Unless you explicitly state otherwise, any contribution intentionally submitted
 //! for inclusion in the work by you, as defined in the Apache-2.0 license, shall be
 //! dual licensed as above, without any additional terms or conditions.!
-pub use crate::registration::{CharacteristicProperties, Registration};
+pub use crate::attribute::Attribute;
+pub use crate::database::{Database, DatabaseInconsistency, Inconsistency};
+pub use crate::registration::{
+    CharacteristicProperties, ConnectionParameters, GenericAccessConfig, HandleAllocation,
+    HandlesExhausted, InvalidConnectionParameters, InvalidValues, PinHandleError, Registration,
+    RegistrationError, SimpleRegistration,
+};
 pub use crate::server::Server;
 pub use att::Uuid;
 
+pub mod adv;
 mod attribute;
+#[doc(hidden)]
+pub mod bench;
 pub mod characteristics;
+pub mod codec;
+pub mod compat;
 mod database;
+mod export;
+pub mod profiles;
 mod registration;
 pub mod server;
 pub mod services;
+pub mod types;
@@ -0,0 +1,3 @@
+//! Services of adopted profiles, registered with their required attributes.
+pub mod battery;
+pub mod nus;
@@ -0,0 +1,232 @@
+//! Battery Service, one instance per battery.
+//!
+//! ```
+//! use gatt::profiles::battery::{add_battery_service, BatteryInstance};
+//! use gatt::Registration;
+//!
+//! let mut registration = Registration::new();
+//! for (token, description) in [("main", 0x0106), ("stylus", 0x0107)] {
+//!     let instance = BatteryInstance {
+//!         token,
+//!         description,
+//!         initial_level: 100,
+//!     };
+//!     add_battery_service(&mut registration, instance).unwrap();
+//! }
+//! ```
+use std::convert::TryFrom;
+use std::io;
+
+use crate::characteristics as ch;
+use crate::server::NotificationSender;
+use crate::services as srv;
+use crate::{CharacteristicProperties, Registration};
+use att::server::Error as AttError;
+
+/// Characteristic Presentation Format of unsigned 8-bit integer.
+const FORMAT_UINT8: u8 = 0x04;
+/// Unit of percentage.
+const UNIT_PERCENTAGE: u16 = 0x27AD;
+/// Name space of the Bluetooth SIG, for [`BatteryInstance::description`].
+const NAME_SPACE_BLUETOOTH_SIG: u8 = 0x01;
+
+/// Battery Level out of 0..=100.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid battery level {0}. (expected 0..=100)")]
+pub struct InvalidBatteryLevel(pub u8);
+
+/// Battery Level in percent, 0..=100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BatteryLevel(u8);
+
+impl BatteryLevel {
+    pub fn as_u8(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for BatteryLevel {
+    type Error = InvalidBatteryLevel;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0..=100 => Ok(Self(v)),
+            v => Err(InvalidBatteryLevel(v)),
+        }
+    }
+}
+
+impl From<BatteryLevel> for u8 {
+    fn from(v: BatteryLevel) -> Self {
+        v.0
+    }
+}
+
+/// A battery, registered as its own Battery Service.
+#[derive(Debug, Clone)]
+pub struct BatteryInstance<T> {
+    /// Token of the Battery Level characteristic.
+    pub token: T,
+    /// Description of the Presentation Format, in the Bluetooth SIG name
+    /// space. (e.g. 0x0106 "main", 0x0107 "backup")
+    pub description: u16,
+    /// Battery Level in percent, 0..=100.
+    pub initial_level: u8,
+}
+
+/// Add a Battery Service with a readable and notifiable Battery Level.
+///
+/// The Battery Level has a Presentation Format descriptor whose description
+/// tells the instances apart. The specification requires it once there is
+/// more than one instance, and it is harmless with one. Fails with
+/// [`InvalidBatteryLevel`] and adds nothing when `initial_level` is over 100.
+///
+/// Notifications of the Battery Level other than a single byte of 0..=100
+/// fail with [`io::ErrorKind::InvalidInput`], whichever writer sends them.
+pub fn add_battery_service<T>(
+    registration: &mut Registration<T>,
+    instance: BatteryInstance<T>,
+) -> Result<(), InvalidBatteryLevel>
+where
+    T: std::hash::Hash + Eq + Clone,
+{
+    let level = BatteryLevel::try_from(instance.initial_level)?;
+    registration.add_primary_service(srv::BATTERY);
+    registration.add_characteristic_with_token(
+        instance.token.clone(),
+        ch::BATTERY_LEVEL,
+        [level.as_u8()],
+        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+    );
+    registration.set_value_check(&instance.token, |value| match value {
+        [level] => BatteryLevel::try_from(*level)
+            .map(drop)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "battery level is a single byte.",
+        )),
+    });
+    registration.add_presentation_format(
+        FORMAT_UINT8,
+        0,
+        UNIT_PERCENTAGE,
+        NAME_SPACE_BLUETOOTH_SIG,
+        instance.description,
+    );
+    Ok(())
+}
+
+/// Notify `level` through the sender of a Battery Level.
+///
+/// Take a [`BatteryLevel`] with `BatteryLevel::try_from`, which rejects
+/// levels over 100 before anything is sent.
+pub async fn notify_level<T, IO>(
+    sender: &NotificationSender<T, IO>,
+    level: BatteryLevel,
+) -> Result<(), AttError> {
+    sender.notify(&[level.as_u8()]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Uuid;
+    use att::Handle;
+
+    #[test]
+    fn test_two_instances() {
+        let mut registration = Registration::new();
+        for (token, description, initial_level) in [(1u8, 0x0106, 80), (2, 0x0107, 5)] {
+            let instance = BatteryInstance {
+                token,
+                description,
+                initial_level,
+            };
+            add_battery_service(&mut registration, instance).unwrap();
+        }
+
+        let layout = registration
+            .iter()
+            .map(|attr| (attr.handle().as_u16(), attr.attr_type().clone()))
+            .collect::<Vec<_>>();
+        let uuid = Uuid::new_uuid16;
+        assert_eq!(
+            layout,
+            [
+                (0x0001, uuid(0x2800)),
+                (0x0002, uuid(0x2803)),
+                (0x0003, uuid(0x2A19)),
+                (0x0004, uuid(0x2902)),
+                (0x0005, uuid(0x2904)),
+                (0x0006, uuid(0x2800)),
+                (0x0007, uuid(0x2803)),
+                (0x0008, uuid(0x2A19)),
+                (0x0009, uuid(0x2902)),
+                (0x000A, uuid(0x2904)),
+            ]
+        );
+
+        let (db, _, handles) = registration.build();
+        assert_eq!(handles[&1], Handle::new(0x0003));
+        assert_eq!(handles[&2], Handle::new(0x0008));
+        assert_eq!(&db.read(&0x0003.into(), false, false).unwrap()[..], [80]);
+        assert_eq!(&db.read(&0x0008.into(), false, false).unwrap()[..], [5]);
+        assert_eq!(
+            &db.read(&0x000A.into(), false, false).unwrap()[..],
+            [0x04, 0x00, 0xAD, 0x27, 0x01, 0x07, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_invalid_level() {
+        let mut registration = Registration::new();
+        let instance = BatteryInstance {
+            token: 1u8,
+            description: 0x0106,
+            initial_level: 101,
+        };
+        assert!(matches!(
+            add_battery_service(&mut registration, instance),
+            Err(InvalidBatteryLevel(101))
+        ));
+        assert!(registration.is_empty());
+
+        assert_eq!(BatteryLevel::try_from(100).unwrap().as_u8(), 100);
+        assert!(BatteryLevel::try_from(101).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_level_notified() {
+        use crate::server::{Connection, TryNotifyError};
+        use tokio::io::AsyncWriteExt;
+
+        let mut registration = Registration::new();
+        let instance = BatteryInstance {
+            token: 1u8,
+            description: 0x0106,
+            initial_level: 100,
+        };
+        add_battery_service(&mut registration, instance).unwrap();
+        let inner = att::server::Connection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, registration);
+        let invalid = |err: &AttError| matches!(err, AttError::Io(err) if err.kind() == io::ErrorKind::InvalidInput);
+
+        let sender = connection.notification_sender(&1).unwrap();
+        assert!(invalid(&sender.notify(&[101]).await.unwrap_err()));
+        assert!(invalid(&sender.clone().notify(&[]).await.unwrap_err()));
+        assert!(matches!(
+            sender.try_notify(&[50, 0]),
+            Err(TryNotifyError::Error(err)) if invalid(&err)
+        ));
+        let mut notification = connection.notification(&1).unwrap();
+        let err = notification.clone().notify(&[0xFF]).await.unwrap_err();
+        assert!(invalid(&err));
+        let err = notification.write_all(&[101]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        sender.try_notify(&[50]).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+//! Nordic UART Service, a serial pipe over GATT.
+//!
+//! The client writes to the RX characteristic and is notified on the TX
+//! characteristic. [`Connection::nus_pipe`] reads and writes those as an
+//! [`AsyncRead`] + [`AsyncWrite`] byte stream.
+//!
+//! ```no_run
+//! use gatt::profiles::nus::add_nus;
+//! use gatt::{Registration, Server};
+//!
+//! #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+//! enum Token {
+//!     Rx,
+//!     Tx,
+//! }
+//!
+//! # async fn f() -> anyhow::Result<()> {
+//! let mut registration = Registration::new();
+//! let tokens = add_nus(&mut registration, Token::Rx, Token::Tx);
+//!
+//! let mut server = Server::bind()?;
+//! let mut connection = server.accept(registration).await?.unwrap();
+//! let pipe = connection.nus_pipe(&tokens)?;
+//! let (mut rx, mut tx) = tokio::io::split(pipe);
+//! tokio::spawn(async move { tokio::io::copy(&mut rx, &mut tx).await });
+//! connection.run().await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use att::{Handle, Uuid};
+use futures_channel::mpsc;
+use futures_util::ready;
+use futures_util::stream::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::server::{
+    notifications_enabled, ClientConfigStore, Connection, Notification, Subscriptions,
+};
+use crate::{CharacteristicProperties, Registration};
+
+/// Nordic UART Service
+pub const SERVICE: Uuid = Uuid::new_uuid128(0x6E40_0001_B5A3_F393_E0A9_E50E_24DC_CA9E);
+/// RX Characteristic, written by the client.
+pub const RX: Uuid = Uuid::new_uuid128(0x6E40_0002_B5A3_F393_E0A9_E50E_24DC_CA9E);
+/// TX Characteristic, notified to the client.
+pub const TX: Uuid = Uuid::new_uuid128(0x6E40_0003_B5A3_F393_E0A9_E50E_24DC_CA9E);
+
+/// Tokens of the characteristics added by [`add_nus`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NusTokens<T> {
+    pub rx: T,
+    pub tx: T,
+}
+
+/// Add the Nordic UART Service, with `rx` and `tx` as the tokens of its
+/// characteristics.
+pub fn add_nus<T>(registration: &mut Registration<T>, rx: T, tx: T) -> NusTokens<T>
+where
+    T: std::hash::Hash + Eq + Clone,
+{
+    registration.add_primary_service(SERVICE);
+    registration.add_characteristic_with_token(
+        rx.clone(),
+        RX,
+        "",
+        CharacteristicProperties::WRITE | CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+    );
+    registration.add_characteristic_with_token(
+        tx.clone(),
+        TX,
+        "",
+        CharacteristicProperties::NOTIFY,
+    );
+    NusTokens { rx, tx }
+}
+
+/// Byte stream over the Nordic UART Service of a connection.
+///
+/// Reads yield the values the client writes to RX, in order, and end once
+/// the connection is done. Writes are notified on TX in pieces of at most
+/// `ATT_MTU - 3` octets, taking the MTU at the time of each write, and wait
+/// while the client is not subscribed or the outgoing queue is full.
+/// Received values are not limited; read them as they arrive.
+pub struct NusPipe<IO> {
+    /// Values written to RX.
+    rx: mpsc::UnboundedReceiver<Box<[u8]>>,
+    /// Value being read and the position in it.
+    received: Box<[u8]>,
+    pos: usize,
+    notification: Notification<IO>,
+    cccd: Handle,
+    client_config: ClientConfigStore,
+    subscriptions: Subscriptions,
+}
+
+impl<IO> NusPipe<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    pub(crate) fn new<T>(
+        connection: &mut Connection<T, IO>,
+        tokens: &NusTokens<T>,
+    ) -> Result<Self, crate::server::HandleNotFound<T>>
+    where
+        T: Eq + std::hash::Hash + Clone,
+    {
+        let notification = connection.notification(&tokens.tx)?;
+        let cccd = connection
+            .resolve(&tokens.tx)
+            .and_then(|info| info.cccd_handle.clone())
+            .ok_or_else(|| crate::server::HandleNotFound(tokens.tx.clone()))?;
+        let rx = connection
+            .write_pipe(&tokens.rx)
+            .ok_or_else(|| crate::server::HandleNotFound(tokens.rx.clone()))?;
+        Ok(Self {
+            rx,
+            received: Box::new([]),
+            pos: 0,
+            notification,
+            cccd,
+            client_config: connection.client_config_store(),
+            subscriptions: connection.subscriptions(),
+        })
+    }
+}
+
+impl<IO> NusPipe<IO> {
+    /// Wait until the client enables notifications of TX.
+    fn poll_subscribed(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if notifications_enabled(&self.client_config, &self.cccd) {
+                return Poll::Ready(Ok(()));
+            }
+            if ready!(self.subscriptions.0.poll_next_unpin(cx)).is_none() {
+                return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+            }
+        }
+    }
+}
+
+impl<IO> AsyncRead for NusPipe<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.pos == this.received.len() {
+            match ready!(this.rx.poll_next_unpin(cx)) {
+                Some(value) => {
+                    this.received = value;
+                    this.pos = 0;
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let len = buf.remaining().min(this.received.len() - this.pos);
+        buf.put_slice(&this.received[this.pos..this.pos + len]);
+        this.pos += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<IO> AsyncWrite for NusPipe<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let this = self.get_mut();
+        ready!(this.poll_subscribed(cx))?;
+        let len = buf.len().min(this.notification.current_mtu() - 3);
+        Pin::new(&mut this.notification).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Nothing to do; the service has no end of stream. The connection stays open.
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use att::server::Connection as AttConnection;
+    use futures_channel::mpsc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Transport keeping packet boundaries, like an L2CAP socket.
+    struct Packets {
+        rx: mpsc::UnboundedReceiver<Vec<u8>>,
+        tx: mpsc::UnboundedSender<Vec<u8>>,
+    }
+
+    impl AsyncRead for Packets {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if let Some(packet) = ready!(self.get_mut().rx.poll_next_unpin(cx)) {
+                buf.put_slice(&packet);
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for Packets {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.tx.unbounded_send(buf.to_vec()).ok();
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Token {
+        Rx,
+        Tx,
+    }
+
+    const RX_VALUE: u8 = 0x03;
+    const TX_VALUE: u8 = 0x05;
+    const TX_CCCD: u8 = 0x06;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|n| (n * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_add_nus() {
+        let mut registration = Registration::new();
+        let tokens = add_nus(&mut registration, Token::Rx, Token::Tx);
+        assert_eq!(tokens.rx, Token::Rx);
+        let layout = registration
+            .iter()
+            .map(|attr| (attr.handle().as_u16(), attr.attr_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layout,
+            [
+                (0x0001, Uuid::new_uuid16(0x2800)),
+                (0x0002, Uuid::new_uuid16(0x2803)),
+                (RX_VALUE.into(), RX),
+                (0x0004, Uuid::new_uuid16(0x2803)),
+                (TX_VALUE.into(), TX),
+                (TX_CCCD.into(), Uuid::new_uuid16(0x2902)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipe() {
+        let (client_tx, rx) = mpsc::unbounded();
+        let (tx, mut client_rx) = mpsc::unbounded();
+        let io = Packets { rx, tx };
+        let mut registration = Registration::new();
+        let tokens = add_nus(&mut registration, Token::Rx, Token::Tx);
+        let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+        let mut connection = Connection::new(connection, registration);
+        let mut pipe = connection.nus_pipe(&tokens).unwrap();
+        let task = tokio::spawn(connection.run());
+
+        // client to server
+        let sent = data(10 * 1024);
+        for chunk in sent.chunks(20) {
+            let mut packet = vec![0x52, RX_VALUE, 0x00];
+            packet.extend_from_slice(chunk);
+            client_tx.unbounded_send(packet).unwrap();
+        }
+        let mut received = vec![0; sent.len()];
+        pipe.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, sent);
+
+        // server to client, waiting for the subscription
+        let sent = data(10 * 1024);
+        let (first, second) = sent.split_at(5 * 1024);
+        let first = first.to_vec();
+        let write = tokio::spawn(async move {
+            pipe.write_all(&first).await.unwrap();
+            pipe
+        });
+        tokio::task::yield_now().await;
+        assert!(client_rx.try_recv().is_err());
+        client_tx
+            .unbounded_send(vec![0x12, TX_CCCD, 0x00, 0x01, 0x00])
+            .unwrap();
+        assert_eq!(client_rx.next().await.unwrap(), [0x13]);
+
+        /// Append the value of a TX notification and return the PDU length.
+        fn notified(received: &mut Vec<u8>, packet: Vec<u8>, mtu: usize) -> usize {
+            assert_eq!(packet[..3], [0x1B, TX_VALUE, 0x00]);
+            assert!(packet.len() <= mtu);
+            received.extend_from_slice(&packet[3..]);
+            packet.len()
+        }
+        let mut received = vec![];
+        while received.len() < 5 * 1024 {
+            notified(&mut received, client_rx.next().await.unwrap(), 23);
+        }
+        let mut pipe = write.await.unwrap();
+
+        // the MTU grows mid-stream
+        client_tx.unbounded_send(vec![0x02, 0x40, 0x00]).unwrap();
+        assert_eq!(client_rx.next().await.unwrap(), [0x03, 0x05, 0x02]);
+        pipe.write_all(second).await.unwrap();
+        let mut longest = 0;
+        while received.len() < sent.len() {
+            let packet = client_rx.next().await.unwrap();
+            longest = longest.max(notified(&mut received, packet, 0x40));
+        }
+        assert_eq!(longest, 0x40);
+        assert_eq!(received, sent);
+
+        drop(client_tx);
+        task.await.unwrap().unwrap();
+        assert_eq!(pipe.read(&mut [0; 1]).await.unwrap(), 0);
+    }
+}
@@ -1,17 +1,29 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::hash::Hash;
+use std::io;
 
 use att::{Handle, Uuid};
 
-use crate::attribute::{
-    Attribute, CharacteristicExtendedProperties as AttExProperties,
+use crate::attribute::Attribute;
+use crate::characteristics as ch;
+use crate::codec::{CodecError, StructCodec, Value};
+use crate::database::{Database, Generator, ValueCheck, ValueLength};
+use crate::services as srv;
+use crate::types::{
+    impl_flags_display, CharacteristicExtendedProperties as AttExProperties,
     CharacteristicProperties as AttProperties, ClientCharacteristicConfiguration, Permission,
-    ServerCharacteristicConfiguration,
+    ServerCharacteristicConfiguration, UndefinedBits,
 };
-use crate::database::Database;
 
 bitflags::bitflags! {
-    /// Characteristic Properties
+    /// Characteristic Properties for registration.
+    ///
+    /// The low byte is [`types::CharacteristicProperties`](crate::types::CharacteristicProperties)
+    /// without `EXTENDED_PROPERTIES`, the second byte is
+    /// [`types::CharacteristicExtendedProperties`](crate::types::CharacteristicExtendedProperties).
+    /// `AUTHORIZATION_REQUIRED` is not transmitted.
     pub struct CharacteristicProperties: u32 {
         const BROADCAST = 0x0001;
         const READ = 0x0002;
@@ -27,6 +39,25 @@ bitflags::bitflags! {
 }
 
 impl CharacteristicProperties {
+    /// Raw bits, e.g. to store the properties.
+    pub fn to_u32(self) -> u32 {
+        self.bits()
+    }
+
+    /// Properties from raw bits. Undefined bits are dropped; use
+    /// [`TryFrom`] to reject them instead.
+    pub fn from_u32_truncated(v: u32) -> Self {
+        Self::from_bits_truncate(v)
+    }
+
+    /// Split into the Characteristic Properties of the declaration and the
+    /// Characteristic Extended Properties descriptor value.
+    ///
+    /// Same as the [`From`] conversion into the tuple.
+    pub fn to_att_properties(self) -> (AttProperties, AttExProperties) {
+        self.into()
+    }
+
     fn perm(&self) -> Permission {
         let mut perm = Permission::empty();
         if self.contains(Self::READ) {
@@ -45,6 +76,34 @@ impl CharacteristicProperties {
     }
 }
 
+impl_flags_display!(CharacteristicProperties {
+    BROADCAST,
+    READ,
+    WRITE_WITHOUT_RESPONSE,
+    WRITE,
+    NOTIFY,
+    INDICATE,
+    AUTHENTICATED_SIGNED_WRITES,
+    RELIABLE_WRITE,
+    WRITABLE_AUXILIARIES,
+    AUTHORIZATION_REQUIRED,
+});
+
+impl TryFrom<u32> for CharacteristicProperties {
+    type Error = UndefinedBits;
+
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        Self::from_bits(v).ok_or(UndefinedBits(v & !Self::all().bits()))
+    }
+}
+
+impl From<(AttProperties, AttExProperties)> for CharacteristicProperties {
+    fn from((prop, exprop): (AttProperties, AttExProperties)) -> Self {
+        let prop = prop - AttProperties::EXTENDED_PROPERTIES;
+        Self::from_bits_truncate(u32::from(prop.bits()) | u32::from(exprop.bits()) << 8)
+    }
+}
+
 impl From<CharacteristicProperties> for (AttProperties, AttExProperties) {
     fn from(v: CharacteristicProperties) -> Self {
         let mut prop = AttProperties::from_bits_truncate((v.bits() & 0xFF) as u8);
@@ -56,12 +115,170 @@ impl From<CharacteristicProperties> for (AttProperties, AttExProperties) {
     }
 }
 
+/// Peripheral Preferred Connection Parameters
+///
+/// Intervals are in units of 1.25 ms, timeout in units of 10 ms.
+/// `0xFFFF` means no specific value for intervals and timeout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionParameters {
+    pub min_interval: u16,
+    pub max_interval: u16,
+    pub latency: u16,
+    pub timeout: u16,
+}
+
+impl ConnectionParameters {
+    fn validate(&self) -> Result<(), InvalidConnectionParameters> {
+        const NO_SPECIFIC: u16 = 0xFFFF;
+        let interval = |v: u16| v == NO_SPECIFIC || (0x0006..=0x0C80).contains(&v);
+
+        if !interval(self.min_interval) {
+            return Err(InvalidConnectionParameters("min_interval"));
+        }
+        if !interval(self.max_interval)
+            || (self.min_interval != NO_SPECIFIC
+                && self.max_interval != NO_SPECIFIC
+                && self.max_interval < self.min_interval)
+        {
+            return Err(InvalidConnectionParameters("max_interval"));
+        }
+        if self.latency > 0x01F3 {
+            return Err(InvalidConnectionParameters("latency"));
+        }
+        if self.timeout != NO_SPECIFIC && !(0x000A..=0x0C80).contains(&self.timeout) {
+            return Err(InvalidConnectionParameters("timeout"));
+        }
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> [u8; 8] {
+        let mut result = [0; 8];
+        result[0..2].copy_from_slice(&self.min_interval.to_le_bytes());
+        result[2..4].copy_from_slice(&self.max_interval.to_le_bytes());
+        result[4..6].copy_from_slice(&self.latency.to_le_bytes());
+        result[6..8].copy_from_slice(&self.timeout.to_le_bytes());
+        result
+    }
+}
+
+/// Error for [`Registration::add_generic_access`]
+#[derive(Debug, thiserror::Error)]
+#[error("invalid connection parameters. ({0} out of range)")]
+pub struct InvalidConnectionParameters(&'static str);
+
+/// Generic Access Service configuration for [`Registration::add_generic_access`]
 #[derive(Debug)]
+pub struct GenericAccessConfig<T> {
+    pub device_name: String,
+    pub device_name_writable: bool,
+    /// Token for write events of Device Name. (used when writable)
+    pub device_name_token: Option<T>,
+    pub appearance: u16,
+    pub preferred_connection_parameters: Option<ConnectionParameters>,
+}
+
+impl<T> Default for GenericAccessConfig<T> {
+    fn default() -> Self {
+        Self {
+            device_name: Default::default(),
+            device_name_writable: false,
+            device_name_token: None,
+            appearance: 0,
+            preferred_connection_parameters: None,
+        }
+    }
+}
+
+/// Error for [`Registration::at_handle`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PinHandleError {
+    #[error("handle 0x0000 is reserved.")]
+    Reserved,
+
+    #[error("handle {0} is already assigned.")]
+    AlreadyAssigned(Handle),
+
+    #[error("handle {0} is below the next free handle {1}.")]
+    NotMonotonic(Handle, Handle),
+}
+
+/// How [`Registration`] assigns the handles of service declarations, to
+/// leave room for the services to grow without moving the ones after them.
+/// Attributes within a service are assigned sequentially, and a handle
+/// pinned by [`Registration::at_handle`] is kept as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandleAllocation {
+    /// Right after the previous attribute.
+    #[default]
+    Compact,
+    /// At the next multiple of `service_alignment`, the first service at
+    /// 0x0001. (e.g. 0x0001, 0x0010, 0x0020 for 0x0010)
+    Aligned { service_alignment: u16 },
+    /// Every `per_service_slots` handles from 0x0001, so each service spans
+    /// `per_service_slots` handles. A service outgrowing its span takes the
+    /// spans after it too.
+    Reserved { per_service_slots: u16 },
+}
+
+impl HandleAllocation {
+    /// Handle of a service declaration added when `next` is free.
+    fn service_handle(self, next: u32) -> u32 {
+        let round_up = |v: u32, n: u32| v.div_ceil(n) * n;
+        match self {
+            Self::Aligned { service_alignment } if service_alignment > 0 && next > 0x0001 => {
+                round_up(next, service_alignment.into())
+            }
+            Self::Reserved { per_service_slots } if per_service_slots > 0 => {
+                0x0001 + round_up(next - 0x0001, per_service_slots.into())
+            }
+            _ => next,
+        }
+    }
+}
+
+/// Error for [`Registration::validate`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("attribute handles exhausted. ({0} attributes past 0xFFFF)")]
+pub struct HandlesExhausted(pub usize);
+
+/// Error for [`Registration::add_characteristic_with_separate_cccd_token`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RegistrationError {
+    /// Neither NOTIFY nor INDICATE, so the characteristic has no Client
+    /// Characteristic Configuration.
+    #[error("characteristic is neither notifiable nor indicatable.")]
+    NotNotifiable,
+
+    #[error(transparent)]
+    HandlesExhausted(#[from] HandlesExhausted),
+}
+
+/// Error for [`Registration::with_values`]. Lists every rejected token.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid values for tokens {0:?}.")]
+pub struct InvalidValues<T>(pub Vec<T>);
+
+/// Attribute table to serve. Clone it to serve the same table on several
+/// connections.
+#[derive(Debug, Clone)]
 pub struct Registration<T> {
-    next_handle: u16,
+    /// Past `0xFFFF` once the handle space is exhausted.
+    next_handle: u32,
     attrs: Vec<Attribute>,
     write_handles: HashMap<Handle, T>,
-    notify_or_indicate_handles: HashMap<T, Handle>,
+    /// Value handle of every characteristic added with a token.
+    value_handles: HashMap<T, Handle>,
+    value_lengths: HashMap<Handle, ValueLength>,
+    generators: HashMap<Handle, Generator>,
+    value_checks: HashMap<Handle, ValueCheck>,
+    /// Presentation Format handles of the characteristic being added.
+    formats: Vec<Handle>,
+    allocation: HandleAllocation,
+    /// The next handle was pinned by [`at_handle`](Self::at_handle).
+    pinned: bool,
+    /// Attributes added once the handles were exhausted. They get
+    /// [`Handle::NULL`] and are left out of the table.
+    exhausted: usize,
 }
 
 impl<T> Default for Registration<T> {
@@ -70,14 +287,111 @@ impl<T> Default for Registration<T> {
     }
 }
 
+/// Registration without tokens, for tables nothing reacts to.
+///
+/// Add characteristics with [`add_characteristic`](Registration::add_characteristic)
+/// and friends. `()` is a single anonymous token: writes to every
+/// characteristic added with it are reported as `Event::Write((), ..)`, and
+/// [`Connection::notification`](crate::server::Connection::notification) of
+/// `()` writes to the last one added with it, which needs NOTIFY or INDICATE.
+pub type SimpleRegistration = Registration<()>;
+
+impl Registration<()> {
+    /// Same as [`new`](Self::new), without naming the token type.
+    pub fn new_simple() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Registration<T> {
     pub fn new() -> Self {
         Self {
             next_handle: 0x0001,
             attrs: vec![],
             write_handles: HashMap::new(),
-            notify_or_indicate_handles: HashMap::new(),
+            value_handles: HashMap::new(),
+            value_lengths: HashMap::new(),
+            generators: HashMap::new(),
+            value_checks: HashMap::new(),
+            formats: vec![],
+            allocation: HandleAllocation::default(),
+            pinned: false,
+            exhausted: 0,
+        }
+    }
+
+    /// Assign the handles of the services added from now on by
+    /// `allocation`, instead of [`HandleAllocation::Compact`]. This covers
+    /// every method adding a service, such as
+    /// [`add_generic_access`](Self::add_generic_access),
+    /// [`add_generic_attribute`](Self::add_generic_attribute) and the
+    /// [`profiles`](crate::profiles).
+    pub fn set_handle_allocation(&mut self, allocation: HandleAllocation) {
+        self.allocation = allocation;
+    }
+
+    /// Check every attribute added got a handle.
+    ///
+    /// Attributes added past 0xFFFF get [`Handle::NULL`] and are left out of
+    /// the table served. [`Server::accept`](crate::Server::accept) refuses
+    /// such a registration.
+    pub fn validate(&self) -> Result<(), HandlesExhausted> {
+        if self.exhausted > 0 {
+            Err(HandlesExhausted(self.exhausted))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Attributes added so far, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = &Attribute> {
+        self.attrs.iter()
+    }
+
+    /// UUIDs of the primary services, in declaration order, each once. (e.g.
+    /// for [`ServiceUuids`](crate::adv::ServiceUuids))
+    pub fn advertised_services(&self) -> Vec<Uuid> {
+        let mut uuids = Vec::<Uuid>::new();
+        for attr in &self.attrs {
+            if let Attribute::Service {
+                primary: true,
+                uuid,
+                ..
+            } = attr
+            {
+                let uuid = uuid.canonical();
+                if !uuids.contains(&uuid) {
+                    uuids.push(uuid);
+                }
+            }
         }
+        uuids
+    }
+
+    /// Stored value of every characteristic added with a token, unless generated.
+    pub(crate) fn stored_values(&self) -> impl Iterator<Item = (&T, &Handle, &[u8])> {
+        self.value_handles
+            .iter()
+            .filter(|(_, handle)| *handle != &Handle::NULL)
+            .filter(move |(_, handle)| !self.generators.contains_key(handle))
+            .filter_map(move |(token, handle)| {
+                self.attrs.iter().find_map(|attr| match attr {
+                    Attribute::CharacteristicValue {
+                        handle: h, value, ..
+                    } if h == handle => Some((token, handle, &value[..])),
+                    _ => None,
+                })
+            })
+    }
+
+    /// Number of attributes added so far.
+    pub fn len(&self) -> usize {
+        self.attrs.len()
+    }
+
+    /// Returns `true` if no attribute added.
+    pub fn is_empty(&self) -> bool {
+        self.attrs.is_empty()
     }
 }
 
@@ -86,9 +400,73 @@ where
     T: Hash + Eq + Clone,
 {
     fn next_handle(&mut self) -> Handle {
-        let result = Handle::new(self.next_handle);
-        self.next_handle += 1;
-        result
+        match self.next_free_handle() {
+            Some(handle) => {
+                self.next_handle += 1;
+                self.pinned = false;
+                handle
+            }
+            None => {
+                self.exhausted += 1;
+                Handle::NULL
+            }
+        }
+    }
+
+    /// Handle of the next service declaration, by the allocation strategy.
+    fn next_service_handle(&mut self) -> Handle {
+        if !self.pinned {
+            self.next_handle = self.allocation.service_handle(self.next_handle);
+        }
+        self.next_handle()
+    }
+
+    /// Handle the next added attribute gets, or `None` when all handles are
+    /// assigned.
+    pub fn next_free_handle(&self) -> Option<Handle> {
+        u16::try_from(self.next_handle).ok().map(Handle::new)
+    }
+
+    /// End the characteristic being added.
+    ///
+    /// A characteristic with two or more Presentation Format descriptors gets
+    /// an Aggregate Format descriptor listing them, on the handle after its
+    /// last descriptor.
+    fn end_characteristic(&mut self) {
+        let formats = std::mem::take(&mut self.formats);
+        if formats.len() < 2 {
+            return;
+        }
+        let handle = self.next_handle();
+        self.attrs
+            .push(Attribute::new_characteristic_aggregate_format(
+                handle, formats,
+            ));
+    }
+
+    /// Assign `handle` to the next added attribute, usually a service
+    /// declaration, to keep the layout of an already deployed attribute table.
+    ///
+    /// Handles after it are assigned sequentially again. Handles skipped over
+    /// are left unassigned. Pinned handles must not go below
+    /// [`next_free_handle`](Self::next_free_handle).
+    pub fn at_handle(&mut self, handle: u16) -> Result<(), PinHandleError> {
+        if handle == 0x0000 {
+            return Err(PinHandleError::Reserved);
+        }
+        self.end_characteristic();
+        let handle = Handle::new(handle);
+        if self.attrs.iter().any(|attr| attr.handle() == &handle) {
+            return Err(PinHandleError::AlreadyAssigned(handle));
+        }
+        if let Some(next) = self.next_free_handle() {
+            if handle < next {
+                return Err(PinHandleError::NotMonotonic(handle, next));
+            }
+        }
+        self.next_handle = handle.as_u16().into();
+        self.pinned = true;
+        Ok(())
     }
 
     pub fn add_primary_service<U>(&mut self, uuid: U)
@@ -96,11 +474,14 @@ where
         U: Into<Uuid>,
     {
         let uuid = uuid.into();
-        let handle = self.next_handle();
+        self.end_characteristic();
+        let handle = self.next_service_handle();
         self.attrs
             .push(Attribute::new_primary_service(handle, uuid));
     }
 
+    /// Add Characteristic. `val` is the initial value; clients may
+    /// overwrite it when `properties` allow writing.
     pub fn add_characteristic<U, B>(
         &mut self,
         uuid: U,
@@ -110,9 +491,53 @@ where
         U: Into<Uuid>,
         B: AsRef<[u8]>,
     {
-        self.add_characteristic_internal(None, uuid, val.as_ref(), properties)
+        self.add_characteristic_internal(None, uuid, val.as_ref(), properties);
+    }
+
+    /// Add Characteristic holding `initial_value` until a client writes it.
+    ///
+    /// Returns the handle of the characteristic value.
+    pub fn add_characteristic_with_initial_value<U, B>(
+        &mut self,
+        uuid: U,
+        initial_value: B,
+        properties: CharacteristicProperties,
+    ) -> Handle
+    where
+        U: Into<Uuid>,
+        B: AsRef<[u8]>,
+    {
+        self.add_characteristic_internal(None, uuid, initial_value.as_ref(), properties)
+    }
+
+    /// Add Characteristic whose value clients may write at most `max_len`
+    /// octets of. Longer writes are rejected with Invalid Attribute Value
+    /// Length. (e.g. a fixed size sensor reading)
+    ///
+    /// `max_len` above 512 is capped at 512, the longest value allowed.
+    /// Returns the handle of the characteristic value.
+    pub fn add_characteristic_with_max_length<U, B>(
+        &mut self,
+        uuid: U,
+        initial: B,
+        properties: CharacteristicProperties,
+        max_len: usize,
+    ) -> Handle
+    where
+        U: Into<Uuid>,
+        B: AsRef<[u8]>,
+    {
+        let handle = self.add_characteristic_internal(None, uuid, initial.as_ref(), properties);
+        self.value_lengths
+            .insert(handle.clone(), ValueLength::AtMost(max_len));
+        handle
     }
 
+    /// Add Characteristic named `token` in events, notifications and lookups.
+    ///
+    /// Adding a token again is allowed. Writes of the earlier characteristics
+    /// are still reported with it, while notifications and lookups of the
+    /// token refer to the last characteristic added with it.
     pub fn add_characteristic_with_token<U, B>(
         &mut self,
         token: T,
@@ -124,7 +549,114 @@ where
         T: Hash + Eq + Clone,
         B: AsRef<[u8]>,
     {
-        self.add_characteristic_internal(Some(token), uuid, val.as_ref(), properties)
+        self.add_characteristic_internal(Some(token), uuid, val.as_ref(), properties);
+    }
+
+    /// Add Characteristic like [`add_characteristic_with_token`], and report
+    /// writes to its Client Characteristic Configuration as
+    /// [`Event::Write`](crate::server::Event::Write) of `cccd_token`.
+    ///
+    /// `value_token` still names the characteristic for notifications and
+    /// writes of its value, so subscription changes can be told apart.
+    ///
+    /// Fails with [`RegistrationError::NotNotifiable`] without adding
+    /// anything if `properties` has neither NOTIFY nor INDICATE, and with
+    /// [`RegistrationError::HandlesExhausted`] if the characteristic does not
+    /// fit in the handle space.
+    ///
+    /// [`add_characteristic_with_token`]: Self::add_characteristic_with_token
+    pub fn add_characteristic_with_separate_cccd_token<U, B>(
+        &mut self,
+        value_token: T,
+        cccd_token: T,
+        uuid: U,
+        val: B,
+        properties: CharacteristicProperties,
+    ) -> Result<(), RegistrationError>
+    where
+        U: Into<Uuid>,
+        T: Hash + Eq + Clone,
+        B: AsRef<[u8]>,
+    {
+        if !properties
+            .intersects(CharacteristicProperties::NOTIFY | CharacteristicProperties::INDICATE)
+        {
+            return Err(RegistrationError::NotNotifiable);
+        }
+        let val_handle =
+            self.add_characteristic_internal(Some(value_token), uuid, val.as_ref(), properties);
+        let cccd_handle = self
+            .attrs
+            .iter()
+            .rev()
+            .take_while(|attr| attr.handle() != &val_handle)
+            .find(|attr| matches!(attr, Attribute::ClientCharacteristicConfiguration { .. }))
+            .map(|attr| attr.handle().clone())
+            .filter(|handle| handle != &Handle::NULL);
+        match (val_handle, cccd_handle) {
+            (val_handle, Some(cccd_handle)) if val_handle != Handle::NULL => {
+                self.write_handles.insert(cccd_handle, cccd_token);
+                Ok(())
+            }
+            _ => Err(HandlesExhausted(self.exhausted).into()),
+        }
+    }
+
+    /// Add Characteristic whose value is laid out by `codec`.
+    ///
+    /// `initial` is encoded with `codec`. Writes of another length than
+    /// [`StructCodec::len`] are rejected with Invalid Attribute Value Length.
+    /// Decode [`Event::Write`](crate::server::Event::Write) payloads with
+    /// [`StructCodec::decode`].
+    pub fn add_characteristic_typed<U>(
+        &mut self,
+        token: T,
+        uuid: U,
+        codec: &StructCodec,
+        initial: &Value,
+        properties: CharacteristicProperties,
+    ) -> Result<(), CodecError>
+    where
+        U: Into<Uuid>,
+    {
+        let val = codec.encode(initial)?;
+        let handle = self.add_characteristic_internal(Some(token), uuid, &val, properties);
+        self.value_lengths
+            .insert(handle, ValueLength::Exactly(codec.len()));
+        Ok(())
+    }
+
+    /// Add Characteristic whose value `generator` produces on every read.
+    /// (e.g. a nonce or the current time)
+    ///
+    /// Read Blob Requests continuing a read are served from the value
+    /// generated for it, so a long read sees a single value. Writes are
+    /// reported but do not change what reads return.
+    pub fn add_characteristic_with_generator<U, F, B>(
+        &mut self,
+        token: T,
+        uuid: U,
+        properties: CharacteristicProperties,
+        generator: F,
+    ) where
+        U: Into<Uuid>,
+        F: Fn() -> B + Send + Sync + 'static,
+        B: AsRef<[u8]>,
+    {
+        let handle = self.add_characteristic_internal(Some(token), uuid, &[], properties);
+        self.generators.insert(handle, Generator::new(generator));
+    }
+
+    /// Refuse notifications of the characteristic registered with `token`
+    /// whose value `check` rejects, whichever writer sends them.
+    pub(crate) fn set_value_check<F>(&mut self, token: &T, check: F)
+    where
+        F: Fn(&[u8]) -> io::Result<()> + Send + Sync + 'static,
+    {
+        if let Some(handle) = self.value_handles.get(token) {
+            self.value_checks
+                .insert(handle.clone(), ValueCheck::new(check));
+        }
     }
 
     fn add_characteristic_internal<U>(
@@ -133,12 +665,14 @@ where
         uuid: U,
         val: &[u8],
         properties: CharacteristicProperties,
-    ) where
+    ) -> Handle
+    where
         U: Into<Uuid>,
     {
         let uuid = uuid.into();
         let val = val.into();
 
+        self.end_characteristic();
         let decl_handle = self.next_handle();
         let val_handle = self.next_handle();
         let perm = properties.perm();
@@ -169,10 +703,6 @@ where
         }
         if notify || indicate {
             let handle = self.next_handle();
-            if let Some(token) = &token {
-                self.notify_or_indicate_handles
-                    .insert(token.clone(), val_handle.clone());
-            }
             self.attrs
                 .push(Attribute::new_client_characteristic_configuration(
                     handle,
@@ -192,9 +722,74 @@ where
 
         if writable {
             if let Some(token) = &token {
-                self.write_handles.insert(val_handle, token.clone());
+                self.write_handles.insert(val_handle.clone(), token.clone());
             }
         }
+        if let Some(token) = token {
+            self.value_handles.insert(token, val_handle.clone());
+        }
+        val_handle
+    }
+
+    /// Add Generic Access Service with Device Name, Appearance and
+    /// (optionally) Peripheral Preferred Connection Parameters.
+    pub fn add_generic_access(
+        &mut self,
+        config: GenericAccessConfig<T>,
+    ) -> Result<(), InvalidConnectionParameters> {
+        let GenericAccessConfig {
+            device_name,
+            device_name_writable,
+            device_name_token,
+            appearance,
+            preferred_connection_parameters,
+        } = config;
+        if let Some(params) = &preferred_connection_parameters {
+            params.validate()?;
+        }
+
+        self.add_primary_service(srv::GENERIC_ACCESS);
+        let properties = if device_name_writable {
+            CharacteristicProperties::READ | CharacteristicProperties::WRITE
+        } else {
+            CharacteristicProperties::READ
+        };
+        self.add_characteristic_internal(
+            device_name_token,
+            ch::DEVICE_NAME,
+            device_name.as_bytes(),
+            properties,
+        );
+        self.add_characteristic(
+            ch::APPEARANCE,
+            appearance.to_le_bytes(),
+            CharacteristicProperties::READ,
+        );
+        if let Some(params) = preferred_connection_parameters {
+            self.add_characteristic(
+                ch::PERIPHERAL_PREFERRED_CONNECTION_PARAMETERS,
+                params.to_bytes(),
+                CharacteristicProperties::READ,
+            );
+        }
+        Ok(())
+    }
+
+    /// Add Generic Attribute Service with (optionally) Service Changed.
+    ///
+    /// The Service Changed value is the affected handle range, Start and End
+    /// Handle (Core spec Vol 3 Part G 7.1), initially the whole table.
+    pub fn add_generic_attribute(&mut self, service_changed: bool) {
+        self.add_primary_service(srv::GENERIC_ATTRIBUTE);
+        if service_changed {
+            let [start_lo, start_hi] = Handle::MIN.as_u16().to_le_bytes();
+            let [end_lo, end_hi] = Handle::MAX.as_u16().to_le_bytes();
+            self.add_characteristic(
+                ch::SERVICE_CHANGED,
+                [start_lo, start_hi, end_lo, end_hi],
+                CharacteristicProperties::INDICATE,
+            );
+        }
     }
 
     pub fn add_descriptor<U, B>(&mut self, uuid: U, val: B, writable: bool)
@@ -217,15 +812,116 @@ where
         ));
     }
 
-    pub(crate) fn build(self) -> (Database, HashMap<Handle, T>, HashMap<T, Handle>) {
+    /// Add Characteristic Presentation Format descriptor to the last added
+    /// characteristic. (`exponent` is signed, as in the descriptor)
+    ///
+    /// When a characteristic has two or more, a Characteristic Aggregate
+    /// Format descriptor listing them in order is added after its other
+    /// descriptors.
+    pub fn add_presentation_format(
+        &mut self,
+        format: u8,
+        exponent: i8,
+        unit: u16,
+        name_space: u8,
+        description: u16,
+    ) -> Handle {
+        let handle = self.next_handle();
+        self.attrs
+            .push(Attribute::new_characteristic_presentation_format(
+                handle.clone(),
+                format,
+                exponent as u8,
+                unit,
+                name_space,
+                description,
+            ));
+        self.formats.push(handle.clone());
+        handle
+    }
+
+    /// Copy of this registration with the initial values of the
+    /// characteristics named by the tokens replaced, e.g. with values
+    /// persisted per peer.
+    ///
+    /// Fails if a token names no characteristic, names one whose value is
+    /// generated, or its value is of a length clients could not write.
+    pub fn with_values<I, B>(&self, values: I) -> Result<Self, InvalidValues<T>>
+    where
+        I: IntoIterator<Item = (T, B)>,
+        B: AsRef<[u8]>,
+    {
+        let mut registration = self.clone();
+        let mut invalid = vec![];
+        for (token, value) in values {
+            let value = value.as_ref();
+            let handle = match registration.value_handles.get(&token) {
+                Some(handle) if !registration.generators.contains_key(handle) => handle,
+                _ => {
+                    invalid.push(token);
+                    continue;
+                }
+            };
+            if matches!(registration.value_lengths.get(handle), Some(len) if !len.accepts(value.len()))
+            {
+                invalid.push(token);
+                continue;
+            }
+            let attr = registration
+                .attrs
+                .iter_mut()
+                .find(|attr| attr.handle() == handle);
+            if !matches!(attr.map(|attr| attr.set_value(value)), Some(Ok(()))) {
+                invalid.push(token);
+            }
+        }
+        if invalid.is_empty() {
+            Ok(registration)
+        } else {
+            Err(InvalidValues(invalid))
+        }
+    }
+
+    pub(crate) fn build(mut self) -> (Database, HashMap<Handle, T>, HashMap<T, Handle>) {
+        self.end_characteristic();
+        if let Err(err) = self.validate() {
+            log::warn!("{} they are left out.", err);
+        }
         let Self {
-            attrs,
-            write_handles,
-            notify_or_indicate_handles,
+            mut attrs,
+            mut write_handles,
+            mut value_handles,
+            mut value_lengths,
+            mut generators,
+            mut value_checks,
             ..
         } = self;
-        let db = attrs.into_iter().collect();
-        (db, write_handles, notify_or_indicate_handles)
+        // attributes past 0xFFFF, and declarations of their values.
+        attrs.retain(|attr| match attr {
+            Attribute::Characteristic { value_handle, .. } => value_handle != &Handle::NULL,
+            attr => attr.handle() != &Handle::NULL,
+        });
+        write_handles.remove(&Handle::NULL);
+        value_handles.retain(|_, handle| handle != &Handle::NULL);
+        value_lengths.remove(&Handle::NULL);
+        generators.remove(&Handle::NULL);
+        value_checks.remove(&Handle::NULL);
+        let mut db: Database = attrs.into_iter().collect();
+        for (handle, len) in value_lengths {
+            db.set_value_length(handle, len);
+        }
+        for (handle, generator) in generators {
+            db.set_generator(handle, generator);
+        }
+        for (handle, check) in value_checks {
+            db.set_value_check(handle, check);
+        }
+        if cfg!(debug_assertions) {
+            if let Err(err) = db.validate() {
+                panic!("{}", err);
+            }
+        }
+        (db, write_handles, value_handles)
     }
 }
 
@@ -285,4 +981,742 @@ mod tests {
 
         println!("{:#?}", registration.build());
     }
+
+    #[test]
+    fn test_generic_access() {
+        #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+        enum Token {
+            DeviceName,
+        }
+
+        let mut registration = Registration::<Token>::new();
+        registration
+            .add_generic_access(GenericAccessConfig {
+                device_name: "abc".into(),
+                device_name_writable: true,
+                device_name_token: Some(Token::DeviceName),
+                appearance: 0x03C0,
+                preferred_connection_parameters: Some(ConnectionParameters {
+                    min_interval: 0x0018,
+                    max_interval: 0x0028,
+                    latency: 0x0000,
+                    timeout: 0x01F4,
+                }),
+            })
+            .unwrap();
+        registration.add_generic_attribute(true);
+        let service_changed = registration
+            .iter()
+            .find(|attr| attr.handle() == &Handle::new(0x000A))
+            .unwrap();
+        assert!(matches!(
+            service_changed,
+            Attribute::CharacteristicValue { value, .. } if **value == [0x01, 0x00, 0xFF, 0xFF]
+        ));
+
+        let (db, write_tokens, _) = registration.build();
+        let result = db
+            .read_by_group_type(
+                0x0001.into()..=0xFFFF.into(),
+                &Uuid::new_uuid16(0x2800),
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            &[
+                (0x0001.into(), 0x0007.into(), vec![0x00, 0x18].into()),
+                (0x0008.into(), 0x000B.into(), vec![0x01, 0x18].into()),
+            ]
+        );
+        let result = db
+            .read_by_type(
+                0x0001.into()..=0xFFFF.into(),
+                &Uuid::new_uuid16(0x2803),
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            &[
+                (0x0002.into(), vec![0x0A, 0x03, 0x00, 0x00, 0x2A].into()),
+                (0x0004.into(), vec![0x02, 0x05, 0x00, 0x01, 0x2A].into()),
+                (0x0006.into(), vec![0x02, 0x07, 0x00, 0x04, 0x2A].into()),
+                (0x0009.into(), vec![0x20, 0x0A, 0x00, 0x05, 0x2A].into()),
+            ]
+        );
+        assert_eq!(&*db.read(&0x0003.into(), false, false).unwrap(), b"abc");
+        assert_eq!(
+            &*db.read(&0x0005.into(), false, false).unwrap(),
+            &[0xC0, 0x03]
+        );
+        assert_eq!(
+            &*db.read(&0x0007.into(), false, false).unwrap(),
+            &[0x18, 0x00, 0x28, 0x00, 0x00, 0x00, 0xF4, 0x01]
+        );
+        assert_eq!(
+            db.find_information(0x000B.into()..=0xFFFF.into()).unwrap(),
+            &[(0x000B.into(), Uuid::new_uuid16(0x2902))]
+        );
+        assert_eq!(write_tokens.get(&0x0003.into()), Some(&Token::DeviceName));
+    }
+
+    #[test]
+    fn test_connection_parameters() {
+        let valid = ConnectionParameters {
+            min_interval: 0x0006,
+            max_interval: 0x0C80,
+            latency: 0x01F3,
+            timeout: 0x000A,
+        };
+        valid.validate().unwrap();
+        ConnectionParameters {
+            min_interval: 0xFFFF,
+            max_interval: 0xFFFF,
+            latency: 0,
+            timeout: 0xFFFF,
+        }
+        .validate()
+        .unwrap();
+
+        for invalid in [
+            ConnectionParameters {
+                min_interval: 0x0005,
+                ..valid.clone()
+            },
+            ConnectionParameters {
+                max_interval: 0x0C81,
+                ..valid.clone()
+            },
+            ConnectionParameters {
+                min_interval: 0x0010,
+                max_interval: 0x0008,
+                ..valid.clone()
+            },
+            ConnectionParameters {
+                latency: 0x01F4,
+                ..valid.clone()
+            },
+            ConnectionParameters {
+                timeout: 0x0009,
+                ..valid.clone()
+            },
+        ] {
+            invalid.validate().unwrap_err();
+        }
+
+        let mut registration = Registration::<()>::new();
+        registration
+            .add_generic_access(GenericAccessConfig {
+                preferred_connection_parameters: Some(ConnectionParameters {
+                    latency: 0xFFFF,
+                    ..valid
+                }),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(registration.attrs.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut registration = Registration::<()>::new();
+        assert!(registration.is_empty());
+        registration.add_primary_service(srv::BATTERY);
+        registration.add_characteristic(
+            ch::BATTERY_LEVEL,
+            [100],
+            CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+        );
+
+        assert_eq!(registration.len(), 4);
+        let attrs = registration
+            .iter()
+            .map(|attr| (attr.handle().as_u16(), attr.attr_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attrs,
+            [
+                (0x0001, Uuid::new_uuid16(0x2800)),
+                (0x0002, Uuid::new_uuid16(0x2803)),
+                (0x0003, ch::BATTERY_LEVEL),
+                (0x0004, Uuid::new_uuid16(0x2902)),
+            ]
+        );
+        assert!(matches!(
+            registration.iter().next(),
+            Some(Attribute::Service { uuid, .. }) if uuid == &srv::BATTERY
+        ));
+    }
+
+    #[test]
+    fn test_at_handle() {
+        let mut registration = Registration::<()>::new();
+        registration.add_primary_service(srv::GENERIC_ACCESS);
+        registration.add_characteristic(ch::DEVICE_NAME, "abc", CharacteristicProperties::READ);
+        assert_eq!(registration.next_free_handle(), Some(Handle::new(0x0004)));
+
+        registration.at_handle(0x0100).unwrap();
+        registration.add_primary_service(srv::BATTERY);
+        let handle = registration.add_characteristic_with_initial_value(
+            ch::BATTERY_LEVEL,
+            [100],
+            CharacteristicProperties::READ,
+        );
+        assert_eq!(handle, Handle::new(0x0102));
+        assert_eq!(registration.next_free_handle(), Some(Handle::new(0x0103)));
+
+        assert_eq!(
+            registration.at_handle(0x0102),
+            Err(PinHandleError::AlreadyAssigned(Handle::new(0x0102)))
+        );
+        assert_eq!(
+            registration.at_handle(0x0050),
+            Err(PinHandleError::NotMonotonic(
+                Handle::new(0x0050),
+                Handle::new(0x0103)
+            ))
+        );
+        assert_eq!(
+            registration.at_handle(0x0000),
+            Err(PinHandleError::Reserved)
+        );
+
+        registration.at_handle(0xFFFF).unwrap();
+        registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+        assert_eq!(registration.next_free_handle(), None);
+        assert_eq!(
+            registration.at_handle(0xFFFF),
+            Err(PinHandleError::AlreadyAssigned(Handle::new(0xFFFF)))
+        );
+
+        let db = registration.build().0;
+        let primary = Uuid::new_uuid16(0x2800);
+        let groups = db
+            .read_by_group_type(0x0001.into()..=0xFFFF.into(), &primary, false, false)
+            .unwrap();
+        let groups = groups
+            .into_iter()
+            .map(|(start, end, _)| (start.as_u16(), end.as_u16()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [(0x0001, 0x0003), (0x0100, 0x0102), (0xFFFF, 0xFFFF)]
+        );
+
+        let err = db
+            .find_information(0x0004.into()..=0x00FF.into())
+            .unwrap_err();
+        assert_eq!(
+            err,
+            (
+                Handle::new(0x0004),
+                att::packet::ErrorCode::AttributeNotFound
+            )
+        );
+        let err = db.read(&0x0080.into(), false, false).unwrap_err();
+        assert_eq!(err.1, att::packet::ErrorCode::AttributeNotFound);
+        assert_eq!(
+            db.find_information(0x0003.into()..=0x0100.into())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    fn service_groups(registration: Registration<()>) -> Vec<(u16, u16)> {
+        let db = registration.build().0;
+        let primary = Uuid::new_uuid16(0x2800);
+        db.read_by_group_type(0x0001.into()..=0xFFFF.into(), &primary, false, false)
+            .unwrap()
+            .into_iter()
+            .map(|(start, end, _)| (start.as_u16(), end.as_u16()))
+            .collect()
+    }
+
+    #[test]
+    fn test_handle_allocation_aligned() {
+        let mut registration = Registration::<()>::new();
+        registration.set_handle_allocation(HandleAllocation::Aligned {
+            service_alignment: 0x0010,
+        });
+
+        registration.add_primary_service(srv::GENERIC_ACCESS);
+        registration.add_characteristic(ch::DEVICE_NAME, "abc", CharacteristicProperties::WRITE);
+        registration.add_characteristic(
+            ch::APPEARANCE,
+            0x03c0u16.to_le_bytes(),
+            CharacteristicProperties::READ,
+        );
+
+        registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+        registration.add_characteristic(
+            ch::SERVICE_CHANGED,
+            "",
+            CharacteristicProperties::INDICATE,
+        );
+
+        registration.add_primary_service(srv::DEVICE_INFORMATION);
+        registration.add_characteristic(
+            ch::MANUFACTURER_NAME_STRING,
+            "機械",
+            CharacteristicProperties::READ,
+        );
+        registration.add_characteristic(
+            ch::MODEL_NUMBER_STRING,
+            "A123",
+            CharacteristicProperties::READ,
+        );
+        registration.add_characteristic(
+            ch::SERIAL_NUMBER_STRING,
+            "333-444",
+            CharacteristicProperties::READ,
+        );
+
+        registration.add_primary_service(srv::BATTERY);
+        registration.add_characteristic(ch::BATTERY_LEVEL, "", CharacteristicProperties::NOTIFY);
+
+        assert_eq!(
+            service_groups(registration),
+            [
+                (0x0001, 0x0005),
+                (0x0010, 0x0013),
+                (0x0020, 0x0026),
+                (0x0030, 0x0033)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_allocation_reserved() {
+        let mut registration = Registration::<()>::new();
+        registration.set_handle_allocation(HandleAllocation::Reserved {
+            per_service_slots: 8,
+        });
+
+        registration.add_primary_service(srv::GENERIC_ACCESS);
+        registration.add_characteristic(ch::DEVICE_NAME, "abc", CharacteristicProperties::READ);
+        registration.add_primary_service(srv::DEVICE_INFORMATION);
+        for _ in 0..4 {
+            registration.add_characteristic(
+                ch::MODEL_NUMBER_STRING,
+                "A123",
+                CharacteristicProperties::READ,
+            );
+        }
+        registration.at_handle(0x0014).unwrap();
+        registration.add_primary_service(srv::BATTERY);
+        registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+
+        assert_eq!(
+            service_groups(registration),
+            [
+                (0x0001, 0x0003),
+                (0x0009, 0x0011),
+                (0x0014, 0x0014),
+                (0x0019, 0x0019)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_allocation_service_helpers() {
+        use crate::profiles::battery::{add_battery_service, BatteryInstance};
+
+        let mut registration = Registration::<()>::new();
+        registration.set_handle_allocation(HandleAllocation::Aligned {
+            service_alignment: 0x0010,
+        });
+
+        registration
+            .add_generic_access(GenericAccessConfig::default())
+            .unwrap();
+        registration.add_generic_attribute(true);
+        add_battery_service(
+            &mut registration,
+            BatteryInstance {
+                token: (),
+                description: 0x0106,
+                initial_level: 100,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            service_groups(registration),
+            [(0x0001, 0x0005), (0x0010, 0x0013), (0x0020, 0x0024)]
+        );
+    }
+
+    #[test]
+    fn test_handle_allocation_overflow() {
+        let mut registration = Registration::<()>::new();
+        registration.set_handle_allocation(HandleAllocation::Aligned {
+            service_alignment: 0x8000,
+        });
+        registration.add_primary_service(srv::GENERIC_ACCESS);
+        registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+        assert_eq!(registration.validate(), Ok(()));
+        registration.add_primary_service(srv::BATTERY);
+        let handle = registration.add_characteristic_with_initial_value(
+            ch::BATTERY_LEVEL,
+            [100],
+            CharacteristicProperties::READ,
+        );
+        assert_eq!(handle, Handle::NULL);
+        assert_eq!(registration.validate(), Err(HandlesExhausted(3)));
+
+        assert_eq!(
+            service_groups(registration),
+            [(0x0001, 0x0001), (0x8000, 0x8000)]
+        );
+    }
+
+    #[test]
+    fn test_initial_value() {
+        let mut registration = Registration::<()>::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x1800));
+        let handle = registration.add_characteristic_with_initial_value(
+            Uuid::new_uuid16(0x2A00),
+            "abc",
+            CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+        );
+        assert_eq!(handle, Handle::new(0x0003));
+
+        let (mut db, _, _) = registration.build();
+        assert_eq!(&*db.read(&handle, false, false).unwrap(), b"abc");
+        db.write(&handle, b"xyz", false, false).unwrap();
+        assert_eq!(&*db.read(&handle, false, false).unwrap(), b"xyz");
+    }
+
+    #[test]
+    fn test_max_length() {
+        use att::packet::ErrorCode;
+
+        let mut registration = Registration::<()>::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x1800));
+        let limited = registration.add_characteristic_with_max_length(
+            Uuid::new_uuid128(0xFFFF_0001),
+            [0; 20],
+            CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+            20,
+        );
+        let unlimited = registration.add_characteristic_with_initial_value(
+            Uuid::new_uuid128(0xFFFF_0002),
+            [],
+            CharacteristicProperties::WRITE,
+        );
+
+        let (mut db, _, _) = registration.build();
+        db.write(&limited, &[1; 20], false, false).unwrap();
+        db.write(&limited, &[2; 3], false, false).unwrap();
+        assert_eq!(
+            db.write(&limited, &[3; 21], false, false),
+            Err((limited.clone(), ErrorCode::InvalidAttributeValueLength))
+        );
+        assert_eq!(&*db.read(&limited, false, false).unwrap(), [2; 3]);
+
+        db.write(&unlimited, &[0; 512], false, false).unwrap();
+        assert_eq!(
+            db.write(&unlimited, &[0; 513], false, false),
+            Err((unlimited.clone(), ErrorCode::InvalidAttributeValueLength))
+        );
+    }
+
+    #[test]
+    fn test_properties_conversion() {
+        let props = CharacteristicProperties::READ
+            | CharacteristicProperties::NOTIFY
+            | CharacteristicProperties::RELIABLE_WRITE
+            | CharacteristicProperties::WRITABLE_AUXILIARIES;
+        let (prop, exprop) = props.into();
+        assert_eq!(
+            prop,
+            AttProperties::READ | AttProperties::NOTIFY | AttProperties::EXTENDED_PROPERTIES
+        );
+        assert_eq!(exprop, AttExProperties::all());
+        assert_eq!(CharacteristicProperties::from((prop, exprop)), props);
+
+        let (prop, exprop) = CharacteristicProperties::WRITE.into();
+        assert_eq!(prop, AttProperties::WRITE);
+        assert!(exprop.is_empty());
+        assert_eq!(
+            CharacteristicProperties::from((prop, exprop)),
+            CharacteristicProperties::WRITE
+        );
+
+        assert_eq!(
+            CharacteristicProperties::try_from(0x0000_0112),
+            Ok(props - CharacteristicProperties::WRITABLE_AUXILIARIES)
+        );
+        assert_eq!(
+            CharacteristicProperties::try_from(0x0000_0480),
+            Err(UndefinedBits(0x0000_0480))
+        );
+        assert_eq!(
+            props.to_string(),
+            "READ|NOTIFY|RELIABLE_WRITE|WRITABLE_AUXILIARIES"
+        );
+
+        assert_eq!(props.to_u32(), 0x0000_0312);
+        assert_eq!(
+            CharacteristicProperties::from_u32_truncated(0x0000_0692),
+            CharacteristicProperties::READ
+                | CharacteristicProperties::NOTIFY
+                | CharacteristicProperties::WRITABLE_AUXILIARIES
+        );
+        assert_eq!(props.to_att_properties(), props.into());
+    }
+
+    #[test]
+    fn test_characteristic_typed() {
+        use att::packet::ErrorCode;
+
+        let codec = StructCodec::builder()
+            .u16le("seq")
+            .i16le("temp")
+            .u8("flags")
+            .build();
+        let initial = Value::new()
+            .with("seq", 1u16)
+            .with("temp", -40i16)
+            .with("flags", 0u8);
+
+        let mut registration = Registration::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x1800));
+        registration
+            .add_characteristic_typed(
+                "telemetry",
+                Uuid::new_uuid128(0xFFFF_0001),
+                &codec,
+                &Value::new().with("seq", 1u16),
+                CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+            )
+            .unwrap_err();
+        registration
+            .add_characteristic_typed(
+                "telemetry",
+                Uuid::new_uuid128(0xFFFF_0001),
+                &codec,
+                &initial,
+                CharacteristicProperties::READ
+                    | CharacteristicProperties::WRITE
+                    | CharacteristicProperties::NOTIFY,
+            )
+            .unwrap();
+
+        let (mut db, write_tokens, value_handles) = registration.build();
+        let handle = Handle::new(0x0003);
+        assert_eq!(write_tokens.get(&handle), Some(&"telemetry"));
+        assert_eq!(value_handles.get(&"telemetry"), Some(&handle));
+        assert_eq!(
+            codec.decode(&db.read(&handle, false, false).unwrap()),
+            Ok(initial)
+        );
+
+        for invalid in [&[][..], &[0x02, 0x00, 0x00, 0x00], &[0; 6]] {
+            assert_eq!(
+                db.write(&handle, invalid, false, false),
+                Err((handle.clone(), ErrorCode::InvalidAttributeValueLength))
+            );
+        }
+        let value = Value::new()
+            .with("seq", 2u16)
+            .with("temp", 25i16)
+            .with("flags", 1u8);
+        db.write(&handle, &codec.encode(&value).unwrap(), false, false)
+            .unwrap();
+        assert_eq!(
+            codec.decode(&db.read(&handle, false, false).unwrap()),
+            Ok(value)
+        );
+
+        // CCCD is not constrained by the codec.
+        db.write(&0x0004.into(), &[0x01, 0x00], false, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_aggregate_format() {
+        let mut registration = Registration::<u8>::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x181A));
+        registration.add_characteristic(
+            Uuid::new_uuid16(0x2A6E),
+            [0; 6],
+            CharacteristicProperties::READ,
+        );
+        let formats = [
+            registration.add_presentation_format(0x0E, -2, 0x272F, 0x01, 0x0000),
+            registration.add_presentation_format(0x0E, -2, 0x272F, 0x01, 0x0001),
+            registration.add_presentation_format(0x0E, -2, 0x272F, 0x01, 0x0002),
+        ];
+        registration.add_characteristic(
+            Uuid::new_uuid16(0x2A6F),
+            [0; 2],
+            CharacteristicProperties::READ,
+        );
+        registration.add_presentation_format(0x06, 0, 0x27AD, 0x01, 0x0000);
+        registration.add_primary_service(Uuid::new_uuid16(0x180F));
+
+        let (db, _, _) = registration.build();
+        let aggregates = db
+            .read_by_type(
+                0x0001.into()..=0xFFFF.into(),
+                &Uuid::new_uuid16(0x2905),
+                false,
+                false,
+            )
+            .unwrap();
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].0, Handle::new(0x0007));
+        assert_eq!(
+            formats,
+            [
+                Handle::new(0x0004),
+                Handle::new(0x0005),
+                Handle::new(0x0006)
+            ]
+        );
+        assert_eq!(
+            &db.read(&0x0007.into(), false, false).unwrap()[..],
+            [0x04, 0x00, 0x05, 0x00, 0x06, 0x00]
+        );
+        assert_eq!(
+            &db.read(&0x0006.into(), false, false).unwrap()[..],
+            [0x0E, 0xFE, 0x2F, 0x27, 0x01, 0x02, 0x00]
+        );
+        assert!(db.read(&0x000B.into(), false, false).is_ok());
+    }
+
+    #[test]
+    fn test_aggregate_format_on_build() {
+        let mut registration = Registration::<u8>::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x181A));
+        registration.add_characteristic(
+            Uuid::new_uuid16(0x2A6E),
+            [0; 4],
+            CharacteristicProperties::READ,
+        );
+        registration.add_presentation_format(0x0E, -2, 0x272F, 0x01, 0x0001);
+        registration.add_presentation_format(0x0E, -2, 0x272F, 0x01, 0x0002);
+        registration.add_descriptor(Uuid::new_uuid16(0x2901), "inside, outside", false);
+
+        let (db, _, _) = registration.build();
+        assert_eq!(
+            &db.read(&0x0006.into(), false, false).unwrap()[..],
+            b"inside, outside"
+        );
+        assert_eq!(
+            &db.read(&0x0007.into(), false, false).unwrap()[..],
+            [0x04, 0x00, 0x05, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_separate_cccd_token() {
+        let mut registration = Registration::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x180D));
+        registration
+            .add_characteristic_with_separate_cccd_token(
+                "measurement",
+                "subscription",
+                Uuid::new_uuid16(0x2A37),
+                "",
+                CharacteristicProperties::NOTIFY,
+            )
+            .unwrap();
+        assert_eq!(
+            registration.add_characteristic_with_separate_cccd_token(
+                "level",
+                "level subscription",
+                Uuid::new_uuid16(0x2A19),
+                "",
+                CharacteristicProperties::READ,
+            ),
+            Err(RegistrationError::NotNotifiable)
+        );
+        assert_eq!(registration.len(), 4);
+
+        let (_, write_handles, notify_handles) = registration.build();
+        assert_eq!(write_handles.len(), 1);
+        assert_eq!(write_handles[&Handle::new(0x0004)], "subscription");
+        assert_eq!(notify_handles[&"measurement"], Handle::new(0x0003));
+
+        let mut registration = Registration::new();
+        registration.at_handle(0xFFFE).unwrap();
+        registration.add_primary_service(Uuid::new_uuid16(0x180D));
+        assert_eq!(
+            registration.add_characteristic_with_separate_cccd_token(
+                "measurement",
+                "subscription",
+                Uuid::new_uuid16(0x2A37),
+                "",
+                CharacteristicProperties::NOTIFY,
+            ),
+            Err(RegistrationError::HandlesExhausted(HandlesExhausted(2)))
+        );
+    }
+
+    #[test]
+    fn test_with_values() {
+        let codec = StructCodec::builder().u16le("seq").build();
+        let mut registration = Registration::new();
+        registration.add_primary_service(Uuid::new_uuid16(0x1800));
+        registration.add_characteristic_with_token(
+            "name",
+            Uuid::new_uuid16(0x2A00),
+            "default",
+            CharacteristicProperties::READ,
+        );
+        registration
+            .add_characteristic_typed(
+                "seq",
+                Uuid::new_uuid128(0xFFFF_0001),
+                &codec,
+                &Value::new().with("seq", 1u16),
+                CharacteristicProperties::READ,
+            )
+            .unwrap();
+        registration.add_characteristic_with_generator(
+            "nonce",
+            Uuid::new_uuid128(0xFFFF_0002),
+            CharacteristicProperties::READ,
+            || [0; 4],
+        );
+
+        assert_eq!(
+            registration
+                .with_values(vec![
+                    ("name", &b"ok"[..]),
+                    ("seq", &[0x01][..]),
+                    ("nonce", &[0; 4][..]),
+                    ("missing", &[][..]),
+                    ("name", &[0; 513][..]),
+                ])
+                .unwrap_err(),
+            InvalidValues(vec!["seq", "nonce", "missing", "name"])
+        );
+
+        let (db, _, _) = registration
+            .with_values(vec![("name", &b"stored"[..]), ("seq", &[0x02, 0x00][..])])
+            .unwrap()
+            .build();
+        assert_eq!(
+            &db.read(&0x0003.into(), false, false).unwrap()[..],
+            b"stored"
+        );
+        assert_eq!(
+            &db.read(&0x0005.into(), false, false).unwrap()[..],
+            [0x02, 0x00]
+        );
+
+        // the table structure is shared, the values are not.
+        let (db, _, _) = registration.build();
+        assert_eq!(
+            &db.read(&0x0003.into(), false, false).unwrap()[..],
+            b"default"
+        );
+    }
 }
@@ -1,47 +1,392 @@
 //! GATT Protocol Server
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
 use std::hash::Hash;
 use std::io;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::iter::FromIterator;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 
+pub use att::machine::MtuState;
 use att::packet as pkt;
 use att::server::{
-    Connection as AttConnection, Error as AttError, ErrorResponse, Handler, Server as AttServer,
+    AttStream, AuthenticationState, Connection as AttConnection, Error as AttError, Handler,
+    HandlerError, Notification as AttNotification, Server as AttServer, SharedMtu,
 };
-pub use att::server::{Indication, Notification};
-use att::Handle;
+pub use att::server::{
+    Indication, L2capMode, Outgoing, ServerState, StatsSnapshot, TryNotifyError, WeakOutgoing,
+};
+use att::{Handle, Uuid};
 use futures_channel::mpsc;
+use futures_util::future::{self, Either, FutureExt};
 use futures_util::stream::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
 
-use crate::database::Database;
+use crate::database::{Database, ReadContext, ValueCheck, CCCD_IMPROPERLY_CONFIGURED};
+use crate::profiles::nus::{NusPipe, NusTokens};
+use crate::registration::InvalidValues;
+use crate::types::ClientCharacteristicConfiguration;
 use crate::Registration;
 
+/// Events buffered for each [`Events`] stream by default.
+pub const DEFAULT_EVENT_CAPACITY: usize = 16;
+
+/// Sequence number of the next [`EventRecord`].
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Stamp `event` and send it to every [`Events`] stream.
+fn emit<T>(tx: &broadcast::Sender<EventRecord<T>>, event: Event<T>) {
+    let record = EventRecord {
+        event,
+        sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        timestamp: SystemTime::now(),
+    };
+    // fails only without any stream.
+    tx.send(record).ok();
+}
+
+/// Enter a `gatt_request` span until the end of the handler method. Needs the
+/// `tracing` feature.
+macro_rules! request_span {
+    ($self:ident, $packet:ty, $handle:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "gatt_request",
+            opcode = ?<$packet as pkt::Packet>::OPCODE,
+            handle = ?$handle,
+            peer = ?$self.peer,
+        )
+        .entered();
+    };
+}
+
 #[derive(Debug)]
-struct GattHandler<T> {
+pub(crate) struct GattHandler<T> {
     db: Database,
     write_tokens: HashMap<Handle, T>,
-    events_txs: Vec<mpsc::UnboundedSender<Event<T>>>,
-    authenticated: Arc<AtomicBool>,
+    events_tx: broadcast::Sender<EventRecord<T>>,
+    authenticated: AuthenticationState,
+    write_command_limit: Option<RateLimiter>,
+    stats: Arc<StatsCounters>,
+    client_config: ClientConfigStore,
+    values: ValueStore<T>,
+    subscription_txs: Vec<mpsc::UnboundedSender<Subscription>>,
+    /// Every value written to these handles, for byte streams that must not
+    /// lose any. (e.g. [`NusPipe`])
+    write_pipes: HashMap<Handle, mpsc::UnboundedSender<Box<[u8]>>>,
+    /// Generated value of the last read, for the Read Blob Requests continuing it.
+    read_snapshot: Option<ReadSnapshot>,
+    /// Negotiated ATT_MTU of the connection the handler runs on.
+    pub(crate) mtu: SharedMtu,
+    #[cfg(feature = "tracing")]
+    peer: Option<att::Address>,
 }
 
 impl<T> GattHandler<T> {
-    fn new(
+    pub(crate) fn new(
         db: Database,
         write_tokens: HashMap<Handle, T>,
-        events_txs: Vec<mpsc::UnboundedSender<Event<T>>>,
-        authenticated: Arc<AtomicBool>,
+        events_tx: broadcast::Sender<EventRecord<T>>,
+        authenticated: AuthenticationState,
     ) -> Self {
+        let client_config = ClientConfigStore::new(&db);
         Self {
             db,
             write_tokens,
-            events_txs,
+            events_tx,
             authenticated,
+            write_command_limit: None,
+            stats: Arc::default(),
+            client_config,
+            values: ValueStore::default(),
+            subscription_txs: vec![],
+            write_pipes: HashMap::new(),
+            read_snapshot: None,
+            mtu: SharedMtu::default(),
+            #[cfg(feature = "tracing")]
+            peer: None,
         }
     }
 
     fn authenticated(&self) -> bool {
-        self.authenticated.load(Ordering::SeqCst)
+        self.authenticated.is_authenticated()
+    }
+
+    fn emit(&self, event: Event<T>) {
+        emit(&self.events_tx, event);
+    }
+
+    /// Write `value` and report it to the app, only once the database accepted it.
+    fn write(
+        &mut self,
+        handle: &Handle,
+        value: &[u8],
+        authenticated: bool,
+    ) -> Result<(), (Handle, pkt::ErrorCode)>
+    where
+        T: Clone,
+    {
+        self.db.write(handle, value, false, authenticated)?;
+        self.client_config.set(handle, value);
+        self.values.set(handle, value);
+        if let Some(uuid) = self.db.client_configuration_owner(handle) {
+            let subscription = Subscription {
+                handle: handle.clone(),
+                uuid: uuid.clone(),
+                config: ClientCharacteristicConfiguration::from_bits_truncate(u16::from_le_bytes(
+                    [value[0], value[1]],
+                )),
+            };
+            for tx in &self.subscription_txs {
+                tx.unbounded_send(subscription.clone()).ok();
+            }
+        }
+        if let Some(tx) = self.write_pipes.get(handle) {
+            tx.unbounded_send(value.into()).ok();
+        }
+        if let Some(token) = self.write_tokens.get(handle) {
+            self.emit(Event::Write(token.clone(), value.to_vec().into()));
+        } else if self.client_config.get(handle).is_none() {
+            // written, but nobody hears of it unless registered with a token.
+            log::debug!(
+                "write to untracked handle {:?}; no event emitted. add the characteristic with a token to receive Event::Write.",
+                handle
+            );
+        }
+        Ok(())
+    }
+
+    /// Read `handle` for a Read Request, or a Read Blob Request at `offset`.
+    /// See [`Database::read_at`].
+    ///
+    /// A read at a non zero offset continues the previous read of a generated
+    /// value, within the ATT transaction timeout.
+    fn read(
+        &mut self,
+        handle: &Handle,
+        offset: usize,
+        blob: bool,
+    ) -> Result<Box<[u8]>, HandlerError> {
+        let now = Instant::now();
+        let snapshot = match &self.read_snapshot {
+            Some(snapshot)
+                if offset > 0 && &snapshot.handle == handle && now < snapshot.expires =>
+            {
+                Some(snapshot.value.clone())
+            }
+            _ => None,
+        };
+        let continued = snapshot.is_some();
+        let ctx = ReadContext {
+            authorized: false,
+            authenticated: self.authenticated(),
+            blob_mtu: if blob { Some(self.mtu.get()) } else { None },
+        };
+        let value = snapshot.or_else(|| self.client_config.value(handle));
+        let value = match self.db.read_at(handle, offset, &ctx, value) {
+            Ok(v) => v,
+            Err((h, e)) => return Err(HandlerError::new(h, e)),
+        };
+        if continued {
+            return Ok(value);
+        }
+        self.read_snapshot = if self.db.is_generated(handle) {
+            Some(ReadSnapshot {
+                handle: handle.clone(),
+                value: value.clone(),
+                expires: now + READ_SNAPSHOT_TIMEOUT,
+            })
+        } else {
+            None
+        };
+        Ok(value)
+    }
+
+    /// Write for a Write Command, which has no response to report errors in.
+    fn write_command(&mut self, handle: &Handle, value: &[u8], authenticated: bool)
+    where
+        T: Clone,
+    {
+        if let Err(err) = self.write(handle, value, authenticated) {
+            if err.1 == CCCD_IMPROPERLY_CONFIGURED {
+                self.stats
+                    .protocol_violations
+                    .fetch_add(1, Ordering::Relaxed);
+                log::debug!(
+                    "client characteristic configuration {:?} written with unsupported bits {:02X?}",
+                    handle,
+                    value
+                );
+            }
+            log::warn!("write command to {} failed: {:?}", err.0, err.1);
+        }
+    }
+
+    /// Count a Write Command and decide whether to process it.
+    fn accept_write_command(&mut self) -> bool {
+        let stats = &self.stats;
+        stats
+            .write_commands_received
+            .fetch_add(1, Ordering::Relaxed);
+        let accept = match &mut self.write_command_limit {
+            Some(limit) => limit.acquire(Instant::now()),
+            None => true,
+        };
+        if !accept {
+            stats.write_commands_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        accept
+    }
+}
+
+/// ATT transaction timeout. (3.3.3)
+const READ_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct ReadSnapshot {
+    handle: Handle,
+    value: Box<[u8]>,
+    expires: Instant,
+}
+
+/// Token bucket. Holds up to `burst` tokens, refilled at `per_second`.
+#[derive(Debug)]
+struct RateLimiter {
+    per_second: u32,
+    burst: u32,
+    tokens: u32,
+    /// Time the next token is due.
+    next: Instant,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32, burst: u32) -> Self {
+        Self {
+            per_second,
+            burst,
+            tokens: burst,
+            next: Instant::now(),
+        }
+    }
+
+    fn acquire(&mut self, now: Instant) -> bool {
+        if self.per_second > 0 {
+            let interval = Duration::from_secs(1) / self.per_second;
+            while self.tokens < self.burst && self.next <= now {
+                self.tokens += 1;
+                self.next += interval;
+            }
+            if self.tokens == self.burst {
+                self.next = now + interval;
+            }
+        }
+
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StatsCounters {
+    write_requests_received: AtomicU64,
+    write_commands_received: AtomicU64,
+    write_commands_dropped: AtomicU64,
+    protocol_violations: AtomicU64,
+}
+
+impl StatsCounters {
+    fn snapshot(&self, protocol: StatsSnapshot) -> ConnectionStats {
+        let StatsSnapshot {
+            received,
+            error_responses,
+            notifications_sent,
+            indications_sent,
+            confirmations_received,
+            bytes_received,
+            bytes_sent,
+            mtu,
+            outgoing_queue_depth,
+            uptime,
+        } = protocol;
+        ConnectionStats {
+            received,
+            error_responses,
+            notifications_sent,
+            indications_sent,
+            confirmations_received,
+            bytes_received,
+            bytes_sent,
+            mtu,
+            outgoing_queue_depth,
+            uptime,
+            write_requests_received: self.write_requests_received.load(Ordering::Relaxed),
+            write_commands_received: self.write_commands_received.load(Ordering::Relaxed),
+            write_commands_dropped: self.write_commands_dropped.load(Ordering::Relaxed),
+            protocol_violations: self.protocol_violations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of a [`Connection`] at one point in time.
+///
+/// Obtained by [`Connection::stats`] or, while the connection is running,
+/// [`StatsHandle::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// PDUs received, by Op Code. PDUs of unknown Op Codes are not counted.
+    pub received: HashMap<pkt::OpCode, u64>,
+    /// Error Responses sent, by Error Code.
+    pub error_responses: HashMap<pkt::ErrorCode, u64>,
+    pub notifications_sent: u64,
+    pub indications_sent: u64,
+    pub confirmations_received: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    /// Negotiated ATT_MTU.
+    pub mtu: usize,
+    /// Packets waiting in the outgoing queue.
+    pub outgoing_queue_depth: usize,
+    /// Time since the connection was created.
+    pub uptime: Duration,
+    pub write_requests_received: u64,
+    /// Write Commands and Signed Write Commands, including dropped ones.
+    pub write_commands_received: u64,
+    /// Write Commands ignored by the rate limit.
+    pub write_commands_dropped: u64,
+    /// Write Commands the client should not have sent, as they would be
+    /// answered with an error: Client Characteristic Configuration values
+    /// with bits the characteristic does not support.
+    pub protocol_violations: u64,
+}
+
+/// Reads the counters of a [`Connection`], also while it is running.
+pub struct StatsHandle<IO = AttStream> {
+    counters: Arc<StatsCounters>,
+    outgoing: Outgoing<IO>,
+}
+
+impl<IO> Clone for StatsHandle<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            counters: self.counters.clone(),
+            outgoing: self.outgoing.clone(),
+        }
+    }
+}
+
+impl<IO> StatsHandle<IO> {
+    pub fn stats(&self) -> ConnectionStats {
+        self.counters.snapshot(self.outgoing.stats())
     }
 }
 
@@ -49,31 +394,26 @@ impl<T> Handler for GattHandler<T>
 where
     T: Clone,
 {
-    fn handle_exchange_mtu_request(
-        &mut self,
-        item: &pkt::ExchangeMtuRequest,
-    ) -> Result<pkt::ExchangeMtuResponse, ErrorResponse> {
-        Ok(pkt::ExchangeMtuResponse::new(*item.client_rx_mtu()))
-    }
-
     fn handle_find_information_request(
         &mut self,
         item: &pkt::FindInformationRequest,
-    ) -> Result<pkt::FindInformationResponse, ErrorResponse> {
+    ) -> Result<pkt::FindInformationResponse, HandlerError> {
+        request_span!(self, pkt::FindInformationRequest, item.starting_handle());
         let r = match self
             .db
             .find_information(item.starting_handle().clone()..=item.ending_handle().clone())
         {
             Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
+            Err((h, e)) => return Err(HandlerError::new(h, e)),
         };
-        Ok(r.into_iter().map(Into::into).collect())
+        Ok(r.into_iter().collect())
     }
 
     fn handle_find_by_type_value_request(
         &mut self,
         item: &pkt::FindByTypeValueRequest,
-    ) -> Result<pkt::FindByTypeValueResponse, ErrorResponse> {
+    ) -> Result<pkt::FindByTypeValueResponse, HandlerError> {
+        request_span!(self, pkt::FindByTypeValueRequest, item.starting_handle());
         let r = match self.db.find_by_type_value(
             item.starting_handle().clone()..=item.ending_handle().clone(),
             item.attribute_type(),
@@ -82,15 +422,16 @@ where
             self.authenticated(),
         ) {
             Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
+            Err((h, e)) => return Err(HandlerError::new(h, e)),
         };
-        Ok(r.into_iter().map(Into::into).collect())
+        Ok(r.into_iter().collect())
     }
 
     fn handle_read_by_type_request(
         &mut self,
         item: &pkt::ReadByTypeRequest,
-    ) -> Result<pkt::ReadByTypeResponse, ErrorResponse> {
+    ) -> Result<pkt::ReadByTypeResponse, HandlerError> {
+        request_span!(self, pkt::ReadByTypeRequest, item.starting_handle());
         let r = match self.db.read_by_type(
             item.starting_handle().clone()..=item.ending_handle().clone(),
             item.attribute_type(),
@@ -98,44 +439,40 @@ where
             self.authenticated(),
         ) {
             Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
+            Err((h, e)) => return Err(HandlerError::new(h, e)),
         };
-        Ok(r.into_iter().map(Into::into).collect())
+        Ok(r.into_iter()
+            .map(|(h, v)| {
+                let v = self.client_config.value(&h).unwrap_or(v);
+                (h, v)
+            })
+            .collect())
     }
 
     fn handle_read_request(
         &mut self,
         item: &pkt::ReadRequest,
-    ) -> Result<pkt::ReadResponse, ErrorResponse> {
-        let r = match self
-            .db
-            .read(item.attribute_handle(), false, self.authenticated())
-        {
-            Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
-        };
+    ) -> Result<pkt::ReadResponse, HandlerError> {
+        request_span!(self, pkt::ReadRequest, item.attribute_handle());
+        let r = self.read(item.attribute_handle(), 0, false)?;
         Ok(pkt::ReadResponse::new(r))
     }
 
     fn handle_read_blob_request(
         &mut self,
         item: &pkt::ReadBlobRequest,
-    ) -> Result<pkt::ReadBlobResponse, ErrorResponse> {
-        let r = match self
-            .db
-            .read(item.attribute_handle(), false, self.authenticated())
-        {
-            Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
-        };
+    ) -> Result<pkt::ReadBlobResponse, HandlerError> {
+        request_span!(self, pkt::ReadBlobRequest, item.attribute_handle());
         let offset = *item.attribute_offset() as usize;
+        let r = self.read(item.attribute_handle(), offset, true)?;
         Ok(pkt::ReadBlobResponse::new(r[offset..].into()))
     }
 
     fn handle_read_by_group_type_request(
         &mut self,
         item: &pkt::ReadByGroupTypeRequest,
-    ) -> Result<pkt::ReadByGroupTypeResponse, ErrorResponse> {
+    ) -> Result<pkt::ReadByGroupTypeResponse, HandlerError> {
+        request_span!(self, pkt::ReadByGroupTypeRequest, item.starting_handle());
         let r = match self.db.read_by_group_type(
             item.starting_handle().clone()..=item.ending_handle().clone(),
             item.attribute_group_type(),
@@ -143,208 +480,1274 @@ where
             self.authenticated(),
         ) {
             Ok(v) => v,
-            Err((h, e)) => return Err(ErrorResponse::new(h, e)),
+            Err((h, e)) => return Err(HandlerError::new(h, e)),
         };
-        Ok(r.into_iter().map(Into::into).collect())
+        Ok(r.into_iter().collect())
     }
 
     fn handle_write_request(
         &mut self,
         item: &pkt::WriteRequest,
-    ) -> Result<pkt::WriteResponse, ErrorResponse> {
-        let value = item.attribute_value();
-        if let Some(token) = self.write_tokens.get(item.attribute_handle()) {
-            for tx in &self.events_txs {
-                tx.unbounded_send(Event::Write(token.clone(), value.to_vec().into()))
-                    .ok();
-            }
+    ) -> Result<pkt::WriteResponse, HandlerError> {
+        request_span!(self, pkt::WriteRequest, item.attribute_handle());
+        self.stats
+            .write_requests_received
+            .fetch_add(1, Ordering::Relaxed);
+        match self.write(item.attribute_handle(), item.attribute_value(), false) {
+            Ok(()) => Ok(pkt::WriteResponse::new()),
+            Err((h, e)) => Err(HandlerError::new(h, e)),
         }
+    }
 
-        match self.db.write(item.attribute_handle(), value, false, false) {
-            Ok(_) => Ok(pkt::WriteResponse::new()),
-            Err((h, e)) => Err(ErrorResponse::new(h, e)),
+    fn handle_write_command(&mut self, item: &pkt::WriteCommand) {
+        request_span!(self, pkt::WriteCommand, item.attribute_handle());
+        if !self.accept_write_command() {
+            return;
         }
+        self.write_command(item.attribute_handle(), item.attribute_value(), false);
     }
 
-    fn handle_write_command(&mut self, item: &pkt::WriteCommand) {
-        let value = item.attribute_value();
-        if let Some(token) = self.write_tokens.get(item.attribute_handle()) {
-            for tx in &self.events_txs {
-                tx.unbounded_send(Event::Write(token.clone(), value.to_vec().into()))
-                    .ok();
+    fn handle_signed_write_command(&mut self, item: &pkt::SignedWriteCommand) {
+        request_span!(self, pkt::SignedWriteCommand, item.attribute_handle());
+        if !self.accept_write_command() {
+            return;
+        }
+        self.write_command(item.attribute_handle(), item.attribute_value(), true);
+    }
+}
+
+/// Client Characteristic Configuration and Server Characteristic
+/// Configuration descriptor values of a connection, by descriptor handle.
+///
+/// Restore it with [`Connection::restore_client_config`] for bonded peers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientConfig(BTreeMap<Handle, u16>);
+
+impl ClientConfig {
+    pub fn get(&self, handle: &Handle) -> Option<u16> {
+        self.0.get(handle).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Handle, u16)> {
+        self.0.iter().map(|(h, v)| (h, *v))
+    }
+}
+
+impl FromIterator<(Handle, u16)> for ClientConfig {
+    fn from_iter<I: IntoIterator<Item = (Handle, u16)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Descriptor values of a [`Connection`], starting at zero. Clones share the values.
+///
+/// Reads and writes of these descriptors are served from here, not from the
+/// attribute table.
+#[derive(Debug, Clone)]
+pub struct ClientConfigStore(Arc<Mutex<BTreeMap<Handle, u16>>>);
+
+impl ClientConfigStore {
+    fn new(db: &Database) -> Self {
+        let values = db.configuration_handles().map(|h| (h.clone(), 0)).collect();
+        Self(Arc::new(Mutex::new(values)))
+    }
+
+    pub fn get(&self, handle: &Handle) -> Option<u16> {
+        self.0.lock().unwrap().get(handle).copied()
+    }
+
+    /// Snapshot of the current values.
+    pub fn export(&self) -> ClientConfig {
+        ClientConfig(self.0.lock().unwrap().clone())
+    }
+
+    /// Overwrite values with `config`, ignoring handles without such a descriptor.
+    pub fn restore(&self, config: &ClientConfig) {
+        let mut values = self.0.lock().unwrap();
+        for (handle, v) in config.iter() {
+            if let Some(slot) = values.get_mut(handle) {
+                *slot = v;
             }
         }
+    }
 
-        if let Err(err) = self.db.write(
-            item.attribute_handle(),
-            item.attribute_value(),
-            false,
-            false,
-        ) {
-            log::warn!("{:?}", err);
+    fn value(&self, handle: &Handle) -> Option<Box<[u8]>> {
+        self.get(handle).map(|v| v.to_le_bytes().to_vec().into())
+    }
+
+    /// Store a written value. The database validated its length already.
+    fn set(&self, handle: &Handle, value: &[u8]) {
+        if let Some(slot) = self.0.lock().unwrap().get_mut(handle) {
+            *slot = u16::from_le_bytes([value[0], value[1]]);
+        }
+    }
+}
+
+/// Values of the characteristics of a [`Connection`] added with a token,
+/// unless generated. Clones share the values.
+///
+/// Updated as clients write them, e.g. to persist them at disconnect and
+/// serve them again with [`Server::accept_with_values`].
+#[derive(Debug, Clone)]
+pub struct ValueStore<T>(Arc<Mutex<TokenValues<T>>>);
+
+/// Value handle -> token and value.
+type TokenValues<T> = BTreeMap<Handle, (T, Box<[u8]>)>;
+
+impl<T> Default for ValueStore<T> {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(BTreeMap::new())))
+    }
+}
+
+impl<T> ValueStore<T>
+where
+    T: Clone,
+{
+    fn new(registration: &Registration<T>) -> Self
+    where
+        T: Hash + Eq,
+    {
+        let values = registration
+            .stored_values()
+            .map(|(token, handle, value)| (handle.clone(), (token.clone(), value.into())))
+            .collect();
+        Self(Arc::new(Mutex::new(values)))
+    }
+
+    /// Snapshot of the current values by token, in handle order.
+    pub fn snapshot(&self) -> Vec<(T, Box<[u8]>)> {
+        self.0.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Store a written value. The database validated it already.
+    fn set(&self, handle: &Handle, value: &[u8]) {
+        if let Some((_, slot)) = self.0.lock().unwrap().get_mut(handle) {
+            *slot = value.into();
+        }
+    }
+}
+
+/// Notifications retained while the client is not subscribed, by value handle.
+#[derive(Debug, Clone, Default)]
+struct ReplayBuffers(Arc<Mutex<HashMap<Handle, ReplayBuffer>>>);
+
+#[derive(Debug)]
+struct ReplayBuffer {
+    /// Client Characteristic Configuration descriptor of the characteristic.
+    cccd: Handle,
+    capacity: usize,
+    values: VecDeque<Box<[u8]>>,
+    /// The front value is being replayed. It stays until it is queued, so
+    /// that no newer value overtakes it.
+    sending: bool,
+}
+
+pub(crate) fn notifications_enabled(config: &ClientConfigStore, cccd: &Handle) -> bool {
+    let bits = config.get(cccd).unwrap_or_default();
+    ClientCharacteristicConfiguration::from_bits_truncate(bits)
+        .contains(ClientCharacteristicConfiguration::NOTIFICATION)
+}
+
+impl ReplayBuffers {
+    fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    fn handles(&self) -> Vec<Handle> {
+        self.0.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn insert(&self, handle: Handle, cccd: Handle, capacity: usize) {
+        let buffer = ReplayBuffer {
+            cccd,
+            capacity,
+            values: VecDeque::with_capacity(capacity),
+            sending: false,
         };
+        self.0.lock().unwrap().insert(handle, buffer);
     }
 
-    fn handle_signed_write_command(&mut self, item: &pkt::SignedWriteCommand) {
-        let value = item.attribute_value();
-        if let Some(token) = self.write_tokens.get(item.attribute_handle()) {
-            for tx in &self.events_txs {
-                tx.unbounded_send(Event::Write(token.clone(), value.to_vec().into()))
-                    .ok();
+    /// Keep `value` for later unless it can be notified now, i.e. the client
+    /// is subscribed and no older value is waiting.
+    fn retain(&self, handle: &Handle, value: &[u8], config: &ClientConfigStore) -> bool {
+        let mut buffers = self.0.lock().unwrap();
+        let buffer = match buffers.get_mut(handle) {
+            Some(buffer) => buffer,
+            None => return false,
+        };
+        if buffer.values.is_empty() && notifications_enabled(config, &buffer.cccd) {
+            return false;
+        }
+        if buffer.capacity > 0 {
+            // the value being replayed is neither dropped nor counted.
+            let sending = usize::from(buffer.sending);
+            if buffer.values.len() - sending == buffer.capacity {
+                buffer.values.remove(sending);
             }
+            buffer.values.push_back(value.into());
         }
+        true
+    }
 
-        if let Err(err) =
-            self.db
-                .write(item.attribute_handle(), item.attribute_value(), false, true)
-        {
-            log::warn!("{:?}", err);
-        };
+    /// Oldest value of `handle` to replay, if the client is subscribed. It
+    /// is kept until [`replayed`](Self::replayed).
+    fn next_replay(&self, handle: &Handle, config: &ClientConfigStore) -> Option<Box<[u8]>> {
+        let mut buffers = self.0.lock().unwrap();
+        let buffer = buffers.get_mut(handle)?;
+        buffer.sending = !buffer.values.is_empty() && notifications_enabled(config, &buffer.cccd);
+        if buffer.sending {
+            buffer.values.front().cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Drop the value [`next_replay`](Self::next_replay) returned, once queued.
+    fn replayed(&self, handle: &Handle) {
+        if let Some(buffer) = self.0.lock().unwrap().get_mut(handle) {
+            buffer.values.pop_front();
+            buffer.sending = false;
+        }
+    }
+
+    /// Notify retained values whenever the client subscribes, until
+    /// `subscriptions` ends with the connection.
+    async fn replay<IO>(
+        self,
+        config: ClientConfigStore,
+        mut notifications: HashMap<Handle, AttNotification<IO>>,
+        mut subscriptions: mpsc::UnboundedReceiver<Subscription>,
+    ) {
+        loop {
+            for (handle, notification) in &mut notifications {
+                while let Some(value) = self.next_replay(handle, &config) {
+                    let result = notification.notify(&value).await;
+                    self.replayed(handle);
+                    if let Err(err) = result {
+                        log::warn!("failed to replay notification. {}", err);
+                        break;
+                    }
+                }
+            }
+            if subscriptions.next().await.is_none() {
+                return;
+            }
+        }
     }
 }
 
-/// Error for [`Control::notify`] | [`Control::indicate`]
-#[derive(Debug, thiserror::Error)]
-#[error("channel error")]
-pub struct ChannelError;
+/// How the link was paired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PairingMethod {
+    JustWorks,
+    PasskeyEntry,
+    OutOfBand,
+    NumericComparison,
+}
+
+/// Peer and pairing method recorded by
+/// [`Authenticator::mark_authenticated_with_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthInfo {
+    pub peer: att::Address,
+    pub method: PairingMethod,
+}
 
 /// GATT Server control.
 #[derive(Debug)]
 pub struct Authenticator {
-    authenticated: Arc<AtomicBool>,
+    authenticated: AuthenticationState,
+    /// Address of the connection's peer.
+    peer: att::Address,
+    info: Arc<Mutex<Option<AuthInfo>>>,
 }
 
 impl Authenticator {
+    #[deprecated(note = "use `mark_authenticated_with_info` to record the pairing method")]
     pub fn mark_authenticated(&self) {
-        self.authenticated.store(true, Ordering::SeqCst);
+        self.authenticated.mark_authenticated();
+    }
+
+    /// Mark the link authenticated, recording that the peer of the
+    /// connection paired by `method`. See [`Connection::auth_info`].
+    pub fn mark_authenticated_with_info(&self, method: PairingMethod) {
+        *self.info.lock().unwrap() = Some(AuthInfo {
+            peer: self.peer.clone(),
+            method,
+        });
+        self.authenticated.mark_authenticated();
     }
 }
 
 /// GATT Event
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Event<T> {
+    /// [`Connection::run`] started. Precedes every other event of the connection.
+    Connected(att::Address),
     Write(T, Box<[u8]>),
+    /// [`Connection::run`] is returning, with the error it fails with if any.
+    /// No event follows.
+    Disconnected(att::Address, Option<Arc<io::Error>>),
 }
 
-/// GATT Event Stream
-#[derive(Debug)]
-pub struct Events<T>(mpsc::UnboundedReceiver<Event<T>>);
-
-impl<T> Events<T> {
-    pub async fn next(&mut self) -> Option<Event<T>> {
-        self.0.next().await
+impl<T> Event<T> {
+    /// Kind of this event.
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Connected(..) => EventKind::Connected,
+            Self::Write(..) => EventKind::Write,
+            Self::Disconnected(..) => EventKind::Disconnected,
+        }
     }
-}
 
-#[derive(Debug, thiserror::Error)]
-#[error("handle not found.")]
-pub struct HandleNotFound;
+    /// Token of the characteristic this event is about.
+    pub fn token(&self) -> Option<&T> {
+        match self {
+            Self::Write(token, _) => Some(token),
+            Self::Connected(..) | Self::Disconnected(..) => None,
+        }
+    }
 
-/// Run [`Connection::run`]
-#[derive(Debug, thiserror::Error)]
-#[error(transparent)]
-pub struct RunError(#[from] AttError);
+    /// Value carried by this event.
+    pub fn payload(&self) -> Option<&[u8]> {
+        match self {
+            Self::Write(_, value) => Some(value),
+            Self::Connected(..) | Self::Disconnected(..) => None,
+        }
+    }
+}
 
-/// GATT Connection
-pub struct Connection<T> {
-    inner: AttConnection,
-    event_txs: Vec<mpsc::UnboundedSender<Event<T>>>,
-    db: Database,
-    write_tokens: HashMap<Handle, T>,
-    notify_or_indicate_handles: HashMap<T, Handle>,
-    authenticated: Arc<AtomicBool>, // TODO
+/// `connected <address>`, `write <token> <payload>` or
+/// `disconnected <address>[: <error>]`.
+impl<T> fmt::Display for Event<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected(address) => write!(f, "connected {}", address),
+            Self::Write(token, value) => write!(f, "write {:?} {:02X?}", token, value),
+            Self::Disconnected(address, None) => write!(f, "disconnected {}", address),
+            Self::Disconnected(address, Some(err)) => {
+                write!(f, "disconnected {}: {}", address, err)
+            }
+        }
+    }
 }
 
-impl<T> Connection<T>
+/// Serialized as `{"kind": .., "token": .., "payload": ..}`.
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Event<T>
 where
-    T: Eq + Hash + Clone,
+    T: serde::Serialize,
 {
-    fn new(inner: AttConnection, registration: Registration<T>) -> Self {
-        let (db, write_tokens, notify_or_indicate_handles) = registration.build();
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
 
-        Self {
-            inner,
-            event_txs: vec![],
-            db,
-            write_tokens,
-            notify_or_indicate_handles,
-            authenticated: Arc::new(AtomicBool::from(false)),
-        }
+        let mut state = serializer.serialize_struct("Event", 3)?;
+        state.serialize_field("kind", &self.kind())?;
+        state.serialize_field("token", &self.token())?;
+        state.serialize_field("payload", &self.payload())?;
+        state.end()
     }
+}
 
-    pub fn authenticator(&self) -> Authenticator {
-        Authenticator {
-            authenticated: self.authenticated.clone(),
-        }
+/// Kind of [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EventKind {
+    Connected,
+    Write,
+    Disconnected,
+}
+
+/// [`Event`] with the time it was emitted.
+///
+/// The timestamp is wall clock time (`SystemTime`) taken when the server
+/// handled the packet, so it can be correlated with other systems. It is not
+/// guaranteed to be monotonic if the system clock is adjusted; order events by
+/// [`sequence`](Self::sequence) instead.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EventRecord<T> {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    event: Event<T>,
+    sequence: u64,
+    timestamp: SystemTime,
+}
+
+impl<T> EventRecord<T> {
+    pub fn event(&self) -> &Event<T> {
+        &self.event
     }
 
-    pub fn events(&mut self) -> Events<T> {
-        let (tx, rx) = mpsc::unbounded();
-        self.event_txs.push(tx);
-        Events(rx)
+    pub fn into_event(self) -> Event<T> {
+        self.event
     }
 
-    pub fn notification(&self, token: &T) -> Result<Notification, HandleNotFound> {
-        if let Some(handle) = self.notify_or_indicate_handles.get(token) {
-            let notification = self.inner.notification(handle.clone());
-            Ok(notification)
-        } else {
-            Err(HandleNotFound)
-        }
+    /// Increases with every event emitted in this process, across connections.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
     }
 
-    pub fn indication(&self, token: &T) -> Result<Indication, HandleNotFound> {
-        if let Some(handle) = self.notify_or_indicate_handles.get(token) {
-            let indication = self.inner.indication(handle.clone());
-            Ok(indication)
-        } else {
-            Err(HandleNotFound)
-        }
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
     }
+}
 
-    pub fn address(&self) -> &att::Address {
-        self.inner.address()
+/// GATT Event Stream
+///
+/// Every stream of a connection receives every event. A stream falling
+/// further behind than the capacity of the connection (see
+/// [`Connection::set_event_capacity`]) loses the oldest events.
+#[derive(Debug)]
+pub struct Events<T>(pub(crate) broadcast::Receiver<EventRecord<T>>);
+
+impl<T> Events<T>
+where
+    T: Clone,
+{
+    pub async fn next(&mut self) -> Option<Event<T>> {
+        self.next_record().await.map(EventRecord::into_event)
     }
 
-    pub async fn run(self) -> Result<(), RunError> {
-        let Self {
-            db,
-            write_tokens,
-            event_txs,
-            authenticated,
-            ..
-        } = self;
-        self.inner
-            .run(GattHandler::<T>::new(
-                db,
-                write_tokens,
-                event_txs,
-                authenticated,
-            ))
-            .await?;
-        Ok(())
+    /// Next event with its metadata.
+    pub async fn next_record(&mut self) -> Option<EventRecord<T>> {
+        loop {
+            match self.0.recv().await {
+                Ok(record) => return Some(record),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    log::warn!("{} events dropped; the event stream lagged behind.", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
     }
 }
 
-/// GATT Protocol Server
-pub struct Server {
-    inner: AttServer,
+/// Client Characteristic Configuration descriptor written by the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    handle: Handle,
+    uuid: Uuid,
+    config: ClientCharacteristicConfiguration,
 }
 
-impl Server {
-    pub fn bind() -> io::Result<Self> {
-        let server = AttServer::new()?;
-        Ok(Self { inner: server })
+impl Subscription {
+    /// Handle of the descriptor.
+    pub fn handle(&self) -> &Handle {
+        &self.handle
     }
 
-    /// Accept [`Connection`]
-    pub async fn accept<T>(
-        &mut self,
-        registration: Registration<T>,
-    ) -> io::Result<Option<Connection<T>>>
-    where
-        T: Eq + Hash + Clone,
+    /// Type of the characteristic the descriptor belongs to.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+
+    /// Written value.
+    pub fn config(&self) -> ClientCharacteristicConfiguration {
+        self.config
+    }
+}
+
+/// Stream of [`Subscription`]s obtained by [`Connection::subscriptions`].
+#[derive(Debug)]
+pub struct Subscriptions(pub(crate) mpsc::UnboundedReceiver<Subscription>);
+
+impl Subscriptions {
+    pub async fn next(&mut self) -> Option<Subscription> {
+        self.0.next().await
+    }
+}
+
+/// No notifiable / indicatable characteristic is registered with the token.
+#[derive(Debug, thiserror::Error)]
+#[error("handle not found for token {0:?}.")]
+pub struct HandleNotFound<T>(pub T);
+
+/// Error of [`Connection::set_event_capacity`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EventCapacityError {
+    #[error("event capacity must not be zero.")]
+    Zero,
+
+    /// [`Connection::events`] was called already.
+    #[error("event capacity set after events() was called.")]
+    AlreadySubscribed,
+}
+
+/// Error of [`Server::accept_with_values`].
+#[derive(Debug, thiserror::Error)]
+pub enum AcceptWithValuesError<T> {
+    /// Values rejected before accepting a connection.
+    #[error(transparent)]
+    InvalidValues(#[from] InvalidValues<T>),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Notification writer obtained by [`Connection::notification`]. See
+/// [`att::server::Notification`].
+///
+/// While the client is not subscribed, values of a characteristic set up
+/// with [`Connection::buffer_notifications`] are retained instead.
+pub struct Notification<IO = AttStream> {
+    inner: AttNotification<IO>,
+    handle: Handle,
+    replay: ReplayBuffers,
+    client_config: ClientConfigStore,
+    check: Option<ValueCheck>,
+}
+
+impl<IO> Clone for Notification<IO> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            handle: self.handle.clone(),
+            replay: self.replay.clone(),
+            client_config: self.client_config.clone(),
+            check: self.check.clone(),
+        }
+    }
+}
+
+impl<IO> Notification<IO> {
+    fn retain(&self, value: &[u8]) -> bool {
+        self.replay.retain(&self.handle, value, &self.client_config)
+    }
+
+    /// Reject `value` if the characteristic does not allow it. (e.g. a
+    /// Battery Level over 100)
+    fn check(&self, value: &[u8]) -> io::Result<()> {
+        match &self.check {
+            Some(check) => check.check(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Queue a notification, waiting while the outgoing queue is full.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the characteristic does
+    /// not allow `value`.
+    pub async fn notify(&mut self, value: &[u8]) -> Result<(), AttError> {
+        self.check(value)?;
+        if self.retain(value) {
+            return Ok(());
+        }
+        self.inner.notify(value).await
+    }
+
+    /// Queue a notification if the outgoing queue has space.
+    pub fn try_notify(&self, value: &[u8]) -> Result<(), TryNotifyError> {
+        self.check(value).map_err(AttError::from)?;
+        if self.retain(value) {
+            return Ok(());
+        }
+        self.inner.try_notify(value)
+    }
+
+    /// Negotiated ATT_MTU of the connection. A notification carries at most
+    /// `current_mtu() - 3` bytes of value.
+    pub fn current_mtu(&self) -> usize {
+        self.inner.current_mtu()
+    }
+}
+
+impl<IO> AsyncWrite for Notification<IO>
+where
+    IO: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.check(buf)?;
+        if this.retain(buf) {
+            return Poll::Ready(Ok(buf.len()));
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Notification sender obtained by [`Connection::notification_sender`].
+pub struct NotificationSender<T, IO = AttStream> {
+    token: T,
+    notification: Notification<IO>,
+}
+
+impl<T, IO> Clone for NotificationSender<T, IO>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            token: self.token.clone(),
+            notification: self.notification.clone(),
+        }
+    }
+}
+
+impl<T, IO> NotificationSender<T, IO> {
+    pub fn token(&self) -> &T {
+        &self.token
+    }
+
+    /// Queue a notification, waiting while the outgoing queue is full.
+    ///
+    /// Fails with [`AttError::StaleHandle`] once the attribute table changed,
+    /// and [`AttError::NotReady`] if `value` does not fit in a packet. See
+    /// [`Connection::buffer_notifications`] for values retained instead.
+    pub async fn notify(&self, value: &[u8]) -> Result<(), AttError> {
+        self.notification.clone().notify(value).await
+    }
+
+    /// Queue a notification if the outgoing queue has space.
+    pub fn try_notify(&self, value: &[u8]) -> Result<(), TryNotifyError> {
+        self.notification.try_notify(value)
+    }
+
+    /// Negotiated ATT_MTU of the connection.
+    pub fn current_mtu(&self) -> usize {
+        self.notification.current_mtu()
+    }
+}
+
+/// Error of [`Connection::run`].
+///
+/// A client closing the connection cleanly, so that a read returns no
+/// bytes, ends `run` with `Ok(())` rather than
+/// [`PeerDisconnected`](Self::PeerDisconnected): it is how every connection
+/// ends, and `run().await?` must not fail on it. [`Connection::disconnected`]
+/// reports both as [`DisconnectReason::Closed`]. Requests the attribute table refuses are
+/// answered with an Error Response and do not end the connection, so there
+/// is no variant for them.
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    /// The transport reported the end of the connection as an error
+    /// ([`io::ErrorKind::UnexpectedEof`]). Handle it as a normal disconnect.
+    #[error("peer disconnected.")]
+    PeerDisconnected(#[source] io::Error),
+
+    /// Reading from or writing to the connection failed, e.g. the link was
+    /// reset or timed out. The connection may have been dropped.
+    #[error("connection transport failed.")]
+    Transport(#[source] io::Error),
+
+    /// A packet to send could not be encoded, or a queued packet broke the protocol.
+    #[error("attribute protocol failed.")]
+    Protocol(#[source] AttError),
+
+    /// An indication was not confirmed within the timeout set by
+    /// [`Connection::set_indication_timeout`]. The client has to reconnect.
+    #[error("ATT transaction timed out.")]
+    TransactionTimeout,
+}
+
+impl From<AttError> for RunError {
+    fn from(err: AttError) -> Self {
+        match err {
+            AttError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                Self::PeerDisconnected(err)
+            }
+            AttError::Io(err) => Self::Transport(err),
+            AttError::TransactionTimeout => Self::TransactionTimeout,
+            err => Self::Protocol(err),
+        }
+    }
+}
+
+/// How a connection ended, reported by [`Connection::disconnected`].
+#[derive(Debug, Clone)]
+pub enum DisconnectReason {
+    /// The client closed the connection. Also when the transport reported it
+    /// as [`RunError::PeerDisconnected`].
+    Closed,
+    /// `shutdown` of [`Connection::run_with_shutdown`] completed.
+    Shutdown,
+    /// [`Connection::run`] failed. The same error as in
+    /// [`Event::Disconnected`].
+    Failed(Arc<io::Error>),
+    /// The connection, or the future running it, was dropped before it ended.
+    Dropped,
+}
+
+/// Handles, type and properties a token of a [`Connection`] resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharacteristicInfo {
+    pub declaration_handle: Handle,
+    pub value_handle: Handle,
+    /// Client Characteristic Configuration descriptor, for characteristics
+    /// that notify or indicate.
+    pub cccd_handle: Option<Handle>,
+    pub uuid: Uuid,
+    pub properties: crate::CharacteristicProperties,
+}
+
+impl CharacteristicInfo {
+    fn resolve(db: &Database, value_handle: &Handle) -> Option<Self> {
+        let (declaration_handle, properties, extended_properties, uuid) =
+            db.characteristic_of(value_handle)?;
+        Some(Self {
+            declaration_handle: declaration_handle.clone(),
+            value_handle: value_handle.clone(),
+            cccd_handle: db.client_configuration_of(value_handle).cloned(),
+            uuid: uuid.clone(),
+            properties: (properties, extended_properties).into(),
+        })
+    }
+}
+
+/// GATT Connection
+pub struct Connection<T, IO = AttStream> {
+    inner: AttConnection<IO>,
+    event_tx: broadcast::Sender<EventRecord<T>>,
+    db: Database,
+    write_tokens: HashMap<Handle, T>,
+    authenticated: AuthenticationState,
+    auth_info: Arc<Mutex<Option<AuthInfo>>>,
+    write_command_limit: Option<RateLimiter>,
+    stats: Arc<StatsCounters>,
+    client_config: ClientConfigStore,
+    values: ValueStore<T>,
+    subscription_txs: Vec<mpsc::UnboundedSender<Subscription>>,
+    write_pipes: HashMap<Handle, mpsc::UnboundedSender<Box<[u8]>>>,
+    replay: ReplayBuffers,
+    tokens: HashMap<T, CharacteristicInfo>,
+    /// Set once [`run`](Connection::run) returns.
+    disconnected: (
+        watch::Sender<Option<DisconnectReason>>,
+        watch::Receiver<Option<DisconnectReason>>,
+    ),
+}
+
+impl<T, IO> Connection<T, IO>
+where
+    T: Eq + Hash + Clone,
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Serve `registration` over an ATT connection.
+    ///
+    /// [`Server::accept`] does this for accepted L2CAP sockets.
+    pub fn new(inner: AttConnection<IO>, registration: Registration<T>) -> Self {
+        let values = ValueStore::new(&registration);
+        let (db, write_tokens, value_handles) = registration.build();
+        let tokens = value_handles
+            .into_iter()
+            .filter_map(|(token, handle)| Some((token, CharacteristicInfo::resolve(&db, &handle)?)))
+            .collect();
+        let authenticated = inner.authentication_state();
+        let client_config = ClientConfigStore::new(&db);
+
+        Self {
+            inner,
+            event_tx: broadcast::channel(DEFAULT_EVENT_CAPACITY).0,
+            db,
+            write_tokens,
+            authenticated,
+            auth_info: Arc::new(Mutex::new(None)),
+            write_command_limit: None,
+            stats: Arc::default(),
+            client_config,
+            values,
+            subscription_txs: vec![],
+            write_pipes: HashMap::new(),
+            replay: ReplayBuffers::default(),
+            tokens,
+            disconnected: watch::channel(None),
+        }
+    }
+
+    pub fn authenticator(&self) -> Authenticator {
+        Authenticator {
+            authenticated: self.authenticated.clone(),
+            peer: self.inner.address().clone(),
+            info: self.auth_info.clone(),
+        }
+    }
+
+    /// Peer and pairing method, once an [`Authenticator`] marked the link
+    /// authenticated with them.
+    pub fn auth_info(&self) -> Option<AuthInfo> {
+        self.auth_info.lock().unwrap().clone()
+    }
+
+    /// Completes with how the connection ended, once [`run`](Self::run)
+    /// returns or the connection is dropped.
+    pub fn disconnected(&self) -> impl Future<Output = DisconnectReason> {
+        let mut rx = self.disconnected.1.clone();
+        async move {
+            loop {
+                if let Some(reason) = rx.borrow().clone() {
+                    return reason;
+                }
+                if rx.changed().await.is_err() {
+                    return DisconnectReason::Dropped;
+                }
+            }
+        }
+    }
+
+    /// Stream of the events emitted from now on.
+    pub fn events(&mut self) -> Events<T> {
+        Events(self.event_tx.subscribe())
+    }
+
+    /// Buffer up to `capacity` events for each [`Events`] stream, instead of
+    /// [`DEFAULT_EVENT_CAPACITY`].
+    ///
+    /// Fails if `capacity` is zero, or if [`events`](Self::events) was called
+    /// already, leaving the capacity unchanged.
+    pub fn set_event_capacity(&mut self, capacity: usize) -> Result<(), EventCapacityError> {
+        if capacity == 0 {
+            return Err(EventCapacityError::Zero);
+        }
+        if self.event_tx.receiver_count() > 0 {
+            return Err(EventCapacityError::AlreadySubscribed);
+        }
+        self.event_tx = broadcast::channel(capacity).0;
+        Ok(())
+    }
+
+    /// Writes of every Client Characteristic Configuration descriptor, also
+    /// of characteristics registered without a token.
+    ///
+    /// Every accepted write is reported, even if it does not change the value.
+    pub fn subscriptions(&mut self) -> Subscriptions {
+        let (tx, rx) = mpsc::unbounded();
+        self.subscription_txs.push(tx);
+        Subscriptions(rx)
+    }
+
+    /// Notification writer for the characteristic registered with `token`.
+    ///
+    /// A characteristic has one stream of notifications: every writer of
+    /// `token`, including clones and
+    /// [`notification_sender`](Self::notification_sender)s, queues whole
+    /// packets to the same outgoing queue, so values are never interleaved.
+    /// Fails with [`HandleNotFound`] unless the characteristic was
+    /// registered with `NOTIFY` or `INDICATE`.
+    pub fn notification(&self, token: &T) -> Result<Notification<IO>, HandleNotFound<T>> {
+        Ok(self.writer(self.notification_handle(token)?))
+    }
+
+    fn writer(&self, handle: Handle) -> Notification<IO> {
+        Notification {
+            inner: self.inner.notification(handle.clone()),
+            check: self.db.value_check(&handle).cloned(),
+            handle,
+            replay: self.replay.clone(),
+            client_config: self.client_config.clone(),
+        }
+    }
+
+    fn notification_handle(&self, token: &T) -> Result<Handle, HandleNotFound<T>> {
+        self.tokens
+            .get(token)
+            .filter(|info| info.cccd_handle.is_some())
+            .map(|info| info.value_handle.clone())
+            .ok_or_else(|| HandleNotFound(token.clone()))
+    }
+
+    /// Indication writer for the characteristic registered with `token`.
+    ///
+    /// Fails with [`HandleNotFound`] unless it was registered with
+    /// `NOTIFY` or `INDICATE`.
+    pub fn indication(&self, token: &T) -> Result<Indication<IO>, HandleNotFound<T>> {
+        Ok(self.inner.indication(self.notification_handle(token)?))
+    }
+
+    /// Every value written to the characteristic of `token`, ending once the
+    /// connection is done. Unlike [`events`](Self::events), none is dropped.
+    pub(crate) fn write_pipe(&mut self, token: &T) -> Option<mpsc::UnboundedReceiver<Box<[u8]>>> {
+        let handle = self.tokens.get(token)?.value_handle.clone();
+        let (tx, rx) = mpsc::unbounded();
+        self.write_pipes.insert(handle, tx);
+        Some(rx)
+    }
+
+    /// Byte stream over the Nordic UART Service added with
+    /// [`add_nus`](crate::profiles::nus::add_nus). Create it before
+    /// [`run`](Self::run); fails like [`notification`](Self::notification)
+    /// for the TX token.
+    pub fn nus_pipe(&mut self, tokens: &NusTokens<T>) -> Result<NusPipe<IO>, HandleNotFound<T>> {
+        NusPipe::new(self, tokens)
+    }
+
+    /// Clonable notification sender for `token`.
+    ///
+    /// Unlike [`notification`](Self::notification), every clone can notify
+    /// concurrently from its own task, also after this connection was moved
+    /// into [`run`](Self::run).
+    pub fn notification_sender(
+        &self,
+        token: &T,
+    ) -> Result<NotificationSender<T, IO>, HandleNotFound<T>> {
+        let handle = self.notification_handle(token)?;
+        Ok(NotificationSender {
+            token: token.clone(),
+            notification: self.writer(handle),
+        })
+    }
+
+    /// Retain up to `capacity` notifications of `token` while the client is
+    /// not subscribed, and notify them in order once it enables
+    /// notifications, e.g. for readings taken before the client subscribed.
+    ///
+    /// Older values are dropped when more than `capacity` are waiting. Values
+    /// sent through [`notification`](Self::notification) and
+    /// [`notification_sender`](Self::notification_sender) are retained, also
+    /// those written while the retained ones are being notified, so that
+    /// none overtakes them. Fails with [`HandleNotFound`] unless the
+    /// characteristic was registered with `NOTIFY`; retained values are sent
+    /// as notifications, which a characteristic registered only with
+    /// `INDICATE` never has enabled.
+    pub fn buffer_notifications(
+        &mut self,
+        token: &T,
+        capacity: usize,
+    ) -> Result<(), HandleNotFound<T>> {
+        let handle = self.notification_handle(token)?;
+        let cccd = self
+            .db
+            .client_configuration_of(&handle)
+            .filter(|cccd| {
+                matches!(
+                    self.db.client_configuration_supported(cccd),
+                    Some(bits) if bits.contains(ClientCharacteristicConfiguration::NOTIFICATION)
+                )
+            })
+            .cloned()
+            .ok_or_else(|| HandleNotFound(token.clone()))?;
+        self.replay.insert(handle, cccd, capacity);
+        Ok(())
+    }
+
+    /// Record a structural change of the attribute table, e.g. before
+    /// indicating Service Changed.
+    ///
+    /// `handles` are the handles removed or reassigned to another attribute.
+    /// Writers created before this call for one of them fail with
+    /// [`AttError::StaleHandle`]; obtain new ones with
+    /// [`refresh_writer`](Self::refresh_writer). Use
+    /// [`Outgoing::handles_changed`] once the connection is moved into
+    /// [`run`](Self::run). See [`AttConnection::handles_changed`].
+    pub fn handles_changed<I>(&self, handles: I)
+    where
+        I: IntoIterator<Item = Handle>,
     {
+        self.inner.handles_changed(handles)
+    }
+
+    /// Obtain a new notification writer for `token`, e.g. to replace one
+    /// failing with [`AttError::StaleHandle`].
+    ///
+    /// Same as [`notification`](Self::notification): `token` resolves to the
+    /// same handle as before, as the table of a connection does not change,
+    /// and the new writer only counts changes recorded by
+    /// [`handles_changed`](Self::handles_changed) after this call. Other
+    /// writers are left as they are: those created before `handles_changed`
+    /// named their handle keep failing, and any other stays usable. Use
+    /// [`indication`](Self::indication) for indication writers.
+    pub fn refresh_writer(&self, token: &T) -> Result<Notification<IO>, HandleNotFound<T>> {
+        self.notification(token)
+    }
+
+    pub fn address(&self) -> &att::Address {
+        self.inner.address()
+    }
+
+    /// Handles, type and properties of the characteristic registered with
+    /// `token`, e.g. to log the attribute table for other tooling.
+    pub fn resolve(&self, token: &T) -> Option<&CharacteristicInfo> {
+        self.tokens.get(token)
+    }
+
+    /// Every token with its characteristic, in no particular order.
+    pub fn tokens(&self) -> impl Iterator<Item = (&T, &CharacteristicInfo)> {
+        self.tokens.iter()
+    }
+
+    /// Resolved attribute table, for inspecting handle assignments before [`run`](Self::run).
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Number of packets waiting in the outgoing queue.
+    pub fn outgoing_queue_depth(&self) -> usize {
+        self.inner.outgoing_queue_depth()
+    }
+
+    /// Negotiated ATT_MTU. Use [`Notification::current_mtu`] to follow
+    /// updates once the connection is running.
+    pub fn current_mtu(&self) -> usize {
+        self.inner.current_mtu()
+    }
+
+    /// How long an indication waits for its confirmation before the
+    /// connection is considered timed out. See
+    /// [`AttConnection::set_indication_timeout`].
+    pub fn set_indication_timeout(&self, timeout: Duration) {
+        self.inner.set_indication_timeout(timeout)
+    }
+
+    /// Whether the client exchanged MTU. See [`AttConnection::mtu_state`].
+    pub fn mtu_state(&self) -> MtuState {
+        self.inner.mtu_state()
+    }
+
+    /// Keep notifying after the client shut down its sending half.
+    /// See [`AttConnection::allow_half_close`].
+    pub fn allow_half_close(&self, allow: bool) {
+        self.inner.allow_half_close(allow)
+    }
+
+    /// Server Rx MTU to advertise when the client exchanges MTU, clamped to
+    /// 23..=517. See [`AttConnection::set_server_mtu`].
+    pub fn set_server_mtu(&self, mtu: u16) {
+        self.inner.set_server_mtu(mtu)
+    }
+
+    /// Limit Write Commands (and Signed Write Commands) to `max_per_second`
+    /// on average, allowing bursts of `burst` commands.
+    ///
+    /// Commands over the limit are ignored, as the client expects no
+    /// response, and counted in [`ConnectionStats::write_commands_dropped`].
+    /// Write Requests are never limited.
+    pub fn set_write_command_rate_limit(&mut self, max_per_second: u32, burst: u32) {
+        self.write_command_limit = Some(RateLimiter::new(max_per_second, burst));
+    }
+
+    /// Counters of this connection: PDUs by Op Code, Error Responses by
+    /// Error Code, bytes, MTU, uptime and writes.
+    ///
+    /// Use [`stats_handle`](Self::stats_handle) once the connection is moved
+    /// into [`run`](Self::run).
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.snapshot(self.inner.stats())
+    }
+
+    /// Handle reading [`stats`](Self::stats) while and after
+    /// [`run`](Self::run) is in progress.
+    pub fn stats_handle(&self) -> StatsHandle<IO> {
+        StatsHandle {
+            counters: self.stats.clone(),
+            outgoing: self.inner.outgoing(),
+        }
+    }
+
+    /// Sender not bound to a characteristic, e.g. for [`Outgoing::send_batch`].
+    pub fn outgoing(&self) -> Outgoing<IO> {
+        self.inner.outgoing()
+    }
+
+    /// Descriptor values of this connection, updated while [`run`](Self::run)
+    /// is in progress. Every connection starts unsubscribed.
+    pub fn client_config_store(&self) -> ClientConfigStore {
+        self.client_config.clone()
+    }
+
+    /// Snapshot of the descriptor values, to persist for a bonded peer.
+    pub fn export_client_config(&self) -> ClientConfig {
+        self.client_config.export()
+    }
+
+    /// Restore descriptor values persisted for a bonded peer.
+    pub fn restore_client_config(&self, config: &ClientConfig) {
+        self.client_config.restore(config);
+    }
+
+    /// Values of the characteristics added with a token, updated while
+    /// [`run`](Self::run) is in progress.
+    pub fn value_store(&self) -> ValueStore<T> {
+        self.values.clone()
+    }
+
+    /// Snapshot of the values of the characteristics added with a token, to
+    /// persist and restore with [`Server::accept_with_values`].
+    pub fn snapshot_values(&self) -> Vec<(T, Box<[u8]>)> {
+        self.values.snapshot()
+    }
+
+    /// Give up the GATT layer and return the underlying ATT connection, e.g.
+    /// to serve a proprietary protocol with an own [`Handler`].
+    ///
+    /// This is an escape hatch: the attribute table, the descriptor values
+    /// and the event streams are dropped, and none of the guarantees of this
+    /// module apply. The socket stays open.
+    pub fn into_att_connection(self) -> AttConnection<IO> {
+        self.inner
+    }
+
+    /// Serve until the client disconnects.
+    ///
+    /// [`Event::Connected`] and [`Event::Disconnected`] are emitted when this
+    /// starts and returns. Fails with [`RunError::Transport`] if the socket
+    /// fails and [`RunError::Protocol`] if a packet cannot be encoded.
+    pub async fn run(self) -> Result<(), RunError> {
+        self.run_with_shutdown(future::pending()).await
+    }
+
+    /// Same as [`run`](Self::run), and stop serving once `shutdown`
+    /// completes. See [`AttConnection::run_with_shutdown`].
+    pub async fn run_with_shutdown<S>(self, shutdown: S) -> Result<(), RunError>
+    where
+        S: Future<Output = ()>,
+    {
+        let Self {
+            inner,
+            db,
+            write_tokens,
+            event_tx,
+            authenticated,
+            write_command_limit,
+            stats,
+            client_config,
+            values,
+            mut subscription_txs,
+            write_pipes,
+            replay,
+            disconnected: (disconnected, _),
+            ..
+        } = self;
+        let address = inner.address().clone();
+        emit(&event_tx, Event::Connected(address.clone()));
+
+        let replay = if replay.is_empty() {
+            None
+        } else {
+            let notifications = replay
+                .handles()
+                .into_iter()
+                .map(|handle| (handle.clone(), inner.notification(handle)))
+                .collect();
+            let (tx, rx) = mpsc::unbounded();
+            subscription_txs.push(tx);
+            Some(replay.replay(client_config.clone(), notifications, rx))
+        };
+
+        let mut handler = GattHandler::<T>::new(db, write_tokens, event_tx.clone(), authenticated);
+        handler.write_command_limit = write_command_limit;
+        handler.stats = stats;
+        handler.client_config = client_config;
+        handler.values = values;
+        handler.subscription_txs = subscription_txs;
+        handler.write_pipes = write_pipes;
+        handler.mtu = inner.shared_mtu();
+        #[cfg(feature = "tracing")]
+        {
+            handler.peer = Some(address.clone());
+        }
+        let shut_down = AtomicBool::new(false);
+        let shutdown = shutdown.map(|()| shut_down.store(true, Ordering::Relaxed));
+        let run = inner.run_with_shutdown(handler, shutdown);
+        let result = match replay {
+            None => run.await,
+            Some(replay) => match future::select(Box::pin(run), Box::pin(replay)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(((), run)) => run.await,
+            },
+        };
+        let result = result.map_err(RunError::from);
+
+        let err = result.as_ref().err().map(|err| {
+            Arc::new(match err {
+                RunError::PeerDisconnected(err) | RunError::Transport(err) => {
+                    io::Error::new(err.kind(), err.to_string())
+                }
+                RunError::Protocol(err) => {
+                    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+                }
+                RunError::TransactionTimeout => {
+                    io::Error::new(io::ErrorKind::TimedOut, err.to_string())
+                }
+            })
+        });
+        let reason = match (&result, &err) {
+            (Err(RunError::PeerDisconnected(..)), _) => DisconnectReason::Closed,
+            (_, Some(err)) => DisconnectReason::Failed(err.clone()),
+            (Ok(()), None) if shut_down.load(Ordering::Relaxed) => DisconnectReason::Shutdown,
+            (_, None) => DisconnectReason::Closed,
+        };
+        disconnected.send(Some(reason)).ok();
+        emit(&event_tx, Event::Disconnected(address, err));
+        result
+    }
+}
+
+impl<T> Connection<T, AttStream> {
+    /// Address of the local adapter this connection was accepted on.
+    /// See [`AttConnection::local_address`].
+    pub fn local_address(&self) -> io::Result<att::Address> {
+        self.inner.local_address()
+    }
+}
+
+/// Refuse to serve a registration with attributes left out.
+fn validate<T>(registration: &Registration<T>) -> io::Result<()>
+where
+    T: Eq + Hash + Clone,
+{
+    registration
+        .validate()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+/// GATT Protocol Server
+pub struct Server {
+    inner: AttServer,
+}
+
+impl Server {
+    /// Listen with the default options. Same as
+    /// `ServerBuilder::default().build()`.
+    pub fn bind() -> io::Result<Self> {
+        ServerBuilder::default().build()
+    }
+
+    /// Builder to listen with other socket options.
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    /// Report accept errors retried internally to `hook`.
+    /// See [`att::server::Server::on_accept_error`].
+    pub fn on_accept_error<F>(&mut self, hook: F)
+    where
+        F: FnMut(&io::Error) + Send + 'static,
+    {
+        self.inner.on_accept_error(hook);
+    }
+
+    /// Accept [`Connection`]
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `registration` ran out
+    /// of handles. See [`Registration::validate`].
+    pub async fn accept<T>(
+        &mut self,
+        registration: Registration<T>,
+    ) -> io::Result<Option<Connection<T>>>
+    where
+        T: Eq + Hash + Clone,
+    {
+        validate(&registration)?;
         if let Some((connection, _)) = self.inner.accept().await? {
             Ok(Some(Connection::new(connection, registration)))
         } else {
@@ -352,13 +1755,1073 @@ impl Server {
         }
     }
 
+    /// Accept [`Connection`], restoring the descriptor values `restore`
+    /// returns for the peer. (e.g. looked up from bonding storage)
+    pub async fn accept_with_client_config<T, F>(
+        &mut self,
+        registration: Registration<T>,
+        restore: F,
+    ) -> io::Result<Option<Connection<T>>>
+    where
+        T: Eq + Hash + Clone,
+        F: FnOnce(&att::Address) -> Option<ClientConfig>,
+    {
+        validate(&registration)?;
+        if let Some((connection, peer)) = self.inner.accept().await? {
+            let connection = Connection::new(connection, registration);
+            if let Some(config) = restore(&peer.address) {
+                connection.restore_client_config(&config);
+            }
+            Ok(Some(connection))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Accept [`Connection`] serving `registration` with the initial values
+    /// of the characteristics named by the tokens replaced. (e.g. loaded from
+    /// flash) See [`Registration::with_values`].
+    ///
+    /// The values are validated before accepting.
+    pub async fn accept_with_values<T, I, B>(
+        &mut self,
+        registration: &Registration<T>,
+        values: I,
+    ) -> Result<Option<Connection<T>>, AcceptWithValuesError<T>>
+    where
+        T: Eq + Hash + Clone,
+        I: IntoIterator<Item = (T, B)>,
+        B: AsRef<[u8]>,
+    {
+        let registration = registration.with_values(values)?;
+        Ok(self.accept(registration).await?)
+    }
+
+    /// Address of the local adapter. (e.g. to show for pairing via a QR code)
+    ///
+    /// The server listens on any adapter, so this is the address of the only
+    /// adapter up, and fails with [`io::ErrorKind::AddrNotAvailable`] with
+    /// none or several up. [`Connection::local_address`] tells the adapter a
+    /// connection was accepted on. See [`att::server::Server::local_address`].
+    pub fn local_address(&self) -> io::Result<att::Address> {
+        self.inner.local_address()
+    }
+
+    /// Follow the state of this server, e.g. to advertise only while it is listening.
+    ///
+    /// See [`att::server::Server::state_events`].
+    pub fn state_events(&self) -> tokio::sync::watch::Receiver<ServerState> {
+        self.inner.state_events()
+    }
+
+    #[deprecated(note = "use `ServerBuilder::security` with `SecurityLevel::Medium`")]
     pub fn needs_bond(&self) -> io::Result<()> {
         self.inner.needs_bond()?;
         Ok(())
     }
 
+    #[deprecated(note = "use `ServerBuilder::security` with `SecurityLevel::High`")]
     pub fn needs_bond_mitm(&self) -> io::Result<()> {
         self.inner.needs_bond_mitm()?;
         Ok(())
     }
+
+    /// Set the L2CAP mode of accepted channels. See [`L2capMode`].
+    pub fn set_l2cap_mode(&self, mode: L2capMode) -> io::Result<()> {
+        self.inner.set_l2cap_mode(mode)
+    }
+}
+
+/// Security the link must have before the client is served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SecurityLevel {
+    /// No pairing required.
+    #[default]
+    Low,
+    /// Paired and bonded, also without MITM protection. (e.g. Just Works)
+    Medium,
+    /// Paired and bonded with MITM protection.
+    High,
+}
+
+/// [`Server`] Builder.
+///
+/// ```no_run
+/// use gatt::server::{Server, SecurityLevel};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let server = Server::builder()
+///     .security(SecurityLevel::Medium)
+///     .backlog(4)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ServerBuilder {
+    security: SecurityLevel,
+    backlog: Option<u32>,
+}
+
+impl ServerBuilder {
+    /// Defaults to [`SecurityLevel::Low`].
+    pub fn security(mut self, level: SecurityLevel) -> Self {
+        self.security = level;
+        self
+    }
+
+    /// Number of connections the kernel queues until they are accepted.
+    /// See [`att::server::ServerBuilder::backlog`].
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = Some(backlog);
+        self
+    }
+
+    /// Bind and listen on the fixed ATT channel. The security level is set
+    /// before the socket listens, so no link is accepted without it.
+    pub fn build(self) -> io::Result<Server> {
+        let inner = self.att_builder().build()?;
+        Ok(Server { inner })
+    }
+
+    fn att_builder(&self) -> att::server::ServerBuilder {
+        let mut builder = AttServer::builder();
+        if let Some(backlog) = self.backlog {
+            builder = builder.backlog(backlog);
+        }
+        match self.security {
+            SecurityLevel::Low => builder,
+            SecurityLevel::Medium => builder.needs_bond(),
+            SecurityLevel::High => builder.needs_bond_mitm(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_builder() {
+        assert_eq!(Server::builder().att_builder(), AttServer::builder());
+
+        let builder = Server::builder().security(SecurityLevel::Medium).backlog(4);
+        assert_eq!(
+            builder.att_builder(),
+            AttServer::builder().backlog(4).needs_bond()
+        );
+        let builder = builder.security(SecurityLevel::High);
+        assert_eq!(
+            builder.att_builder(),
+            AttServer::builder().backlog(4).needs_bond_mitm()
+        );
+        let builder = builder.security(SecurityLevel::Low);
+        assert_eq!(builder.att_builder(), AttServer::builder().backlog(4));
+    }
+
+    #[test]
+    fn test_authenticator() {
+        let peer = att::Address::le_random_from([1, 2, 3, 4, 5, 6]);
+        let inner = AttConnection::new(tokio_test::io::Builder::new().build(), peer.clone());
+        let state = inner.authentication_state();
+        let connection = Connection::new(inner, Registration::<()>::new());
+        assert!(!state.is_authenticated());
+        assert_eq!(connection.auth_info(), None);
+
+        connection
+            .authenticator()
+            .mark_authenticated_with_info(PairingMethod::PasskeyEntry);
+        assert!(state.is_authenticated());
+        assert_eq!(
+            connection.auth_info(),
+            Some(AuthInfo {
+                peer,
+                method: PairingMethod::PasskeyEntry,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_att_connection() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&[0x0A, 0x01, 0x00])
+                .write(&[0x01, 0x0A, 0x01, 0x00, 0x06])
+                .build(),
+            att::Address::le_public_from([1; 6]),
+        );
+        let mut registration = Registration::<()>::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+        let mut connection = Connection::new(inner, registration);
+        let mut events = connection.events();
+
+        let inner = connection.into_att_connection();
+        assert_eq!(inner.address(), &att::Address::le_public_from([1; 6]));
+        assert!(events.next().await.is_none());
+
+        struct H;
+        impl Handler for H {}
+        inner.run(H).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_with_values() {
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+        registration.add_characteristic_with_token(
+            "name",
+            att::Uuid::new_uuid16(0x2A00),
+            "default",
+            crate::CharacteristicProperties::READ | crate::CharacteristicProperties::WRITE,
+        );
+        let registration = registration
+            .with_values(vec![("name", b"flash".to_vec())])
+            .unwrap();
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&[0x0A, 0x03, 0x00])
+                .write(&[0x0B, b'f', b'l', b'a', b's', b'h'])
+                .read(&[0x12, 0x03, 0x00, b'n', b'e', b'w'])
+                .write(&[0x13])
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, registration);
+        assert_eq!(
+            connection.snapshot_values(),
+            vec![("name", b"flash".to_vec().into())]
+        );
+        let values = connection.value_store();
+        connection.run().await.unwrap();
+        assert_eq!(values.snapshot(), vec![("name", b"new".to_vec().into())]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_disconnected() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        assert!(connection.disconnected().now_or_never().is_none());
+        connection.run().await.unwrap();
+        assert!(matches!(disconnected.await, DisconnectReason::Closed));
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read_error(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        connection.run().await.unwrap_err();
+        assert!(matches!(
+            disconnected.await,
+            DisconnectReason::Failed(err) if err.kind() == io::ErrorKind::ConnectionReset
+        ));
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read_error(io::ErrorKind::UnexpectedEof.into())
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        connection.run().await.unwrap_err();
+        assert!(matches!(disconnected.await, DisconnectReason::Closed));
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .wait(Duration::from_secs(1))
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        connection
+            .run_with_shutdown(future::ready(()))
+            .await
+            .unwrap();
+        assert!(matches!(disconnected.await, DisconnectReason::Shutdown));
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        drop(connection);
+        assert!(matches!(disconnected.await, DisconnectReason::Dropped));
+    }
+
+    #[tokio::test]
+    async fn test_run_error_source() {
+        use std::error::Error as _;
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read_error(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let err = Connection::new(inner, Registration::<()>::new())
+            .run()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RunError::Transport(..)));
+        assert_eq!(err.to_string(), "connection transport failed.");
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::ConnectionReset);
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read_error(io::Error::from(io::ErrorKind::UnexpectedEof))
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let mut connection = Connection::new(inner, Registration::<()>::new());
+        let mut events = connection.events();
+        let err = connection.run().await.unwrap_err();
+        assert!(matches!(err, RunError::PeerDisconnected(..)));
+        assert_eq!(err.to_string(), "peer disconnected.");
+        assert!(matches!(events.next().await, Some(Event::Connected(..))));
+        match events.next().await {
+            Some(Event::Disconnected(_, Some(err))) => {
+                assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            event => panic!("{:?}", event),
+        }
+
+        let err = RunError::from(AttError::from(pkt::pack::Error::from(io::Error::from(
+            io::ErrorKind::WriteZero,
+        ))));
+        assert!(matches!(err, RunError::Protocol(AttError::Pack(..))));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<AttError>().is_some());
+        let io = source
+            .source()
+            .unwrap()
+            .downcast_ref::<io::Error>()
+            .unwrap();
+        assert_eq!(io.kind(), io::ErrorKind::WriteZero);
+
+        assert!(matches!(
+            RunError::from(AttError::TransactionTimeout),
+            RunError::TransactionTimeout
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_socket_closed() {
+        let (sock, client) = tokio::net::UnixStream::pair().unwrap();
+        let inner = AttConnection::new(sock, att::Address::le_public_from([0; 6]));
+        let connection = Connection::new(inner, Registration::<()>::new());
+        let disconnected = connection.disconnected();
+        drop(client);
+
+        connection.run().await.unwrap();
+        assert!(matches!(disconnected.await, DisconnectReason::Closed));
+    }
+
+    #[test]
+    fn test_notification_writers() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration.add_characteristic_with_token(
+            "battery",
+            att::Uuid::new_uuid16(0x2A19),
+            [100],
+            crate::CharacteristicProperties::NOTIFY,
+        );
+        let connection = Connection::new(inner, registration);
+
+        // every writer sends to the same queue.
+        let first = connection.notification(&"battery").unwrap();
+        let second = connection.notification(&"battery").unwrap();
+        let sender = connection.notification_sender(&"battery").unwrap();
+        first.try_notify(&[1]).unwrap();
+        second.try_notify(&[2]).unwrap();
+        sender.try_notify(&[3]).unwrap();
+        assert_eq!(connection.outgoing_queue_depth(), 3);
+        assert!(connection.notification(&"other").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_notifications() {
+        use tokio::io::AsyncWriteExt;
+
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&[0x12, 0x04, 0x00, 0x01, 0x00])
+                .write(&[0x13])
+                .write(&[0x1B, 0x03, 0x00, 2])
+                .write(&[0x1B, 0x03, 0x00, 3])
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration.add_characteristic_with_token(
+            "battery",
+            att::Uuid::new_uuid16(0x2A19),
+            [100],
+            crate::CharacteristicProperties::READ | crate::CharacteristicProperties::NOTIFY,
+        );
+        registration.add_characteristic_with_token(
+            "alert",
+            att::Uuid::new_uuid16(0x2A06),
+            [0],
+            crate::CharacteristicProperties::INDICATE,
+        );
+        let mut connection = Connection::new(inner, registration);
+        connection.buffer_notifications(&"battery", 2).unwrap();
+        assert!(connection.buffer_notifications(&"unknown", 2).is_err());
+        // retained values would never be notified.
+        assert!(connection.buffer_notifications(&"alert", 2).is_err());
+
+        // through every writer
+        let sender = connection.notification_sender(&"battery").unwrap();
+        let mut notification = connection.notification(&"battery").unwrap();
+        sender.try_notify(&[0]).unwrap();
+        sender.notify(&[1]).await.unwrap();
+        notification.notify(&[2]).await.unwrap();
+        assert_eq!(notification.write(&[3]).await.unwrap(), 1);
+        assert_eq!(connection.outgoing_queue_depth(), 0);
+
+        connection.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refresh_writer() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .write(&[0x1B, 0x03, 0x00, 1])
+                .write(&[0x1B, 0x03, 0x00, 3])
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration.add_characteristic_with_token(
+            "battery",
+            att::Uuid::new_uuid16(0x2A19),
+            [100],
+            crate::CharacteristicProperties::NOTIFY,
+        );
+        let connection = Connection::new(inner, registration);
+        let handle = connection.resolve(&"battery").unwrap().value_handle.clone();
+
+        let mut stale = connection.notification(&"battery").unwrap();
+        stale.notify(&[1]).await.unwrap();
+        connection.handles_changed(vec![handle.clone()]);
+        let err = stale.notify(&[2]).await.unwrap_err();
+        assert!(matches!(err, AttError::StaleHandle(h) if h == handle));
+
+        let mut refreshed = connection.refresh_writer(&"battery").unwrap();
+        refreshed.notify(&[3]).await.unwrap();
+        // the old writer is not refreshed along.
+        assert!(stale.notify(&[4]).await.is_err());
+
+        connection.run().await.unwrap();
+    }
+
+    #[test]
+    fn test_replay_order() {
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration.add_characteristic_with_token(
+            "battery",
+            att::Uuid::new_uuid16(0x2A19),
+            [100],
+            crate::CharacteristicProperties::NOTIFY,
+        );
+        let (db, _, _) = registration.build();
+        let config = ClientConfigStore::new(&db);
+        let (value, cccd) = (Handle::new(0x0003), Handle::new(0x0004));
+        let replay = ReplayBuffers::default();
+        replay.insert(value.clone(), cccd.clone(), 2);
+
+        assert!(replay.retain(&value, &[1], &config));
+        assert!(replay.retain(&value, &[2], &config));
+        assert_eq!(replay.next_replay(&value, &config), None);
+
+        config.set(&cccd, &[0x01, 0x00]);
+        assert_eq!(
+            replay.next_replay(&value, &config).as_deref(),
+            Some(&[1][..])
+        );
+        // written while 1 is being replayed: queued behind it, and 1 is not
+        // dropped for room.
+        assert!(replay.retain(&value, &[3], &config));
+        assert!(replay.retain(&value, &[4], &config));
+        replay.replayed(&value);
+        assert_eq!(
+            replay.next_replay(&value, &config).as_deref(),
+            Some(&[3][..])
+        );
+        replay.replayed(&value);
+        assert_eq!(
+            replay.next_replay(&value, &config).as_deref(),
+            Some(&[4][..])
+        );
+        replay.replayed(&value);
+        assert_eq!(replay.next_replay(&value, &config), None);
+
+        // nothing waiting: sent right away.
+        assert!(!replay.retain(&value, &[5], &config));
+    }
+
+    #[test]
+    fn test_resolve_tokens() {
+        use crate::characteristics as ch;
+        use crate::services as srv;
+        use crate::CharacteristicProperties;
+
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        enum Token {
+            DeviceName,
+            BatteryLevelNotify,
+        }
+
+        let mut registration = Registration::new();
+        registration.add_primary_service(srv::GENERIC_ACCESS);
+        registration.add_characteristic_with_token(
+            Token::DeviceName,
+            ch::DEVICE_NAME,
+            "abc",
+            CharacteristicProperties::WRITE,
+        );
+        registration.add_characteristic_with_initial_value(
+            ch::APPEARANCE,
+            0x03c0u16.to_le_bytes(),
+            CharacteristicProperties::READ,
+        );
+        registration.add_primary_service(srv::BATTERY);
+        registration.at_handle(0x0020).unwrap();
+        registration.add_characteristic_with_token(
+            Token::BatteryLevelNotify,
+            ch::BATTERY_LEVEL,
+            "",
+            CharacteristicProperties::NOTIFY,
+        );
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, registration);
+
+        assert_eq!(
+            connection.resolve(&Token::DeviceName),
+            Some(&CharacteristicInfo {
+                declaration_handle: 0x0002.into(),
+                value_handle: 0x0003.into(),
+                cccd_handle: None,
+                uuid: ch::DEVICE_NAME,
+                properties: CharacteristicProperties::WRITE,
+            })
+        );
+        assert_eq!(
+            connection.resolve(&Token::BatteryLevelNotify),
+            Some(&CharacteristicInfo {
+                declaration_handle: 0x0020.into(),
+                value_handle: 0x0021.into(),
+                cccd_handle: Some(0x0022.into()),
+                uuid: ch::BATTERY_LEVEL,
+                properties: CharacteristicProperties::NOTIFY,
+            })
+        );
+        assert_eq!(connection.tokens().count(), 2);
+    }
+
+    #[test]
+    fn test_handle_not_found() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new().build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, Registration::<&str>::new());
+        let err = connection.notification(&"battery").err().unwrap();
+        assert_eq!(err.to_string(), "handle not found for token \"battery\".");
+    }
+
+    fn handler_with_events<T>(registration: Registration<T>) -> (GattHandler<T>, Events<T>)
+    where
+        T: Hash + Eq + Clone,
+    {
+        let (db, write_tokens, _) = registration.build();
+        let (tx, rx) = broadcast::channel(DEFAULT_EVENT_CAPACITY);
+        let handler = GattHandler::new(db, write_tokens, tx, AuthenticationState::default());
+        (handler, Events(rx))
+    }
+
+    fn writable_registration() -> Registration<&'static str> {
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+        registration.add_characteristic_with_token(
+            "name",
+            att::Uuid::new_uuid16(0x2A00),
+            "",
+            crate::CharacteristicProperties::WRITE,
+        );
+        registration
+    }
+
+    #[tokio::test]
+    async fn test_events_broadcast() {
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&[0x12, 0x03, 0x00, 0x01])
+                .write(&[0x13])
+                .read(&[0x12, 0x03, 0x00, 0x02])
+                .write(&[0x13])
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let mut connection = Connection::new(inner, writable_registration());
+        assert_eq!(
+            connection.set_event_capacity(0),
+            Err(EventCapacityError::Zero)
+        );
+        connection.set_event_capacity(2).unwrap();
+        let streams = vec![connection.events(), connection.events()];
+        assert_eq!(
+            connection.set_event_capacity(4),
+            Err(EventCapacityError::AlreadySubscribed)
+        );
+        connection.run().await.unwrap();
+
+        // Connected and the first write are dropped by the lagging streams.
+        for mut events in streams {
+            let event = events.next().await.unwrap();
+            assert_eq!(event.payload(), Some(&[0x02][..]));
+            assert!(matches!(events.next().await, Some(Event::Disconnected(..))));
+            assert!(events.next().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_metadata() {
+        let (mut handler, mut events) = handler_with_events(writable_registration());
+
+        let write = pkt::WriteRequest::new(0x0003.into(), vec![0x01].into());
+        handler.handle_write_request(&write).unwrap();
+        let write = pkt::WriteRequest::new(0x0003.into(), vec![0x02].into());
+        handler.handle_write_request(&write).unwrap();
+        drop(handler);
+
+        let first = events.next_record().await.unwrap();
+        let second = events.next_record().await.unwrap();
+        assert!(first.sequence() < second.sequence());
+
+        let event = first.into_event();
+        assert_eq!(event.kind(), EventKind::Write);
+        assert_eq!(event.to_string(), "write \"name\" [01]");
+        assert_eq!(event.token(), Some(&"name"));
+        assert_eq!(event.payload(), Some(&[0x01][..]));
+        assert!(events.next().await.is_none());
+    }
+
+    #[test]
+    fn test_read_blob_invalid_offset() {
+        let (mut handler, _events) = handler_with_events(writable_registration());
+
+        let read = pkt::ReadBlobRequest::new(0x0001.into(), 2);
+        assert!(handler
+            .handle_read_blob_request(&read)
+            .unwrap()
+            .attribute_value()
+            .is_empty());
+        let read = pkt::ReadBlobRequest::new(0x0001.into(), 3);
+        let err = handler.handle_read_blob_request(&read).unwrap_err();
+        assert_eq!(err.code(), &pkt::ErrorCode::InvalidOffset);
+        let read = pkt::ReadBlobRequest::new(0x0001.into(), 0);
+        let err = handler.handle_read_blob_request(&read).unwrap_err();
+        assert_eq!(err.code(), &pkt::ErrorCode::AttributeNotLong);
+    }
+
+    #[tokio::test]
+    async fn test_read_blob_attribute_not_long() {
+        let registration = || {
+            let mut registration = Registration::<()>::new();
+            registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+            registration.add_characteristic(
+                att::Uuid::new_uuid16(0x2A00),
+                [0x61; 30],
+                crate::CharacteristicProperties::READ,
+            );
+            registration
+        };
+        let read_blob = [0x0C, 0x03, 0x00, 0x00, 0x00];
+        let not_long = [0x01, 0x0C, 0x03, 0x00, 0x0B];
+
+        // longer than a Read Blob Response at ATT_MTU 23, not at 64
+        let mut response = vec![0x0D];
+        response.extend_from_slice(&[0x61; 22]);
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&read_blob)
+                .write(&response)
+                .read(&[0x02, 0x40, 0x00])
+                .write(&[0x03, 0x05, 0x02])
+                .read(&read_blob)
+                .write(&not_long)
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        Connection::new(inner, registration()).run().await.unwrap();
+
+        // the connection answers Exchange MTU with its server MTU
+        let inner = AttConnection::new(
+            tokio_test::io::Builder::new()
+                .read(&[0x02, 0xF7, 0x00])
+                .write(&[0x03, 0x64, 0x00])
+                .read(&read_blob)
+                .write(&not_long)
+                .build(),
+            att::Address::le_public_from([0; 6]),
+        );
+        let connection = Connection::new(inner, registration());
+        connection.set_server_mtu(100);
+        connection.run().await.unwrap();
+    }
+
+    #[test]
+    fn test_generated_value() {
+        let count = Arc::new(AtomicU64::new(0));
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+        registration.add_characteristic_with_generator(
+            "nonce",
+            att::Uuid::new_uuid128(0x1234),
+            crate::CharacteristicProperties::READ,
+            {
+                let count = count.clone();
+                move || {
+                    let n = count.fetch_add(1, Ordering::SeqCst) as u8;
+                    (0..40).map(|i| n.wrapping_add(i)).collect::<Vec<_>>()
+                }
+            },
+        );
+        let (mut handler, _events) = handler_with_events(registration);
+        let read = |handler: &mut GattHandler<_>| {
+            let read = pkt::ReadRequest::new(0x0003.into());
+            handler
+                .handle_read_request(&read)
+                .unwrap()
+                .attribute_value()
+                .clone()
+        };
+        let read_blob = |handler: &mut GattHandler<_>, offset| {
+            let read = pkt::ReadBlobRequest::new(0x0003.into(), offset);
+            handler
+                .handle_read_blob_request(&read)
+                .unwrap()
+                .attribute_value()
+                .clone()
+        };
+
+        let first = read(&mut handler);
+        let second = read(&mut handler);
+        assert_ne!(first, second);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // long read: Read Request, then Read Blob Requests from offset 22
+        let value = read(&mut handler);
+        assert_eq!(&read_blob(&mut handler, 22)[..], &value[22..]);
+        assert!(read_blob(&mut handler, 40).is_empty());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // a new read at offset 0 generates again
+        let value = read_blob(&mut handler, 0);
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+        assert_eq!(&read_blob(&mut handler, 10)[..], &value[10..]);
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+
+        let by_type = pkt::ReadByTypeRequest::new(
+            0x0001.into(),
+            0xFFFF.into(),
+            att::Uuid::new_uuid128(0x1234),
+        );
+        handler.handle_read_by_type_request(&by_type).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_read_by_type_uuid_forms() {
+        const ALIAS_2A00: u128 = 0x00002A00_0000_1000_8000_00805F9B34FB;
+        const ALIAS_2803: u128 = 0x00002803_0000_1000_8000_00805F9B34FB;
+        const CUSTOM: u128 = 0x6E400001_B5A3_F393_E0A9_E50E24DCCA9E;
+
+        let mut registration = Registration::<u8>::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x1800));
+        registration.add_characteristic(
+            att::Uuid::new_uuid16(0x2A00),
+            "abc",
+            crate::CharacteristicProperties::READ,
+        );
+        registration.add_primary_service(att::Uuid::new_uuid128(CUSTOM - 1));
+        registration.add_characteristic(
+            att::Uuid::new_uuid128(CUSTOM),
+            "xyz",
+            crate::CharacteristicProperties::READ,
+        );
+        let (mut handler, _events) = handler_with_events(registration);
+
+        let mut read_by_type = |uuid: att::Uuid| {
+            let request = pkt::ReadByTypeRequest::new(0x0001.into(), 0xFFFF.into(), uuid);
+            handler
+                .handle_read_by_type_request(&request)
+                .map(|response| {
+                    response
+                        .values()
+                        .map(|(h, v)| (h.as_u16(), v.to_vec()))
+                        .collect::<Vec<_>>()
+                })
+                .map_err(|err| *err.code())
+        };
+
+        let device_name = Ok(vec![(0x0003, b"abc".to_vec())]);
+        // 16bit request, 16bit attribute
+        assert_eq!(read_by_type(att::Uuid::new_uuid16(0x2A00)), device_name);
+        // 128bit alias request, 16bit attribute
+        assert_eq!(
+            read_by_type(att::Uuid::new_uuid128(ALIAS_2A00)),
+            device_name
+        );
+        // 128bit request, 128bit attribute
+        assert_eq!(
+            read_by_type(att::Uuid::new_uuid128(CUSTOM)),
+            Ok(vec![(0x0006, b"xyz".to_vec())])
+        );
+        // 16bit request never matches a custom 128bit attribute
+        assert_eq!(
+            read_by_type(att::Uuid::new_uuid16(0x0001)),
+            Err(pkt::ErrorCode::AttributeNotFound)
+        );
+        // characteristic declarations by the 128bit alias of 0x2803. the one
+        // of the 128bit characteristic differs in length, so it is left out.
+        let declarations = read_by_type(att::Uuid::new_uuid128(ALIAS_2803)).unwrap();
+        assert_eq!(
+            declarations.iter().map(|(h, _)| *h).collect::<Vec<_>>(),
+            [0x0002]
+        );
+        let declarations = read_by_type(att::Uuid::new_uuid16(0x2803)).unwrap();
+        assert_eq!(
+            declarations.iter().map(|(h, _)| *h).collect::<Vec<_>>(),
+            [0x0002]
+        );
+    }
+
+    #[test]
+    fn test_write_declaration() {
+        let (mut handler, _events) = handler_with_events(writable_registration());
+
+        // 0x0001 service declaration, 0x0002 characteristic declaration.
+        for handle in [0x0001, 0x0002] {
+            let read = pkt::ReadRequest::new(handle.into());
+            let before = handler.handle_read_request(&read).unwrap();
+            let before = before.attribute_value().clone();
+
+            let write = pkt::WriteRequest::new(handle.into(), before.clone());
+            let err = handler.handle_write_request(&write).unwrap_err();
+            assert_eq!(err.code(), &pkt::ErrorCode::WriteNotPermitted);
+            let write = pkt::WriteRequest::new(handle.into(), vec![0x0A, 0x18].into());
+            let err = handler.handle_write_request(&write).unwrap_err();
+            assert_eq!(err.code(), &pkt::ErrorCode::WriteNotPermitted);
+
+            let after = handler.handle_read_request(&read).unwrap();
+            assert_eq!(after.attribute_value(), &before);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_write_emits_no_event() {
+        let codec = crate::codec::StructCodec::builder().u8("level").build();
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration
+            .add_characteristic_typed(
+                "level",
+                att::Uuid::new_uuid16(0x2A19),
+                &codec,
+                &crate::codec::Value::new().with("level", 100u8),
+                crate::CharacteristicProperties::WRITE
+                    | crate::CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+            )
+            .unwrap();
+        let (mut handler, mut events) = handler_with_events(registration);
+
+        let write = pkt::WriteRequest::new(0x0003.into(), vec![0x01, 0x02].into());
+        let err = handler.handle_write_request(&write).unwrap_err();
+        assert_eq!(err.code(), &pkt::ErrorCode::InvalidAttributeValueLength);
+        handler.handle_write_command(&pkt::WriteCommand::new(
+            0x0003.into(),
+            vec![0x01, 0x02].into(),
+        ));
+        handler.handle_write_command(&pkt::WriteCommand::new(0x0003.into(), vec![0x01].into()));
+        drop(handler);
+
+        let event = events.next().await.unwrap();
+        assert_eq!(event.payload(), Some(&[0x01][..]));
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let mut registration = Registration::<()>::new();
+        registration.add_generic_attribute(true);
+        let (mut handler, _events) = handler_with_events(registration);
+        let (tx, rx) = mpsc::unbounded();
+        handler.subscription_txs.push(tx);
+        let mut subscriptions = Subscriptions(rx);
+
+        let cccd = handler
+            .db
+            .iter()
+            .find(|(_, uuid)| **uuid == Uuid::new_uuid16(0x2902))
+            .map(|(h, _)| h.clone())
+            .unwrap();
+        let write = pkt::WriteRequest::new(cccd.clone(), vec![0x02, 0x00].into());
+        handler.handle_write_request(&write).unwrap();
+        drop(handler);
+
+        let subscription = subscriptions.next().await.unwrap();
+        assert_eq!(subscription.handle(), &cccd);
+        assert_eq!(
+            subscription.uuid(),
+            &crate::characteristics::SERVICE_CHANGED
+        );
+        assert_eq!(
+            subscription.config(),
+            ClientCharacteristicConfiguration::INDICATION
+        );
+        assert!(subscriptions.next().await.is_none());
+    }
+
+    fn notify_only_connection(
+        io: tokio_test::io::Mock,
+    ) -> Connection<&'static str, tokio_test::io::Mock> {
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180F));
+        registration.add_characteristic_with_token(
+            "level",
+            att::Uuid::new_uuid16(0x2A19),
+            [100],
+            crate::CharacteristicProperties::READ | crate::CharacteristicProperties::NOTIFY,
+        );
+        let inner = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+        Connection::new(inner, registration)
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_cccd_bits_write_request() {
+        let io = tokio_test::io::Builder::new()
+            // Write Request (CCCD, notification and indication)
+            .read(&[0x12, 0x04, 0x00, 0x03, 0x00])
+            .write(&[0x01, 0x12, 0x04, 0x00, 0xFD])
+            // Read Request (CCCD)
+            .read(&[0x0A, 0x04, 0x00])
+            .write(&[0x0B, 0x00, 0x00])
+            .build();
+        let mut connection = notify_only_connection(io);
+        let mut subscriptions = connection.subscriptions();
+        let mut events = connection.events();
+        let stats = connection.stats_handle();
+        connection.run().await.unwrap();
+
+        assert!(subscriptions.next().await.is_none());
+        assert!(matches!(events.next().await, Some(Event::Connected(..))));
+        assert!(matches!(events.next().await, Some(Event::Disconnected(..))));
+        assert_eq!(stats.stats().protocol_violations, 0);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_cccd_bits_write_command() {
+        let io = tokio_test::io::Builder::new()
+            // Write Command (CCCD, notification and indication); no response
+            .read(&[0x52, 0x04, 0x00, 0x03, 0x00])
+            // Read Request (CCCD)
+            .read(&[0x0A, 0x04, 0x00])
+            .write(&[0x0B, 0x00, 0x00])
+            // Write Command (CCCD, notification)
+            .read(&[0x52, 0x04, 0x00, 0x01, 0x00])
+            .build();
+        let mut connection = notify_only_connection(io);
+        let mut subscriptions = connection.subscriptions();
+        let stats = connection.stats_handle();
+        connection.run().await.unwrap();
+
+        let subscription = subscriptions.next().await.unwrap();
+        assert_eq!(
+            subscription.config(),
+            ClientCharacteristicConfiguration::NOTIFICATION
+        );
+        assert!(subscriptions.next().await.is_none());
+        let stats = stats.stats();
+        assert_eq!(stats.protocol_violations, 1);
+        assert_eq!(stats.write_commands_received, 2);
+        assert_eq!(stats.received[&pkt::OpCode::WriteCommand], 2);
+    }
+
+    #[tokio::test]
+    async fn test_separate_cccd_token() {
+        let mut registration = Registration::new();
+        registration.add_primary_service(att::Uuid::new_uuid16(0x180D));
+        registration
+            .add_characteristic_with_separate_cccd_token(
+                "measurement",
+                "subscription",
+                att::Uuid::new_uuid16(0x2A37),
+                "",
+                crate::CharacteristicProperties::NOTIFY,
+            )
+            .unwrap();
+        let (mut handler, mut events) = handler_with_events(registration);
+
+        let write = pkt::WriteRequest::new(0x0004.into(), vec![0x01, 0x00].into());
+        handler.handle_write_request(&write).unwrap();
+        drop(handler);
+
+        let event = events.next().await.unwrap();
+        assert_eq!(event.token(), Some(&"subscription"));
+        assert_eq!(event.payload(), Some(&[0x01, 0x00][..]));
+        assert!(events.next().await.is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_event_serialize() {
+        let (mut handler, mut events) = handler_with_events(writable_registration());
+
+        let write = pkt::WriteRequest::new(0x0003.into(), vec![0x01, 0x02].into());
+        handler.handle_write_request(&write).unwrap();
+
+        let record = events.next_record().await.unwrap();
+        let json = serde_json::to_value(&record).unwrap();
+        let since_epoch = record
+            .timestamp()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "write",
+                "token": "name",
+                "payload": [1, 2],
+                "sequence": record.sequence(),
+                "timestamp": {
+                    "secs_since_epoch": since_epoch.as_secs(),
+                    "nanos_since_epoch": since_epoch.subsec_nanos(),
+                },
+            })
+        );
+    }
 }
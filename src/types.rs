@@ -0,0 +1,195 @@
+//! Bit flags as they appear on the wire.
+//!
+//! ref BLUETOOTH CORE SPECIFICATION Version 5.1 | Vol 3, Part G
+//!     3.3 Characteristic Definition
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Render set flags by name, like `READ|NOTIFY`.
+macro_rules! impl_flags_display {
+    ($ty:ty { $($flag:ident),* $(,)? }) => {
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut first = true;
+                $(
+                    if self.contains(Self::$flag) {
+                        if !first {
+                            f.write_str("|")?;
+                        }
+                        f.write_str(stringify!($flag))?;
+                        first = false;
+                    }
+                )*
+                if first {
+                    f.write_str("(empty)")?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+pub(crate) use impl_flags_display;
+
+/// Error for `TryFrom` conversions of bit flags with undefined bits set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("undefined bits {0:#x}.")]
+pub struct UndefinedBits(pub u32);
+
+bitflags::bitflags! {
+    /// Attribute Permissions (server side, not transmitted).
+    pub struct Permission: u8 {
+        /// Attribute can be read.
+        const READABLE = 0b0000_0001;
+        /// Attribute can be written.
+        const WRITEABLE = 0b0000_0010;
+        /// Access needs authorization by the server.
+        const AUTHORIZATION_REQUIRED = 0b0000_0100;
+        /// Access needs an authenticated link.
+        const AUTHENTICATION_REQUIRED = 0b0000_1000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Characteristic Properties of a Characteristic Declaration. (3.3.1.1)
+    pub struct CharacteristicProperties: u8 {
+        /// Value may be broadcast in advertising data.
+        const BROADCAST = 0x01;
+        /// Value may be read.
+        const READ = 0x02;
+        /// Value may be written with Write Command.
+        const WRITE_WITHOUT_RESPONSE = 0x04;
+        /// Value may be written with Write Request.
+        const WRITE = 0x08;
+        /// Value may be notified.
+        const NOTIFY = 0x10;
+        /// Value may be indicated.
+        const INDICATE = 0x20;
+        /// Value may be written with Signed Write Command.
+        const AUTHENTICATED_SIGNED_WRITES = 0x40;
+        /// Characteristic Extended Properties descriptor is present.
+        const EXTENDED_PROPERTIES = 0x80;
+    }
+}
+
+impl_flags_display!(CharacteristicProperties {
+    BROADCAST,
+    READ,
+    WRITE_WITHOUT_RESPONSE,
+    WRITE,
+    NOTIFY,
+    INDICATE,
+    AUTHENTICATED_SIGNED_WRITES,
+    EXTENDED_PROPERTIES,
+});
+
+impl From<u8> for CharacteristicProperties {
+    fn from(v: u8) -> Self {
+        // every bit is defined.
+        Self::from_bits_truncate(v)
+    }
+}
+
+bitflags::bitflags! {
+    /// Characteristic Extended Properties descriptor value. (3.3.3.1)
+    pub struct CharacteristicExtendedProperties: u8 {
+        /// Value may be written with Reliable Writes.
+        const RELIABLE_WRITE = 0b0001;
+        /// Characteristic User Description descriptor is writable.
+        const WRITABLE_AUXILIARIES = 0b0010;
+    }
+}
+
+impl_flags_display!(CharacteristicExtendedProperties {
+    RELIABLE_WRITE,
+    WRITABLE_AUXILIARIES,
+});
+
+impl TryFrom<u8> for CharacteristicExtendedProperties {
+    type Error = UndefinedBits;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        Self::from_bits(v).ok_or(UndefinedBits(v.into()))
+    }
+}
+
+bitflags::bitflags! {
+    /// Client Characteristic Configuration descriptor value. (3.3.3.3)
+    pub struct ClientCharacteristicConfiguration: u16 {
+        /// Client subscribed to notifications.
+        const NOTIFICATION = 0b0001;
+        /// Client subscribed to indications.
+        const INDICATION = 0b0010;
+    }
+}
+
+impl_flags_display!(ClientCharacteristicConfiguration {
+    NOTIFICATION,
+    INDICATION,
+});
+
+impl TryFrom<u16> for ClientCharacteristicConfiguration {
+    type Error = UndefinedBits;
+
+    fn try_from(v: u16) -> Result<Self, Self::Error> {
+        Self::from_bits(v).ok_or(UndefinedBits(v.into()))
+    }
+}
+
+bitflags::bitflags! {
+    /// Server Characteristic Configuration descriptor value. (3.3.3.4)
+    pub struct ServerCharacteristicConfiguration: u16 {
+        /// Value is broadcast while advertising.
+        const BROADCAST = 0b0001;
+    }
+}
+
+impl_flags_display!(ServerCharacteristicConfiguration { BROADCAST });
+
+impl TryFrom<u16> for ServerCharacteristicConfiguration {
+    type Error = UndefinedBits;
+
+    fn try_from(v: u16) -> Result<Self, Self::Error> {
+        Self::from_bits(v).ok_or(UndefinedBits(v.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let props = CharacteristicProperties::READ | CharacteristicProperties::NOTIFY;
+        assert_eq!(props.to_string(), "READ|NOTIFY");
+        assert_eq!(CharacteristicProperties::empty().to_string(), "(empty)");
+        assert_eq!(
+            ClientCharacteristicConfiguration::all().to_string(),
+            "NOTIFICATION|INDICATION"
+        );
+    }
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(
+            CharacteristicProperties::from(0x12),
+            CharacteristicProperties::READ | CharacteristicProperties::NOTIFY
+        );
+        assert_eq!(
+            CharacteristicExtendedProperties::try_from(0x03),
+            Ok(CharacteristicExtendedProperties::all())
+        );
+        assert_eq!(
+            CharacteristicExtendedProperties::try_from(0x04),
+            Err(UndefinedBits(0x04))
+        );
+        assert_eq!(
+            ClientCharacteristicConfiguration::try_from(0x0001),
+            Ok(ClientCharacteristicConfiguration::NOTIFICATION)
+        );
+        assert_eq!(
+            ClientCharacteristicConfiguration::try_from(0x0100),
+            Err(UndefinedBits(0x0100))
+        );
+        assert!(ServerCharacteristicConfiguration::try_from(0x0002).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Golden file of the table in the crate documentation.
+use gatt::characteristics as ch;
+use gatt::services as srv;
+use gatt::{CharacteristicProperties, Registration};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Token {
+    DeviceName,
+    BatteryLevelNotify,
+}
+
+fn new_registration() -> Registration<Token> {
+    let mut registration = Registration::new();
+
+    registration.add_primary_service(srv::GENERIC_ACCESS);
+    registration.add_characteristic_with_token(
+        Token::DeviceName,
+        ch::DEVICE_NAME,
+        "abc",
+        CharacteristicProperties::WRITE,
+    );
+    registration.add_characteristic_with_initial_value(
+        ch::APPEARANCE,
+        0x03c0u16.to_le_bytes(),
+        CharacteristicProperties::READ,
+    );
+
+    registration.add_primary_service(srv::GENERIC_ATTRIBUTE);
+    registration.add_characteristic_with_initial_value(
+        ch::SERVICE_CHANGED,
+        [0x01, 0x00, 0xFF, 0xFF],
+        CharacteristicProperties::INDICATE,
+    );
+
+    registration.add_primary_service(srv::DEVICE_INFORMATION);
+    registration.add_characteristic_with_initial_value(
+        ch::MANUFACTURER_NAME_STRING,
+        "機械",
+        CharacteristicProperties::READ,
+    );
+    registration.add_characteristic_with_initial_value(
+        ch::MODEL_NUMBER_STRING,
+        "A123",
+        CharacteristicProperties::READ,
+    );
+    registration.add_characteristic_with_initial_value(
+        ch::SERIAL_NUMBER_STRING,
+        "333-444",
+        CharacteristicProperties::READ,
+    );
+
+    registration.add_primary_service(srv::BATTERY);
+    registration.add_characteristic_with_token(
+        Token::BatteryLevelNotify,
+        ch::BATTERY_LEVEL,
+        "",
+        CharacteristicProperties::NOTIFY,
+    );
+
+    registration
+}
+
+#[test]
+fn test_export_json_golden() {
+    assert_eq!(
+        new_registration().export_json(),
+        include_str!("data/example_table.json")
+    );
+}
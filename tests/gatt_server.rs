@@ -0,0 +1,327 @@
+use std::io;
+use std::time::Duration;
+
+use att::server::Connection as AttConnection;
+use att::Handle;
+use gatt::characteristics as ch;
+use gatt::server::{Connection, Event};
+use gatt::services as srv;
+use gatt::{CharacteristicProperties, Registration, SimpleRegistration};
+use tokio_test::io::Builder;
+
+#[path = "../examples/minimal.rs"]
+#[allow(dead_code)]
+mod minimal;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Token {
+    DeviceName,
+    BatteryLevel,
+}
+
+fn registration() -> Registration<Token> {
+    let mut registration = Registration::new();
+
+    registration.add_primary_service(srv::GENERIC_ACCESS);
+    registration.add_characteristic_with_token(
+        Token::DeviceName,
+        ch::DEVICE_NAME,
+        "abc",
+        CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+    );
+
+    registration.add_primary_service(srv::BATTERY);
+    registration.add_characteristic_with_token(
+        Token::BatteryLevel,
+        ch::BATTERY_LEVEL,
+        [100],
+        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+    );
+
+    registration
+}
+
+#[tokio::test]
+async fn test_discovery_write_and_notify() {
+    let io = Builder::new()
+        // Read By Group Type Request (Primary Service)
+        .read(&[0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+        .write(&[
+            0x11, 0x06, 0x01, 0x00, 0x03, 0x00, 0x00, 0x18, 0x04, 0x00, 0x07, 0x00, 0x0F, 0x18,
+        ])
+        .read(&[0x10, 0x08, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+        .write(&[0x01, 0x10, 0x08, 0x00, 0x0A])
+        // Read By Type Request (Characteristic)
+        .read(&[0x08, 0x01, 0x00, 0xFF, 0xFF, 0x03, 0x28])
+        .write(&[
+            0x09, 0x07, 0x02, 0x00, 0x0A, 0x03, 0x00, 0x00, 0x2A, 0x05, 0x00, 0x12, 0x06, 0x00,
+            0x19, 0x2A,
+        ])
+        // Find Information Request (Descriptors of Battery Level)
+        .read(&[0x04, 0x07, 0x00, 0xFF, 0xFF])
+        .write(&[0x05, 0x01, 0x07, 0x00, 0x02, 0x29])
+        // Write Request (Device Name)
+        .read(&[0x12, 0x03, 0x00, b'x', b'y', b'z'])
+        .write(&[0x13])
+        // Handle Value Notification (Battery Level)
+        .write(&[0x1B, 0x06, 0x00, 0x63])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let mut connection = Connection::new(connection, registration());
+
+    let mut events = connection.events();
+    let mut notification = connection.notification(&Token::BatteryLevel).unwrap();
+
+    let app = async {
+        assert!(matches!(events.next().await, Some(Event::Connected(..))));
+        let event = events.next().await.unwrap();
+        notification.notify(&[0x63]).await.unwrap();
+        event
+    };
+    let (result, event) = tokio::join!(connection.run(), app);
+    result.unwrap();
+
+    assert!(matches!(
+        event,
+        Event::Write(Token::DeviceName, value) if &*value == b"xyz"
+    ));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_write_command_rate_limit() {
+    let mut builder = Builder::new();
+    for n in 0..1000u16 {
+        if n == 500 {
+            builder.wait(Duration::from_secs(1));
+        }
+        // Write Command (Device Name)
+        let [lo, hi] = n.to_le_bytes();
+        builder.read(&[0x52, 0x03, 0x00, lo, hi]);
+    }
+    let connection = AttConnection::new(builder.build(), att::Address::le_public_from([0; 6]));
+    let mut connection = Connection::new(connection, registration());
+    connection.set_write_command_rate_limit(10, 10);
+    // every event is read after the connection is done.
+    connection.set_event_capacity(32).unwrap();
+
+    let mut events = connection.events();
+    let stats = connection.stats_handle();
+    connection.run().await.unwrap();
+
+    let mut received = vec![];
+    while let Some(event) = events.next().await {
+        if let Event::Write(Token::DeviceName, value) = event {
+            received.push(u16::from_le_bytes([value[0], value[1]]));
+        }
+    }
+    let expected = (0..10).chain(500..510).collect::<Vec<_>>();
+    assert_eq!(received, expected);
+    let stats = stats.stats();
+    assert_eq!(stats.write_commands_received, 1000);
+    assert_eq!(stats.write_commands_dropped, 980);
+    assert_eq!(stats.write_requests_received, 0);
+}
+
+#[tokio::test]
+async fn test_notification_sender() {
+    let io = Builder::new()
+        // Handle Value Notification (Battery Level)
+        .write(&[0x1B, 0x06, 0x00, 0x01])
+        .write(&[0x1B, 0x06, 0x00, 0x02])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let connection = Connection::new(connection, registration());
+
+    let sender = connection
+        .notification_sender(&Token::BatteryLevel)
+        .unwrap();
+    assert!(connection.notification_sender(&Token::DeviceName).is_err());
+    let stats = connection.stats_handle();
+
+    let webhook = {
+        let sender = sender.clone();
+        tokio::spawn(async move { sender.notify(&[0x01]).await })
+    };
+    let app = async {
+        webhook.await.unwrap().unwrap();
+        sender.notify(&[0x02]).await.unwrap();
+        assert_eq!(sender.token(), &Token::BatteryLevel);
+    };
+    let (result, ()) = tokio::join!(connection.run(), app);
+    result.unwrap();
+
+    let stats = stats.stats();
+    assert_eq!(stats.notifications_sent, 2);
+    assert_eq!(stats.bytes_sent, 8);
+    assert_eq!(stats.write_requests_received, 0);
+}
+
+#[tokio::test]
+async fn test_rejected_write_emits_no_event() {
+    let io = Builder::new()
+        // Write Request (Battery Level, not writable)
+        .read(&[0x12, 0x06, 0x00, 0x01])
+        .write(&[0x01, 0x12, 0x06, 0x00, 0x03])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let mut connection = Connection::new(connection, registration());
+
+    let mut events = connection.events();
+    connection.run().await.unwrap();
+    assert!(matches!(events.next().await, Some(Event::Connected(..))));
+    assert!(matches!(
+        events.next().await,
+        Some(Event::Disconnected(_, None))
+    ));
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_lifecycle_events() {
+    let io = Builder::new()
+        .read(&[0x0A, 0x03, 0x00])
+        .write(&[0x0B, b'a', b'b', b'c'])
+        .read_error(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+        .build();
+    let address = att::Address::le_public_from([1, 2, 3, 4, 5, 6]);
+    let connection = AttConnection::new(io, address.clone());
+    let mut connection = Connection::new(connection, registration());
+
+    let mut events = connection.events();
+    assert!(connection.run().await.is_err());
+
+    let connected = events.next_record().await.unwrap();
+    assert!(matches!(connected.event(), Event::Connected(a) if a == &address));
+    assert_eq!(
+        connected.event().to_string(),
+        format!("connected {}", address)
+    );
+    let disconnected = events.next_record().await.unwrap();
+    assert!(connected.sequence() < disconnected.sequence());
+    match disconnected.into_event() {
+        Event::Disconnected(a, Some(err)) => {
+            assert_eq!(a, address);
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        }
+        event => panic!("{:?}", event),
+    }
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_client_config_per_connection() {
+    // first connection enables notifications
+    let io = Builder::new()
+        // Write Request (CCCD of Battery Level)
+        .read(&[0x12, 0x07, 0x00, 0x01, 0x00])
+        .write(&[0x13])
+        .read(&[0x0A, 0x07, 0x00])
+        .write(&[0x0B, 0x01, 0x00])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let connection = Connection::new(connection, registration());
+    let store = connection.client_config_store();
+    connection.run().await.unwrap();
+
+    let bonded = store.export();
+    assert_eq!(bonded.get(&Handle::new(0x0007)), Some(0x0001));
+
+    // next connection starts unsubscribed
+    let io = Builder::new()
+        .read(&[0x0A, 0x07, 0x00])
+        .write(&[0x0B, 0x00, 0x00])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let connection = Connection::new(connection, registration());
+    assert_eq!(
+        connection.export_client_config().get(&Handle::new(0x0007)),
+        Some(0x0000)
+    );
+    connection.run().await.unwrap();
+
+    // bonded peer starts subscribed
+    let io = Builder::new()
+        .read(&[0x0A, 0x07, 0x00])
+        .write(&[0x0B, 0x01, 0x00])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let connection = Connection::new(connection, registration());
+    connection.restore_client_config(&bonded);
+    connection.run().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_minimal_example() {
+    let io = Builder::new()
+        // Read Request (Battery Level)
+        .read(&[0x0A, 0x03, 0x00])
+        .write(&[0x0B, 100])
+        // Write Request (Battery Level, read only)
+        .read(&[0x12, 0x03, 0x00, 0x01])
+        .write(&[0x01, 0x12, 0x03, 0x00, 0x03])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let connection = Connection::new(connection, minimal::registration());
+
+    let layout = connection
+        .database()
+        .iter()
+        .map(|(handle, uuid)| (handle.as_u16(), uuid.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        layout,
+        [
+            (0x0001, att::Uuid::new_uuid16(0x2800)),
+            (0x0002, att::Uuid::new_uuid16(0x2803)),
+            (0x0003, ch::BATTERY_LEVEL),
+        ]
+    );
+    connection.run().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_simple_registration_anonymous_token() {
+    let mut registration = SimpleRegistration::new_simple();
+    registration.add_primary_service(srv::GENERIC_ACCESS);
+    for uuid in [ch::DEVICE_NAME, ch::APPEARANCE] {
+        registration.add_characteristic_with_token(
+            (),
+            uuid,
+            [0],
+            CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY,
+        );
+    }
+
+    let io = Builder::new()
+        // Write Requests (Device Name, Appearance)
+        .read(&[0x12, 0x03, 0x00, 0x01])
+        .write(&[0x13])
+        .read(&[0x12, 0x06, 0x00, 0x02])
+        .write(&[0x13])
+        // Handle Value Notification (Appearance)
+        .write(&[0x1B, 0x06, 0x00, 0x03])
+        .build();
+    let connection = AttConnection::new(io, att::Address::le_public_from([0; 6]));
+    let mut connection = Connection::new(connection, registration);
+    let mut events = connection.events();
+    let mut writes = connection.events();
+    let sender = connection.notification_sender(&()).unwrap();
+
+    let (result, notified) = tokio::join!(connection.run(), async {
+        // after both writes
+        for _ in 0..3 {
+            writes.next().await;
+        }
+        sender.notify(&[0x03]).await
+    });
+    result.unwrap();
+    notified.unwrap();
+
+    assert!(matches!(events.next().await, Some(Event::Connected(..))));
+    for payload in [[0x01], [0x02]] {
+        match events.next().await {
+            Some(Event::Write((), value)) => assert_eq!(&value[..], &payload[..]),
+            event => panic!("{:?}", event),
+        }
+    }
+}